@@ -3,6 +3,8 @@
 //! Provides both HTTP REST API and IPC communication with the hyperboxd daemon.
 
 use anyhow::{Context, Result};
+use hyperbox_core::runtime::ContainerFilter;
+use hyperbox_core::types::{FilesystemChange, Platform, ResourceLimits, ResourceUpdateReport};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 /// Default daemon HTTP address.
@@ -23,6 +25,7 @@ pub struct DaemonClient {
 #[derive(Debug, Deserialize)]
 pub struct ApiResponse<T> {
     pub success: bool,
+    #[serde(alias = "error")]
     pub message: Option<String>,
     pub data: Option<T>,
 }
@@ -52,6 +55,9 @@ pub struct ImageInfo {
     pub id: String,
     pub repo: Option<String>,
     pub tag: Option<String>,
+    /// References (`registry/repository:tag`) pointing at the image
+    #[serde(default)]
+    pub tags: Vec<String>,
     pub size: u64,
     pub created_at: String,
 }
@@ -65,6 +71,15 @@ pub struct CreateContainerRequest {
     pub ports: Option<Vec<PortMappingRequest>>,
     pub volumes: Option<Vec<String>>,
     pub command: Option<Vec<String>>,
+    pub cap_add: Option<Vec<String>>,
+    pub cap_drop: Option<Vec<String>>,
+    pub devices: Option<Vec<String>>,
+    pub ulimits: Option<Vec<String>>,
+    pub sysctls: Option<std::collections::HashMap<String, String>>,
+    pub shm_size: Option<u64>,
+    pub group_add: Option<Vec<String>>,
+    pub stop_signal: Option<String>,
+    pub init: Option<bool>,
 }
 
 /// Port mapping in request.
@@ -75,6 +90,25 @@ pub struct PortMappingRequest {
     pub protocol: Option<String>,
 }
 
+/// Commit container request.
+#[derive(Debug, Serialize)]
+pub struct CommitRequest {
+    pub reference: Option<String>,
+    pub author: Option<String>,
+    pub comment: Option<String>,
+    pub pause: bool,
+    pub config: hyperbox_core::storage::ContainerConfig,
+}
+
+/// Import image request.
+#[derive(Debug, Default)]
+pub struct ImportRequest {
+    pub reference: Option<String>,
+    pub comment: Option<String>,
+    /// Dockerfile instructions to apply to the image configuration
+    pub changes: Vec<String>,
+}
+
 /// Daemon health status.
 #[derive(Debug, Deserialize)]
 pub struct HealthStatus {
@@ -117,14 +151,24 @@ impl DaemonClient {
     }
 
     /// List all containers.
-    pub async fn list_containers(&self, all: bool) -> Result<Vec<ContainerInfo>> {
-        let url = if all {
-            format!("{}/api/v1/containers?all=true", self.base_url)
-        } else {
-            format!("{}/api/v1/containers", self.base_url)
-        };
-
-        let resp: ApiResponse<Vec<ContainerInfo>> = self.get(&url).await?;
+    pub async fn list_containers(
+        &self,
+        all: bool,
+        filter: &ContainerFilter,
+    ) -> Result<Vec<ContainerInfo>> {
+        let mut url = reqwest::Url::parse(&format!("{}/api/v1/containers", self.base_url))?;
+        if all {
+            url.query_pairs_mut().append_pair("all", "true");
+        }
+        if !filter.is_empty() {
+            url.query_pairs_mut()
+                .append_pair("filters", &serde_json::to_string(&filter.to_map())?);
+        }
+
+        let resp: ApiResponse<Vec<ContainerInfo>> = self.get(url.as_str()).await?;
+        if !resp.success {
+            anyhow::bail!(resp.message.unwrap_or_else(|| "Unknown error".to_string()));
+        }
         Ok(resp.data.unwrap_or_default())
     }
 
@@ -185,6 +229,50 @@ impl DaemonClient {
         Ok(())
     }
 
+    /// Pause a container.
+    pub async fn pause_container(&self, id: &str) -> Result<()> {
+        let url = format!("{}/api/v1/containers/{}/pause", self.base_url, id);
+        let resp: ApiResponse<serde_json::Value> = self.post_empty(&url).await?;
+
+        if !resp.success {
+            anyhow::bail!(resp
+                .message
+                .unwrap_or_else(|| "Failed to pause container".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Unpause a container.
+    pub async fn unpause_container(&self, id: &str) -> Result<()> {
+        let url = format!("{}/api/v1/containers/{}/unpause", self.base_url, id);
+        let resp: ApiResponse<serde_json::Value> = self.post_empty(&url).await?;
+
+        if !resp.success {
+            anyhow::bail!(resp
+                .message
+                .unwrap_or_else(|| "Failed to unpause container".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Change the resource limits of a container.
+    pub async fn update_container_resources(
+        &self,
+        id: &str,
+        limits: &ResourceLimits,
+    ) -> Result<ResourceUpdateReport> {
+        let url = format!("{}/api/v1/containers/{}/resources", self.base_url, id);
+        let resp: ApiResponse<ResourceUpdateReport> = self.patch(&url, limits).await?;
+
+        if !resp.success {
+            anyhow::bail!(resp
+                .message
+                .unwrap_or_else(|| "Failed to update container".to_string()));
+        }
+        resp.data
+            .ok_or_else(|| anyhow::anyhow!("Daemon returned no update report"))
+    }
+
     /// Remove a container.
     pub async fn remove_container(&self, id: &str, force: bool) -> Result<()> {
         let url = format!("{}/api/v1/containers/{}?force={}", self.base_url, id, force);
@@ -286,6 +374,135 @@ impl DaemonClient {
         Ok(stream)
     }
 
+    /// Download a tar archive of a path in a container.
+    pub async fn download_archive(&self, id: &str, path: &str) -> Result<Vec<u8>> {
+        let url = format!("{}/api/v1/containers/{}/archive", self.base_url, id);
+        let resp = self
+            .http_client
+            .get(&url)
+            .query(&[("path", path)])
+            .send()
+            .await
+            .context("Failed to connect to daemon")?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let resp: ApiResponse<()> = resp.json().await.context("Failed to parse response")?;
+            anyhow::bail!(resp
+                .message
+                .unwrap_or_else(|| format!("Failed to copy from container: HTTP {}", status)));
+        }
+
+        let bytes = resp.bytes().await.context("Failed to read archive")?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Extract a tar archive into a directory in a container.
+    pub async fn upload_archive(&self, id: &str, path: &str, archive: Vec<u8>) -> Result<()> {
+        let url = format!("{}/api/v1/containers/{}/archive", self.base_url, id);
+        let resp: ApiResponse<()> = self
+            .http_client
+            .put(&url)
+            .query(&[("path", path)])
+            .header("Content-Type", "application/x-tar")
+            .body(archive)
+            .send()
+            .await
+            .context("Failed to connect to daemon")?
+            .json()
+            .await
+            .context("Failed to parse response")?;
+
+        if !resp.success {
+            anyhow::bail!(resp
+                .message
+                .unwrap_or_else(|| "Failed to copy into container".to_string()));
+        }
+        Ok(())
+    }
+
+    /// List the paths a container changed relative to its image.
+    pub async fn container_changes(&self, id: &str) -> Result<Vec<FilesystemChange>> {
+        let url = format!("{}/api/v1/containers/{}/changes", self.base_url, id);
+        let resp: ApiResponse<Vec<FilesystemChange>> = self.get(&url).await?;
+
+        if !resp.success {
+            anyhow::bail!(resp
+                .message
+                .unwrap_or_else(|| "Failed to get container changes".to_string()));
+        }
+        Ok(resp.data.unwrap_or_default())
+    }
+
+    /// Commit a container to a new image, returning the image ID.
+    pub async fn commit_container(&self, id: &str, req: &CommitRequest) -> Result<String> {
+        let url = format!("{}/api/v1/containers/{}/commit", self.base_url, id);
+        let resp: ApiResponse<serde_json::Value> = self.post(&url, req).await?;
+
+        if !resp.success {
+            anyhow::bail!(resp
+                .message
+                .unwrap_or_else(|| "Failed to commit container".to_string()));
+        }
+
+        resp.data
+            .and_then(|d| d.get("id").and_then(|v| v.as_str()).map(String::from))
+            .ok_or_else(|| anyhow::anyhow!("No image ID in response"))
+    }
+
+    /// Download a container's flattened root filesystem as a tar archive.
+    pub async fn export_container(&self, id: &str) -> Result<Vec<u8>> {
+        let url = format!("{}/api/v1/containers/{}/export", self.base_url, id);
+        let resp = self
+            .http_client
+            .get(&url)
+            .send()
+            .await
+            .context("Failed to connect to daemon")?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let resp: ApiResponse<()> = resp.json().await.context("Failed to parse response")?;
+            anyhow::bail!(resp
+                .message
+                .unwrap_or_else(|| format!("Failed to export container: HTTP {}", status)));
+        }
+
+        let bytes = resp.bytes().await.context("Failed to read archive")?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Create an image from a root filesystem tar archive.
+    pub async fn import_image(&self, archive: Vec<u8>, req: &ImportRequest) -> Result<String> {
+        let url = format!("{}/api/v1/images/import", self.base_url);
+        let mut query = vec![("changes", req.changes.join("\n"))];
+        query.extend(req.reference.clone().map(|r| ("reference", r)));
+        query.extend(req.comment.clone().map(|c| ("comment", c)));
+
+        let resp: ApiResponse<serde_json::Value> = self
+            .http_client
+            .post(&url)
+            .query(&query)
+            .header("Content-Type", "application/x-tar")
+            .body(archive)
+            .send()
+            .await
+            .context("Failed to connect to daemon")?
+            .json()
+            .await
+            .context("Failed to parse response")?;
+
+        if !resp.success {
+            anyhow::bail!(resp
+                .message
+                .unwrap_or_else(|| "Failed to import image".to_string()));
+        }
+
+        resp.data
+            .and_then(|d| d.get("id").and_then(|v| v.as_str()).map(String::from))
+            .ok_or_else(|| anyhow::anyhow!("No image ID in response"))
+    }
+
     /// List all images.
     pub async fn list_images(&self) -> Result<Vec<ImageInfo>> {
         let url = format!("{}/api/v1/images", self.base_url);
@@ -293,10 +510,13 @@ impl DaemonClient {
         Ok(resp.data.unwrap_or_default())
     }
 
-    /// Pull an image.
-    pub async fn pull_image(&self, image: &str) -> Result<()> {
+    /// Pull an image, for `platform` if given and the host otherwise.
+    pub async fn pull_image(&self, image: &str, platform: Option<&Platform>) -> Result<()> {
         let url = format!("{}/api/v1/images/pull", self.base_url);
-        let req = serde_json::json!({ "image": image });
+        let req = serde_json::json!({
+            "image": image,
+            "platform": platform.map(ToString::to_string),
+        });
         let resp: ApiResponse<()> = self.post(&url, &req).await?;
 
         if !resp.success {
@@ -307,6 +527,60 @@ impl DaemonClient {
         Ok(())
     }
 
+    /// Push an image to its registry, returning the manifest digest.
+    pub async fn push_image(&self, image: &str) -> Result<String> {
+        let url = format!("{}/api/v1/images/push", self.base_url);
+        let req = serde_json::json!({ "image": image });
+        let resp: ApiResponse<serde_json::Value> = self.post(&url, &req).await?;
+
+        if !resp.success {
+            anyhow::bail!(resp
+                .message
+                .unwrap_or_else(|| "Failed to push image".to_string()));
+        }
+
+        resp.data
+            .and_then(|d| d.get("digest").and_then(|v| v.as_str()).map(String::from))
+            .ok_or_else(|| anyhow::anyhow!("No digest in response"))
+    }
+
+    /// Log the daemon in to a registry, returning where it saved the
+    /// credentials.
+    pub async fn login(&self, server: &str, username: &str, password: &str) -> Result<String> {
+        let url = format!("{}/api/v1/registries/login", self.base_url);
+        let req = serde_json::json!({
+            "server": server,
+            "username": username,
+            "password": password,
+        });
+        let resp: ApiResponse<serde_json::Value> = self.post(&url, &req).await?;
+
+        if !resp.success {
+            anyhow::bail!(resp.message.unwrap_or_else(|| "Login failed".to_string()));
+        }
+
+        resp.data
+            .and_then(|d| d.get("config").and_then(|v| v.as_str()).map(String::from))
+            .ok_or_else(|| anyhow::anyhow!("No credential store in response"))
+    }
+
+    /// Remove the daemon's credentials for a registry, returning whether it
+    /// had any.
+    pub async fn logout(&self, server: &str) -> Result<bool> {
+        let url = format!("{}/api/v1/registries/logout", self.base_url);
+        let req = serde_json::json!({ "server": server });
+        let resp: ApiResponse<serde_json::Value> = self.post(&url, &req).await?;
+
+        if !resp.success {
+            anyhow::bail!(resp.message.unwrap_or_else(|| "Logout failed".to_string()));
+        }
+
+        Ok(resp
+            .data
+            .and_then(|d| d.get("removed").and_then(serde_json::Value::as_bool))
+            .unwrap_or(false))
+    }
+
     /// Remove an image.
     pub async fn remove_image(&self, id: &str, force: bool) -> Result<()> {
         let url = format!("{}/api/v1/images/{}?force={}", self.base_url, id, force);
@@ -345,6 +619,18 @@ impl DaemonClient {
         resp.json().await.context("Failed to parse response")
     }
 
+    async fn patch<T: DeserializeOwned, B: Serialize>(&self, url: &str, body: &B) -> Result<T> {
+        let resp = self
+            .http_client
+            .patch(url)
+            .json(body)
+            .send()
+            .await
+            .context("Failed to connect to daemon")?;
+
+        resp.json().await.context("Failed to parse response")
+    }
+
     async fn post_empty<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
         let resp = self
             .http_client
@@ -42,6 +42,9 @@ pub enum DockerSubcommand {
     /// Pull an image (maps to: hb image pull)
     Pull(DockerPullArgs),
 
+    /// Push an image (maps to: hb image push)
+    Push(DockerPushArgs),
+
     /// Build an image (maps to: hb image build)
     Build(DockerBuildArgs),
 
@@ -218,6 +221,21 @@ pub struct DockerPullArgs {
     pub image: String,
 }
 
+/// Arguments for docker push.
+#[derive(Parser, Debug)]
+pub struct DockerPushArgs {
+    /// Push all tags of the repository
+    #[arg(short, long)]
+    pub all_tags: bool,
+
+    /// Quiet mode
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Image name
+    pub image: String,
+}
+
 /// Arguments for docker build.
 #[derive(Parser, Debug)]
 pub struct DockerBuildArgs {
@@ -359,6 +377,7 @@ impl DockerCommand {
             DockerSubcommand::Rm(args) => Self::rm(args).await,
             DockerSubcommand::Images(args) => Self::images(args).await,
             DockerSubcommand::Pull(args) => Self::pull(args).await,
+            DockerSubcommand::Push(args) => Self::push(args).await,
             DockerSubcommand::Build(args) => Self::build(args).await,
             DockerSubcommand::Exec(args) => Self::exec(args).await,
             DockerSubcommand::Logs(args) => Self::logs(args).await,
@@ -400,20 +419,22 @@ impl DockerCommand {
     }
 
     async fn ps(args: &DockerPsArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
-        println!("📋 HyperBox Docker Compat: Listing containers...");
+        if !args.quiet {
+            println!("📋 HyperBox Docker Compat: Listing containers...");
 
-        let filter_desc = if args.all { "all" } else { "running" };
-        println!("   Filter: {}", filter_desc);
+            let filter_desc = if args.all { "all" } else { "running" };
+            println!("   Filter: {}", filter_desc);
 
-        // Translate to HyperBox native command
-        let all_flag = if args.all { "--all" } else { "" };
-        println!("\n   → Translating to: hb container list {}", all_flag);
-
-        // Would call container::list() here
-        println!("\nCONTAINER ID   IMAGE          STATUS    NAME");
-        println!("─────────────────────────────────────────────────");
-        println!("(Use 'hb container list' for native HyperBox output)");
+            // Translate to HyperBox native command
+            let mut native = vec!["hb container list".to_string()];
+            if args.all {
+                native.push("--all".to_string());
+            }
+            native.extend(args.filter.iter().map(|f| format!("--filter {f}")));
+            println!("\n   → Translating to: {}\n", native.join(" "));
+        }
 
+        super::container::list_containers(args.all, None, args.quiet, &args.filter).await?;
         Ok(ExitCode::SUCCESS)
     }
 
@@ -482,18 +503,37 @@ impl DockerCommand {
     }
 
     async fn pull(args: &DockerPullArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
-        println!("⬇️  HyperBox Docker Compat: Pulling image...");
-        println!("   Image: {}", args.image);
-
-        if let Some(platform) = &args.platform {
-            println!("   Platform: {}", platform);
+        if !args.quiet {
+            println!("⬇️  HyperBox Docker Compat: Pulling image...");
+            println!("   Image: {}", args.image);
+
+            let mut native = vec!["hb image pull".to_string()];
+            if let Some(platform) = &args.platform {
+                println!("   Platform: {}", platform);
+                native.push(format!("--platform {platform}"));
+            }
+            native.push(args.image.clone());
+            println!("\n   → Translating to: {}\n", native.join(" "));
         }
 
-        println!("\n   → Translating to: hb image pull {}", args.image);
+        super::image::pull_image(args.image.clone(), args.all_tags, args.platform.clone()).await?;
+        Ok(ExitCode::SUCCESS)
+    }
+
+    async fn push(args: &DockerPushArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+        if !args.quiet {
+            println!("⬆️  HyperBox Docker Compat: Pushing image...");
+            println!("   Image: {}", args.image);
 
-        // Would call image::pull() here
-        println!("\n✅ Image pulled with HyperBox optimizations (eStargz lazy loading)");
+            let mut native = vec!["hb image push".to_string()];
+            if args.all_tags {
+                native.push("--all-tags".to_string());
+            }
+            native.push(args.image.clone());
+            println!("\n   → Translating to: {}\n", native.join(" "));
+        }
 
+        super::image::push_image(args.image.clone(), args.all_tags).await?;
         Ok(ExitCode::SUCCESS)
     }
 
@@ -682,6 +722,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_docker_ps_filter_parse() {
+        let args = DockerCommand::try_parse_from([
+            "docker",
+            "ps",
+            "--filter",
+            "label=env=prod",
+            "-f",
+            "status=exited",
+        ]);
+        assert!(args.is_ok());
+
+        if let Ok(cmd) = args {
+            match cmd.command {
+                DockerSubcommand::Ps(ps_args) => {
+                    assert_eq!(ps_args.filter, vec!["label=env=prod", "status=exited"]);
+                    assert!(hyperbox_core::runtime::ContainerFilter::parse(&ps_args.filter).is_ok());
+                }
+                _ => panic!("Expected Ps command"),
+            }
+        }
+    }
+
     #[test]
     fn test_docker_stop_parse() {
         let args = DockerCommand::try_parse_from([
@@ -758,6 +821,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_docker_push_all_tags() {
+        let args = DockerCommand::try_parse_from([
+            "docker",
+            "push",
+            "--all-tags",
+            "registry.example.com/team/app",
+        ]);
+        assert!(args.is_ok());
+
+        if let Ok(cmd) = args {
+            match cmd.command {
+                DockerSubcommand::Push(push_args) => {
+                    assert!(push_args.all_tags);
+                    assert_eq!(push_args.image, "registry.example.com/team/app");
+                }
+                _ => panic!("Expected Push command"),
+            }
+        }
+    }
+
     #[test]
     fn test_docker_build_with_tags() {
         let args = DockerCommand::try_parse_from([
@@ -3,9 +3,15 @@
 use anyhow::Result;
 use clap::{Args, Subcommand};
 use colored::*;
+use std::ffi::{OsStr, OsString};
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Component, Path, PathBuf};
 use tabled::{Table, Tabled};
 
-use crate::client::{CreateContainerRequest, DaemonClient, PortMappingRequest};
+use crate::client::{CommitRequest, CreateContainerRequest, DaemonClient, PortMappingRequest};
+use hyperbox_core::runtime::ContainerFilter;
+use hyperbox_core::storage::ContainerConfig;
+use hyperbox_core::types::{ChangeKind, DeviceMapping, ResourceLimits, Ulimit};
 
 /// Container management commands.
 #[derive(Args)]
@@ -30,6 +36,11 @@ pub enum ContainerAction {
         /// Quiet mode (only show IDs)
         #[arg(short, long)]
         quiet: bool,
+
+        /// Filter output (label=KEY[=VALUE], name=GLOB, status=STATE,
+        /// ancestor=IMAGE, project=NAME, before=TIME, since=TIME)
+        #[arg(short, long, action = clap::ArgAction::Append)]
+        filter: Vec<String>,
     },
 
     /// Run a new container
@@ -73,6 +84,9 @@ pub enum ContainerAction {
         #[arg(short, long)]
         workdir: Option<String>,
 
+        #[command(flatten)]
+        options: RunOptions,
+
         /// Command to run
         #[arg(last = true)]
         command: Vec<String>,
@@ -104,6 +118,27 @@ pub enum ContainerAction {
         timeout: u64,
     },
 
+    /// Pause all processes in a container
+    Pause {
+        /// Container ID or name
+        container: String,
+    },
+
+    /// Unpause a paused container
+    Unpause {
+        /// Container ID or name
+        container: String,
+    },
+
+    /// Change the resource limits of a container
+    Update {
+        /// Container ID or name
+        container: String,
+
+        #[command(flatten)]
+        limits: ResourceOptions,
+    },
+
     /// Remove a container
     #[command(alias = "rm")]
     Remove {
@@ -178,7 +213,53 @@ pub enum ContainerAction {
         no_stream: bool,
     },
 
+    /// Show paths a container changed in its filesystem
+    Diff {
+        /// Container ID or name
+        container: String,
+    },
+
+    /// Create a new image from a container's changes
+    Commit {
+        /// Container ID or name
+        container: String,
+
+        /// Image reference for the new image (e.g. myapp:dev)
+        reference: Option<String>,
+
+        /// Author of the image
+        #[arg(short, long)]
+        author: Option<String>,
+
+        /// Commit message
+        #[arg(short, long)]
+        message: Option<String>,
+
+        /// Apply a Dockerfile instruction to the image configuration
+        /// (CMD, ENTRYPOINT, ENV, EXPOSE, LABEL, STOPSIGNAL, USER, VOLUME,
+        /// WORKDIR)
+        #[arg(short, long = "change", value_name = "INSTRUCTION")]
+        changes: Vec<String>,
+
+        /// Do not pause the container while committing
+        #[arg(long)]
+        no_pause: bool,
+    },
+
+    /// Export a container's filesystem as a tar archive
+    Export {
+        /// Container ID or name
+        container: String,
+
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
     /// Copy files between container and host
+    ///
+    /// Container paths are written as CONTAINER:PATH. Use `-` as the host
+    /// path to read or write a tar archive on stdin or stdout.
     Cp {
         /// Source path
         source: String,
@@ -188,13 +269,179 @@ pub enum ContainerAction {
     },
 }
 
+/// Process and kernel options of `container run`.
+#[derive(Args, Default)]
+pub struct RunOptions {
+    /// Add a Linux capability (e.g. NET_ADMIN, or ALL)
+    #[arg(long)]
+    cap_add: Vec<String>,
+
+    /// Drop a Linux capability (e.g. NET_RAW, or ALL)
+    #[arg(long)]
+    cap_drop: Vec<String>,
+
+    /// Expose a host device (host[:container][:permissions])
+    #[arg(long, value_parser = validate::<DeviceMapping>)]
+    device: Vec<String>,
+
+    /// Set a resource limit (name=soft[:hard], e.g. nofile=1024:4096)
+    #[arg(long, value_parser = validate::<Ulimit>)]
+    ulimit: Vec<String>,
+
+    /// Set a namespaced kernel parameter (key=value)
+    #[arg(long, value_parser = parse_key_value)]
+    sysctl: Vec<(String, String)>,
+
+    /// Size of /dev/shm (e.g. 64m, 1g)
+    #[arg(long, value_parser = parse_size)]
+    shm_size: Option<u64>,
+
+    /// Add a supplementary group (name or gid)
+    #[arg(long)]
+    group_add: Vec<String>,
+
+    /// Signal to stop the container (e.g. SIGQUIT)
+    #[arg(long)]
+    stop_signal: Option<String>,
+
+    /// Run an init process as PID 1 that forwards signals and reaps zombies
+    #[arg(long)]
+    init: bool,
+}
+
+/// Resource limits of `container update`; unset limits are left unchanged.
+#[derive(Args, Default)]
+pub struct ResourceOptions {
+    /// Hard CPU limit in CPUs (e.g. 0.5)
+    #[arg(long, value_parser = parse_cpus)]
+    cpus: Option<u64>,
+
+    /// Relative CPU weight under contention (1-10000, default 100)
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..=10_000))]
+    cpu_weight: Option<u16>,
+
+    /// CPUs the container may run on (e.g. 0-3,6)
+    #[arg(long)]
+    cpuset_cpus: Option<String>,
+
+    /// Memory limit (e.g. 512m)
+    #[arg(short, long, value_parser = parse_size)]
+    memory: Option<u64>,
+
+    /// Memory plus swap limit (e.g. 1g)
+    #[arg(long, value_parser = parse_size)]
+    memory_swap: Option<u64>,
+
+    /// Maximum number of processes
+    #[arg(long)]
+    pids_limit: Option<u64>,
+
+    /// Read rate limit in bytes per second (e.g. 10m)
+    #[arg(long, value_parser = parse_size)]
+    device_read_bps: Option<u64>,
+
+    /// Write rate limit in bytes per second (e.g. 10m)
+    #[arg(long, value_parser = parse_size)]
+    device_write_bps: Option<u64>,
+
+    /// Read rate limit in IO operations per second
+    #[arg(long)]
+    device_read_iops: Option<u64>,
+
+    /// Write rate limit in IO operations per second
+    #[arg(long)]
+    device_write_iops: Option<u64>,
+
+    /// Relative IO weight (1-10000, default 100)
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..=10_000))]
+    io_weight: Option<u16>,
+}
+
+impl ResourceOptions {
+    fn into_limits(self) -> ResourceLimits {
+        ResourceLimits {
+            cpu_millicores: self.cpus,
+            cpu_weight: self.cpu_weight,
+            cpuset_cpus: self.cpuset_cpus,
+            memory_bytes: self.memory,
+            memory_swap_bytes: self.memory_swap,
+            pids_limit: self.pids_limit,
+            io_read_bps: self.device_read_bps,
+            io_write_bps: self.device_write_bps,
+            io_read_iops: self.device_read_iops,
+            io_write_iops: self.device_write_iops,
+            io_weight: self.io_weight,
+        }
+    }
+}
+
+/// Check that a flag value parses as `T`, keeping it as a string.
+fn validate<T>(value: &str) -> std::result::Result<String, String>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse::<T>()
+        .map(|_| value.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn parse_key_value(value: &str) -> std::result::Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("{value} is not key=value"))
+}
+
+/// Parse a CPU count with up to three decimals (e.g. 1.5) as millicores.
+fn parse_cpus(value: &str) -> std::result::Result<u64, String> {
+    let error = || format!("{value} is not a CPU count (e.g. 0.5)");
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if fraction.len() > 3 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return Err(error());
+    }
+
+    let whole = if whole.is_empty() {
+        0
+    } else {
+        whole.parse::<u64>().map_err(|_| error())?
+    };
+    let fraction = format!("{fraction:0<3}")
+        .parse::<u64>()
+        .map_err(|_| error())?;
+    whole
+        .checked_mul(1000)
+        .and_then(|millicores| millicores.checked_add(fraction))
+        .filter(|&millicores| millicores > 0)
+        .ok_or_else(error)
+}
+
+/// Parse a size with an optional binary unit suffix (b, k, m, g).
+fn parse_size(value: &str) -> std::result::Result<u64, String> {
+    let lower = value.to_lowercase();
+    let trimmed = lower.trim_end_matches("ib").trim_end_matches('b');
+    let (digits, multiplier) = match trimmed.chars().last() {
+        Some('k') => (&trimmed[..trimmed.len() - 1], 1024),
+        Some('m') => (&trimmed[..trimmed.len() - 1], 1024 * 1024),
+        Some('g') => (&trimmed[..trimmed.len() - 1], 1024 * 1024 * 1024),
+        _ => (trimmed, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("{value} is not a size (e.g. 64m)"))
+}
+
 pub async fn run(cmd: ContainerCommand) -> Result<()> {
     match cmd.action {
         ContainerAction::List {
             all,
             project,
             quiet,
-        } => list_containers(all, project, quiet).await,
+            filter,
+        } => list_containers(all, project, quiet, &filter).await,
         ContainerAction::Run {
             image,
             name,
@@ -206,6 +453,7 @@ pub async fn run(cmd: ContainerCommand) -> Result<()> {
             interactive,
             tty,
             workdir,
+            options,
             command,
         } => {
             run_container(
@@ -219,6 +467,7 @@ pub async fn run(cmd: ContainerCommand) -> Result<()> {
                 interactive,
                 tty,
                 workdir,
+                options,
                 command,
             )
             .await
@@ -228,6 +477,11 @@ pub async fn run(cmd: ContainerCommand) -> Result<()> {
         ContainerAction::Restart { container, timeout } => {
             restart_container(container, timeout).await
         }
+        ContainerAction::Pause { container } => pause_container(container).await,
+        ContainerAction::Unpause { container } => unpause_container(container).await,
+        ContainerAction::Update { container, limits } => {
+            update_container(container, limits.into_limits()).await
+        }
         ContainerAction::Remove {
             containers,
             force,
@@ -252,6 +506,25 @@ pub async fn run(cmd: ContainerCommand) -> Result<()> {
             containers,
             no_stream,
         } => show_stats(containers, no_stream).await,
+        ContainerAction::Diff { container } => show_diff(container).await,
+        ContainerAction::Commit {
+            container,
+            reference,
+            author,
+            message,
+            changes,
+            no_pause,
+        } => {
+            let req = CommitRequest {
+                reference,
+                author,
+                comment: message,
+                pause: !no_pause,
+                config: ContainerConfig::from_changes(&changes)?,
+            };
+            commit_container(container, req).await
+        }
+        ContainerAction::Export { container, output } => export_container(container, output).await,
         ContainerAction::Cp { source, dest } => copy_files(source, dest).await,
     }
 }
@@ -270,7 +543,13 @@ struct ContainerInfo {
     name: String,
 }
 
-async fn list_containers(all: bool, project: Option<String>, quiet: bool) -> Result<()> {
+pub async fn list_containers(
+    all: bool,
+    project: Option<String>,
+    quiet: bool,
+    filter: &[String],
+) -> Result<()> {
+    let filter = ContainerFilter::parse(filter)?;
     let client = DaemonClient::new();
 
     // Check if daemon is running
@@ -280,7 +559,7 @@ async fn list_containers(all: bool, project: Option<String>, quiet: bool) -> Res
         return Ok(());
     }
 
-    let containers = client.list_containers(all).await?;
+    let containers = client.list_containers(all, &filter).await?;
 
     // Filter by project if specified
     let containers: Vec<_> = if let Some(ref proj) = project {
@@ -340,6 +619,7 @@ async fn run_container(
     interactive: bool,
     tty: bool,
     workdir: Option<String>,
+    options: RunOptions,
     command: Vec<String>,
 ) -> Result<()> {
     let client = DaemonClient::new();
@@ -394,6 +674,19 @@ async fn run_container(
         } else {
             Some(command)
         },
+        cap_add: non_empty(options.cap_add),
+        cap_drop: non_empty(options.cap_drop),
+        devices: non_empty(options.device),
+        ulimits: non_empty(options.ulimit),
+        sysctls: if options.sysctl.is_empty() {
+            None
+        } else {
+            Some(options.sysctl.into_iter().collect())
+        },
+        shm_size: options.shm_size,
+        group_add: non_empty(options.group_add),
+        stop_signal: options.stop_signal,
+        init: options.init.then_some(true),
     };
 
     let container_id = client.create_container(req).await?;
@@ -411,6 +704,14 @@ async fn run_container(
     Ok(())
 }
 
+fn non_empty(values: Vec<String>) -> Option<Vec<String>> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values)
+    }
+}
+
 async fn start_container(container: String) -> Result<()> {
     let client = DaemonClient::new();
 
@@ -453,6 +754,61 @@ async fn restart_container(container: String, timeout: u64) -> Result<()> {
     Ok(())
 }
 
+async fn pause_container(container: String) -> Result<()> {
+    let client = DaemonClient::new();
+
+    if !client.is_running().await {
+        eprintln!("{} Daemon is not running. Start it with: hyperboxd", "✗".red());
+        return Err(anyhow::anyhow!("Daemon not running"));
+    }
+
+    client.pause_container(&container).await?;
+    println!("{} Container {} paused", "✓".green(), container.cyan());
+    Ok(())
+}
+
+async fn unpause_container(container: String) -> Result<()> {
+    let client = DaemonClient::new();
+
+    if !client.is_running().await {
+        eprintln!("{} Daemon is not running. Start it with: hyperboxd", "✗".red());
+        return Err(anyhow::anyhow!("Daemon not running"));
+    }
+
+    client.unpause_container(&container).await?;
+    println!("{} Container {} unpaused", "✓".green(), container.cyan());
+    Ok(())
+}
+
+async fn update_container(container: String, limits: ResourceLimits) -> Result<()> {
+    let client = DaemonClient::new();
+
+    if !client.is_running().await {
+        eprintln!("{} Daemon is not running. Start it with: hyperboxd", "✗".red());
+        return Err(anyhow::anyhow!("Daemon not running"));
+    }
+
+    if limits.set_fields().is_empty() {
+        anyhow::bail!("No resource limit given (see hb container update --help)");
+    }
+
+    let report = client
+        .update_container_resources(&container, &limits)
+        .await?;
+    println!("{} Container {} updated", "✓".green(), container.cyan());
+    if !report.applied.is_empty() {
+        println!("  Applied: {}", report.applied.join(", "));
+    }
+    if !report.unsupported.is_empty() {
+        println!(
+            "  {} Not supported by the runtime: {}",
+            "!".yellow(),
+            report.unsupported.join(", ")
+        );
+    }
+    Ok(())
+}
+
 async fn remove_containers(containers: Vec<String>, force: bool, volumes: bool) -> Result<()> {
     let client = DaemonClient::new();
 
@@ -618,8 +974,200 @@ async fn show_stats(containers: Vec<String>, no_stream: bool) -> Result<()> {
     Ok(())
 }
 
+async fn show_diff(container: String) -> Result<()> {
+    let client = DaemonClient::new();
+
+    if !client.is_running().await {
+        eprintln!("{} Daemon is not running. Start it with: hyperboxd", "✗".red());
+        return Err(anyhow::anyhow!("Daemon not running"));
+    }
+
+    for change in client.container_changes(&container).await? {
+        let kind = match change.kind {
+            ChangeKind::Added => change.kind.to_string().green(),
+            ChangeKind::Modified => change.kind.to_string().yellow(),
+            ChangeKind::Deleted => change.kind.to_string().red(),
+        };
+        println!("{} {}", kind, change.path);
+    }
+    Ok(())
+}
+
+async fn commit_container(container: String, req: CommitRequest) -> Result<()> {
+    let client = DaemonClient::new();
+
+    if !client.is_running().await {
+        eprintln!("{} Daemon is not running. Start it with: hyperboxd", "✗".red());
+        return Err(anyhow::anyhow!("Daemon not running"));
+    }
+
+    println!("{} Committing container {}...", "→".blue(), container.cyan());
+    let image_id = client.commit_container(&container, &req).await?;
+    match &req.reference {
+        Some(reference) => {
+            println!("{} Created image {} ({})", "✓".green(), reference.cyan(), image_id);
+        }
+        None => println!("{} Created image {}", "✓".green(), image_id),
+    }
+    Ok(())
+}
+
+async fn export_container(container: String, output: Option<PathBuf>) -> Result<()> {
+    if output.is_none() && std::io::stdout().is_terminal() {
+        anyhow::bail!(
+            "refusing to write an archive to a terminal; use --output or redirect stdout"
+        );
+    }
+
+    let client = DaemonClient::new();
+
+    if !client.is_running().await {
+        eprintln!("{} Daemon is not running. Start it with: hyperboxd", "✗".red());
+        return Err(anyhow::anyhow!("Daemon not running"));
+    }
+
+    let archive = client.export_container(&container).await?;
+    match output {
+        Some(path) => {
+            std::fs::write(&path, archive)?;
+            eprintln!(
+                "{} Exported {} to {}",
+                "✓".green(),
+                container.cyan(),
+                path.display().to_string().cyan()
+            );
+        }
+        None => std::io::stdout().write_all(&archive)?,
+    }
+    Ok(())
+}
+
+/// One side of `container cp`.
+#[derive(Debug, PartialEq)]
+enum CopyPath {
+    Host(PathBuf),
+    Container { container: String, path: String },
+}
+
+impl CopyPath {
+    /// Parse a `cp` operand. As with `docker cp`, a host path containing a
+    /// colon must start with `/` or `.`.
+    fn parse(arg: &str) -> Self {
+        if arg.starts_with('/') || arg.starts_with('.') {
+            return Self::Host(PathBuf::from(arg));
+        }
+        match arg.split_once(':') {
+            Some((container, path)) if !container.is_empty() => Self::Container {
+                container: container.to_string(),
+                path: path.to_string(),
+            },
+            _ => Self::Host(PathBuf::from(arg)),
+        }
+    }
+}
+
 async fn copy_files(source: String, dest: String) -> Result<()> {
-    println!("{} Copying {} to {}...", "→".blue(), source.cyan(), dest.cyan());
-    println!("{} Copy complete", "✓".green());
+    let client = DaemonClient::new();
+
+    if !client.is_running().await {
+        eprintln!("{} Daemon is not running. Start it with: hyperboxd", "✗".red());
+        return Err(anyhow::anyhow!("Daemon not running"));
+    }
+
+    match (CopyPath::parse(&source), CopyPath::parse(&dest)) {
+        (CopyPath::Container { container, path }, CopyPath::Host(dest)) => {
+            let archive = client.download_archive(&container, &path).await?;
+            if dest == Path::new("-") {
+                std::io::stdout().write_all(&archive)?;
+                return Ok(());
+            }
+            unpack_archive(&archive, &dest)?;
+        }
+        (CopyPath::Host(source), CopyPath::Container { container, path }) => {
+            if source == Path::new("-") {
+                let mut archive = Vec::new();
+                std::io::stdin().read_to_end(&mut archive)?;
+                client.upload_archive(&container, &path, archive).await?;
+                return Ok(());
+            }
+            upload_path(&client, &source, &container, &path).await?;
+        }
+        (CopyPath::Container { .. }, CopyPath::Container { .. }) => {
+            anyhow::bail!("copying between containers is not supported");
+        }
+        (CopyPath::Host(_), CopyPath::Host(_)) => {
+            anyhow::bail!("must specify at least one container path as CONTAINER:PATH");
+        }
+    }
+
+    println!("{} Copied {} to {}", "✓".green(), source.cyan(), dest.cyan());
+    Ok(())
+}
+
+/// Copy a host path into a container directory, or, if the destination is
+/// not a directory, to the destination path itself.
+async fn upload_path(
+    client: &DaemonClient,
+    source: &Path,
+    container: &str,
+    path: &str,
+) -> Result<()> {
+    let name = source
+        .file_name()
+        .map_or_else(|| OsString::from("."), ToOwned::to_owned);
+    let err = match client
+        .upload_archive(container, path, pack_archive(source, &name)?)
+        .await
+    {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+
+    let target = Path::new(path);
+    match (path.ends_with('/'), target.parent(), target.file_name()) {
+        (false, Some(parent), Some(name)) => {
+            let archive = pack_archive(source, name)?;
+            client
+                .upload_archive(container, &parent.to_string_lossy(), archive)
+                .await
+                .map_err(|_| err)
+        }
+        _ => Err(err),
+    }
+}
+
+/// Archive a host path under `name`, without following symlinks.
+fn pack_archive(source: &Path, name: &OsStr) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.follow_symlinks(false);
+    if std::fs::symlink_metadata(source)?.is_dir() {
+        builder.append_dir_all(name, source)?;
+    } else {
+        builder.append_path_with_name(source, name)?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+/// Unpack an archive from a container into an existing directory, or, if
+/// `dest` is not a directory, rename its top-level entry to `dest`.
+fn unpack_archive(archive: &[u8], dest: &Path) -> Result<()> {
+    let mut archive = tar::Archive::new(archive);
+    if dest.is_dir() {
+        archive.unpack(dest)?;
+        return Ok(());
+    }
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let mut target = dest.to_path_buf();
+        for component in path.components().skip(1) {
+            match component {
+                Component::Normal(part) => target.push(part),
+                _ => anyhow::bail!("invalid path in archive: {}", path.display()),
+            }
+        }
+        entry.unpack(&target)?;
+    }
     Ok(())
 }
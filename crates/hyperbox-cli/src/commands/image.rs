@@ -4,10 +4,14 @@ use anyhow::Result;
 use clap::{Args, Subcommand};
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
+use std::io::Read;
+use std::path::PathBuf;
 use std::time::Duration;
 use tabled::{Table, Tabled};
 
-use crate::client::DaemonClient;
+use crate::client::{DaemonClient, ImportRequest};
+use hyperbox_core::storage::ContainerConfig;
+use hyperbox_core::types::{ImageRef, Platform};
 
 /// Image management commands.
 #[derive(Args)]
@@ -85,6 +89,25 @@ pub enum ImageAction {
         pull: bool,
     },
 
+    /// Create an image from a root filesystem tar archive
+    Import {
+        /// Archive path (optionally gzip-compressed), or `-` for stdin
+        file: PathBuf,
+
+        /// Image reference for the new image (e.g. myapp:dev)
+        reference: Option<String>,
+
+        /// Import message
+        #[arg(short, long)]
+        message: Option<String>,
+
+        /// Apply a Dockerfile instruction to the image configuration
+        /// (CMD, ENTRYPOINT, ENV, EXPOSE, LABEL, STOPSIGNAL, USER, VOLUME,
+        /// WORKDIR)
+        #[arg(short, long = "change", value_name = "INSTRUCTION")]
+        changes: Vec<String>,
+    },
+
     /// Remove images
     #[command(alias = "rm")]
     Remove {
@@ -151,6 +174,21 @@ pub async fn run(cmd: ImageCommand) -> Result<()> {
             no_cache,
             pull,
         } => build_image(path, tag, file, build_arg, target, no_cache, pull).await,
+        ImageAction::Import {
+            file,
+            reference,
+            message,
+            changes,
+        } => {
+            // Reject bad instructions before uploading the archive.
+            ContainerConfig::from_changes(&changes)?;
+            let req = ImportRequest {
+                reference,
+                comment: message,
+                changes,
+            };
+            import_image(file, req).await
+        }
         ImageAction::Remove { images, force } => remove_images(images, force).await,
         ImageAction::Inspect { image } => inspect_image(image).await,
         ImageAction::History { image, no_trunc } => show_history(image, no_trunc).await,
@@ -205,7 +243,11 @@ async fn list_images(all: bool, quiet: bool) -> Result<()> {
     Ok(())
 }
 
-async fn pull_image(image: String, all_tags: bool, platform: Option<String>) -> Result<()> {
+pub async fn pull_image(image: String, all_tags: bool, platform: Option<String>) -> Result<()> {
+    let platform = platform
+        .as_deref()
+        .map(str::parse::<Platform>)
+        .transpose()?;
     let client = DaemonClient::new();
 
     if !client.is_running().await {
@@ -213,7 +255,10 @@ async fn pull_image(image: String, all_tags: bool, platform: Option<String>) ->
         return Ok(());
     }
 
-    println!("{} Pulling {}...", "→".blue(), image.cyan());
+    match &platform {
+        Some(platform) => println!("{} Pulling {} for {}...", "→".blue(), image.cyan(), platform),
+        None => println!("{} Pulling {}...", "→".blue(), image.cyan()),
+    }
 
     let pb = ProgressBar::new_spinner();
     pb.set_style(
@@ -224,7 +269,7 @@ async fn pull_image(image: String, all_tags: bool, platform: Option<String>) ->
     pb.set_message("Pulling image layers...");
     pb.enable_steady_tick(Duration::from_millis(80));
 
-    match client.pull_image(&image).await {
+    match client.pull_image(&image, platform.as_ref()).await {
         Ok(_) => {
             pb.finish_and_clear();
             println!("{} Pulled {} successfully", "✓".green(), image.cyan());
@@ -238,25 +283,57 @@ async fn pull_image(image: String, all_tags: bool, platform: Option<String>) ->
     Ok(())
 }
 
-async fn push_image(image: String, all_tags: bool) -> Result<()> {
-    println!("{} Pushing {}...", "→".blue(), image.cyan());
-
-    let pb = ProgressBar::new(100);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>3}/{len:3}")
-            .unwrap()
-            .progress_chars("█▓▒░"),
-    );
+pub async fn push_image(image: String, all_tags: bool) -> Result<()> {
+    let client = DaemonClient::new();
 
-    for i in 0..=100 {
-        pb.set_position(i);
-        tokio::time::sleep(Duration::from_millis(20)).await;
+    if !client.is_running().await {
+        eprintln!("{} Daemon is not running. Start it with: hyperboxd", "✗".red());
+        return Ok(());
     }
 
-    pb.finish_with_message("Done");
+    let references = if all_tags {
+        let target = ImageRef::parse(&image);
+        let mut references: Vec<String> = client
+            .list_images()
+            .await?
+            .into_iter()
+            .flat_map(|img| img.tags)
+            .filter(|tag| {
+                let other = ImageRef::parse(tag);
+                other.registry == target.registry && other.repository == target.repository
+            })
+            .collect();
+        references.sort();
+        references.dedup();
+        if references.is_empty() {
+            anyhow::bail!("No local tags for {image}");
+        }
+        references
+    } else {
+        vec![image]
+    };
 
-    println!("{} Pushed {}", "✓".green(), image.cyan());
+    for reference in references {
+        println!("{} Pushing {}...", "→".blue(), reference.cyan());
+
+        let pb = ProgressBar::new_spinner();
+        pb.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.cyan} {msg}")
+                .unwrap(),
+        );
+        pb.set_message("Uploading image layers...");
+        pb.enable_steady_tick(Duration::from_millis(80));
+
+        let pushed = client.push_image(&reference).await;
+        pb.finish_and_clear();
+        match pushed {
+            Ok(digest) => {
+                println!("{} Pushed {} ({})", "✓".green(), reference.cyan(), digest.dimmed());
+            }
+            Err(e) => eprintln!("{} Failed to push {}: {}", "✗".red(), reference, e),
+        }
+    }
 
     Ok(())
 }
@@ -304,6 +381,33 @@ async fn build_image(
     Ok(())
 }
 
+async fn import_image(file: PathBuf, req: ImportRequest) -> Result<()> {
+    let client = DaemonClient::new();
+
+    if !client.is_running().await {
+        eprintln!("{} Daemon is not running. Start it with: hyperboxd", "✗".red());
+        return Err(anyhow::anyhow!("Daemon not running"));
+    }
+
+    let archive = if file.as_os_str() == "-" {
+        let mut archive = Vec::new();
+        std::io::stdin().read_to_end(&mut archive)?;
+        archive
+    } else {
+        std::fs::read(&file)?
+    };
+
+    println!("{} Importing {}...", "→".blue(), file.display().to_string().cyan());
+    let image_id = client.import_image(archive, &req).await?;
+    match &req.reference {
+        Some(reference) => {
+            println!("{} Created image {} ({})", "✓".green(), reference.cyan(), image_id);
+        }
+        None => println!("{} Created image {}", "✓".green(), image_id),
+    }
+    Ok(())
+}
+
 async fn remove_images(images: Vec<String>, force: bool) -> Result<()> {
     let client = DaemonClient::new();
 
@@ -0,0 +1,97 @@
+//! Registry login and logout.
+//!
+//! Credentials are checked and saved by the daemon, which pulls and pushes
+//! with them.
+
+use anyhow::Result;
+use clap::Args;
+use colored::*;
+use std::io::Read;
+
+use crate::client::DaemonClient;
+use hyperbox_core::storage::ImageRegistry;
+
+/// Log in to a container registry.
+#[derive(Args)]
+pub struct LoginCommand {
+    /// Registry server (defaults to Docker Hub)
+    pub server: Option<String>,
+
+    /// Username
+    #[arg(short, long)]
+    pub username: Option<String>,
+
+    /// Password or access token
+    #[arg(short, long, conflicts_with = "password_stdin")]
+    pub password: Option<String>,
+
+    /// Read the password from stdin
+    #[arg(long)]
+    pub password_stdin: bool,
+}
+
+/// Log out from a container registry.
+#[derive(Args)]
+pub struct LogoutCommand {
+    /// Registry server (defaults to Docker Hub)
+    pub server: Option<String>,
+}
+
+pub async fn login(cmd: LoginCommand) -> Result<()> {
+    let client = connect().await?;
+    let server = cmd.server.unwrap_or_default();
+
+    let username = match cmd.username {
+        Some(username) => username,
+        None => dialoguer::Input::new()
+            .with_prompt("Username")
+            .interact_text()?,
+    };
+    let password = if cmd.password_stdin {
+        let mut password = String::new();
+        std::io::stdin().read_to_string(&mut password)?;
+        password.trim_end_matches(['\r', '\n']).to_string()
+    } else if let Some(password) = cmd.password {
+        eprintln!(
+            "{} Using --password on the command line is insecure. Use --password-stdin.",
+            "!".yellow()
+        );
+        password
+    } else {
+        dialoguer::Password::new()
+            .with_prompt("Password")
+            .interact()?
+    };
+    if username.is_empty() || password.is_empty() {
+        anyhow::bail!("Username and password are required");
+    }
+
+    let config = client.login(&server, &username, &password).await?;
+
+    println!("{} Login succeeded", "✓".green());
+    println!("{} Credentials saved in {}", "→".blue(), config);
+    Ok(())
+}
+
+pub async fn logout(cmd: LogoutCommand) -> Result<()> {
+    let client = connect().await?;
+    let server = cmd.server.unwrap_or_default();
+    let registry_url = ImageRegistry::registry_url(&server);
+
+    if client.logout(&server).await? {
+        println!("{} Removed login credentials for {}", "✓".green(), registry_url);
+    } else {
+        println!("{} Not logged in to {}", "→".blue(), registry_url);
+    }
+    Ok(())
+}
+
+/// A client for the running daemon.
+async fn connect() -> Result<DaemonClient> {
+    let client = DaemonClient::new();
+    if !client.is_running().await {
+        eprintln!("{} Daemon is not running. Start it with: hyperboxd", "✗".red());
+        anyhow::bail!("Daemon not running");
+    }
+    Ok(client)
+}
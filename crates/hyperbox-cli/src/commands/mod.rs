@@ -6,6 +6,7 @@ pub mod compat;
 pub mod completion;
 pub mod container;
 pub mod image;
+pub mod login;
 pub mod project;
 pub mod system;
 
@@ -67,6 +68,12 @@ pub enum Commands {
     #[command(alias = "i")]
     Image(image::ImageCommand),
 
+    /// Log in to a container registry
+    Login(login::LoginCommand),
+
+    /// Log out from a container registry
+    Logout(login::LogoutCommand),
+
     /// System commands
     #[command(alias = "sys")]
     System(system::SystemCommand),
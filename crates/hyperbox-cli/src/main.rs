@@ -35,6 +35,8 @@ async fn main() -> Result<()> {
         Commands::Project(cmd) => commands::project::run(cmd).await,
         Commands::Container(cmd) => commands::container::run(cmd).await,
         Commands::Image(cmd) => commands::image::run(cmd).await,
+        Commands::Login(cmd) => commands::login::login(cmd).await,
+        Commands::Logout(cmd) => commands::login::logout(cmd).await,
         Commands::System(cmd) => commands::system::run(cmd).await,
         Commands::Completion(cmd) => commands::completion::run(cmd),
         Commands::Docker(cmd) => {
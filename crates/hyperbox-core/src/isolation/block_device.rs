@@ -0,0 +1,372 @@
+//! Block device resolution for cgroup v2 IO control.
+//!
+//! `io.max` and per-device `io.weight` entries are keyed by the `MAJ:MIN` of
+//! a whole disk. [`BlockDeviceResolver`] finds the disk(s) behind a path: the
+//! mount holding it comes from `/proc/self/mountinfo`, overlay mounts are
+//! followed to their upper and lower directories, and partitions are mapped
+//! to their parent disk through `/sys/dev/block`.
+
+use crate::error::{CoreError, Result};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Mount table of the current process.
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+/// sysfs directory with one entry per block device number.
+const SYS_DEV_BLOCK: &str = "/sys/dev/block";
+/// Limit on nested overlay mounts followed while resolving a path.
+const MAX_OVERLAY_DEPTH: usize = 8;
+
+/// A block device number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockDevice {
+    /// Major number
+    pub major: u32,
+    /// Minor number
+    pub minor: u32,
+}
+
+impl BlockDevice {
+    /// Parse the `MAJ:MIN` form used by mountinfo and sysfs.
+    fn parse(s: &str) -> Option<Self> {
+        let (major, minor) = s.trim().split_once(':')?;
+        Some(Self {
+            major: major.parse().ok()?,
+            minor: minor.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for BlockDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.major, self.minor)
+    }
+}
+
+/// One line of `/proc/self/mountinfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MountEntry {
+    device: BlockDevice,
+    mount_point: PathBuf,
+    fs_type: String,
+    source: String,
+    super_options: String,
+}
+
+/// Finds the whole-disk block devices backing a path.
+#[derive(Debug, Clone)]
+pub struct BlockDeviceResolver {
+    mountinfo: PathBuf,
+    sys_dev_block: PathBuf,
+}
+
+impl BlockDeviceResolver {
+    /// Create a resolver reading the host's mountinfo and sysfs.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_paths(MOUNTINFO, SYS_DEV_BLOCK)
+    }
+
+    /// Create a resolver reading a custom mountinfo file and
+    /// `/sys/dev/block` directory.
+    #[must_use]
+    pub fn with_paths(mountinfo: impl Into<PathBuf>, sys_dev_block: impl Into<PathBuf>) -> Self {
+        Self {
+            mountinfo: mountinfo.into(),
+            sys_dev_block: sys_dev_block.into(),
+        }
+    }
+
+    /// Whole-disk devices backing `path`, sorted and deduplicated.
+    ///
+    /// The result is empty when the path lives on a filesystem without a
+    /// block device, such as tmpfs.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::CgroupOperation`] if the mount table cannot be
+    /// read.
+    pub fn resolve(&self, path: &Path) -> Result<Vec<BlockDevice>> {
+        let content =
+            fs::read_to_string(&self.mountinfo).map_err(|e| CoreError::CgroupOperation {
+                operation: "read mountinfo".to_string(),
+                reason: e.to_string(),
+            })?;
+        let mounts = parse_mountinfo(&content);
+
+        let mut devices = Vec::new();
+        self.collect(&mounts, &canonical_prefix(path), 0, &mut devices);
+        devices.sort_unstable();
+        devices.dedup();
+        Ok(devices)
+    }
+
+    fn collect(
+        &self,
+        mounts: &[MountEntry],
+        path: &Path,
+        depth: usize,
+        devices: &mut Vec<BlockDevice>,
+    ) {
+        let Some(mount) = mount_containing(mounts, path) else {
+            return;
+        };
+
+        if mount.fs_type == "overlay" && depth < MAX_OVERLAY_DEPTH {
+            for dir in overlay_dirs(&mount.super_options) {
+                self.collect(mounts, &dir, depth + 1, devices);
+            }
+            return;
+        }
+
+        // Filesystems such as btrfs report an anonymous device; the
+        // mount source still names the real one.
+        let device = if mount.device.major == 0 {
+            source_device(&mount.source)
+        } else {
+            Some(mount.device)
+        };
+        if let Some(disk) = device.and_then(|device| self.whole_disk(device)) {
+            devices.push(disk);
+        }
+    }
+
+    /// Map a partition to its disk; `None` for devices sysfs doesn't know.
+    fn whole_disk(&self, device: BlockDevice) -> Option<BlockDevice> {
+        let entry = self.sys_dev_block.join(device.to_string());
+        if !entry.exists() {
+            return None;
+        }
+        if !entry.join("partition").exists() {
+            return Some(device);
+        }
+
+        let disk = fs::canonicalize(&entry).ok()?.parent()?.join("dev");
+        BlockDevice::parse(&fs::read_to_string(disk).ok()?)
+    }
+}
+
+impl Default for BlockDeviceResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_mountinfo(content: &str) -> Vec<MountEntry> {
+    content.lines().filter_map(parse_mount_entry).collect()
+}
+
+/// Parse `ID PARENT MAJ:MIN ROOT MOUNT_POINT OPTIONS [OPTIONAL...] - TYPE SOURCE SUPER_OPTIONS`.
+fn parse_mount_entry(line: &str) -> Option<MountEntry> {
+    let (fields, rest) = line.split_once(" - ")?;
+    let fields: Vec<&str> = fields.split(' ').collect();
+    let mut rest = rest.split(' ');
+
+    Some(MountEntry {
+        device: BlockDevice::parse(fields.get(2)?)?,
+        mount_point: PathBuf::from(unescape(fields.get(4)?)),
+        fs_type: rest.next()?.to_string(),
+        source: unescape(rest.next()?),
+        super_options: unescape(rest.next().unwrap_or_default()),
+    })
+}
+
+/// Undo the octal escaping (`\040` for a space) the kernel applies to paths.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        if let Some(byte) = escaped {
+            out.push(byte);
+            i += 4;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The mount `path` lives on: the deepest containing mount point, and the
+/// last one listed when several share it.
+fn mount_containing<'a>(mounts: &'a [MountEntry], path: &Path) -> Option<&'a MountEntry> {
+    mounts
+        .iter()
+        .filter(|mount| path.starts_with(&mount.mount_point))
+        .max_by_key(|mount| mount.mount_point.components().count())
+}
+
+/// Upper directory followed by the lower directories of an overlay mount.
+fn overlay_dirs(super_options: &str) -> Vec<PathBuf> {
+    let mut upper = Vec::new();
+    let mut lower = Vec::new();
+    for option in super_options.split(',') {
+        if let Some(dir) = option.strip_prefix("upperdir=") {
+            upper.push(PathBuf::from(dir));
+        } else if let Some(dirs) = option.strip_prefix("lowerdir=") {
+            lower.extend(dirs.split(':').map(PathBuf::from));
+        }
+    }
+    upper.extend(lower);
+    upper
+}
+
+/// Canonicalize the longest existing prefix of `path`, keeping the rest.
+fn canonical_prefix(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = fs::canonicalize(existing) {
+            return rest
+                .iter()
+                .rev()
+                .fold(canonical, |acc, name| acc.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// Device number of a `/dev` mount source.
+#[cfg(target_os = "linux")]
+fn source_device(source: &str) -> Option<BlockDevice> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    if !source.starts_with("/dev/") {
+        return None;
+    }
+    let metadata = fs::metadata(source).ok()?;
+    if !metadata.file_type().is_block_device() {
+        return None;
+    }
+    Some(BlockDevice {
+        major: u32::try_from(nix::sys::stat::major(metadata.rdev())).ok()?,
+        minor: u32::try_from(nix::sys::stat::minor(metadata.rdev())).ok()?,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn source_device(_source: &str) -> Option<BlockDevice> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MOUNTINFO: &str = "\
+22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/sda2 rw
+23 22 0:21 / /proc rw,nosuid - proc proc rw
+24 22 8:17 / /hyperbox-test/data\\040disk rw,relatime shared:5 master:1 - xfs /dev/sdb1 rw
+25 22 0:30 / /hyperbox-test/tmp rw - tmpfs tmpfs rw,size=1024k
+26 22 0:31 / /hyperbox-test/merged rw - overlay overlay rw,lowerdir=/hyperbox-test/data\\040disk/l1:/hyperbox-test/l2,upperdir=/hyperbox-test/tmp/upper,workdir=/hyperbox-test/tmp/work
+27 22 253:0 / /hyperbox-test/mapper rw - ext4 /dev/mapper/vg-data rw
+";
+
+    /// Fake `/sys/dev/block` with disks sda (8:0) and sdb (8:16), their
+    /// partitions sda2 (8:2) and sdb1 (8:17), and no entry for dm-0.
+    fn fake_sysfs(root: &Path) -> PathBuf {
+        let devices = root.join("devices");
+        let dev_block = root.join("dev/block");
+        std::fs::create_dir_all(&dev_block).unwrap();
+
+        for (disk, disk_dev, part, part_dev) in [
+            ("sda", "8:0", "sda2", "8:2"),
+            ("sdb", "8:16", "sdb1", "8:17"),
+        ] {
+            let disk_dir = devices.join(disk);
+            let part_dir = disk_dir.join(part);
+            std::fs::create_dir_all(&part_dir).unwrap();
+            std::fs::write(disk_dir.join("dev"), format!("{disk_dev}\n")).unwrap();
+            std::fs::write(part_dir.join("dev"), format!("{part_dev}\n")).unwrap();
+            std::fs::write(part_dir.join("partition"), "1\n").unwrap();
+            std::os::unix::fs::symlink(&disk_dir, dev_block.join(disk_dev)).unwrap();
+            std::os::unix::fs::symlink(&part_dir, dev_block.join(part_dev)).unwrap();
+        }
+
+        dev_block
+    }
+
+    fn resolver(temp: &TempDir) -> BlockDeviceResolver {
+        let mountinfo = temp.path().join("mountinfo");
+        std::fs::write(&mountinfo, MOUNTINFO).unwrap();
+        BlockDeviceResolver::with_paths(mountinfo, fake_sysfs(temp.path()))
+    }
+
+    const fn dev(major: u32, minor: u32) -> BlockDevice {
+        BlockDevice { major, minor }
+    }
+
+    #[test]
+    fn test_parse_mountinfo() {
+        let mounts = parse_mountinfo(MOUNTINFO);
+        assert_eq!(mounts.len(), 6);
+
+        let data = &mounts[2];
+        assert_eq!(data.device, dev(8, 17));
+        assert_eq!(data.mount_point, PathBuf::from("/hyperbox-test/data disk"));
+        assert_eq!(data.fs_type, "xfs");
+        assert_eq!(data.source, "/dev/sdb1");
+        assert_eq!(dev(8, 17).to_string(), "8:17");
+    }
+
+    #[test]
+    fn test_partition_maps_to_disk() {
+        let temp = TempDir::new().unwrap();
+        let resolver = resolver(&temp);
+
+        let devices = resolver
+            .resolve(Path::new("/hyperbox-test/data disk/containers/abc"))
+            .unwrap();
+        assert_eq!(devices, vec![dev(8, 16)]);
+
+        let devices = resolver.resolve(Path::new("/hyperbox-test/other")).unwrap();
+        assert_eq!(devices, vec![dev(8, 0)]);
+    }
+
+    #[test]
+    fn test_overlay_follows_upper_and_lower_dirs() {
+        let temp = TempDir::new().unwrap();
+        let devices = resolver(&temp)
+            .resolve(Path::new("/hyperbox-test/merged/usr"))
+            .unwrap();
+
+        // upperdir is on tmpfs; the lower dirs are on sdb1 and the root disk.
+        assert_eq!(devices, vec![dev(8, 0), dev(8, 16)]);
+    }
+
+    #[test]
+    fn test_devices_without_sysfs_entry_are_skipped() {
+        let temp = TempDir::new().unwrap();
+        let resolver = resolver(&temp);
+
+        assert!(resolver
+            .resolve(Path::new("/hyperbox-test/tmp/x"))
+            .unwrap()
+            .is_empty());
+        assert!(resolver
+            .resolve(Path::new("/hyperbox-test/mapper/x"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_missing_mountinfo() {
+        let temp = TempDir::new().unwrap();
+        let resolver = BlockDeviceResolver::with_paths(temp.path().join("missing"), temp.path());
+        assert!(resolver.resolve(Path::new("/")).is_err());
+    }
+}
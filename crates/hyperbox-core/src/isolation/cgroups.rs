@@ -1,10 +1,31 @@
 //! cgroups v2 management for resource isolation.
 
 use crate::error::{CoreError, Result};
-use crate::types::ResourceLimits;
+use crate::isolation::block_device::BlockDeviceResolver;
+use crate::runtime::{cpu_quota, CPU_PERIOD_US};
+use crate::types::{ResourceEvents, ResourceLimits};
+#[cfg(target_os = "linux")]
+use futures::stream::{BoxStream, StreamExt};
+#[cfg(target_os = "linux")]
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+#[cfg(target_os = "linux")]
+use std::os::fd::{AsFd, AsRawFd, RawFd};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::fs;
-use tracing::{debug, instrument};
+#[cfg(target_os = "linux")]
+use tokio::io::unix::AsyncFd;
+use tokio::time::Instant;
+use tracing::{debug, instrument, warn};
+
+/// How long to wait for the kernel to report a freezer state change.
+const FREEZE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Interval between `cgroup.events` reads while waiting on the freezer.
+const FREEZE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Valid range of `cpu.weight`.
+const CPU_WEIGHT_RANGE: std::ops::RangeInclusive<u16> = 1..=10_000;
+/// Valid range of `io.weight`.
+const IO_WEIGHT_RANGE: std::ops::RangeInclusive<u16> = 1..=10_000;
 
 /// cgroups v2 manager for container resource isolation.
 pub struct CgroupManager {
@@ -12,6 +33,8 @@ pub struct CgroupManager {
     base_path: PathBuf,
     /// HyperBox slice path
     hyperbox_slice: PathBuf,
+    /// Finds the disks that IO limits apply to
+    block_devices: BlockDeviceResolver,
 }
 
 impl CgroupManager {
@@ -29,6 +52,7 @@ impl CgroupManager {
         Self {
             base_path,
             hyperbox_slice,
+            block_devices: BlockDeviceResolver::new(),
         }
     }
 
@@ -41,9 +65,17 @@ impl CgroupManager {
         Self {
             base_path,
             hyperbox_slice,
+            block_devices: BlockDeviceResolver::new(),
         }
     }
 
+    /// Use a custom resolver for the disks behind a container's storage.
+    #[must_use]
+    pub fn with_block_device_resolver(mut self, resolver: BlockDeviceResolver) -> Self {
+        self.block_devices = resolver;
+        self
+    }
+
     /// Initialize the HyperBox cgroup slice.
     #[instrument(skip(self))]
     pub async fn initialize(&self) -> Result<()> {
@@ -84,7 +116,7 @@ impl CgroupManager {
 
     /// Enable controllers in a cgroup.
     async fn enable_controllers(&self, path: &Path) -> Result<()> {
-        let controllers = "+cpu +memory +io +pids";
+        let controllers = "+cpu +cpuset +memory +io +pids";
         let subtree_control = path.join("cgroup.subtree_control");
 
         fs::write(&subtree_control, controllers)
@@ -97,10 +129,17 @@ impl CgroupManager {
         Ok(())
     }
 
+    /// Path of the cgroup that holds a container's processes.
+    #[must_use]
+    pub fn container_cgroup_path(&self, container_id: &str) -> PathBuf {
+        self.hyperbox_slice
+            .join(format!("container-{container_id}"))
+    }
+
     /// Create a cgroup for a container.
     #[instrument(skip(self))]
     pub async fn create_container_cgroup(&self, container_id: &str) -> Result<PathBuf> {
-        let cgroup_path = self.hyperbox_slice.join(format!("container-{container_id}"));
+        let cgroup_path = self.container_cgroup_path(container_id);
 
         fs::create_dir_all(&cgroup_path).await.map_err(|e| {
             CoreError::CgroupOperation {
@@ -114,8 +153,16 @@ impl CgroupManager {
     }
 
     /// Apply resource limits to a container cgroup.
+    ///
+    /// IO limits are set on every disk backing `storage_path`, normally the
+    /// container's root filesystem.
     #[instrument(skip(self, limits))]
-    pub async fn apply_limits(&self, cgroup_path: &Path, limits: &ResourceLimits) -> Result<()> {
+    pub async fn apply_limits(
+        &self,
+        cgroup_path: &Path,
+        storage_path: &Path,
+        limits: &ResourceLimits,
+    ) -> Result<()> {
         // Memory limits
         if let Some(memory) = limits.memory_bytes {
             let memory_max = cgroup_path.join("memory.max");
@@ -142,10 +189,9 @@ impl CgroupManager {
         if let Some(cpu) = limits.cpu_millicores {
             // cpu.max format: "quota period" in microseconds
             // 1000 millicores = 1 CPU = 100000us quota per 100000us period
-            let quota = (cpu * 100) as u64; // microseconds
-            let period = 100_000u64;
+            let quota = cpu_quota(cpu)?;
             let cpu_max = cgroup_path.join("cpu.max");
-            fs::write(&cpu_max, format!("{quota} {period}"))
+            fs::write(&cpu_max, format!("{quota} {CPU_PERIOD_US}"))
                 .await
                 .map_err(|e| CoreError::CgroupOperation {
                     operation: "set cpu limit".to_string(),
@@ -153,6 +199,38 @@ impl CgroupManager {
                 })?;
         }
 
+        // CPU weight
+        if let Some(weight) = limits.cpu_weight {
+            if !CPU_WEIGHT_RANGE.contains(&weight) {
+                return Err(CoreError::CgroupOperation {
+                    operation: "set cpu weight".to_string(),
+                    reason: format!(
+                        "weight {weight} is outside {}-{}",
+                        CPU_WEIGHT_RANGE.start(),
+                        CPU_WEIGHT_RANGE.end()
+                    ),
+                });
+            }
+            let cpu_weight = cgroup_path.join("cpu.weight");
+            fs::write(&cpu_weight, weight.to_string())
+                .await
+                .map_err(|e| CoreError::CgroupOperation {
+                    operation: "set cpu weight".to_string(),
+                    reason: e.to_string(),
+                })?;
+        }
+
+        // CPU pinning
+        if let Some(cpus) = &limits.cpuset_cpus {
+            let cpuset_cpus = cgroup_path.join("cpuset.cpus");
+            fs::write(&cpuset_cpus, cpus)
+                .await
+                .map_err(|e| CoreError::CgroupOperation {
+                    operation: "set cpuset".to_string(),
+                    reason: e.to_string(),
+                })?;
+        }
+
         // PIDs limit
         if let Some(pids) = limits.pids_limit {
             let pids_max = cgroup_path.join("pids.max");
@@ -164,22 +242,50 @@ impl CgroupManager {
                 })?;
         }
 
-        // IO limits
-        if limits.io_read_bps.is_some() || limits.io_write_bps.is_some() {
-            // Would need device major:minor for io.max
-            // This is simplified; production would query devices
-            let io_max = cgroup_path.join("io.max");
-            let mut io_config = String::new();
-
-            if let Some(read_bps) = limits.io_read_bps {
-                io_config.push_str(&format!("rbps={read_bps} "));
+        // IO weight
+        if let Some(weight) = limits.io_weight {
+            if !IO_WEIGHT_RANGE.contains(&weight) {
+                return Err(CoreError::CgroupOperation {
+                    operation: "set io weight".to_string(),
+                    reason: format!(
+                        "weight {weight} is outside {}-{}",
+                        IO_WEIGHT_RANGE.start(),
+                        IO_WEIGHT_RANGE.end()
+                    ),
+                });
             }
-            if let Some(write_bps) = limits.io_write_bps {
-                io_config.push_str(&format!("wbps={write_bps}"));
+            let io_weight = cgroup_path.join("io.weight");
+            fs::write(&io_weight, format!("default {weight}"))
+                .await
+                .map_err(|e| CoreError::CgroupOperation {
+                    operation: "set io weight".to_string(),
+                    reason: e.to_string(),
+                })?;
+        }
+
+        // IO limits, keyed by the disks backing the container's storage
+        if let Some(settings) = io_max_settings(limits) {
+            let resolver = self.block_devices.clone();
+            let storage = storage_path.to_path_buf();
+            let devices = tokio::task::spawn_blocking(move || resolver.resolve(&storage))
+                .await
+                .map_err(|e| CoreError::CgroupOperation {
+                    operation: "resolve block devices".to_string(),
+                    reason: e.to_string(),
+                })??;
+            if devices.is_empty() {
+                warn!("No block device backs {:?}; IO limits not applied", storage_path);
             }
 
-            // Note: This would need device specification in real implementation
-            debug!("IO limits would be: {}", io_config);
+            let io_max = cgroup_path.join("io.max");
+            for device in devices {
+                fs::write(&io_max, format!("{device} {settings}"))
+                    .await
+                    .map_err(|e| CoreError::CgroupOperation {
+                        operation: format!("set io limit on {device}"),
+                        reason: e.to_string(),
+                    })?;
+            }
         }
 
         debug!("Applied resource limits to {:?}", cgroup_path);
@@ -199,6 +305,128 @@ impl CgroupManager {
         Ok(())
     }
 
+    /// Freeze every process in a cgroup.
+    ///
+    /// Returns once `cgroup.events` reports `frozen 1`. If the kernel does
+    /// not finish freezing in time the cgroup is thawed again so it is not
+    /// left half frozen.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the freezer cannot be written or the cgroup does
+    /// not become frozen.
+    #[instrument(skip(self))]
+    pub async fn freeze(&self, cgroup_path: &Path) -> Result<()> {
+        if let Err(e) = self.set_frozen(cgroup_path, true).await {
+            if let Err(thaw) = fs::write(cgroup_path.join("cgroup.freeze"), "0").await {
+                warn!("Failed to thaw {:?} after freeze error: {}", cgroup_path, thaw);
+            }
+            return Err(e);
+        }
+        debug!("Froze cgroup {:?}", cgroup_path);
+        Ok(())
+    }
+
+    /// Thaw a frozen cgroup.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the freezer cannot be written or the cgroup does
+    /// not leave the frozen state.
+    #[instrument(skip(self))]
+    pub async fn thaw(&self, cgroup_path: &Path) -> Result<()> {
+        self.set_frozen(cgroup_path, false).await?;
+        debug!("Thawed cgroup {:?}", cgroup_path);
+        Ok(())
+    }
+
+    /// Whether the kernel reports a cgroup as frozen.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `cgroup.events` cannot be read.
+    pub async fn is_frozen(&self, cgroup_path: &Path) -> Result<bool> {
+        let events = fs::read_to_string(cgroup_path.join("cgroup.events"))
+            .await
+            .map_err(|e| CoreError::CgroupOperation {
+                operation: "read cgroup events".to_string(),
+                reason: e.to_string(),
+            })?;
+
+        Ok(events.lines().any(|line| {
+            let mut parts = line.split_whitespace();
+            parts.next() == Some("frozen") && parts.next() == Some("1")
+        }))
+    }
+
+    /// Write `cgroup.freeze` and wait for `cgroup.events` to agree.
+    async fn set_frozen(&self, cgroup_path: &Path, frozen: bool) -> Result<()> {
+        let operation = if frozen {
+            "freeze cgroup"
+        } else {
+            "thaw cgroup"
+        };
+
+        fs::write(cgroup_path.join("cgroup.freeze"), if frozen { "1" } else { "0" })
+            .await
+            .map_err(|e| CoreError::CgroupOperation {
+                operation: operation.to_string(),
+                reason: e.to_string(),
+            })?;
+
+        let deadline = Instant::now() + FREEZE_TIMEOUT;
+        while self.is_frozen(cgroup_path).await? != frozen {
+            if Instant::now() >= deadline {
+                return Err(CoreError::CgroupOperation {
+                    operation: operation.to_string(),
+                    reason: format!(
+                        "cgroup did not report frozen {} within {:?}",
+                        u8::from(frozen),
+                        FREEZE_TIMEOUT
+                    ),
+                });
+            }
+            tokio::time::sleep(FREEZE_POLL_INTERVAL).await;
+        }
+
+        Ok(())
+    }
+
+    /// Read the resource limit event counters of a cgroup.
+    ///
+    /// Counters of controllers that are not enabled read as zero.
+    pub async fn read_events(&self, cgroup_path: &Path) -> ResourceEvents {
+        read_resource_events(cgroup_path).await
+    }
+
+    /// Watch a cgroup's `memory.events` and `pids.events` with inotify.
+    ///
+    /// The stream yields the current counters first and then again every
+    /// time the kernel changes them. It ends once the cgroup is removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if inotify is unavailable or the cgroup has neither
+    /// event file.
+    #[cfg(target_os = "linux")]
+    pub fn watch_events(&self, cgroup_path: &Path) -> Result<BoxStream<'static, ResourceEvents>> {
+        let watcher = CgroupEventWatcher::new(cgroup_path)?;
+
+        Ok(futures::stream::unfold((watcher, None), |(watcher, last)| async move {
+            loop {
+                if last.is_some() && !watcher.changed().await {
+                    return None;
+                }
+                let events = read_resource_events(&watcher.cgroup_path).await;
+                // Other counters in the same files, e.g. `low`, also wake us.
+                if last != Some(events) {
+                    return Some((events, (watcher, Some(events))));
+                }
+            }
+        })
+        .boxed())
+    }
+
     /// Read resource statistics for a cgroup.
     pub async fn read_stats(&self, cgroup_path: &Path) -> Result<CgroupStats> {
         let mut stats = CgroupStats::default();
@@ -273,6 +501,136 @@ impl Default for CgroupManager {
     }
 }
 
+/// The `io.max` keys for `limits`, or `None` if no IO limit is set.
+///
+/// Unset keys are written as `max` so that a previous limit is lifted.
+fn io_max_settings(limits: &ResourceLimits) -> Option<String> {
+    let keys = [
+        ("rbps", limits.io_read_bps),
+        ("wbps", limits.io_write_bps),
+        ("riops", limits.io_read_iops),
+        ("wiops", limits.io_write_iops),
+    ];
+    if keys.iter().all(|(_, value)| value.is_none()) {
+        return None;
+    }
+
+    let settings = keys
+        .iter()
+        .map(|(key, value)| {
+            value.map_or_else(|| format!("{key}=max"), |value| format!("{key}={value}"))
+        })
+        .collect::<Vec<_>>();
+    Some(settings.join(" "))
+}
+
+/// Parse a flat keyed cgroup file such as `memory.events`.
+fn parse_flat_keyed(content: &str) -> impl Iterator<Item = (&str, u64)> {
+    content.lines().filter_map(|line| {
+        let (key, value) = line.split_once(' ')?;
+        Some((key, value.trim().parse().ok()?))
+    })
+}
+
+/// Read the event counters of a cgroup; missing files read as zero.
+async fn read_resource_events(cgroup_path: &Path) -> ResourceEvents {
+    let mut events = ResourceEvents::default();
+
+    if let Ok(content) = fs::read_to_string(cgroup_path.join("memory.events")).await {
+        for (key, value) in parse_flat_keyed(&content) {
+            match key {
+                "high" => events.memory_high = value,
+                "max" => events.memory_max = value,
+                "oom" => events.oom = value,
+                "oom_kill" => events.oom_kill = value,
+                _ => {}
+            }
+        }
+    }
+
+    if let Ok(content) = fs::read_to_string(cgroup_path.join("pids.events")).await {
+        if let Some((_, value)) = parse_flat_keyed(&content).find(|(key, _)| *key == "max") {
+            events.pids_max = value;
+        }
+    }
+
+    events
+}
+
+/// [`Inotify`] instance that [`AsyncFd`] can poll.
+#[cfg(target_os = "linux")]
+struct InotifyFd(Inotify);
+
+#[cfg(target_os = "linux")]
+impl AsRawFd for InotifyFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_fd().as_raw_fd()
+    }
+}
+
+/// inotify watch on the event files of one cgroup.
+#[cfg(target_os = "linux")]
+struct CgroupEventWatcher {
+    inotify: AsyncFd<InotifyFd>,
+    cgroup_path: PathBuf,
+}
+
+#[cfg(target_os = "linux")]
+impl CgroupEventWatcher {
+    fn new(cgroup_path: &Path) -> Result<Self> {
+        let watch_error = |reason: String| CoreError::CgroupOperation {
+            operation: "watch cgroup events".to_string(),
+            reason,
+        };
+
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+            .map_err(|e| watch_error(e.to_string()))?;
+
+        let mut watched = false;
+        for file in ["memory.events", "pids.events"] {
+            let path = cgroup_path.join(file);
+            if path.exists() {
+                inotify
+                    .add_watch(&path, AddWatchFlags::IN_MODIFY)
+                    .map_err(|e| watch_error(format!("{}: {e}", path.display())))?;
+                watched = true;
+            }
+        }
+        if !watched {
+            return Err(watch_error(format!("no event files in {}", cgroup_path.display())));
+        }
+
+        let inotify = AsyncFd::new(InotifyFd(inotify)).map_err(|e| watch_error(e.to_string()))?;
+        Ok(Self {
+            inotify,
+            cgroup_path: cgroup_path.to_path_buf(),
+        })
+    }
+
+    /// Wait until the kernel updates an event file.
+    ///
+    /// Returns `false` once the cgroup has been removed.
+    async fn changed(&self) -> bool {
+        loop {
+            let Ok(mut guard) = self.inotify.readable().await else {
+                return false;
+            };
+            match guard.try_io(|fd| fd.get_ref().0.read_events().map_err(std::io::Error::from)) {
+                Ok(Ok(events)) => {
+                    return !events
+                        .iter()
+                        .any(|event| event.mask.contains(AddWatchFlags::IN_IGNORED));
+                }
+                Ok(Err(e)) => {
+                    warn!("Failed to read cgroup events of {:?}: {}", self.cgroup_path, e);
+                    return false;
+                }
+                Err(_would_block) => {}
+            }
+        }
+    }
+}
+
 /// cgroup statistics.
 #[derive(Debug, Default, Clone)]
 pub struct CgroupStats {
@@ -364,12 +722,95 @@ mod tests {
             };
 
             // This will fail without real cgroup files, but we test the logic
-            let result = manager.apply_limits(&container_cgroup, &limits).await;
+            let result = manager
+                .apply_limits(&container_cgroup, temp_dir.path(), &limits)
+                .await;
             // On non-cgroup systems, this will error, which is expected
             // The important thing is the code path executes without panics
             assert!(result.is_err() || result.is_ok());
         }
 
+        #[tokio::test]
+        async fn test_apply_cpu_limits() {
+            let temp_dir = TempDir::new().unwrap();
+            let container_cgroup = temp_dir.path().join("container-test");
+            std::fs::create_dir_all(&container_cgroup).unwrap();
+
+            let manager = CgroupManager::with_base_path(temp_dir.path());
+            let limits = ResourceLimits {
+                cpu_millicores: Some(500),
+                cpu_weight: Some(200),
+                cpuset_cpus: Some("0-1".to_string()),
+                ..Default::default()
+            };
+
+            manager
+                .apply_limits(&container_cgroup, temp_dir.path(), &limits)
+                .await
+                .unwrap();
+
+            let read = |file: &str| std::fs::read_to_string(container_cgroup.join(file)).unwrap();
+            assert_eq!(read("cpu.max"), "50000 100000");
+            assert_eq!(read("cpu.weight"), "200");
+            assert_eq!(read("cpuset.cpus"), "0-1");
+
+            let limits = ResourceLimits {
+                cpu_weight: Some(0),
+                ..Default::default()
+            };
+            assert!(manager
+                .apply_limits(&container_cgroup, temp_dir.path(), &limits)
+                .await
+                .is_err());
+        }
+
+        #[tokio::test]
+        async fn test_apply_io_limits() {
+            let temp_dir = TempDir::new().unwrap();
+            let container_cgroup = temp_dir.path().join("container-test");
+            std::fs::create_dir_all(&container_cgroup).unwrap();
+
+            let mountinfo = temp_dir.path().join("mountinfo");
+            std::fs::write(&mountinfo, "22 1 8:0 / / rw,relatime shared:1 - ext4 /dev/sda rw\n")
+                .unwrap();
+            let sys_dev_block = temp_dir.path().join("dev-block");
+            std::fs::create_dir_all(sys_dev_block.join("8:0")).unwrap();
+
+            let manager =
+                CgroupManager::with_base_path(temp_dir.path()).with_block_device_resolver(
+                    BlockDeviceResolver::with_paths(mountinfo, sys_dev_block),
+                );
+            let limits = ResourceLimits {
+                io_read_bps: Some(1024 * 1024),
+                io_write_iops: Some(100),
+                io_weight: Some(50),
+                ..Default::default()
+            };
+
+            manager
+                .apply_limits(&container_cgroup, Path::new("/var/lib/hyperbox"), &limits)
+                .await
+                .unwrap();
+
+            assert_eq!(
+                std::fs::read_to_string(container_cgroup.join("io.max")).unwrap(),
+                "8:0 rbps=1048576 wbps=max riops=max wiops=100"
+            );
+            assert_eq!(
+                std::fs::read_to_string(container_cgroup.join("io.weight")).unwrap(),
+                "default 50"
+            );
+
+            let limits = ResourceLimits {
+                io_weight: Some(0),
+                ..Default::default()
+            };
+            assert!(manager
+                .apply_limits(&container_cgroup, temp_dir.path(), &limits)
+                .await
+                .is_err());
+        }
+
         #[tokio::test]
         async fn test_read_stats_missing_files() {
             let temp_dir = TempDir::new().unwrap();
@@ -408,6 +849,136 @@ mod tests {
             assert_eq!(stats.cpu_user_usec, 800000);
             assert_eq!(stats.cpu_system_usec, 200000);
         }
+
+        #[tokio::test]
+        async fn test_freeze_and_thaw() {
+            let temp_dir = TempDir::new().unwrap();
+            let manager = CgroupManager::with_base_path(temp_dir.path());
+            let container_cgroup = manager.container_cgroup_path("test");
+            std::fs::create_dir_all(&container_cgroup).unwrap();
+            std::fs::write(container_cgroup.join("cgroup.events"), "populated 1\nfrozen 0\n")
+                .unwrap();
+            assert!(!manager.is_frozen(&container_cgroup).await.unwrap());
+
+            // The kernel updates cgroup.events asynchronously; emulate that.
+            let events = container_cgroup.join("cgroup.events");
+            let writer = tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                std::fs::write(events, "populated 1\nfrozen 1\n").unwrap();
+            });
+            manager.freeze(&container_cgroup).await.unwrap();
+            writer.await.unwrap();
+            assert_eq!(
+                std::fs::read_to_string(container_cgroup.join("cgroup.freeze")).unwrap(),
+                "1"
+            );
+            assert!(manager.is_frozen(&container_cgroup).await.unwrap());
+
+            std::fs::write(container_cgroup.join("cgroup.events"), "populated 1\nfrozen 0\n")
+                .unwrap();
+            manager.thaw(&container_cgroup).await.unwrap();
+            assert_eq!(
+                std::fs::read_to_string(container_cgroup.join("cgroup.freeze")).unwrap(),
+                "0"
+            );
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn test_freeze_timeout_thaws() {
+            let temp_dir = TempDir::new().unwrap();
+            let container_cgroup = temp_dir.path().join("container-test");
+            std::fs::create_dir_all(&container_cgroup).unwrap();
+            std::fs::write(container_cgroup.join("cgroup.events"), "populated 1\nfrozen 0\n")
+                .unwrap();
+
+            let manager = CgroupManager::with_base_path(temp_dir.path());
+            let result = manager.freeze(&container_cgroup).await;
+            assert!(matches!(result, Err(CoreError::CgroupOperation { .. })));
+            assert_eq!(
+                std::fs::read_to_string(container_cgroup.join("cgroup.freeze")).unwrap(),
+                "0"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_read_events() {
+            let temp_dir = TempDir::new().unwrap();
+            let container_cgroup = temp_dir.path().join("container-test");
+            std::fs::create_dir_all(&container_cgroup).unwrap();
+
+            let manager = CgroupManager::with_base_path(temp_dir.path());
+            let events = manager.read_events(&container_cgroup).await;
+            assert_eq!(events, ResourceEvents::default());
+
+            std::fs::write(
+                container_cgroup.join("memory.events"),
+                "low 0\nhigh 12\nmax 3\noom 2\noom_kill 1\noom_group_kill 0\n",
+            )
+            .unwrap();
+            std::fs::write(container_cgroup.join("pids.events"), "max 7\n").unwrap();
+
+            let events = manager.read_events(&container_cgroup).await;
+            assert_eq!(
+                events,
+                ResourceEvents {
+                    memory_high: 12,
+                    memory_max: 3,
+                    oom: 2,
+                    oom_kill: 1,
+                    pids_max: 7,
+                }
+            );
+        }
+
+        #[tokio::test]
+        async fn test_watch_events() {
+            use futures::StreamExt;
+
+            let temp_dir = TempDir::new().unwrap();
+            let container_cgroup = temp_dir.path().join("container-test");
+            std::fs::create_dir_all(&container_cgroup).unwrap();
+            let memory_events = container_cgroup.join("memory.events");
+            std::fs::write(&memory_events, "low 0\nhigh 0\nmax 0\noom 0\noom_kill 0\n").unwrap();
+
+            let manager = CgroupManager::with_base_path(temp_dir.path());
+            let mut events = manager.watch_events(&container_cgroup).unwrap();
+            assert_eq!(events.next().await, Some(ResourceEvents::default()));
+
+            // Changes to counters we do not report are skipped.
+            std::fs::write(&memory_events, "low 5\nhigh 0\nmax 0\noom 0\noom_kill 0\n").unwrap();
+            std::fs::write(&memory_events, "low 5\nhigh 0\nmax 1\noom 1\noom_kill 1\n").unwrap();
+            let current = tokio::time::timeout(Duration::from_secs(5), events.next())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(current.oom_kill, 1);
+            assert_eq!(current.memory_max, 1);
+
+            // The stream ends once the watched files are gone.
+            std::fs::remove_file(&memory_events).unwrap();
+            let drained = tokio::time::timeout(Duration::from_secs(5), async {
+                while events.next().await.is_some() {}
+            })
+            .await;
+            assert!(drained.is_ok());
+        }
+
+        #[test]
+        fn test_watch_events_requires_event_files() {
+            let temp_dir = TempDir::new().unwrap();
+            let manager = CgroupManager::with_base_path(temp_dir.path());
+            assert!(manager.watch_events(temp_dir.path()).is_err());
+        }
+
+        #[tokio::test]
+        async fn test_is_frozen_missing_events() {
+            let temp_dir = TempDir::new().unwrap();
+            let manager = CgroupManager::with_base_path(temp_dir.path());
+            assert!(manager
+                .is_frozen(&temp_dir.path().join("missing"))
+                .await
+                .is_err());
+        }
     }
 }
 
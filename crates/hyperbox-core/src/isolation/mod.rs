@@ -1,14 +1,17 @@
 //! Isolation layer for container security.
 //!
-//! Provides cgroups v2, namespace management, seccomp, Landlock, and a
-//! composable security stack that orchestrates all layers together.
+//! Provides cgroups v2 (with block device resolution for IO limits),
+//! namespace management, seccomp, Landlock, and a composable security stack
+//! that orchestrates all layers together.
 
+pub mod block_device;
 pub mod cgroups;
 pub mod landlock;
 pub mod namespaces;
 pub mod seccomp;
 pub mod security_stack;
 
+pub use block_device::{BlockDevice, BlockDeviceResolver};
 pub use cgroups::CgroupManager;
 pub use landlock::LandlockManager;
 pub use namespaces::NamespaceManager;
@@ -3,7 +3,7 @@
 use std::collections::HashSet;
 use std::path::PathBuf;
 use std::process::Command;
-use crate::error::CoreError;
+use crate::error::{CoreError, Result};
 
 /// Types of Linux namespaces.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
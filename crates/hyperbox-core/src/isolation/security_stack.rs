@@ -959,7 +959,7 @@ impl SecurityStack {
             Ok(cg_path) => {
                 if let Err(e) = self
                     .cgroup_mgr
-                    .apply_limits(&cg_path, &policy.cgroups.limits)
+                    .apply_limits(&cg_path, &policy.rootfs, &policy.cgroups.limits)
                     .await
                 {
                     warn!(
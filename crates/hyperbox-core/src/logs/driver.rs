@@ -0,0 +1,439 @@
+//! json-file log driver.
+//!
+//! Each container gets `<root>/<id>/container.log`. When the file would
+//! grow past [`LogConfig::max_size`] it is renamed to `container.log.1`
+//! (shifting older files up) and a fresh file is started; at most
+//! [`LogConfig::max_files`] files are kept.
+
+use crate::error::{CoreError, Result};
+use crate::logs::reader::LogReader;
+use crate::logs::{LogEntry, LogStream};
+use crate::types::{ContainerId, LogOptions};
+use dashmap::DashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Name of the active log file inside a container's log directory.
+const LOG_FILE: &str = "container.log";
+
+/// Lines longer than this are split into several entries.
+const MAX_LINE_BYTES: usize = 16 * 1024;
+
+/// Log rotation settings.
+#[derive(Debug, Clone, Copy)]
+pub struct LogConfig {
+    /// Rotate once the active file would exceed this many bytes (0 disables rotation).
+    pub max_size: u64,
+    /// Number of files to keep, including the active one.
+    pub max_files: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 10 * 1024 * 1024,
+            max_files: 3,
+        }
+    }
+}
+
+/// Captures container output into rotated json-file logs.
+pub struct LogDriver {
+    /// Directory holding one log directory per container.
+    root_dir: PathBuf,
+    /// Rotation settings.
+    config: LogConfig,
+    /// Containers whose output is being captured, with a flag that flips to
+    /// `true` once both streams have closed.
+    active: DashMap<String, watch::Receiver<bool>>,
+}
+
+impl LogDriver {
+    /// Create a log driver storing logs under `root_dir`.
+    #[must_use]
+    pub fn new(root_dir: impl Into<PathBuf>, config: LogConfig) -> Self {
+        Self {
+            root_dir: root_dir.into(),
+            config,
+            active: DashMap::new(),
+        }
+    }
+
+    /// Get the root directory.
+    #[must_use]
+    pub fn root_dir(&self) -> &Path {
+        &self.root_dir
+    }
+
+    /// Path of the active log file for a container.
+    #[must_use]
+    pub fn log_path(&self, id: &ContainerId) -> PathBuf {
+        self.root_dir.join(id.as_str()).join(LOG_FILE)
+    }
+
+    /// Start capturing a container's output.
+    ///
+    /// Each stream is read line by line until EOF. The returned handle
+    /// completes once both streams have closed and the log is flushed.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the log file cannot be created.
+    pub async fn attach<O, E>(
+        &self,
+        id: &ContainerId,
+        stdout: Option<O>,
+        stderr: Option<E>,
+    ) -> Result<JoinHandle<()>>
+    where
+        O: AsyncRead + Send + Unpin + 'static,
+        E: AsyncRead + Send + Unpin + 'static,
+    {
+        let writer = JsonFileWriter::open(self.log_path(id), self.config).await?;
+        let writer = Arc::new(Mutex::new(writer));
+
+        let (done_tx, done_rx) = watch::channel(false);
+        self.active.insert(id.to_string(), done_rx);
+
+        let stdout =
+            stdout.map(|r| tokio::spawn(copy_stream(r, LogStream::Stdout, writer.clone())));
+        let stderr =
+            stderr.map(|r| tokio::spawn(copy_stream(r, LogStream::Stderr, writer.clone())));
+
+        let id = id.clone();
+        Ok(tokio::spawn(async move {
+            for task in [stdout, stderr].into_iter().flatten() {
+                let _ = task.await;
+            }
+            if let Err(e) = writer.lock().await.flush().await {
+                warn!(container_id = %id, "Failed to flush log: {}", e);
+            }
+            debug!(container_id = %id, "Log capture finished");
+            let _ = done_tx.send(true);
+        }))
+    }
+
+    /// Read a container's logs according to `opts`.
+    ///
+    /// With `opts.follow` the reader stays open and yields new lines until
+    /// capture finishes, `opts.until` passes, or the reader is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::ContainerNotFound`] if no logs exist for `id`.
+    pub async fn read(
+        &self,
+        id: &ContainerId,
+        opts: &LogOptions,
+    ) -> Result<Box<dyn tokio::io::AsyncRead + Send + Unpin>> {
+        let path = self.log_path(id);
+        if !fs::try_exists(&path).await.unwrap_or(false) {
+            return Err(CoreError::ContainerNotFound(id.to_string()));
+        }
+
+        let done = self.active.get(id.as_str()).map(|r| r.value().clone());
+        let reader = LogReader::new(path, self.config.max_files, opts.clone());
+        if opts.follow {
+            reader.follow(done).await
+        } else {
+            reader.read().await
+        }
+    }
+
+    /// Delete a container's logs.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the log directory cannot be removed.
+    pub async fn remove(&self, id: &ContainerId) -> Result<()> {
+        self.active.remove(id.as_str());
+        let dir = self.root_dir.join(id.as_str());
+        if dir.exists() {
+            fs::remove_dir_all(&dir).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Path of the `n`th rotated file (`container.log.<n>`).
+pub(crate) fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{n}"));
+    path.with_file_name(name)
+}
+
+/// Appends entries to a log file, rotating it by size.
+struct JsonFileWriter {
+    path: PathBuf,
+    config: LogConfig,
+    file: fs::File,
+    size: u64,
+}
+
+impl JsonFileWriter {
+    async fn open(path: PathBuf, config: LogConfig) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let file = Self::open_file(&path, false).await?;
+        let size = file.metadata().await?.len();
+        Ok(Self {
+            path,
+            config,
+            file,
+            size,
+        })
+    }
+
+    async fn open_file(path: &Path, truncate: bool) -> Result<fs::File> {
+        let mut options = fs::OpenOptions::new();
+        options.create(true);
+        if truncate {
+            options.write(true).truncate(true);
+        } else {
+            options.append(true);
+        }
+        Ok(options.open(path).await?)
+    }
+
+    async fn write(&mut self, entry: &LogEntry) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let len = line.len() as u64;
+
+        if self.config.max_size > 0 && self.size > 0 && self.size + len > self.config.max_size {
+            self.rotate().await?;
+        }
+
+        self.file.write_all(&line).await?;
+        // Flush so followers see the line immediately.
+        self.file.flush().await?;
+        self.size += len;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.file.flush().await?;
+        Ok(())
+    }
+
+    async fn rotate(&mut self) -> Result<()> {
+        self.file.flush().await?;
+
+        let keep = self.config.max_files.saturating_sub(1);
+        if keep == 0 {
+            self.file = Self::open_file(&self.path, true).await?;
+        } else {
+            for n in (1..keep).rev() {
+                let from = rotated_path(&self.path, n);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(&self.path, n + 1)).await?;
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1)).await?;
+            self.file = Self::open_file(&self.path, false).await?;
+        }
+
+        debug!("Rotated log {:?}", self.path);
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Copy one output stream into the log until EOF.
+async fn copy_stream<R>(reader: R, stream: LogStream, writer: Arc<Mutex<JsonFileWriter>>)
+where
+    R: AsyncRead + Unpin,
+{
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        match read_line_capped(&mut reader, &mut line, MAX_LINE_BYTES).await {
+            Ok(0) => break,
+            Ok(_) => {
+                let entry = LogEntry::new(stream, &line);
+                if let Err(e) = writer.lock().await.write(&entry).await {
+                    warn!("Failed to write log entry: {}", e);
+                }
+            }
+            Err(e) => {
+                debug!("Log stream closed: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+/// Read up to and including the next newline, or at most `max` bytes.
+async fn read_line_capped<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    max: usize,
+) -> std::io::Result<usize>
+where
+    R: AsyncBufRead + Unpin,
+{
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(buf.len());
+        }
+
+        let window = &available[..available.len().min(max - buf.len())];
+        if let Some(pos) = window.iter().position(|&b| b == b'\n') {
+            buf.extend_from_slice(&window[..=pos]);
+            reader.consume(pos + 1);
+            return Ok(buf.len());
+        }
+
+        let taken = window.len();
+        buf.extend_from_slice(window);
+        reader.consume(taken);
+        if buf.len() >= max {
+            return Ok(buf.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+
+    async fn read_all(driver: &LogDriver, id: &ContainerId, opts: &LogOptions) -> String {
+        let mut out = String::new();
+        driver
+            .read(id, opts)
+            .await
+            .unwrap()
+            .read_to_string(&mut out)
+            .await
+            .unwrap();
+        out
+    }
+
+    #[tokio::test]
+    async fn test_attach_captures_both_streams() {
+        let dir = TempDir::new().unwrap();
+        let driver = LogDriver::new(dir.path(), LogConfig::default());
+        let id = ContainerId::new();
+
+        let stdout: &[u8] = b"hello\nworld\n";
+        let stderr: &[u8] = b"oops";
+        driver
+            .attach(&id, Some(stdout), Some(stderr))
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+
+        let content = std::fs::read_to_string(driver.log_path(&id)).unwrap();
+        let entries: Vec<LogEntry> = content
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 3);
+        assert!(entries
+            .iter()
+            .any(|e| e.stream == LogStream::Stderr && e.log == "oops"));
+
+        let opts = LogOptions {
+            stderr: false,
+            ..LogOptions::default()
+        };
+        assert_eq!(read_all(&driver, &id, &opts).await, "hello\nworld\n");
+    }
+
+    #[tokio::test]
+    async fn test_long_lines_are_split() {
+        let dir = TempDir::new().unwrap();
+        let driver = LogDriver::new(dir.path(), LogConfig::default());
+        let id = ContainerId::new();
+
+        let long = vec![b'x'; MAX_LINE_BYTES + 10];
+        driver
+            .attach(&id, Some(std::io::Cursor::new(long)), None::<&[u8]>)
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+
+        let content = std::fs::read_to_string(driver.log_path(&id)).unwrap();
+        assert_eq!(content.lines().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rotation_keeps_max_files() {
+        let dir = TempDir::new().unwrap();
+        let config = LogConfig {
+            max_size: 200,
+            max_files: 3,
+        };
+        let driver = LogDriver::new(dir.path(), config);
+        let id = ContainerId::new();
+
+        let output = (0..50)
+            .map(|i| format!("line {i}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+            + "\n";
+        driver
+            .attach(&id, Some(std::io::Cursor::new(output)), None::<&[u8]>)
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+
+        let path = driver.log_path(&id);
+        assert!(rotated_path(&path, 1).exists());
+        assert!(rotated_path(&path, 2).exists());
+        assert!(!rotated_path(&path, 3).exists());
+        for p in [path.clone(), rotated_path(&path, 1), rotated_path(&path, 2)] {
+            assert!(std::fs::metadata(p).unwrap().len() <= 200);
+        }
+
+        // Rotated files are read oldest first and the newest line is last.
+        let logs = read_all(&driver, &id, &LogOptions::default()).await;
+        assert!(logs.ends_with("line 49\n"));
+        let numbers: Vec<u32> = logs
+            .lines()
+            .map(|l| l.trim_start_matches("line ").parse().unwrap())
+            .collect();
+        assert!(numbers.windows(2).all(|w| w[0] + 1 == w[1]));
+    }
+
+    #[tokio::test]
+    async fn test_read_unknown_container() {
+        let dir = TempDir::new().unwrap();
+        let driver = LogDriver::new(dir.path(), LogConfig::default());
+        let result = driver
+            .read(&ContainerId::new(), &LogOptions::default())
+            .await;
+        assert!(matches!(result, Err(CoreError::ContainerNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_remove_deletes_logs() {
+        let dir = TempDir::new().unwrap();
+        let driver = LogDriver::new(dir.path(), LogConfig::default());
+        let id = ContainerId::new();
+
+        let stdout: &[u8] = b"bye\n";
+        driver
+            .attach(&id, Some(stdout), None::<&[u8]>)
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        driver.remove(&id).await.unwrap();
+        assert!(!driver.log_path(&id).exists());
+    }
+}
@@ -0,0 +1,67 @@
+//! Container log capture for native runtimes.
+//!
+//! Runtimes hand a container's stdout/stderr pipes to a [`LogDriver`], which
+//! records every line in a json-file log (one JSON object per line with the
+//! stream name and a timestamp, like Docker's `json-file` driver) and rotates
+//! it by size. [`LogDriver::read`] serves those files back according to
+//! [`LogOptions`](crate::types::LogOptions).
+
+pub mod driver;
+pub mod reader;
+
+pub use driver::{LogConfig, LogDriver};
+pub use reader::LogReader;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Output stream a log line was written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    /// Standard output.
+    Stdout,
+    /// Standard error.
+    Stderr,
+}
+
+/// A single line of container output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Line content, including the trailing newline if there was one.
+    pub log: String,
+    /// Stream the line was written to.
+    pub stream: LogStream,
+    /// When the line was captured.
+    pub time: DateTime<Utc>,
+}
+
+impl LogEntry {
+    /// Create an entry for `line` captured now.
+    #[must_use]
+    pub fn new(stream: LogStream, line: &[u8]) -> Self {
+        Self {
+            log: String::from_utf8_lossy(line).into_owned(),
+            stream,
+            time: Utc::now(),
+        }
+    }
+
+    /// Render the entry as it is shown to users.
+    ///
+    /// The output always ends with a newline; with `timestamps` it is
+    /// prefixed by the RFC 3339 capture time.
+    #[must_use]
+    pub fn format(&self, timestamps: bool) -> String {
+        let mut out = String::with_capacity(self.log.len() + 32);
+        if timestamps {
+            out.push_str(&self.time.to_rfc3339_opts(SecondsFormat::Nanos, true));
+            out.push(' ');
+        }
+        out.push_str(&self.log);
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out
+    }
+}
@@ -0,0 +1,416 @@
+//! Reading json-file logs back with [`LogOptions`] applied.
+
+use crate::error::Result;
+use crate::logs::driver::rotated_path;
+use crate::logs::{LogEntry, LogStream};
+use crate::types::LogOptions;
+use chrono::Utc;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::watch;
+use tracing::debug;
+
+/// How often a follower checks the log file for new lines.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Buffer size of the pipe handed to followers.
+const FOLLOW_BUFFER: usize = 64 * 1024;
+
+/// Reads a container's log files.
+pub struct LogReader {
+    /// Active log file; rotated files sit next to it.
+    path: PathBuf,
+    /// Number of files kept by the driver, including the active one.
+    max_files: usize,
+    /// Filters and formatting.
+    opts: LogOptions,
+}
+
+impl LogReader {
+    /// Create a reader for the log file at `path`.
+    #[must_use]
+    pub const fn new(path: PathBuf, max_files: usize, opts: LogOptions) -> Self {
+        Self {
+            path,
+            max_files,
+            opts,
+        }
+    }
+
+    /// Read the current contents of the log.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a log file cannot be read.
+    pub async fn read(&self) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let mut data = self.read_rotated().await?;
+        data.extend(fs::read(&self.path).await?);
+        Ok(Box::new(std::io::Cursor::new(self.backlog(&mut data))))
+    }
+
+    /// Read the current contents, then keep yielding new lines.
+    ///
+    /// The stream ends once `done` reports that capture has finished (or
+    /// immediately after the backlog when there is no active capture), when
+    /// an entry newer than `until` is seen, or when the reader is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a log file cannot be read.
+    pub async fn follow(
+        self,
+        done: Option<watch::Receiver<bool>>,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let mut data = self.read_rotated().await?;
+        let mut file = fs::File::open(&self.path).await?;
+        let mut pos = file.read_to_end(&mut data).await? as u64;
+        let backlog = self.backlog(&mut data);
+
+        if self.opts.until.is_some_and(|until| until <= Utc::now()) {
+            return Ok(Box::new(std::io::Cursor::new(backlog)));
+        }
+
+        let (mut tx, rx) = tokio::io::duplex(FOLLOW_BUFFER);
+        tokio::spawn(async move {
+            if tx.write_all(&backlog).await.is_err() {
+                return;
+            }
+
+            // `data` now only holds a partially written trailing line.
+            let mut pending = data;
+            let mut draining = false;
+            loop {
+                let mut chunk = Vec::new();
+                match file.read_to_end(&mut chunk).await {
+                    Ok(n) => pos += n as u64,
+                    Err(e) => {
+                        debug!("Log follow stopped: {}", e);
+                        return;
+                    }
+                }
+
+                if !chunk.is_empty() {
+                    pending.extend_from_slice(&chunk);
+                    for entry in split_entries(&mut pending) {
+                        if self.opts.until.is_some_and(|until| entry.time > until) {
+                            return;
+                        }
+                        if self.matches(&entry)
+                            && tx
+                                .write_all(entry.format(self.opts.timestamps).as_bytes())
+                                .await
+                                .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    continue;
+                }
+
+                if replaced(&self.path, &file, pos).await {
+                    match fs::File::open(&self.path).await {
+                        Ok(f) => {
+                            file = f;
+                            pos = 0;
+                            pending.clear();
+                            continue;
+                        }
+                        Err(_) if draining => return,
+                        Err(_) => {}
+                    }
+                }
+
+                if draining {
+                    return;
+                }
+                // Read once more after capture ends to pick up the last lines.
+                draining = done
+                    .as_ref()
+                    .map_or(true, |rx| *rx.borrow() || rx.has_changed().is_err());
+                if !draining {
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+        });
+
+        Ok(Box::new(rx))
+    }
+
+    /// Contents of the rotated files, oldest first.
+    async fn read_rotated(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        for n in (1..self.max_files).rev() {
+            match fs::read(rotated_path(&self.path, n)).await {
+                Ok(bytes) => data.extend(bytes),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(data)
+    }
+
+    /// Filter and format the complete lines in `data`, honouring `tail`.
+    fn backlog(&self, data: &mut Vec<u8>) -> Vec<u8> {
+        let entries: Vec<LogEntry> = split_entries(data)
+            .into_iter()
+            .filter(|e| self.matches(e))
+            .collect();
+        let skip = self
+            .opts
+            .tail
+            .map_or(0, |tail| entries.len().saturating_sub(tail));
+
+        entries[skip..]
+            .iter()
+            .flat_map(|e| e.format(self.opts.timestamps).into_bytes())
+            .collect()
+    }
+
+    fn matches(&self, entry: &LogEntry) -> bool {
+        let stream = match entry.stream {
+            LogStream::Stdout => self.opts.stdout,
+            LogStream::Stderr => self.opts.stderr,
+        };
+        stream
+            && self.opts.since.map_or(true, |since| entry.time >= since)
+            && self.opts.until.map_or(true, |until| entry.time <= until)
+    }
+}
+
+/// Parse complete lines out of `data`, leaving any trailing partial line.
+fn split_entries(data: &mut Vec<u8>) -> Vec<LogEntry> {
+    let Some(end) = data.iter().rposition(|&b| b == b'\n') else {
+        return Vec::new();
+    };
+
+    let entries = data[..end]
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| serde_json::from_slice(line).ok())
+        .collect();
+    data.drain(..=end);
+    entries
+}
+
+/// Whether the file at `path` is no longer the one we are reading, either
+/// because it was rotated away or truncated below our position.
+async fn replaced(path: &Path, file: &fs::File, pos: u64) -> bool {
+    let Ok(current) = fs::metadata(path).await else {
+        return true;
+    };
+    if current.len() < pos {
+        return true;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(open) = file.metadata().await {
+            return open.ino() != current.ino() || open.dev() != current.dev();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = file;
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration as ChronoDuration, TimeZone};
+    use tempfile::TempDir;
+
+    fn entry(stream: LogStream, log: &str, secs: i64) -> LogEntry {
+        LogEntry {
+            log: log.to_string(),
+            stream,
+            time: Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap(),
+        }
+    }
+
+    fn write_log(path: &Path, entries: &[LogEntry]) {
+        let mut out = String::new();
+        for e in entries {
+            out.push_str(&serde_json::to_string(e).unwrap());
+            out.push('\n');
+        }
+        std::fs::write(path, out).unwrap();
+    }
+
+    async fn read(path: &Path, opts: LogOptions) -> String {
+        let mut out = String::new();
+        LogReader::new(path.to_path_buf(), 3, opts)
+            .read()
+            .await
+            .unwrap()
+            .read_to_string(&mut out)
+            .await
+            .unwrap();
+        out
+    }
+
+    fn sample() -> Vec<LogEntry> {
+        vec![
+            entry(LogStream::Stdout, "one\n", 0),
+            entry(LogStream::Stderr, "two\n", 10),
+            entry(LogStream::Stdout, "three\n", 20),
+            entry(LogStream::Stdout, "four", 30),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_read_all_and_streams() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("container.log");
+        write_log(&path, &sample());
+
+        assert_eq!(read(&path, LogOptions::default()).await, "one\ntwo\nthree\nfour\n");
+
+        let stderr_only = LogOptions {
+            stdout: false,
+            ..LogOptions::default()
+        };
+        assert_eq!(read(&path, stderr_only).await, "two\n");
+    }
+
+    #[tokio::test]
+    async fn test_tail() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("container.log");
+        write_log(&path, &sample());
+
+        let opts = LogOptions {
+            tail: Some(2),
+            ..LogOptions::default()
+        };
+        assert_eq!(read(&path, opts).await, "three\nfour\n");
+
+        let opts = LogOptions {
+            tail: Some(0),
+            ..LogOptions::default()
+        };
+        assert_eq!(read(&path, opts).await, "");
+    }
+
+    #[tokio::test]
+    async fn test_since_until() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("container.log");
+        let entries = sample();
+        write_log(&path, &entries);
+
+        let opts = LogOptions {
+            since: Some(entries[1].time),
+            until: Some(entries[2].time),
+            ..LogOptions::default()
+        };
+        assert_eq!(read(&path, opts).await, "two\nthree\n");
+    }
+
+    #[tokio::test]
+    async fn test_timestamps() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("container.log");
+        write_log(&path, &sample()[..1]);
+
+        let opts = LogOptions {
+            timestamps: true,
+            ..LogOptions::default()
+        };
+        assert_eq!(read(&path, opts).await, "2023-11-14T22:13:20.000000000Z one\n");
+    }
+
+    #[tokio::test]
+    async fn test_tail_spans_rotated_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("container.log");
+        let entries = sample();
+        write_log(&rotated_path(&path, 2), &entries[..1]);
+        write_log(&rotated_path(&path, 1), &entries[1..3]);
+        write_log(&path, &entries[3..]);
+
+        let opts = LogOptions {
+            tail: Some(3),
+            ..LogOptions::default()
+        };
+        assert_eq!(read(&path, opts).await, "two\nthree\nfour\n");
+    }
+
+    #[tokio::test]
+    async fn test_follow_yields_new_lines_until_done() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("container.log");
+        let entries = sample();
+        write_log(&path, &entries[..1]);
+
+        let (done_tx, done_rx) = watch::channel(false);
+        let opts = LogOptions {
+            follow: true,
+            ..LogOptions::default()
+        };
+        let mut reader = LogReader::new(path.clone(), 3, opts)
+            .follow(Some(done_rx))
+            .await
+            .unwrap();
+
+        // Append a line, then rotate as the driver would.
+        tokio::time::sleep(POLL_INTERVAL * 2).await;
+        let mut second = entries[2].clone();
+        second.time = Utc::now();
+        let mut line = serde_json::to_string(&second).unwrap();
+        line.push('\n');
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .and_then(|mut f| std::io::Write::write_all(&mut f, line.as_bytes()))
+            .unwrap();
+        std::fs::rename(&path, rotated_path(&path, 1)).unwrap();
+        let mut last = entries[3].clone();
+        last.time = Utc::now() + ChronoDuration::seconds(1);
+        write_log(&path, &[last]);
+        done_tx.send(true).unwrap();
+
+        let mut out = String::new();
+        tokio::time::timeout(Duration::from_secs(5), reader.read_to_string(&mut out))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(out, "one\nthree\nfour\n");
+    }
+
+    #[tokio::test]
+    async fn test_follow_without_capture_ends_after_backlog() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("container.log");
+        write_log(&path, &sample());
+
+        let opts = LogOptions {
+            follow: true,
+            tail: Some(1),
+            ..LogOptions::default()
+        };
+        let mut out = String::new();
+        let mut reader = LogReader::new(path, 3, opts).follow(None).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), reader.read_to_string(&mut out))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(out, "four\n");
+    }
+
+    #[tokio::test]
+    async fn test_replaced_detects_truncation() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("container.log");
+        write_log(&path, &sample());
+        let file = fs::File::open(&path).await.unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+
+        assert!(!replaced(&path, &file, len).await);
+        std::fs::write(&path, b"").unwrap();
+        assert!(replaced(&path, &file, len).await);
+    }
+}
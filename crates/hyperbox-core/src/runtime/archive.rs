@@ -0,0 +1,403 @@
+//! Copying files in and out of container filesystems.
+//!
+//! OCI runtimes have no API for this, so [`ContainerFs`] works from the host:
+//! it reads a bundle's `config.json` for the root filesystem (the container's
+//! overlay mount) and its mounts, and resolves container paths against them
+//! the way the kernel would inside the container, with symlinks confined to
+//! the container root.
+//!
+//! Archives follow Docker's archive API: the requested path is archived under
+//! its own name, directories recursively, preserving ownership, permissions,
+//! modification times and symlinks (which are never followed).
+//!
+//! Paths are resolved before the host opens them, so the processes of a
+//! running container could swap a resolved directory for a symlink in between
+//! and redirect the copy to the host (CVE-2018-15664). Runtimes copy with the
+//! container frozen, see [`while_frozen`].
+
+use crate::error::{CoreError, Result};
+use crate::runtime::{read_config, ContainerRuntime};
+use crate::storage::unpack::resolve_in_root;
+use crate::types::{ContainerId, ContainerState};
+use std::ffi::OsString;
+use std::future::Future;
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncRead;
+use tokio_util::io::SyncIoBridge;
+use tracing::warn;
+
+/// A mount in the container.
+#[derive(Debug, Clone)]
+struct MountPoint {
+    destination: PathBuf,
+    /// Host path of a bind mount; `None` for mounts that only exist inside
+    /// the container (tmpfs, proc, ...).
+    source: Option<PathBuf>,
+    read_only: bool,
+}
+
+/// Host-side view of a container's filesystem.
+#[derive(Debug, Clone)]
+pub struct ContainerFs {
+    rootfs: PathBuf,
+    read_only: bool,
+    mounts: Vec<MountPoint>,
+}
+
+impl ContainerFs {
+    /// A filesystem with only a root, such as a stopped container's rootfs.
+    #[must_use]
+    pub fn new(rootfs: impl Into<PathBuf>) -> Self {
+        Self {
+            rootfs: rootfs.into(),
+            read_only: false,
+            mounts: Vec::new(),
+        }
+    }
+
+    /// Read the root filesystem and mounts from a bundle's `config.json`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the config cannot be read or has no root.
+    pub async fn from_bundle(bundle: &Path) -> Result<Self> {
+        let spec = read_config(bundle).await?;
+        let root = spec.root().as_ref().ok_or_else(|| CoreError::InvalidSpec {
+            field: "root".to_string(),
+            reason: format!("{} has no root filesystem", bundle.display()),
+        })?;
+
+        let mut fs = Self::new(bundle.join(root.path()));
+        fs.read_only = root.readonly().unwrap_or(false);
+        for mount in spec.mounts().iter().flatten() {
+            let options = mount.options().clone().unwrap_or_default();
+            let has = |opt: &str| options.iter().any(|o| o == opt);
+            let bind = mount.typ().as_deref() == Some("bind") || has("bind") || has("rbind");
+            fs.mounts.push(MountPoint {
+                destination: mount.destination().clone(),
+                source: mount.source().clone().filter(|_| bind),
+                read_only: has("ro"),
+            });
+        }
+        Ok(fs)
+    }
+
+    /// Resolve a container path to a host path.
+    ///
+    /// Symlinks are resolved relative to the container root, so they cannot
+    /// point outside of it. With `follow_final` unset, a symlink in the
+    /// last component is returned as is.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::InvalidSpec`] for relative paths, symlink loops
+    /// and paths on mounts that are not reachable from the host.
+    pub fn resolve(&self, path: &Path, follow_final: bool) -> Result<PathBuf> {
+        let resolved = self.resolve_in_container(path, follow_final)?;
+        self.host_path(&resolved)
+    }
+
+    /// Write a tar archive of a container path.
+    ///
+    /// The archive is spooled to an unlinked temporary file and complete when
+    /// this returns, so a container frozen for the copy can be thawed before
+    /// the archive is read.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the path cannot be resolved, does not exist or
+    /// cannot be archived.
+    pub async fn archive(&self, path: &Path) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let container_path = self.resolve_in_container(path, false)?;
+        let source = self.host_path(&container_path)?;
+        let metadata = tokio::fs::symlink_metadata(&source).await?;
+        let name = container_path
+            .file_name()
+            .map_or_else(|| OsString::from("."), ToOwned::to_owned);
+
+        let file = tokio::task::spawn_blocking(move || -> std::io::Result<std::fs::File> {
+            let mut builder = tar::Builder::new(tempfile::tempfile()?);
+            builder.follow_symlinks(false);
+            if metadata.is_dir() {
+                builder.append_dir_all(&name, &source)?;
+            } else {
+                builder.append_path_with_name(&source, &name)?;
+            }
+            let mut file = builder.into_inner()?;
+            file.seek(SeekFrom::Start(0))?;
+            Ok(file)
+        })
+        .await
+        .map_err(|e| CoreError::Internal(format!("archiving panicked: {e}")))??;
+
+        Ok(Box::new(tokio::fs::File::from_std(file)))
+    }
+
+    /// Extract a tar archive into a container directory.
+    ///
+    /// Existing files are overwritten. Ownership is preserved when running
+    /// as root.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the destination is not a writable directory or the
+    /// archive cannot be extracted.
+    pub async fn extract(
+        &self,
+        path: &Path,
+        archive: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> Result<()> {
+        let container_path = self.resolve_in_container(path, true)?;
+        if self.is_read_only(&container_path) {
+            return Err(CoreError::InvalidSpec {
+                field: "path".to_string(),
+                reason: format!("{} is on a read-only filesystem", path.display()),
+            });
+        }
+        let destination = self.host_path(&container_path)?;
+        if !tokio::fs::metadata(&destination).await?.is_dir() {
+            return Err(CoreError::InvalidSpec {
+                field: "path".to_string(),
+                reason: format!("{} is not a directory", path.display()),
+            });
+        }
+
+        let reader = SyncIoBridge::new(archive);
+        tokio::task::spawn_blocking(move || {
+            let mut archive = tar::Archive::new(reader);
+            archive.set_preserve_permissions(true);
+            archive.set_preserve_mtime(true);
+            archive.set_preserve_ownerships(running_as_root());
+            archive.set_overwrite(true);
+            archive.unpack(&destination)
+        })
+        .await
+        .map_err(|e| CoreError::Internal(format!("archive extraction panicked: {e}")))??;
+        Ok(())
+    }
+
+    /// Resolve symlinks in a container path, returning a container path.
+    fn resolve_in_container(&self, path: &Path, follow_final: bool) -> Result<PathBuf> {
+        if !path.is_absolute() {
+            return Err(CoreError::InvalidSpec {
+                field: "path".to_string(),
+                reason: format!("{} is not absolute", path.display()),
+            });
+        }
+
+        resolve_in_root(path, follow_final, false, |path| self.host_path(path))
+    }
+
+    /// The innermost mount containing a container path.
+    fn mount_for(&self, path: &Path) -> Option<&MountPoint> {
+        self.mounts
+            .iter()
+            .filter(|m| path.starts_with(&m.destination))
+            .max_by_key(|m| m.destination.components().count())
+    }
+
+    /// Map a resolved container path to the host.
+    fn host_path(&self, path: &Path) -> Result<PathBuf> {
+        let Some(mount) = self.mount_for(path) else {
+            return Ok(self.rootfs.join(path.strip_prefix("/").unwrap_or(path)));
+        };
+        let relative = path.strip_prefix(&mount.destination).unwrap_or(path);
+        match &mount.source {
+            Some(source) if relative.as_os_str().is_empty() => Ok(source.clone()),
+            Some(source) => Ok(source.join(relative)),
+            None => Err(CoreError::InvalidSpec {
+                field: "path".to_string(),
+                reason: format!(
+                    "{} is on the {} mount, which is not reachable from the host",
+                    path.display(),
+                    mount.destination.display()
+                ),
+            }),
+        }
+    }
+
+    fn is_read_only(&self, path: &Path) -> bool {
+        self.mount_for(path).map_or(self.read_only, |m| m.read_only)
+    }
+}
+
+/// Run a copy in or out of a container's filesystem with the container
+/// frozen.
+///
+/// Paused and stopped containers cannot change their filesystem and are left
+/// as they are.
+///
+/// # Errors
+///
+/// Returns the copy's error, or an error if the container cannot be frozen or
+/// thawed.
+pub async fn while_frozen<R, T>(
+    runtime: &R,
+    id: &ContainerId,
+    copy: impl Future<Output = Result<T>>,
+) -> Result<T>
+where
+    R: ContainerRuntime + ?Sized,
+{
+    let running = matches!(runtime.state(id).await?, ContainerState::Running);
+    if running {
+        runtime.pause(id).await?;
+    }
+    let result = copy.await;
+    if running {
+        if let Err(e) = runtime.resume(id).await {
+            warn!(container_id = %id, "Failed to thaw container after copy: {}", e);
+            return result.and(Err(e));
+        }
+    }
+    result
+}
+
+#[cfg(unix)]
+fn running_as_root() -> bool {
+    nix::unistd::geteuid().is_root()
+}
+
+#[cfg(not(unix))]
+const fn running_as_root() -> bool {
+    false
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+
+    fn rootfs() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("etc/app")).unwrap();
+        std::fs::write(root.join("etc/app/config.toml"), "port = 8080\n").unwrap();
+        std::fs::set_permissions(
+            root.join("etc/app/config.toml"),
+            std::fs::Permissions::from_mode(0o640),
+        )
+        .unwrap();
+        symlink("config.toml", root.join("etc/app/current")).unwrap();
+        symlink("/etc", root.join("conf")).unwrap();
+        symlink("../../../../..", root.join("escape")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_resolve_confines_symlinks() {
+        let dir = rootfs();
+        let fs = ContainerFs::new(dir.path());
+
+        assert_eq!(fs.resolve(Path::new("/conf/app"), true).unwrap(), dir.path().join("etc/app"));
+        assert_eq!(fs.resolve(Path::new("/escape/etc"), true).unwrap(), dir.path().join("etc"));
+        assert_eq!(
+            fs.resolve(Path::new("/etc/app/current"), false).unwrap(),
+            dir.path().join("etc/app/current")
+        );
+        assert_eq!(
+            fs.resolve(Path::new("/etc/app/current"), true).unwrap(),
+            dir.path().join("etc/app/config.toml")
+        );
+        assert!(fs.resolve(Path::new("etc"), true).is_err());
+    }
+
+    #[test]
+    fn test_resolve_mounts() {
+        let dir = rootfs();
+        let volume = TempDir::new().unwrap();
+        let mut fs = ContainerFs::new(dir.path());
+        fs.mounts = vec![
+            MountPoint {
+                destination: PathBuf::from("/data"),
+                source: Some(volume.path().to_path_buf()),
+                read_only: true,
+            },
+            MountPoint {
+                destination: PathBuf::from("/proc"),
+                source: None,
+                read_only: false,
+            },
+        ];
+
+        assert_eq!(fs.resolve(Path::new("/data/db"), true).unwrap(), volume.path().join("db"));
+        assert!(fs.is_read_only(Path::new("/data/db")));
+        assert!(!fs.is_read_only(Path::new("/etc")));
+        assert!(fs.resolve(Path::new("/proc/1/status"), true).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_archive_round_trip() {
+        let source_dir = rootfs();
+        let source = ContainerFs::new(source_dir.path());
+        let target_dir = TempDir::new().unwrap();
+        std::fs::create_dir(target_dir.path().join("srv")).unwrap();
+        let target = ContainerFs::new(target_dir.path());
+
+        let archive = source.archive(Path::new("/conf/app")).await.unwrap();
+        target.extract(Path::new("/srv"), archive).await.unwrap();
+
+        let copied = target_dir.path().join("srv/app");
+        assert_eq!(std::fs::read_to_string(copied.join("config.toml")).unwrap(), "port = 8080\n");
+        let mode = std::fs::metadata(copied.join("config.toml"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o640);
+        assert_eq!(std::fs::read_link(copied.join("current")).unwrap(), Path::new("config.toml"));
+    }
+
+    #[tokio::test]
+    async fn test_archive_file_uses_base_name() {
+        let dir = rootfs();
+        let fs = ContainerFs::new(dir.path());
+
+        let mut archive = fs.archive(Path::new("/etc/app/config.toml")).await.unwrap();
+        let mut data = Vec::new();
+        archive.read_to_end(&mut data).await.unwrap();
+
+        let mut tar = tar::Archive::new(data.as_slice());
+        let names: Vec<PathBuf> = tar
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().into_owned())
+            .collect();
+        assert_eq!(names, vec![PathBuf::from("config.toml")]);
+    }
+
+    #[tokio::test]
+    async fn test_archive_is_complete_when_returned() {
+        let dir = rootfs();
+        let fs = ContainerFs::new(dir.path());
+
+        let mut archive = fs.archive(Path::new("/etc/app")).await.unwrap();
+        std::fs::remove_dir_all(dir.path().join("etc/app")).unwrap();
+        let mut data = Vec::new();
+        archive.read_to_end(&mut data).await.unwrap();
+
+        let mut tar = tar::Archive::new(data.as_slice());
+        let names: Vec<PathBuf> = tar
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().into_owned())
+            .collect();
+        assert!(names.contains(&PathBuf::from("app/config.toml")));
+    }
+
+    #[tokio::test]
+    async fn test_extract_rejects_read_only_and_files() {
+        let dir = rootfs();
+        let mut fs = ContainerFs::new(dir.path());
+        let empty = || Box::new(tokio::io::empty()) as Box<dyn AsyncRead + Send + Unpin>;
+
+        assert!(fs
+            .extract(Path::new("/etc/app/config.toml"), empty())
+            .await
+            .is_err());
+        fs.read_only = true;
+        assert!(fs.extract(Path::new("/etc"), empty()).await.is_err());
+    }
+}
@@ -0,0 +1,715 @@
+//! OCI bundle preparation for native runtimes.
+//!
+//! Turns a [`ContainerSpec`] that only names an image into something an OCI
+//! runtime can execute:
+//!
+//! 1. the image is resolved through the local [`ImageStore`], pulling it with
+//!    [`ImageRegistry`] and unpacking its layers into the [`LayerStore`] when
+//!    it is not present yet;
+//! 2. the layers are stacked into a per-container overlay root filesystem;
+//! 3. the image's `ContainerConfig` (`Entrypoint`, `Cmd`, `Env`,
+//!    `WorkingDir`, `User`, `StopSignal`) is merged into the spec, with
+//!    values from the spec taking precedence.
+//!
+//! Storage lives under `<runtime root>/storage`, bundles under
+//! `<runtime root>/bundles/<container id>`. Each bundle records the ID of
+//! its image, so that the container's changes can be diffed against the
+//! image and committed on top of it.
+
+use crate::error::{CoreError, Result};
+use crate::runtime::{ContainerFs, DEFAULT_PATH};
+use crate::storage::{
+    ContainerConfig, ImageRegistry, ImageStore, LayerStore, LocalImage, StoredImage,
+};
+use crate::types::{
+    CommitOptions, ContainerId, ContainerSpec, FilesystemChange, ImageRef, ImportOptions, Platform,
+    PullOptions, PushOptions,
+};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncRead;
+use tokio::sync::OnceCell;
+use tracing::{debug, info, warn};
+
+/// File in a bundle directory holding the ID of the container's image.
+const IMAGE_FILE: &str = "image";
+
+/// A bundle ready to be handed to an OCI runtime.
+#[derive(Debug, Clone)]
+pub struct PreparedBundle {
+    /// Bundle directory (where `config.json` goes).
+    pub path: PathBuf,
+    /// Root filesystem of the container.
+    pub rootfs: PathBuf,
+    /// Container spec with the image configuration merged in.
+    ///
+    /// `command` holds the full argv, `user` is numeric (`uid:gid`) and
+    /// `group_add` holds numeric gids.
+    pub spec: ContainerSpec,
+    /// The image the bundle was built from.
+    pub image: StoredImage,
+}
+
+/// Builds OCI bundles from images.
+pub struct BundleManager {
+    /// Directory holding per-container bundles.
+    bundles_dir: PathBuf,
+    /// Unpacked image layers.
+    layers: LayerStore,
+    /// Local image index.
+    images: ImageStore,
+    /// Registry client used to pull missing images.
+    registry: ImageRegistry,
+    /// Lazily creates the storage directories on first use.
+    initialized: OnceCell<()>,
+}
+
+impl BundleManager {
+    /// Create a bundle manager rooted at a runtime root directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the registry client cannot be constructed.
+    pub fn new(root_dir: &Path) -> Result<Self> {
+        let storage_dir = root_dir.join("storage");
+
+        Ok(Self {
+            bundles_dir: root_dir.join("bundles"),
+            layers: LayerStore::new(storage_dir.join("layers")),
+            images: ImageStore::new(storage_dir.join("images")),
+            registry: ImageRegistry::new(storage_dir.join("cache"))?,
+            initialized: OnceCell::new(),
+        })
+    }
+
+    /// Create storage directories and load persisted state.
+    ///
+    /// Called implicitly by every operation; safe to call more than once.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a storage directory cannot be created or the image
+    /// index cannot be read.
+    pub async fn initialize(&self) -> Result<()> {
+        self.initialized
+            .get_or_try_init(|| async {
+                self.layers.initialize().await?;
+                self.images.initialize().await?;
+                tokio::fs::create_dir_all(&self.bundles_dir).await?;
+                Ok::<_, CoreError>(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Local image index.
+    #[must_use]
+    pub const fn images(&self) -> &ImageStore {
+        &self.images
+    }
+
+    /// Unpacked layer store.
+    #[must_use]
+    pub const fn layers(&self) -> &LayerStore {
+        &self.layers
+    }
+
+    /// Registry client images are pulled and pushed with.
+    #[must_use]
+    pub const fn registry(&self) -> &ImageRegistry {
+        &self.registry
+    }
+
+    /// Bundle directory for a container.
+    #[must_use]
+    pub fn bundle_dir(&self, id: &ContainerId) -> PathBuf {
+        self.bundles_dir.join(id.as_str())
+    }
+
+    /// Pull an image from its registry and unpack its layers.
+    ///
+    /// Always contacts the registry, so a moved tag is picked up.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the pull fails, a layer does not match the image
+    /// configuration, or the image cannot be stored.
+    pub async fn pull(&self, image: &ImageRef, options: &PullOptions) -> Result<StoredImage> {
+        self.initialize().await?;
+
+        let pulled = self.registry.pull_with(&image.full_name(), options).await?;
+
+        let mut layers = Vec::with_capacity(pulled.manifest.layers.len());
+        let blobs = pulled.manifest.layers.iter().zip(&pulled.layer_paths);
+        for (index, (descriptor, path)) in blobs.enumerate() {
+            let layer = self
+                .layers
+                .import_blob(path, &descriptor.digest, &descriptor.media_type)
+                .await?;
+            if let Err(e) = pulled.config.verify_diff_id(index, &layer.diff_id) {
+                self.layers.remove(&descriptor.digest).await?;
+                return Err(e);
+            }
+            layers.push(descriptor.digest.clone());
+        }
+
+        let stored = StoredImage {
+            id: pulled.manifest.config.digest.clone(),
+            references: Vec::new(),
+            size: pulled.manifest.layers.iter().map(|l| l.size).sum(),
+            manifest: pulled.manifest,
+            config: pulled.config,
+            layers,
+            pulled_at: chrono::Utc::now(),
+        };
+
+        self.images.insert(image, stored).await?;
+        info!("Stored image {}", image);
+
+        self.images
+            .get(image)
+            .ok_or_else(|| CoreError::ImageNotFound(image.full_name()))
+    }
+
+    /// Push a local image to the registry and repository it is named
+    /// after, returning the digest of the pushed manifest.
+    ///
+    /// Layers are mounted from the image's other repositories on the same
+    /// registry where the registry allows it.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the image is not stored locally, a layer blob is
+    /// missing, or the registry refuses the push.
+    pub async fn push(&self, image: &ImageRef, options: &PushOptions) -> Result<String> {
+        self.initialize().await?;
+
+        let stored = self
+            .images
+            .get(image)
+            .ok_or_else(|| CoreError::ImageNotFound(image.full_name()))?;
+
+        let mut layer_paths = Vec::with_capacity(stored.layers.len());
+        for digest in &stored.layers {
+            let path = self.layers.blob_path(digest);
+            if !path.is_file() {
+                return Err(CoreError::StorageOperation(format!(
+                    "layer {digest} of {image} has no stored blob"
+                )));
+            }
+            layer_paths.push(path);
+        }
+
+        let mount_from = stored
+            .references
+            .iter()
+            .map(|reference| ImageRef::parse(reference))
+            .filter(|other| {
+                other.registry == image.registry && other.repository != image.repository
+            })
+            .map(|other| other.repository)
+            .collect();
+        let local = LocalImage {
+            manifest: stored.manifest,
+            config: stored.config,
+            layer_paths,
+            mount_from,
+        };
+
+        self.registry
+            .push(&image.full_name(), &local, options)
+            .await
+    }
+
+    /// Return the local copy of an image for `platform`, pulling it if
+    /// necessary.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the image has to be pulled and the pull fails.
+    pub async fn ensure_image(&self, image: &ImageRef, platform: &Platform) -> Result<StoredImage> {
+        self.initialize().await?;
+
+        if let Some(stored) = self.images.get(image) {
+            if !stored.config.platform().matches(platform) {
+                info!(
+                    "Image {} is for {}, pulling it for {}",
+                    image,
+                    stored.config.platform(),
+                    platform
+                );
+            } else if stored.layers.iter().all(|l| self.layers.has(l)) {
+                debug!("Image {} available locally", image);
+                return Ok(stored);
+            } else {
+                warn!("Image {} has missing layers, pulling again", image);
+            }
+        }
+
+        let options = PullOptions {
+            platform: Some(platform.clone()),
+            ..Default::default()
+        };
+        self.pull(image, &options).await
+    }
+
+    /// Prepare the bundle directory and root filesystem for a container.
+    ///
+    /// The caller writes `config.json` into [`PreparedBundle::path`] and
+    /// hands the bundle to the runtime. Call [`cleanup`](Self::cleanup) once
+    /// the container is deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the image cannot be obtained or its root filesystem
+    /// cannot be mounted, and [`CoreError::InvalidSpec`] if neither the spec
+    /// nor the image sets a command or a user or group cannot be resolved.
+    pub async fn prepare(&self, id: &ContainerId, spec: &ContainerSpec) -> Result<PreparedBundle> {
+        let platform = spec.platform.clone().unwrap_or_else(Platform::host);
+        let image = self.ensure_image(&spec.image, &platform).await?;
+
+        let path = self.bundle_dir(id);
+        tokio::fs::create_dir_all(&path).await?;
+        tokio::fs::write(path.join(IMAGE_FILE), &image.id).await?;
+
+        let rootfs = match self.mount_rootfs(id, &image).await {
+            Ok(rootfs) => rootfs,
+            Err(e) => {
+                let _ = tokio::fs::remove_dir_all(&path).await;
+                return Err(e);
+            }
+        };
+
+        let mut resolved = merge_image_config(spec, image.config.config.as_ref());
+        if resolved.command.is_empty() {
+            self.cleanup(id).await?;
+            return Err(CoreError::InvalidSpec {
+                field: "command".to_string(),
+                reason: format!("no command specified and image {} has none", spec.image),
+            });
+        }
+
+        if let Some(user) = resolved.user.take() {
+            match resolve_user(&rootfs, &user) {
+                Ok((uid, gid)) => resolved.user = Some(format!("{uid}:{gid}")),
+                Err(e) => {
+                    self.cleanup(id).await?;
+                    return Err(e);
+                }
+            }
+        }
+
+        let groups: Result<Vec<String>> = resolved
+            .group_add
+            .iter()
+            .map(|group| resolve_group(&rootfs, group).map(|gid| gid.to_string()))
+            .collect();
+        match groups {
+            Ok(groups) => resolved.group_add = groups,
+            Err(e) => {
+                self.cleanup(id).await?;
+                return Err(e);
+            }
+        }
+
+        Ok(PreparedBundle {
+            path,
+            rootfs,
+            spec: resolved,
+            image,
+        })
+    }
+
+    /// List the paths a container changed relative to its image.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the container has no bundle or root filesystem.
+    pub async fn diff(&self, id: &ContainerId) -> Result<Vec<FilesystemChange>> {
+        let image = self.container_image(id).await?;
+        self.layers.diff(&image.layers, id.as_str()).await
+    }
+
+    /// Snapshot a container's filesystem into a new image.
+    ///
+    /// The container's changes become a new top layer on its image. The
+    /// image is tagged with `options.reference`, or stored untagged.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the container has no bundle or root filesystem, or
+    /// the image cannot be stored.
+    pub async fn commit(&self, id: &ContainerId, options: &CommitOptions) -> Result<StoredImage> {
+        let base = self.container_image(id).await?;
+        let layer = self.layers.commit(id.as_str()).await?;
+        let image = base.with_layer(&layer, options)?;
+
+        match &options.reference {
+            Some(reference) => self.images.insert(reference, image.clone()).await?,
+            None => self.images.add(image.clone()).await?,
+        }
+        info!(container_id = %id, "Committed container as image {}", image.id);
+
+        Ok(self.images.get_by_id(&image.id).unwrap_or(image))
+    }
+
+    /// Stream a container's root filesystem as a flat tarball.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the container has no bundle or root filesystem.
+    pub async fn export(&self, id: &ContainerId) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        self.initialize().await?;
+
+        if !self.bundle_dir(id).exists() {
+            return Err(CoreError::ContainerNotFound(id.to_string()));
+        }
+        self.layers.export(id.as_str())
+    }
+
+    /// Create a single-layer image from a root filesystem tarball.
+    ///
+    /// The image is tagged with `options.reference`, or stored untagged.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the archive cannot be unpacked or the image cannot
+    /// be stored.
+    pub async fn import(
+        &self,
+        archive: impl AsyncRead + Send + Unpin + 'static,
+        options: &ImportOptions,
+    ) -> Result<StoredImage> {
+        self.initialize().await?;
+
+        let layer = self.layers.import(archive).await?;
+        let image = StoredImage::from_layer(&layer, options)?;
+
+        match &options.reference {
+            Some(reference) => self.images.insert(reference, image.clone()).await?,
+            None => self.images.add(image.clone()).await?,
+        }
+        info!("Imported image {}", image.id);
+
+        Ok(self.images.get_by_id(&image.id).unwrap_or(image))
+    }
+
+    /// The image a container was created from.
+    async fn container_image(&self, id: &ContainerId) -> Result<StoredImage> {
+        self.initialize().await?;
+
+        let image_id = tokio::fs::read_to_string(self.bundle_dir(id).join(IMAGE_FILE))
+            .await
+            .map_err(|_| CoreError::ContainerNotFound(id.to_string()))?;
+        self.images
+            .get_by_id(image_id.trim())
+            .ok_or_else(|| CoreError::ImageNotFound(image_id.trim().to_string()))
+    }
+
+    /// Unmount the root filesystem and remove the bundle of a container.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the root filesystem cannot be unmounted or the
+    /// bundle directory cannot be removed.
+    pub async fn cleanup(&self, id: &ContainerId) -> Result<()> {
+        #[cfg(unix)]
+        self.layers.unmount_overlay(id.as_str()).await?;
+
+        let path = self.bundle_dir(id);
+        if path.exists() {
+            tokio::fs::remove_dir_all(&path).await?;
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    async fn mount_rootfs(&self, id: &ContainerId, image: &StoredImage) -> Result<PathBuf> {
+        self.layers.mount_overlay(&image.layers, id.as_str()).await
+    }
+
+    #[cfg(not(unix))]
+    async fn mount_rootfs(&self, _id: &ContainerId, _image: &StoredImage) -> Result<PathBuf> {
+        Err(CoreError::StorageOperation(
+            "overlay root filesystems require a Unix host".to_string(),
+        ))
+    }
+}
+
+/// Merge an image's `ContainerConfig` into a container spec.
+///
+/// Follows Docker semantics:
+/// - argv is `Entrypoint` followed by `spec.command` (or the image `Cmd`
+///   when the spec has none), followed by `spec.args`; the result is stored
+///   in `command` and `args` is left empty;
+/// - image `Env` entries are applied first and overridden by `spec.env`,
+///   and `PATH` gets a default if neither sets it;
+/// - `working_dir`, `user` and `stop_signal` fall back to the image values.
+#[must_use]
+pub fn merge_image_config(spec: &ContainerSpec, config: Option<&ContainerConfig>) -> ContainerSpec {
+    let mut resolved = spec.clone();
+    let empty = ContainerConfig::default();
+    let config = config.unwrap_or(&empty);
+
+    let mut argv = config.entrypoint.clone().unwrap_or_default();
+    if spec.command.is_empty() {
+        argv.extend(config.cmd.clone().unwrap_or_default());
+    } else {
+        argv.extend(spec.command.iter().cloned());
+    }
+    argv.extend(spec.args.iter().cloned());
+    resolved.command = argv;
+    resolved.args = Vec::new();
+
+    let mut env = std::collections::HashMap::new();
+    for entry in config.env.iter().flatten() {
+        let (key, value) = entry.split_once('=').unwrap_or((entry.as_str(), ""));
+        env.insert(key.to_string(), value.to_string());
+    }
+    env.extend(spec.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+    env.entry("PATH".to_string())
+        .or_insert_with(|| DEFAULT_PATH.to_string());
+    resolved.env = env;
+
+    if resolved.working_dir.is_none() {
+        resolved.working_dir = config
+            .working_dir
+            .as_deref()
+            .filter(|w| !w.is_empty())
+            .map(PathBuf::from);
+    }
+
+    if resolved.user.is_none() {
+        resolved.user = config.user.clone().filter(|u| !u.is_empty());
+    }
+
+    if resolved.stop_signal.is_none() {
+        resolved.stop_signal = config.stop_signal.clone().filter(|s| !s.is_empty());
+    }
+
+    resolved
+}
+
+/// Resolve a `user[:group]` string to a numeric uid and gid.
+///
+/// Names are looked up in the container's `/etc/passwd` and `/etc/group`,
+/// with symlinks resolved inside `rootfs`. Without an explicit group, the
+/// user's primary group is used (or 0 for a numeric uid with no passwd
+/// entry).
+///
+/// # Errors
+///
+/// Returns [`CoreError::InvalidSpec`] if a name cannot be found.
+pub fn resolve_user(rootfs: &Path, user: &str) -> Result<(u32, u32)> {
+    let (user_part, group_part) = match user.split_once(':') {
+        Some((u, g)) => (u, Some(g)),
+        None => (user, None),
+    };
+
+    let passwd = read_container_file(rootfs, "/etc/passwd");
+    // name:password:uid:gid:...
+    let entries: Vec<(&str, u32, u32)> = passwd
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            Some((
+                fields.first()?.to_owned(),
+                fields.get(2)?.parse().ok()?,
+                fields.get(3)?.parse().ok()?,
+            ))
+        })
+        .collect();
+
+    let (uid, primary_gid) = if let Ok(uid) = user_part.parse::<u32>() {
+        let gid = entries.iter().find(|e| e.1 == uid).map_or(0, |e| e.2);
+        (uid, gid)
+    } else {
+        entries
+            .iter()
+            .find(|e| e.0 == user_part)
+            .map(|e| (e.1, e.2))
+            .ok_or_else(|| CoreError::InvalidSpec {
+                field: "user".to_string(),
+                reason: format!("unable to find user {user_part} in /etc/passwd"),
+            })?
+    };
+
+    let gid = match group_part {
+        None => primary_gid,
+        Some(group) => lookup_group(rootfs, group).ok_or_else(|| CoreError::InvalidSpec {
+            field: "user".to_string(),
+            reason: format!("unable to find group {group} in /etc/group"),
+        })?,
+    };
+
+    Ok((uid, gid))
+}
+
+/// Resolve a group name or gid to a numeric gid.
+///
+/// Names are looked up in the container's `/etc/group`.
+///
+/// # Errors
+///
+/// Returns [`CoreError::InvalidSpec`] if the name cannot be found.
+pub fn resolve_group(rootfs: &Path, group: &str) -> Result<u32> {
+    lookup_group(rootfs, group).ok_or_else(|| CoreError::InvalidSpec {
+        field: "group_add".to_string(),
+        reason: format!("unable to find group {group} in /etc/group"),
+    })
+}
+
+fn lookup_group(rootfs: &Path, group: &str) -> Option<u32> {
+    if let Ok(gid) = group.parse::<u32>() {
+        return Some(gid);
+    }
+    let groups = read_container_file(rootfs, "/etc/group");
+    // name:password:gid:members
+    groups
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.first() == Some(&group))
+        .and_then(|fields| fields.get(2)?.parse().ok())
+}
+
+/// Contents of a file in the container, or an empty string if it cannot be
+/// read.
+///
+/// Symlinks are resolved relative to `rootfs`, so an image cannot make us
+/// read host files.
+fn read_container_file(rootfs: &Path, path: &str) -> String {
+    ContainerFs::new(rootfs)
+        .resolve(Path::new(path), true)
+        .ok()
+        .and_then(|host_path| std::fs::read_to_string(host_path).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn image_config() -> ContainerConfig {
+        ContainerConfig {
+            entrypoint: Some(vec!["docker-entrypoint.sh".to_string()]),
+            cmd: Some(vec!["postgres".to_string()]),
+            env: Some(vec![
+                "PATH=/usr/lib/postgresql/bin:/usr/bin".to_string(),
+                "PGDATA=/var/lib/postgresql/data".to_string(),
+            ]),
+            working_dir: Some("/var/lib/postgresql".to_string()),
+            user: Some("postgres".to_string()),
+            stop_signal: Some("SIGINT".to_string()),
+            ..ContainerConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_uses_image_defaults() {
+        let spec = ContainerSpec::builder().image("postgres:16").build();
+        let resolved = merge_image_config(&spec, Some(&image_config()));
+
+        assert_eq!(resolved.command, vec!["docker-entrypoint.sh", "postgres"]);
+        assert!(resolved.args.is_empty());
+        assert_eq!(resolved.env["PGDATA"], "/var/lib/postgresql/data");
+        assert_eq!(resolved.env["PATH"], "/usr/lib/postgresql/bin:/usr/bin");
+        assert_eq!(resolved.working_dir, Some(PathBuf::from("/var/lib/postgresql")));
+        assert_eq!(resolved.user.as_deref(), Some("postgres"));
+        assert_eq!(resolved.stop_signal.as_deref(), Some("SIGINT"));
+    }
+
+    #[test]
+    fn test_merge_spec_overrides_image() {
+        let mut spec = ContainerSpec::builder()
+            .image("postgres:16")
+            .command(vec!["postgres", "-c"])
+            .env("PGDATA", "/data")
+            .build();
+        spec.args = vec!["fsync=off".to_string()];
+        spec.working_dir = Some(PathBuf::from("/srv"));
+        spec.user = Some("1000:1000".to_string());
+        spec.stop_signal = Some("SIGQUIT".to_string());
+
+        let resolved = merge_image_config(&spec, Some(&image_config()));
+
+        assert_eq!(resolved.command, vec!["docker-entrypoint.sh", "postgres", "-c", "fsync=off"]);
+        assert_eq!(resolved.env["PGDATA"], "/data");
+        assert_eq!(resolved.working_dir, Some(PathBuf::from("/srv")));
+        assert_eq!(resolved.user.as_deref(), Some("1000:1000"));
+        assert_eq!(resolved.stop_signal.as_deref(), Some("SIGQUIT"));
+    }
+
+    #[test]
+    fn test_merge_without_image_config() {
+        let spec = ContainerSpec::builder()
+            .image("scratch-app")
+            .command(vec!["/app"])
+            .build();
+        let resolved = merge_image_config(&spec, None);
+
+        assert_eq!(resolved.command, vec!["/app"]);
+        assert_eq!(resolved.env["PATH"], DEFAULT_PATH);
+        assert!(resolved.working_dir.is_none());
+        assert!(resolved.user.is_none());
+    }
+
+    fn rootfs_with_users() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("etc")).unwrap();
+        std::fs::write(
+            dir.path().join("etc/passwd"),
+            "root:x:0:0:root:/root:/bin/sh\npostgres:x:70:70::/var/lib/postgresql:/bin/sh\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("etc/group"), "root:x:0:\npostgres:x:70:\nstaff:x:50:\n")
+            .unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_resolve_user_names_and_ids() {
+        let rootfs = rootfs_with_users();
+        let root = rootfs.path();
+
+        assert_eq!(resolve_user(root, "postgres").unwrap(), (70, 70));
+        assert_eq!(resolve_user(root, "postgres:staff").unwrap(), (70, 50));
+        assert_eq!(resolve_user(root, "70").unwrap(), (70, 70));
+        assert_eq!(resolve_user(root, "1000").unwrap(), (1000, 0));
+        assert_eq!(resolve_user(root, "1000:1000").unwrap(), (1000, 1000));
+    }
+
+    #[test]
+    fn test_resolve_user_unknown_name() {
+        let rootfs = rootfs_with_users();
+
+        assert!(resolve_user(rootfs.path(), "nobody").is_err());
+        assert!(resolve_user(rootfs.path(), "postgres:wheel").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_user_symlinks_stay_in_rootfs() {
+        let rootfs = rootfs_with_users();
+        let root = rootfs.path();
+        let host = TempDir::new().unwrap();
+        std::fs::write(host.path().join("group"), "wheel:x:10:\n").unwrap();
+
+        std::fs::rename(root.join("etc/passwd"), root.join("etc/passwd.real")).unwrap();
+        std::os::unix::fs::symlink("/etc/passwd.real", root.join("etc/passwd")).unwrap();
+        std::fs::remove_file(root.join("etc/group")).unwrap();
+        std::os::unix::fs::symlink(host.path().join("group"), root.join("etc/group")).unwrap();
+
+        assert_eq!(resolve_user(root, "postgres").unwrap(), (70, 70));
+        assert!(resolve_group(root, "wheel").is_err());
+    }
+
+    #[test]
+    fn test_resolve_group() {
+        let rootfs = rootfs_with_users();
+        let root = rootfs.path();
+
+        assert_eq!(resolve_group(root, "staff").unwrap(), 50);
+        assert_eq!(resolve_group(root, "1234").unwrap(), 1234);
+        assert!(resolve_group(root, "wheel").is_err());
+    }
+}
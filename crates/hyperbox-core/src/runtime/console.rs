@@ -0,0 +1,305 @@
+//! Console sockets and pseudo-terminals.
+//!
+//! When a container or exec process asks for a terminal, OCI runtimes create
+//! the PTY themselves and send its master end over the Unix socket passed as
+//! `--console-socket`, using an `SCM_RIGHTS` control message. [`ConsoleSocket`]
+//! receives that descriptor and [`Pty`] wraps it as an async stream.
+
+use crate::error::{CoreError, Result};
+use crate::types::TtySize;
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::sys::socket::{recvmsg, ControlMessageOwned, MsgFlags};
+use std::io::{self, IoSliceMut};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, Interest, ReadBuf};
+use tokio::net::UnixListener;
+use tracing::debug;
+
+nix::ioctl_write_ptr_bad!(set_window_size, nix::libc::TIOCSWINSZ, nix::libc::winsize);
+
+/// Listening socket passed to the runtime as `--console-socket`.
+///
+/// The socket file is removed when the value is dropped.
+pub struct ConsoleSocket {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl ConsoleSocket {
+    /// Bind a console socket at `path`, replacing any stale socket file.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the socket cannot be bound.
+    pub fn bind(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let _ = std::fs::remove_file(&path);
+
+        let listener = UnixListener::bind(&path)?;
+        Ok(Self { listener, path })
+    }
+
+    /// Path of the socket.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Wait for the runtime to connect and send the PTY master.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the peer disconnects without sending a descriptor.
+    pub async fn receive(&self) -> Result<OwnedFd> {
+        let (stream, _) = self.listener.accept().await?;
+        loop {
+            stream.readable().await?;
+            match stream.try_io(Interest::READABLE, || recv_fd(stream.as_raw_fd())) {
+                Ok(Some(fd)) => {
+                    debug!("Received terminal on {:?}", self.path);
+                    return Ok(fd);
+                }
+                Ok(None) => {
+                    return Err(CoreError::RuntimeExecution(
+                        "console socket closed without a terminal".to_string(),
+                    ))
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for ConsoleSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Receive one message and return the first descriptor it carries.
+fn recv_fd(socket: RawFd) -> io::Result<Option<OwnedFd>> {
+    // The runtime sends the terminal name alongside the descriptor.
+    let mut buf = [0_u8; 4096];
+    let mut iov = [IoSliceMut::new(&mut buf)];
+    let mut cmsg = nix::cmsg_space!([RawFd; 1]);
+
+    let msg = recvmsg::<()>(socket, &mut iov, Some(&mut cmsg), MsgFlags::MSG_CMSG_CLOEXEC)
+        .map_err(io::Error::from)?;
+
+    for cmsg in msg.cmsgs() {
+        if let ControlMessageOwned::ScmRights(fds) = cmsg {
+            let mut fds = fds.into_iter();
+            let first = fds.next();
+            for extra in fds {
+                let _ = nix::unistd::close(extra);
+            }
+            // SAFETY: the kernel just installed this descriptor for us and
+            // nothing else owns it.
+            #[allow(unsafe_code)]
+            return Ok(first.map(|fd| unsafe { OwnedFd::from_raw_fd(fd) }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Master end of a pseudo-terminal.
+///
+/// Reads return end-of-file once every process holding the slave end has
+/// exited.
+pub struct Pty {
+    fd: AsyncFd<OwnedFd>,
+}
+
+impl Pty {
+    /// Wrap a PTY master descriptor, switching it to non-blocking mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the descriptor cannot be registered with the reactor.
+    pub fn new(fd: OwnedFd) -> Result<Self> {
+        let flags = fcntl(fd.as_raw_fd(), FcntlArg::F_GETFL).map_err(io::Error::from)?;
+        let flags = OFlag::from_bits_truncate(flags) | OFlag::O_NONBLOCK;
+        fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(flags)).map_err(io::Error::from)?;
+
+        Ok(Self {
+            fd: AsyncFd::new(fd)?,
+        })
+    }
+
+    /// Duplicate the descriptor, e.g. to read and write from separate tasks.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the descriptor cannot be duplicated.
+    pub fn try_clone(&self) -> Result<Self> {
+        Self::new(self.fd.get_ref().try_clone()?)
+    }
+
+    /// Set the terminal size.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the `TIOCSWINSZ` ioctl fails.
+    pub fn resize(&self, size: TtySize) -> Result<()> {
+        let winsize = nix::libc::winsize {
+            ws_row: size.rows,
+            ws_col: size.cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: `winsize` outlives the call and matches the ioctl's type.
+        #[allow(unsafe_code)]
+        unsafe { set_window_size(self.fd.as_raw_fd(), &winsize) }.map_err(io::Error::from)?;
+        Ok(())
+    }
+}
+
+impl AsyncRead for Pty {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = ready!(self.fd.poll_read_ready(cx))?;
+            let unfilled = buf.initialize_unfilled();
+            match guard
+                .try_io(|fd| nix::unistd::read(fd.as_raw_fd(), unfilled).map_err(io::Error::from))
+            {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                }
+                // EIO means the slave side has been closed.
+                Ok(Err(e)) if e.raw_os_error() == Some(nix::libc::EIO) => {
+                    return Poll::Ready(Ok(()))
+                }
+                Ok(Err(e)) => return Poll::Ready(Err(e)),
+                Err(_would_block) => {}
+            }
+        }
+    }
+}
+
+impl AsyncWrite for Pty {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = ready!(self.fd.poll_write_ready(cx))?;
+            match guard
+                .try_io(|fd| nix::unistd::write(fd.as_raw_fd(), buf).map_err(io::Error::from))
+            {
+                Ok(result) => return Poll::Ready(result),
+                Err(_would_block) => {}
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::sys::socket::{sendmsg, ControlMessage};
+    use std::io::IoSlice;
+    use std::os::unix::net::UnixStream;
+    use tempfile::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    nix::ioctl_read_bad!(get_window_size, nix::libc::TIOCGWINSZ, nix::libc::winsize);
+
+    /// Send `fd` over the console socket the way runtimes do.
+    fn send_fd(path: PathBuf, fd: RawFd) {
+        let stream = UnixStream::connect(path).unwrap();
+        let name = b"/dev/pts/0";
+        let iov = [IoSlice::new(name)];
+        let fds = [fd];
+        sendmsg::<()>(
+            stream.as_raw_fd(),
+            &iov,
+            &[ControlMessage::ScmRights(&fds)],
+            MsgFlags::empty(),
+            None,
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_receive_terminal_and_relay_io() {
+        let dir = TempDir::new().unwrap();
+        let socket = ConsoleSocket::bind(dir.path().join("console.sock")).unwrap();
+        let pty = nix::pty::openpty(None, None).unwrap();
+
+        let path = socket.path().to_path_buf();
+        let master = pty.master.as_raw_fd();
+        let sender = std::thread::spawn(move || send_fd(path, master));
+
+        let received = socket.receive().await.unwrap();
+        sender.join().unwrap();
+        drop(pty.master);
+
+        let mut master = Pty::new(received).unwrap();
+        let mut slave = tokio::fs::File::from_std(std::fs::File::from(pty.slave));
+
+        slave.write_all(b"hello\n").await.unwrap();
+        slave.flush().await.unwrap();
+        let mut buf = [0_u8; 64];
+        let n = master.read(&mut buf).await.unwrap();
+        assert!(String::from_utf8_lossy(&buf[..n]).starts_with("hello"));
+
+        master
+            .resize(TtySize {
+                rows: 40,
+                cols: 120,
+            })
+            .unwrap();
+        let mut winsize = nix::libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        #[allow(unsafe_code)]
+        unsafe { get_window_size(slave.as_raw_fd(), &mut winsize) }.unwrap();
+        assert_eq!((winsize.ws_row, winsize.ws_col), (40, 120));
+    }
+
+    #[tokio::test]
+    async fn test_socket_file_removed_on_drop() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("console.sock");
+        let socket = ConsoleSocket::bind(&path).unwrap();
+        assert!(path.exists());
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_read_returns_eof_when_slave_closes() {
+        let pty = nix::pty::openpty(None, None).unwrap();
+        let mut master = Pty::new(pty.master).unwrap();
+        drop(pty.slave);
+
+        let mut buf = [0_u8; 16];
+        assert_eq!(master.read(&mut buf).await.unwrap(), 0);
+    }
+}
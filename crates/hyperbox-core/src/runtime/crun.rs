@@ -1,14 +1,25 @@
 //! crun runtime implementation.
 //!
 //! crun is the primary runtime for HyperBox, targeting 47ms container lifecycle.
+//! runc has the same command line, so [`RuncRuntime`](super::RuncRuntime)
+//! reuses this implementation with a different binary.
 
 use crate::error::{CoreError, Result};
-use crate::runtime::traits::ProcessInfo;
-use crate::runtime::{ContainerRuntime, RuntimeConfig, RuntimeType};
+use crate::isolation::CgroupManager;
+use crate::logs::{LogConfig, LogDriver};
+use crate::runtime::traits::{ExecSession, ImageInfo, ProcessInfo};
+use crate::runtime::{
+    container_processes, read_config, stop_signal, update_resources, while_frozen, write_config,
+    BundleManager, ContainerFs, ContainerRuntime, ExitMonitor, MetadataStore, OciSpecBuilder,
+    RuntimeConfig, RuntimeType, StdioManager, DEFAULT_STOP_SIGNAL,
+};
 use crate::types::*;
 use async_trait::async_trait;
+use futures::stream::BoxStream;
+use oci_spec::runtime::Spec;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::process::Command;
@@ -25,6 +36,8 @@ struct CrunCgroupStats {
     cpu_usage_usec: u64,
     /// Current PIDs count.
     pids_current: u64,
+    /// Resource limit event counters.
+    events: ResourceEvents,
 }
 
 /// crun runtime implementation.
@@ -33,7 +46,20 @@ struct CrunCgroupStats {
 /// Target performance: 47ms total container lifecycle.
 pub struct CrunRuntime {
     config: RuntimeConfig,
+    /// Runtime driven through this implementation (crun or runc).
+    kind: RuntimeType,
     binary_path: PathBuf,
+    /// State directory passed to the runtime as `--root`.
+    state_dir: PathBuf,
+    /// Image store and bundles, shared with the registry's other runtimes.
+    bundles: Arc<BundleManager>,
+    logs: LogDriver,
+    stdio: StdioManager,
+    monitor: ExitMonitor,
+    /// Specs, names and lifecycle times of containers.
+    metadata: MetadataStore,
+    /// Freezes container cgroups for pause and resume.
+    cgroups: CgroupManager,
 }
 
 impl CrunRuntime {
@@ -43,17 +69,46 @@ impl CrunRuntime {
     ///
     /// Returns error if crun binary is not found.
     pub async fn new(config: RuntimeConfig) -> Result<Self> {
-        let binary_path = Self::find_binary(&config)?;
+        let bundles = Arc::new(BundleManager::new(&config.root_dir)?);
+        Self::with_kind(config, RuntimeType::Crun, bundles).await
+    }
+
+    /// Create an instance driving `kind` with crun's command line and
+    /// keeping images and bundles in `bundles`.
+    pub(crate) async fn with_kind(
+        config: RuntimeConfig,
+        kind: RuntimeType,
+        bundles: Arc<BundleManager>,
+    ) -> Result<Self> {
+        let binary_path = Self::find_binary(&config, kind)?;
+
+        info!("Initializing {} runtime at {:?}", kind, binary_path);
 
-        info!("Initializing crun runtime at {:?}", binary_path);
+        // Each runtime keeps its own state so runtimes sharing `root_dir`
+        // do not see each other's containers.
+        let state_dir = config.root_dir.join("state").join(kind.binary_name());
+
+        let logs = LogDriver::new(config.root_dir.join("logs"), LogConfig::default());
+        let stdio = StdioManager::new(config.root_dir.join("console"));
+        let monitor = ExitMonitor::new(config.root_dir.join("exits"));
+        let metadata =
+            MetadataStore::open(config.root_dir.join("metadata").join(kind.binary_name()));
 
         Ok(Self {
             config,
+            kind,
             binary_path,
+            state_dir,
+            bundles,
+            logs,
+            stdio,
+            monitor,
+            metadata,
+            cgroups: CgroupManager::new(),
         })
     }
 
-    fn find_binary(config: &RuntimeConfig) -> Result<PathBuf> {
+    fn find_binary(config: &RuntimeConfig, kind: RuntimeType) -> Result<PathBuf> {
         // Check explicit path first
         if let Some(ref path) = config.binary_path {
             if path.exists() {
@@ -62,14 +117,14 @@ impl CrunRuntime {
         }
 
         // Search common paths
-        for path in RuntimeType::Crun.search_paths() {
+        for path in kind.search_paths() {
             if path.exists() {
                 return Ok(path);
             }
         }
 
         // Try PATH lookup
-        if let Ok(output) = std::process::Command::new("which").arg("crun").output() {
+        if let Ok(output) = std::process::Command::new("which").arg(kind.binary_name()).output() {
             if output.status.success() {
                 let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
                 return Ok(PathBuf::from(path));
@@ -77,16 +132,16 @@ impl CrunRuntime {
         }
 
         Err(CoreError::RuntimeNotAvailable {
-            runtime: "crun".to_string(),
-            path: PathBuf::from("/usr/bin/crun"),
+            runtime: kind.to_string(),
+            path: PathBuf::from(format!("/usr/bin/{}", kind.binary_name())),
         })
     }
 
-    async fn run_crun(&self, args: &[&str]) -> Result<std::process::Output> {
+    async fn run_cli(&self, args: &[&str]) -> Result<std::process::Output> {
         let mut cmd = Command::new(&self.binary_path);
 
         cmd.arg("--root")
-            .arg(&self.config.root_dir)
+            .arg(&self.state_dir)
             .args(args)
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
@@ -96,131 +151,263 @@ impl CrunRuntime {
             cmd.arg("--debug");
         }
 
-        debug!("Running crun: {:?}", cmd);
+        debug!("Running {}: {:?}", self.kind, cmd);
 
         let output =
             tokio::time::timeout(Duration::from_secs(self.config.timeout_seconds), cmd.output())
                 .await
                 .map_err(|_| CoreError::Timeout {
-                    operation: "crun command".to_string(),
+                    operation: format!("{} command", self.kind),
                     duration_ms: self.config.timeout_seconds * 1000,
                 })?
                 .map_err(|e| CoreError::RuntimeExecution(e.to_string()))?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            error!("crun command failed: {}", stderr);
+            error!("{} command failed: {}", self.kind, stderr);
             return Err(CoreError::RuntimeExecution(stderr.to_string()));
         }
 
         Ok(output)
     }
 
-    async fn generate_bundle(&self, spec: &ContainerSpec) -> Result<PathBuf> {
-        // In production, this would create an OCI bundle with config.json
-        // For now, create a minimal structure
-        let bundle_dir = self.config.root_dir.join("bundles").join(spec.hash());
-
-        tokio::fs::create_dir_all(&bundle_dir).await?;
-        tokio::fs::create_dir_all(bundle_dir.join("rootfs")).await?;
-
-        // Generate OCI config.json
-        let oci_spec = self.spec_to_oci(spec)?;
-        let config_path = bundle_dir.join("config.json");
-        tokio::fs::write(&config_path, serde_json::to_string_pretty(&oci_spec)?).await?;
-
-        Ok(bundle_dir)
-    }
-
-    fn spec_to_oci(&self, spec: &ContainerSpec) -> Result<serde_json::Value> {
-        // Simplified OCI spec generation
-        // Production would use oci-spec crate for full compliance
-        let oci = serde_json::json!({
-            "ociVersion": "1.0.2",
-            "process": {
-                "terminal": spec.tty,
-                "user": {
-                    "uid": 0,
-                    "gid": 0
-                },
-                "args": if spec.command.is_empty() {
-                    vec!["/bin/sh".to_string()]
-                } else {
-                    spec.command.clone()
-                },
-                "env": spec.env.iter()
-                    .map(|(k, v)| format!("{k}={v}"))
-                    .collect::<Vec<_>>(),
-                "cwd": spec.working_dir.as_ref()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "/".to_string())
-            },
-            "root": {
-                "path": "rootfs",
-                "readonly": spec.read_only_rootfs
-            },
-            "hostname": spec.hostname.as_deref().unwrap_or("hyperbox"),
-            "linux": {
-                "namespaces": [
-                    { "type": "pid" },
-                    { "type": "network" },
-                    { "type": "ipc" },
-                    { "type": "uts" },
-                    { "type": "mount" },
-                    { "type": "cgroup" }
-                ],
-                "resources": self.resources_to_oci(&spec.resources)
+    /// `crun exec` options for the environment, user and working directory.
+    fn exec_options(spec: &ExecSpec) -> Vec<String> {
+        let mut args = Vec::new();
+
+        for (key, value) in &spec.env {
+            args.push("--env".to_string());
+            args.push(format!("{key}={value}"));
+        }
+
+        if let Some(ref user) = spec.user {
+            args.push("--user".to_string());
+            args.push(user.clone());
+        }
+
+        if let Some(ref cwd) = spec.working_dir {
+            args.push("--cwd".to_string());
+            args.push(cwd.to_string_lossy().to_string());
+        }
+
+        args
+    }
+
+    /// Run `crun create` with the container's stdio wired up.
+    ///
+    /// Output goes to the log driver and to attached clients: through pipes
+    /// inherited by the init process, or through the PTY crun sends to the
+    /// console socket when `spec.tty` is set. crun's own errors go to
+    /// `crun.log` (or `runc.log`) in the bundle instead.
+    async fn create_container(
+        &self,
+        id: &ContainerId,
+        bundle: &Path,
+        spec: &ContainerSpec,
+    ) -> Result<()> {
+        let log_file = bundle.join(format!("{}.log", self.kind.binary_name()));
+        let mut cmd = Command::new(&self.binary_path);
+
+        cmd.arg("--root")
+            .arg(&self.state_dir)
+            .arg("--log")
+            .arg(&log_file)
+            .arg("create")
+            .arg("--bundle")
+            .arg(bundle);
+
+        debug!("Running {}: {:?}", self.kind, cmd);
+
+        let status = self
+            .stdio
+            .run_create(
+                id,
+                cmd,
+                spec,
+                &self.logs,
+                Duration::from_secs(self.config.timeout_seconds),
+            )
+            .await?;
+
+        if !status.success() {
+            let reason = tokio::fs::read_to_string(&log_file).await.unwrap_or_default();
+            error!("{} create failed: {}", self.kind, reason.trim());
+            return Err(CoreError::RuntimeExecution(format!(
+                "{} create exited with {status}: {}",
+                self.kind,
+                reason.trim()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// PID of the container's init process, as seen from the host.
+    async fn init_pid(&self, id: &ContainerId) -> Result<u32> {
+        let output = self.run_cli(&["state", id.as_str()]).await?;
+        let state: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+        state["pid"]
+            .as_u64()
+            .and_then(|pid| u32::try_from(pid).ok())
+            .filter(|pid| *pid > 0)
+            .ok_or_else(|| {
+                CoreError::RuntimeExecution(format!("{} state reported no pid for {id}", self.kind))
+            })
+    }
+
+    /// Hand the container's init process to the exit monitor.
+    async fn monitor_init(&self, id: &ContainerId) -> Result<()> {
+        let pid = self.init_pid(id).await?;
+        self.monitor.watch(id, pid).await;
+        Ok(())
+    }
+
+    /// Bundle directory of a container, as recorded or reported by the
+    /// runtime.
+    async fn bundle_path(&self, id: &ContainerId) -> Result<PathBuf> {
+        if let Some(bundle) = self.metadata.get(id).and_then(|meta| meta.bundle) {
+            return Ok(bundle);
+        }
+        let output = self.run_cli(&["state", id.as_str()]).await?;
+        let state: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+        state["bundle"].as_str().map(PathBuf::from).ok_or_else(|| {
+            CoreError::RuntimeExecution(format!("{} state reported no bundle for {id}", self.kind))
+        })
+    }
+
+    /// The signal that stops a container, recorded in its `config.json`.
+    async fn stop_signal(&self, id: &ContainerId) -> String {
+        let signal = match self.bundle_path(id).await {
+            Ok(bundle) => read_config(&bundle)
+                .await
+                .map(|spec| stop_signal(&spec).to_string()),
+            Err(e) => Err(e),
+        };
+        signal.unwrap_or_else(|e| {
+            debug!(container_id = %id, "Cannot read stop signal: {}", e);
+            DEFAULT_STOP_SIGNAL.to_string()
+        })
+    }
+
+    /// Recreate a stopped container from its bundle so it can be started
+    /// again.
+    ///
+    /// OCI runtimes cannot restart a stopped container; it has to be deleted
+    /// and created anew. The bundle, root filesystem and logs are kept.
+    async fn recreate(&self, id: &ContainerId) -> Result<()> {
+        let bundle = self.bundle_path(id).await?;
+        let spec = if let Some(meta) = self.metadata.get(id) {
+            meta.spec
+        } else {
+            let tty = read_config(&bundle)
+                .await?
+                .process()
+                .as_ref()
+                .and_then(oci_spec::runtime::Process::terminal)
+                .unwrap_or(false);
+            ContainerSpec {
+                tty,
+                ..ContainerSpec::default()
             }
-        });
+        };
 
-        Ok(oci)
+        debug!(container_id = %id, "Recreating stopped container");
+        if let Err(e) = self.run_cli(&["delete", id.as_str()]).await {
+            if !self.lost_runtime_state(id).await {
+                return Err(e);
+            }
+        }
+        self.stdio.remove(id);
+        self.monitor.remove(id).await?;
+
+        self.create_container(id, &bundle, &spec).await?;
+        if let Err(e) = self.monitor_init(id).await {
+            warn!(container_id = %id, "Exit status will not be recorded: {}", e);
+        }
+        Ok(())
     }
 
-    fn resources_to_oci(&self, resources: &ResourceLimits) -> serde_json::Value {
-        let mut oci_resources = serde_json::json!({});
+    /// Resolve the image, assemble the root filesystem and write
+    /// `config.json` for a new container.
+    async fn generate_bundle(&self, id: &ContainerId, spec: &ContainerSpec) -> Result<PathBuf> {
+        let bundle = self.bundles.prepare(id, spec).await?;
 
-        if let Some(memory) = resources.memory_bytes {
-            oci_resources["memory"] = serde_json::json!({
-                "limit": memory
-            });
+        let written = match Self::spec_to_oci(id, &bundle.spec, &bundle.rootfs) {
+            Ok(oci_spec) => write_config(&bundle.path, &oci_spec).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            let _ = self.bundles.cleanup(id).await;
+            return Err(e);
         }
 
-        if let Some(cpu) = resources.cpu_millicores {
-            oci_resources["cpu"] = serde_json::json!({
-                "shares": (cpu * 1024 / 1000) as u64,
-                "quota": (cpu * 100) as i64,
-                "period": 100000
-            });
+        Ok(bundle.path)
+    }
+
+    /// Recorded metadata of a container, including its exit once the
+    /// monitor has seen it.
+    async fn recorded(&self, id: &ContainerId) -> Result<ContainerMetadata> {
+        if let Ok(Some(exit)) = self.monitor.exit_status(id).await {
+            self.record_exit(id, &exit).await;
         }
+        self.metadata
+            .get(id)
+            .ok_or_else(|| CoreError::ContainerNotFound(id.to_string()))
+    }
 
-        if let Some(pids) = resources.pids_limit {
-            oci_resources["pids"] = serde_json::json!({
-                "limit": pids
-            });
+    /// Whether a container is recorded but unknown to the runtime, whose
+    /// state directory was lost, e.g. on reboot.
+    async fn lost_runtime_state(&self, id: &ContainerId) -> bool {
+        self.metadata.get(id).is_some() && self.run_cli(&["state", id.as_str()]).await.is_err()
+    }
+
+    async fn record_exit(&self, id: &ContainerId, exit: &ContainerExit) {
+        if let Err(e) = self.metadata.record_exit(id, exit).await {
+            debug!(container_id = %id, "Cannot record exit in metadata: {}", e);
         }
+    }
 
-        oci_resources
+    /// Build the OCI runtime spec for a prepared bundle.
+    fn spec_to_oci(id: &ContainerId, spec: &ContainerSpec, rootfs: &Path) -> Result<Spec> {
+        OciSpecBuilder::new(spec)
+            .rootfs(rootfs)
+            .cgroups_path(format!("/hyperbox.slice/container-{}", id.as_str()))
+            .build()
     }
 
-    /// Read cgroup v2 statistics for a container.
-    async fn read_cgroup_stats(&self, id: &ContainerId) -> Result<CrunCgroupStats> {
+    /// Locate a container's cgroup v2 directory.
+    fn cgroup_path(&self, id: &ContainerId) -> Option<PathBuf> {
         // Try multiple possible cgroup paths
         let possible_paths = [
-            PathBuf::from("/sys/fs/cgroup/hyperbox.slice")
-                .join(format!("container-{}", id.as_str())),
+            self.cgroups.container_cgroup_path(id.as_str()),
             PathBuf::from("/sys/fs/cgroup/system.slice")
-                .join(format!("crun-{}.scope", id.as_str())),
+                .join(format!("{}-{}.scope", self.kind.binary_name(), id.as_str())),
             PathBuf::from("/sys/fs/cgroup").join(id.as_str()),
         ];
 
-        for cgroup_path in &possible_paths {
-            if cgroup_path.exists() {
-                return self.read_stats_from_path(cgroup_path).await;
+        possible_paths.into_iter().find(|path| path.exists())
+    }
+
+    /// Thaw a paused container so a signal just sent to it is delivered.
+    async fn thaw_for_signal(&self, id: &ContainerId) {
+        let Some(cgroup_path) = self.cgroup_path(id) else {
+            return;
+        };
+        if matches!(self.cgroups.is_frozen(&cgroup_path).await, Ok(true)) {
+            debug!(container_id = %id, "Thawing paused container to deliver stop signal");
+            if let Err(e) = self.cgroups.thaw(&cgroup_path).await {
+                warn!(container_id = %id, "Failed to thaw container: {}", e);
             }
         }
+    }
 
-        // Return empty stats if cgroup not found
-        Ok(CrunCgroupStats::default())
+    /// Read cgroup v2 statistics for a container.
+    async fn read_cgroup_stats(&self, id: &ContainerId) -> Result<CrunCgroupStats> {
+        match self.cgroup_path(id) {
+            Some(cgroup_path) => self.read_stats_from_path(&cgroup_path).await,
+            // Return empty stats if cgroup not found
+            None => Ok(CrunCgroupStats::default()),
+        }
     }
 
     /// Read stats from a specific cgroup path.
@@ -254,6 +441,8 @@ impl CrunRuntime {
             stats.pids_current = content.trim().parse().unwrap_or(0);
         }
 
+        stats.events = self.cgroups.read_events(path).await;
+
         Ok(stats)
     }
 }
@@ -261,12 +450,12 @@ impl CrunRuntime {
 #[async_trait]
 impl ContainerRuntime for CrunRuntime {
     fn name(&self) -> &'static str {
-        "crun"
+        self.kind.binary_name()
     }
 
     #[instrument(skip(self))]
     async fn version(&self) -> Result<String> {
-        let output = self.run_crun(&["--version"]).await?;
+        let output = self.run_cli(&["--version"]).await?;
         Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
     }
 
@@ -279,10 +468,29 @@ impl ContainerRuntime for CrunRuntime {
         let id = ContainerId::new();
         info!(container_id = %id, "Creating container");
 
-        let bundle = self.generate_bundle(&spec).await?;
+        let meta = ContainerMetadata::new(id.clone(), spec.clone())
+            .with_bundle(self.bundles.bundle_dir(&id));
+        self.metadata.insert(meta).await?;
 
-        self.run_crun(&["create", "--bundle", bundle.to_str().unwrap(), id.as_str()])
-            .await?;
+        let bundle = match self.generate_bundle(&id, &spec).await {
+            Ok(bundle) => bundle,
+            Err(e) => {
+                let _ = self.metadata.remove(&id).await;
+                return Err(e);
+            }
+        };
+
+        if let Err(e) = self.create_container(&id, &bundle, &spec).await {
+            self.stdio.remove(&id);
+            let _ = self.logs.remove(&id).await;
+            let _ = self.bundles.cleanup(&id).await;
+            let _ = self.metadata.remove(&id).await;
+            return Err(e);
+        }
+
+        if let Err(e) = self.monitor_init(&id).await {
+            warn!(container_id = %id, "Exit status will not be recorded: {}", e);
+        }
 
         info!(container_id = %id, "Container created");
         Ok(id)
@@ -291,7 +499,13 @@ impl ContainerRuntime for CrunRuntime {
     #[instrument(skip(self))]
     async fn start(&self, id: &ContainerId) -> Result<()> {
         info!(container_id = %id, "Starting container");
-        self.run_crun(&["start", id.as_str()]).await?;
+        if matches!(self.state(id).await?, ContainerState::Stopped | ContainerState::Exited) {
+            self.recreate(id).await?;
+        }
+        self.run_cli(&["start", id.as_str()]).await?;
+        if let Err(e) = self.metadata.mark_started(id).await {
+            warn!(container_id = %id, "Cannot record container start: {}", e);
+        }
         info!(container_id = %id, "Container started");
         Ok(())
     }
@@ -300,8 +514,9 @@ impl ContainerRuntime for CrunRuntime {
     async fn stop(&self, id: &ContainerId, timeout: Duration) -> Result<()> {
         info!(container_id = %id, "Stopping container");
 
-        // Send SIGTERM first
-        let _ = self.kill(id, "SIGTERM").await;
+        let signal = self.stop_signal(id).await;
+        let _ = self.kill(id, &signal).await;
+        self.thaw_for_signal(id).await;
 
         // Wait for container to stop
         let start = std::time::Instant::now();
@@ -320,23 +535,43 @@ impl ContainerRuntime for CrunRuntime {
     }
 
     async fn kill(&self, id: &ContainerId, signal: &str) -> Result<()> {
-        self.run_crun(&["kill", id.as_str(), signal]).await?;
+        self.run_cli(&["kill", id.as_str(), signal]).await?;
         Ok(())
     }
 
     async fn remove(&self, id: &ContainerId) -> Result<()> {
         info!(container_id = %id, "Removing container");
-        self.run_crun(&["delete", "--force", id.as_str()]).await?;
-        Ok(())
+        if let Err(e) = self.run_cli(&["delete", "--force", id.as_str()]).await {
+            if !self.lost_runtime_state(id).await {
+                return Err(e);
+            }
+        }
+        self.stdio.remove(id);
+        self.monitor.remove(id).await?;
+        self.logs.remove(id).await?;
+        self.bundles.cleanup(id).await?;
+        self.metadata.remove(id).await
     }
 
     async fn pause(&self, id: &ContainerId) -> Result<()> {
-        self.run_crun(&["pause", id.as_str()]).await?;
+        if !matches!(self.state(id).await?, ContainerState::Running) {
+            return Err(CoreError::ContainerNotRunning(id.to_string()));
+        }
+        info!(container_id = %id, "Pausing container");
+        if let Some(cgroup_path) = self.cgroup_path(id) {
+            return self.cgroups.freeze(&cgroup_path).await;
+        }
+        // No cgroup v2 hierarchy; let the runtime use its own freezer.
+        self.run_cli(&["pause", id.as_str()]).await?;
         Ok(())
     }
 
     async fn resume(&self, id: &ContainerId) -> Result<()> {
-        self.run_crun(&["resume", id.as_str()]).await?;
+        info!(container_id = %id, "Resuming container");
+        if let Some(cgroup_path) = self.cgroup_path(id) {
+            return self.cgroups.thaw(&cgroup_path).await;
+        }
+        self.run_cli(&["resume", id.as_str()]).await?;
         Ok(())
     }
 
@@ -348,26 +583,12 @@ impl ContainerRuntime for CrunRuntime {
             args.push("--tty".to_string());
         }
 
-        for (key, value) in &spec.env {
-            args.push("--env".to_string());
-            args.push(format!("{key}={value}"));
-        }
-
-        if let Some(ref user) = spec.user {
-            args.push("--user".to_string());
-            args.push(user.clone());
-        }
-
-        if let Some(ref cwd) = spec.working_dir {
-            args.push("--cwd".to_string());
-            args.push(cwd.to_string_lossy().to_string());
-        }
-
+        args.extend(Self::exec_options(&spec));
         args.push(id.to_string());
         args.extend(spec.command.iter().cloned());
 
         let args_refs: Vec<&str> = args.iter().map(String::as_str).collect();
-        let output = self.run_crun(&args_refs).await?;
+        let output = self.run_cli(&args_refs).await?;
 
         Ok(ExecResult {
             exit_code: output.status.code().unwrap_or(-1),
@@ -377,7 +598,15 @@ impl ContainerRuntime for CrunRuntime {
     }
 
     async fn state(&self, id: &ContainerId) -> Result<ContainerState> {
-        let output = self.run_crun(&["state", id.as_str()]).await?;
+        let output = match self.run_cli(&["state", id.as_str()]).await {
+            Ok(output) => output,
+            // The runtime lost its state, e.g. on reboot, but the container
+            // is still recorded.
+            Err(CoreError::RuntimeExecution(_)) if self.metadata.get(id).is_some() => {
+                return Ok(ContainerState::Exited);
+            }
+            Err(e) => return Err(e),
+        };
         let state_json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
 
         let status = state_json["status"].as_str().unwrap_or("unknown");
@@ -398,11 +627,15 @@ impl ContainerRuntime for CrunRuntime {
         // or uses the system.slice/crun-{id}.scope pattern
         let cgroup_stats = self.read_cgroup_stats(id).await;
 
-        let (memory_usage, memory_limit, cpu_usage_usec, pids) = match cgroup_stats {
-            Ok(stats) => {
-                (stats.memory_usage, stats.memory_limit, stats.cpu_usage_usec, stats.pids_current)
-            }
-            Err(_) => (0, 0, 0, 0),
+        let (memory_usage, memory_limit, cpu_usage_usec, pids, events) = match cgroup_stats {
+            Ok(stats) => (
+                stats.memory_usage,
+                stats.memory_limit,
+                stats.cpu_usage_usec,
+                stats.pids_current,
+                stats.events,
+            ),
+            Err(_) => (0, 0, 0, 0, ResourceEvents::default()),
         };
 
         let memory_percent = if memory_limit > 0 {
@@ -442,32 +675,46 @@ impl ContainerRuntime for CrunRuntime {
                 write_ops: 0,
             },
             pids,
+            events,
         })
     }
 
     async fn logs(
         &self,
-        _id: &ContainerId,
-        _opts: LogOptions,
+        id: &ContainerId,
+        opts: LogOptions,
     ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
-        // Return empty reader for now - would read from log driver
-        let empty: &[u8] = &[];
-        Ok(Box::new(std::io::Cursor::new(empty.to_vec())))
+        self.logs.read(id, &opts).await
     }
 
     async fn attach(
         &self,
-        _id: &ContainerId,
+        id: &ContainerId,
     ) -> Result<(
         Box<dyn AsyncWrite + Send + Unpin>,
         Box<dyn AsyncRead + Send + Unpin>,
         Box<dyn AsyncRead + Send + Unpin>,
     )> {
-        Err(CoreError::Internal("Attach not implemented".to_string()))
+        self.stdio.attach(id)
+    }
+
+    #[instrument(skip(self, spec))]
+    async fn exec_interactive(&self, id: &ContainerId, spec: ExecSpec) -> Result<ExecSession> {
+        let mut cmd = Command::new(&self.binary_path);
+        cmd.arg("--root")
+            .arg(&self.state_dir)
+            .arg("exec")
+            .args(Self::exec_options(&spec));
+
+        self.stdio.run_exec(id, cmd, &spec).await
+    }
+
+    async fn resize(&self, id: &ContainerId, exec_id: Option<&str>, size: TtySize) -> Result<()> {
+        self.stdio.resize(id, exec_id, size)
     }
 
     async fn list(&self) -> Result<Vec<(ContainerId, ContainerState)>> {
-        let output = self.run_crun(&["list", "--format", "json"]).await?;
+        let output = self.run_cli(&["list", "--format", "json"]).await?;
         let containers: Vec<serde_json::Value> =
             serde_json::from_slice(&output.stdout).unwrap_or_default();
 
@@ -485,16 +732,39 @@ impl ContainerRuntime for CrunRuntime {
             }
         }
 
-        Ok(result)
+        Ok(self.metadata.with_recorded(result))
+    }
+
+    async fn inspect(&self, id: &ContainerId) -> Result<ContainerMetadata> {
+        self.recorded(id).await
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<ContainerMetadata>> {
+        let mut records = Vec::new();
+        for meta in self.metadata.list() {
+            records.push(self.recorded(&meta.id).await.unwrap_or(meta));
+        }
+        Ok(records)
+    }
+
+    async fn lookup(&self, name: &str) -> Result<Option<ContainerId>> {
+        Ok(self.metadata.lookup(name))
     }
 
     async fn wait(&self, id: &ContainerId) -> Result<i32> {
+        if let Some(exit) = self.monitor.wait(id).await? {
+            self.record_exit(id, &exit).await;
+            return Ok(exit.exit_code);
+        }
+
+        // Not monitored by this process, e.g. created before a daemon restart.
         loop {
             let state = self.state(id).await?;
             match state {
                 ContainerState::Exited | ContainerState::Stopped => {
-                    // Would parse exit code from state
-                    return Ok(0);
+                    return Err(CoreError::Internal(format!(
+                        "exit status of container {id} was not recorded"
+                    )));
                 }
                 ContainerState::Unknown => {
                     return Err(CoreError::ContainerNotFound(id.to_string()));
@@ -506,6 +776,24 @@ impl ContainerRuntime for CrunRuntime {
         }
     }
 
+    async fn exit_status(&self, id: &ContainerId) -> Result<Option<ContainerExit>> {
+        self.monitor.exit_status(id).await
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn resource_events(
+        &self,
+        id: &ContainerId,
+    ) -> Result<BoxStream<'static, ResourceEvents>> {
+        let cgroup_path = self
+            .cgroup_path(id)
+            .ok_or_else(|| CoreError::CgroupOperation {
+                operation: "watch cgroup events".to_string(),
+                reason: format!("no cgroup found for container {id}"),
+            })?;
+        self.cgroups.watch_events(&cgroup_path)
+    }
+
     #[instrument(skip(self))]
     async fn checkpoint(&self, id: &ContainerId, checkpoint_path: &Path) -> Result<CheckpointId> {
         tokio::fs::create_dir_all(checkpoint_path).await?;
@@ -513,7 +801,7 @@ impl ContainerRuntime for CrunRuntime {
         let checkpoint_id =
             CheckpointId::new(format!("{}-{}", id.short(), chrono::Utc::now().timestamp()));
 
-        self.run_crun(&[
+        self.run_cli(&[
             "checkpoint",
             "--image-path",
             checkpoint_path.to_str().unwrap(),
@@ -527,76 +815,155 @@ impl ContainerRuntime for CrunRuntime {
 
     async fn restore(&self, checkpoint_path: &Path, spec: ContainerSpec) -> Result<ContainerId> {
         let id = ContainerId::new();
-        let bundle = self.generate_bundle(&spec).await?;
-
-        self.run_crun(&[
-            "restore",
-            "--image-path",
-            checkpoint_path.to_str().unwrap(),
-            "--bundle",
-            bundle.to_str().unwrap(),
-            id.as_str(),
-        ])
-        .await?;
+        let meta = ContainerMetadata::new(id.clone(), spec.clone())
+            .with_bundle(self.bundles.bundle_dir(&id));
+        self.metadata.insert(meta).await?;
+
+        let restored = match self.generate_bundle(&id, &spec).await {
+            Ok(bundle) => self
+                .run_cli(&[
+                    "restore",
+                    "--detach",
+                    "--image-path",
+                    checkpoint_path.to_str().unwrap(),
+                    "--bundle",
+                    bundle.to_str().unwrap(),
+                    id.as_str(),
+                ])
+                .await
+                .map(drop),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = restored {
+            let _ = self.bundles.cleanup(&id).await;
+            let _ = self.metadata.remove(&id).await;
+            return Err(e);
+        }
+        if let Err(e) = self.metadata.mark_started(&id).await {
+            warn!(container_id = %id, "Cannot record container start: {}", e);
+        }
+        if let Err(e) = self.monitor_init(&id).await {
+            warn!(container_id = %id, "Exit status will not be recorded: {}", e);
+        }
 
         info!(container_id = %id, "Restored from checkpoint");
         Ok(id)
     }
 
-    async fn update(&self, id: &ContainerId, resources: ResourceLimits) -> Result<()> {
-        let mut args = vec!["update".to_string()];
+    async fn update(
+        &self,
+        id: &ContainerId,
+        resources: ResourceLimits,
+    ) -> Result<ResourceUpdateReport> {
+        let bundle = self.bundle_path(id).await?;
+        let rootfs = read_config(&bundle)
+            .await?
+            .root()
+            .as_ref()
+            .map_or_else(|| bundle.join("rootfs"), |root| bundle.join(root.path()));
+        let (oci, report) = update_resources(&resources, &rootfs)?;
+
+        // The new limits are passed as an OCI `linux.resources` document.
+        let file = bundle.join("update-resources.json");
+        let path = file.to_string_lossy();
+        tokio::fs::write(&file, serde_json::to_vec(&oci)?).await?;
+        let result = self
+            .run_cli(&["update", "--resources", &path, id.as_str()])
+            .await;
+        let _ = tokio::fs::remove_file(&file).await;
+        result?;
+
+        debug!(container_id = %id, applied = ?report.applied, "Updated resource limits");
+        Ok(report)
+    }
 
-        if let Some(memory) = resources.memory_bytes {
-            args.push("--memory".to_string());
-            args.push(memory.to_string());
-        }
+    async fn top(&self, id: &ContainerId) -> Result<Vec<ProcessInfo>> {
+        let pid = self.init_pid(id).await?;
+        container_processes(pid).await
+    }
 
-        if let Some(cpu) = resources.cpu_millicores {
-            args.push("--cpu-shares".to_string());
-            args.push((cpu * 1024 / 1000).to_string());
-        }
+    async fn archive_path(
+        &self,
+        id: &ContainerId,
+        path: &Path,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let fs = ContainerFs::from_bundle(&self.bundle_path(id).await?).await?;
+        while_frozen(self, id, fs.archive(path)).await
+    }
+
+    async fn extract_archive(
+        &self,
+        id: &ContainerId,
+        path: &Path,
+        archive: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> Result<()> {
+        let fs = ContainerFs::from_bundle(&self.bundle_path(id).await?).await?;
+        while_frozen(self, id, fs.extract(path, archive)).await
+    }
+
+    async fn diff(&self, id: &ContainerId) -> Result<Vec<FilesystemChange>> {
+        self.bundles.diff(id).await
+    }
 
-        if let Some(pids) = resources.pids_limit {
-            args.push("--pids-limit".to_string());
-            args.push(pids.to_string());
+    async fn commit(&self, id: &ContainerId, options: CommitOptions) -> Result<String> {
+        let pause = options.pause && self.state(id).await? == ContainerState::Running;
+        if pause {
+            self.pause(id).await?;
+        }
+        let committed = self.bundles.commit(id, &options).await;
+        if pause {
+            self.resume(id).await?;
         }
+        Ok(committed?.id)
+    }
 
-        args.push(id.to_string());
+    async fn export(&self, id: &ContainerId) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        self.bundles.export(id).await
+    }
 
-        let args_refs: Vec<&str> = args.iter().map(String::as_str).collect();
-        self.run_crun(&args_refs).await?;
+    async fn pull_image(&self, image: &crate::types::ImageRef) -> Result<()> {
+        // crun only runs bundles; images are pulled into our own store and
+        // assembled into bundles at create time.
+        self.bundles.pull(image, &PullOptions::default()).await?;
+        Ok(())
+    }
 
+    async fn pull_image_with(&self, image: &ImageRef, options: PullOptions) -> Result<()> {
+        self.bundles.pull(image, &options).await?;
         Ok(())
     }
 
-    async fn top(&self, id: &ContainerId) -> Result<Vec<ProcessInfo>> {
-        // Would read from /proc filesystem for container PID namespace
-        Ok(Vec::new())
+    async fn push_image(&self, image: &ImageRef, options: PushOptions) -> Result<String> {
+        self.bundles.push(image, &options).await
     }
 
-    async fn pull_image(&self, _image: &crate::types::ImageRef) -> Result<()> {
-        // crun doesn't handle image pulling directly - it works with OCI bundles
-        // Image pulling would be delegated to a registry client like skopeo or
-        // our built-in ImageRegistry. For now, we return an error indicating
-        // this should be handled at a higher level.
-        Err(CoreError::Runtime(
-            "crun does not handle image pulling. Use ImageRegistry or Docker/Podman to pull images.".to_string()
-        ))
+    async fn image_exists(&self, image: &str) -> Result<bool> {
+        self.bundles.initialize().await?;
+        Ok(self.bundles.images().has(&ImageRef::parse(image)))
     }
 
-    async fn image_exists(&self, _image: &str) -> Result<bool> {
-        // crun works with OCI bundles, not images directly
-        // Image management should be done at a higher level
-        Err(CoreError::Runtime(
-            "crun does not manage images. Use ImageRegistry for image operations.".to_string(),
-        ))
+    async fn list_images(&self) -> Result<Vec<ImageInfo>> {
+        self.bundles.initialize().await?;
+        Ok(self
+            .bundles
+            .images()
+            .list()
+            .into_iter()
+            .map(|image| ImageInfo {
+                created: image.created(),
+                id: image.id,
+                tags: image.references,
+                size: image.size,
+            })
+            .collect())
     }
 
-    async fn list_images(&self) -> Result<Vec<super::traits::ImageInfo>> {
-        // crun works with OCI bundles, not images directly
-        Err(CoreError::Runtime(
-            "crun does not manage images. Use ImageRegistry for image operations.".to_string(),
-        ))
+    async fn import_image(
+        &self,
+        archive: Box<dyn AsyncRead + Send + Unpin>,
+        options: ImportOptions,
+    ) -> Result<String> {
+        Ok(self.bundles.import(archive, &options).await?.id)
     }
 }
 
@@ -19,24 +19,35 @@ use std::path::Path;
 use std::time::Duration;
 
 use async_trait::async_trait;
+use bollard::auth::DockerCredentials;
 use bollard::container::{
-    Config, CreateContainerOptions, ListContainersOptions, LogsOptions, RemoveContainerOptions,
-    StartContainerOptions, StatsOptions, StopContainerOptions, WaitContainerOptions,
+    Config, CreateContainerOptions, DownloadFromContainerOptions, ListContainersOptions,
+    LogsOptions, RemoveContainerOptions, StartContainerOptions, StatsOptions, StopContainerOptions,
+    UpdateContainerOptions, UploadToContainerOptions, WaitContainerOptions,
 };
 use bollard::exec::{CreateExecOptions, StartExecResults};
-use bollard::image::{CreateImageOptions, ListImagesOptions};
+use bollard::image::{
+    CommitContainerOptions, CreateImageOptions, ListImagesOptions, PushImageOptions,
+};
+use bollard::service::{ContainerSummary, CreateImageInfo};
+use bollard::system::EventsOptions;
 use bollard::Docker;
+use futures::stream::BoxStream;
 use futures::StreamExt;
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
 use tracing::{debug, error, info, instrument, warn};
 
 use crate::error::{CoreError, Result};
+use crate::storage::{CredentialStore, Credentials};
 use crate::types::{
-    BlockIoStats, CheckpointId, ContainerId, ContainerSpec, ContainerState, ContainerStats,
-    CpuStats, ExecResult, ExecSpec, ImageRef, LogOptions, MemoryStats, NetworkStats,
-    ResourceLimits,
+    BlockIoStats, ChangeKind, CheckpointId, CommitOptions, ContainerId, ContainerSpec,
+    ContainerState, ContainerStats, CpuStats, ExecResult, ExecSpec, FilesystemChange, ImageRef,
+    ImportOptions, LogOptions, MemoryStats, NetworkStats, PullOptions, PullProgress, PullStatus,
+    PushOptions, ResourceEvents, ResourceLimits, ResourceUpdateReport,
 };
 
+use super::filter::{ContainerFilter, FilterTarget, PROJECT_LABEL};
+use super::spec::{blkio_weight, cpu_quota, cpu_shares, CPU_PERIOD_US};
 use super::traits::{ContainerRuntime, ProcessInfo};
 
 /// Docker-based container runtime using Bollard.
@@ -109,6 +120,189 @@ impl DockerRuntime {
         format!("{}{}", self.name_prefix, id.short())
     }
 
+    /// Map an error from the archive endpoints.
+    fn archive_error(id: &ContainerId, path: &Path, e: &bollard::errors::Error) -> CoreError {
+        match e {
+            bollard::errors::Error::DockerResponseServerError {
+                status_code,
+                message,
+            } if *status_code == 404 && message.contains("No such container") => {
+                CoreError::ContainerNotFound(id.to_string())
+            }
+            bollard::errors::Error::DockerResponseServerError {
+                status_code: 400 | 404,
+                message,
+            } => CoreError::InvalidSpec {
+                field: "path".to_string(),
+                reason: format!("{}: {message}", path.display()),
+            },
+            _ => CoreError::Runtime(format!("Failed to copy files: {e}")),
+        }
+    }
+
+    /// Pull an image, for `options.platform` if given and the daemon's
+    /// platform otherwise, forwarding Docker's layer progress.
+    async fn pull(&self, image: &ImageRef, options: &PullOptions) -> Result<()> {
+        let platform = options.platform.as_ref().map(ToString::to_string);
+        let create = CreateImageOptions {
+            from_image: format!("{}/{}", image.registry, image.repository),
+            tag: image.tag.clone(),
+            platform: platform.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+
+        info!(image = %image, ?platform, "Pulling image");
+
+        let mut stream = self.client.create_image(Some(create), None, None);
+
+        while let Some(result) = stream.next().await {
+            match result {
+                Ok(info) => {
+                    if let Some(status) = &info.status {
+                        debug!(status = %status, "Image pull progress");
+                    }
+                    if let Some(progress) = Self::pull_progress(&info) {
+                        options.report(progress);
+                    }
+                    if let Some(err) = info.error {
+                        error!(error = %err, "Image pull error");
+                        return Err(CoreError::Runtime(format!("Pull failed: {}", err)));
+                    }
+                }
+                Err(e) => {
+                    return Err(CoreError::Runtime(format!("Pull failed: {}", e)));
+                }
+            }
+        }
+
+        info!(image = %image, "Image pulled successfully");
+        Ok(())
+    }
+
+    /// Push an image with the credentials Docker's own config holds for
+    /// its registry, returning the manifest digest Docker reports.
+    ///
+    /// Bollard drops the layer IDs from push status messages, so no layer
+    /// progress is reported.
+    async fn push(&self, image: &ImageRef) -> Result<String> {
+        let credentials = match CredentialStore::from_env().get(&image.registry).await? {
+            Some(Credentials::Basic { username, password }) => Some(DockerCredentials {
+                username: Some(username),
+                password: Some(password),
+                ..Default::default()
+            }),
+            Some(Credentials::IdentityToken(token)) => Some(DockerCredentials {
+                identitytoken: Some(token),
+                ..Default::default()
+            }),
+            None => None,
+        };
+
+        info!(image = %image, "Pushing image");
+
+        let name = format!("{}/{}", image.registry, image.repository);
+        let push = PushImageOptions {
+            tag: image.tag.clone(),
+        };
+        let mut stream = self.client.push_image(&name, Some(push), credentials);
+
+        let mut digest = None;
+        while let Some(result) = stream.next().await {
+            let info = result.map_err(|e| CoreError::Runtime(format!("Push failed: {e}")))?;
+            if let Some(err) = info.error {
+                error!(error = %err, "Image push error");
+                return Err(CoreError::Runtime(format!("Push failed: {err}")));
+            }
+            if let Some(status) = &info.status {
+                debug!(status = %status, "Image push progress");
+                // The last message reads "<tag>: digest: sha256:... size: N".
+                if let Some((_, rest)) = status.split_once("digest: ") {
+                    digest = rest.split_whitespace().next().map(str::to_string);
+                }
+            }
+        }
+
+        info!(image = %image, "Image pushed successfully");
+        digest.ok_or_else(|| CoreError::Runtime("Push failed: no digest reported".to_string()))
+    }
+
+    /// Layer progress from a Docker pull status message.
+    fn pull_progress(info: &CreateImageInfo) -> Option<PullProgress> {
+        let status = match info.status.as_deref()? {
+            "Pulling fs layer" | "Waiting" => PullStatus::Waiting,
+            "Already exists" => PullStatus::Cached,
+            "Downloading" => PullStatus::Downloading,
+            "Verifying Checksum" => PullStatus::Verifying,
+            "Download complete" => PullStatus::Complete,
+            _ => return None,
+        };
+        let detail = info.progress_detail.as_ref();
+        let bytes = |n: Option<i64>| n.and_then(|n| u64::try_from(n).ok()).unwrap_or(0);
+        Some(PullProgress {
+            layer: info.id.clone()?,
+            status,
+            current: bytes(detail.and_then(|d| d.current)),
+            total: bytes(detail.and_then(|d| d.total)),
+        })
+    }
+
+    /// List HyperBox containers, passing the parts of `filter` that Docker
+    /// understands as list filters.
+    ///
+    /// Docker only sees its own container names (`hb-<id>`) and has no
+    /// creation-time filters, so names and times are matched by the caller.
+    async fn list_summaries(&self, filter: &ContainerFilter) -> Result<Vec<ContainerSummary>> {
+        let mut filters =
+            HashMap::from([("name".to_string(), vec![format!("{}*", self.name_prefix)])]);
+
+        let mut labels: Vec<String> = filter.labels.iter().map(ToString::to_string).collect();
+        // Docker requires every label filter to match, so several
+        // alternative projects cannot be pushed down.
+        if let [project] = filter.projects.as_slice() {
+            labels.push(format!("{PROJECT_LABEL}={project}"));
+        }
+        if !labels.is_empty() {
+            filters.insert("label".to_string(), labels);
+        }
+        if !filter.ancestors.is_empty() {
+            filters.insert("ancestor".to_string(), filter.ancestors.clone());
+        }
+        let statuses = filter
+            .statuses
+            .iter()
+            .map(|state| Self::hyperbox_state_to_docker(*state))
+            .collect::<Option<Vec<_>>>()
+            .map(|states| states.concat());
+        if let Some(statuses) = statuses.filter(|statuses| !statuses.is_empty()) {
+            filters.insert("status".to_string(), statuses.into_iter().map(String::from).collect());
+        }
+
+        let options = ListContainersOptions::<String> {
+            all: true,
+            filters,
+            ..Default::default()
+        };
+        self.client
+            .list_containers(Some(options))
+            .await
+            .map_err(|e| CoreError::Runtime(format!("Failed to list containers: {}", e)))
+    }
+
+    /// The HyperBox id and state of a listed Docker container.
+    fn listed(&self, container: &ContainerSummary) -> Option<(ContainerId, ContainerState)> {
+        let id = container
+            .names
+            .iter()
+            .flatten()
+            .find_map(|name| self.parse_container_id(name))?;
+        let state = Self::docker_state_to_hyperbox(
+            container.state.as_deref().unwrap_or_default(),
+            container.state.as_deref() == Some("running"),
+            false,
+        );
+        Some((id, state))
+    }
+
     /// Parse HyperBox container ID from Docker container name.
     fn parse_container_id(&self, name: &str) -> Option<ContainerId> {
         let clean = name.trim_start_matches('/');
@@ -121,7 +315,7 @@ impl DockerRuntime {
     }
 
     /// Convert HyperBox container spec to Docker config.
-    fn spec_to_docker_config(&self, spec: &ContainerSpec) -> Config<String> {
+    fn spec_to_docker_config(&self, spec: &ContainerSpec) -> Result<Config<String>> {
         let mut env = Vec::new();
         for (key, value) in &spec.env {
             env.push(format!("{}={}", key, value));
@@ -142,12 +336,18 @@ impl DockerRuntime {
             Some(ports)
         };
 
+        let to_i64 = |value: u64| i64::try_from(value).unwrap_or(i64::MAX);
         let host_config = bollard::service::HostConfig {
             memory: spec.resources.memory_bytes.map(|m| m as i64),
+            cpu_period: spec.resources.cpu_millicores.map(|_| to_i64(CPU_PERIOD_US)),
+            cpu_quota: spec.resources.cpu_millicores.map(cpu_quota).transpose()?,
             cpu_shares: spec
                 .resources
-                .cpu_millicores
-                .map(|c| (c * 1024 / 1000) as i64),
+                .cpu_weight
+                .map(cpu_shares)
+                .transpose()?
+                .map(to_i64),
+            cpuset_cpus: spec.resources.cpuset_cpus.clone(),
             pids_limit: spec.resources.pids_limit.map(|p| p as i64),
             binds: Some(
                 spec.mounts
@@ -170,10 +370,38 @@ impl DockerRuntime {
                 }
                 Some(bindings)
             },
+            cap_add: (!spec.cap_add.is_empty()).then(|| spec.cap_add.clone()),
+            cap_drop: (!spec.cap_drop.is_empty()).then(|| spec.cap_drop.clone()),
+            devices: (!spec.devices.is_empty()).then(|| {
+                spec.devices
+                    .iter()
+                    .map(|d| bollard::service::DeviceMapping {
+                        path_on_host: Some(d.host_path.display().to_string()),
+                        path_in_container: Some(d.container_path.display().to_string()),
+                        cgroup_permissions: Some(d.permissions.clone()),
+                    })
+                    .collect()
+            }),
+            ulimits: (!spec.ulimits.is_empty()).then(|| {
+                // Docker uses -1 for unlimited.
+                let limit = |value: u64| i64::try_from(value).unwrap_or(-1);
+                spec.ulimits
+                    .iter()
+                    .map(|u| bollard::service::ResourcesUlimits {
+                        name: Some(u.name.clone()),
+                        soft: Some(limit(u.soft)),
+                        hard: Some(limit(u.hard)),
+                    })
+                    .collect()
+            }),
+            sysctls: (!spec.sysctls.is_empty()).then(|| spec.sysctls.clone()),
+            shm_size: spec.shm_size.map(|s| i64::try_from(s).unwrap_or(i64::MAX)),
+            group_add: (!spec.group_add.is_empty()).then(|| spec.group_add.clone()),
+            init: spec.init.then_some(true),
             ..Default::default()
         };
 
-        Config {
+        Ok(Config {
             image: Some(spec.image.to_string()),
             cmd: if spec.command.is_empty() {
                 None
@@ -183,6 +411,7 @@ impl DockerRuntime {
             env: if env.is_empty() { None } else { Some(env) },
             working_dir: spec.working_dir.as_ref().map(|p| p.display().to_string()),
             user: spec.user.clone(),
+            stop_signal: spec.stop_signal.clone(),
             exposed_ports,
             host_config: Some(host_config),
             labels: if spec.labels.is_empty() {
@@ -191,7 +420,7 @@ impl DockerRuntime {
                 Some(spec.labels.clone())
             },
             ..Default::default()
-        }
+        })
     }
 
     /// Convert Docker container state to HyperBox state.
@@ -213,6 +442,21 @@ impl DockerRuntime {
             }
         }
     }
+
+    /// The Docker states that map to a HyperBox state, or `None` if Docker
+    /// cannot filter by it.
+    const fn hyperbox_state_to_docker(state: ContainerState) -> Option<&'static [&'static str]> {
+        match state {
+            ContainerState::Creating | ContainerState::Created => Some(&["created"]),
+            ContainerState::Running => Some(&["running", "restarting"]),
+            ContainerState::Paused => Some(&["paused"]),
+            ContainerState::Stopped | ContainerState::Exited => {
+                Some(&["exited", "dead", "removing"])
+            }
+            ContainerState::Removing => Some(&["removing"]),
+            ContainerState::Unknown => None,
+        }
+    }
 }
 
 #[async_trait]
@@ -242,7 +486,7 @@ impl ContainerRuntime for DockerRuntime {
     async fn create(&self, spec: ContainerSpec) -> Result<ContainerId> {
         let id = ContainerId::new();
         let container_name = self.container_name(&id);
-        let config = self.spec_to_docker_config(&spec);
+        let config = self.spec_to_docker_config(&spec)?;
 
         debug!(
             container_id = %id,
@@ -254,13 +498,17 @@ impl ContainerRuntime for DockerRuntime {
         let image_name = spec.image.to_string();
         if !self.image_exists(&image_name).await.unwrap_or(false) {
             info!(image = %image_name, "Pulling image");
-            self.pull_image(&spec.image).await?;
+            let options = PullOptions {
+                platform: spec.platform.clone(),
+                ..Default::default()
+            };
+            self.pull(&spec.image, &options).await?;
         }
 
         // Create container
         let options = CreateContainerOptions {
             name: container_name,
-            platform: None,
+            platform: spec.platform.as_ref().map(ToString::to_string),
         };
 
         self.client
@@ -564,6 +812,8 @@ impl ContainerRuntime for DockerRuntime {
                     write_ops: 0,
                 },
                 pids: stats.pids_stats.current.unwrap_or(0),
+                // Docker reports OOM kills as events only
+                events: ResourceEvents::default(),
             });
         }
 
@@ -625,40 +875,45 @@ impl ContainerRuntime for DockerRuntime {
     }
 
     async fn list(&self) -> Result<Vec<(ContainerId, ContainerState)>> {
-        let options = ListContainersOptions::<String> {
-            all: true,
-            filters: {
-                let mut filters = HashMap::new();
-                filters.insert("name".to_string(), vec![format!("{}*", self.name_prefix)]);
-                filters
-            },
-            ..Default::default()
-        };
-
-        let containers = self
-            .client
-            .list_containers(Some(options))
-            .await
-            .map_err(|e| CoreError::Runtime(format!("Failed to list containers: {}", e)))?;
-
-        let mut result = Vec::new();
-        for container in containers {
-            if let Some(names) = container.names {
-                for name in names {
-                    if let Some(id) = self.parse_container_id(&name) {
-                        let state = Self::docker_state_to_hyperbox(
-                            &container.state.clone().unwrap_or_default(),
-                            container.state.as_deref() == Some("running"),
-                            false,
-                        );
-                        result.push((id, state));
-                        break;
-                    }
-                }
-            }
-        }
+        let containers = self.list_summaries(&ContainerFilter::default()).await?;
+        Ok(containers
+            .iter()
+            .filter_map(|container| self.listed(container))
+            .collect())
+    }
 
-        Ok(result)
+    async fn list_filtered(
+        &self,
+        filter: &ContainerFilter,
+    ) -> Result<Vec<(ContainerId, ContainerState)>> {
+        let containers = self.list_summaries(filter).await?;
+        let no_labels = HashMap::new();
+
+        // Docker has already applied what it could; check the rest here.
+        Ok(containers
+            .iter()
+            .filter_map(|container| {
+                let (id, state) = self.listed(container)?;
+                let labels = container.labels.as_ref().unwrap_or(&no_labels);
+                let target = FilterTarget {
+                    name: container
+                        .names
+                        .iter()
+                        .flatten()
+                        .next()
+                        .map(|name| name.trim_start_matches('/')),
+                    image: ImageRef::parse(container.image.as_deref().unwrap_or_default()),
+                    labels,
+                    project: labels.get(PROJECT_LABEL).map(String::as_str),
+                    state,
+                    created_at: container
+                        .created
+                        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+                        .unwrap_or_default(),
+                };
+                filter.matches(&target).then_some((id, state))
+            })
+            .collect())
     }
 
     async fn wait(&self, id: &ContainerId) -> Result<i32> {
@@ -678,6 +933,36 @@ impl ContainerRuntime for DockerRuntime {
         Err(CoreError::Runtime("Wait stream ended unexpectedly".to_string()))
     }
 
+    async fn resource_events(
+        &self,
+        id: &ContainerId,
+    ) -> Result<BoxStream<'static, ResourceEvents>> {
+        // Docker does not expose the cgroup counters; count its `oom` events
+        // instead, each of which is an OOM kill.
+        let options = EventsOptions::<String> {
+            filters: HashMap::from([
+                ("container".to_string(), vec![self.container_name(id)]),
+                ("event".to_string(), vec!["oom".to_string()]),
+            ]),
+            ..Default::default()
+        };
+
+        let mut counters = ResourceEvents::default();
+        let ooms = self
+            .client
+            .events(Some(options))
+            .take_while(|event| futures::future::ready(event.is_ok()))
+            .map(move |_| {
+                counters.oom += 1;
+                counters.oom_kill += 1;
+                counters
+            });
+
+        Ok(futures::stream::once(futures::future::ready(counters))
+            .chain(ooms)
+            .boxed())
+    }
+
     async fn checkpoint(&self, _id: &ContainerId, _checkpoint_path: &Path) -> Result<CheckpointId> {
         // Docker checkpoint requires experimental mode
         Err(CoreError::Internal("Checkpoint requires Docker experimental mode".to_string()))
@@ -687,13 +972,27 @@ impl ContainerRuntime for DockerRuntime {
         Err(CoreError::Internal("Restore requires Docker experimental mode".to_string()))
     }
 
-    async fn update(&self, id: &ContainerId, resources: ResourceLimits) -> Result<()> {
+    async fn update(
+        &self,
+        id: &ContainerId,
+        resources: ResourceLimits,
+    ) -> Result<ResourceUpdateReport> {
         let container_name = self.container_name(id);
-
-        let update_options = bollard::container::UpdateContainerOptions::<String> {
-            memory: resources.memory_bytes.map(|m| m as i64),
-            cpu_shares: resources.cpu_millicores.map(|c| (c * 1024 / 1000) as isize),
-            pids_limit: resources.pids_limit.map(|p| p as i64),
+        let to_i64 = |value: u64| i64::try_from(value).unwrap_or(i64::MAX);
+
+        let update_options = UpdateContainerOptions::<String> {
+            memory: resources.memory_bytes.map(to_i64),
+            memory_swap: resources.memory_swap_bytes.map(to_i64),
+            cpu_shares: resources
+                .cpu_weight
+                .map(cpu_shares)
+                .transpose()?
+                .map(|s| isize::try_from(s).unwrap_or(isize::MAX)),
+            cpu_period: resources.cpu_millicores.map(|_| to_i64(CPU_PERIOD_US)),
+            cpu_quota: resources.cpu_millicores.map(cpu_quota).transpose()?,
+            cpuset_cpus: resources.cpuset_cpus.clone(),
+            pids_limit: resources.pids_limit.map(to_i64),
+            blkio_weight: resources.io_weight.map(blkio_weight).transpose()?,
             ..Default::default()
         };
 
@@ -702,7 +1001,9 @@ impl ContainerRuntime for DockerRuntime {
             .await
             .map_err(|e| CoreError::Runtime(format!("Failed to update container: {}", e)))?;
 
-        Ok(())
+        // Docker only changes the blkio weight of a running container; its
+        // device throttles are fixed at creation.
+        Ok(ResourceUpdateReport::new(&resources, ResourceLimits::IO_THROTTLE_FIELDS))
     }
 
     async fn top(&self, id: &ContainerId) -> Result<Vec<ProcessInfo>> {
@@ -717,27 +1018,31 @@ impl ContainerRuntime for DockerRuntime {
         let mut processes = Vec::new();
 
         if let (Some(titles), Some(procs)) = (top.titles, top.processes) {
-            let pid_idx = titles.iter().position(|t| t.to_uppercase() == "PID");
-            let cmd_idx = titles
-                .iter()
-                .position(|t| t.to_uppercase() == "CMD" || t.to_uppercase() == "COMMAND");
+            fn number<T: std::str::FromStr>(proc: &[String], idx: Option<usize>) -> Option<T> {
+                idx.and_then(|i| proc.get(i)).and_then(|p| p.parse().ok())
+            }
 
-            for proc in procs {
-                let pid = pid_idx
-                    .and_then(|i| proc.get(i))
-                    .and_then(|p| p.parse().ok())
-                    .unwrap_or(0);
-                let cmd = cmd_idx
-                    .and_then(|i| proc.get(i))
-                    .cloned()
-                    .unwrap_or_default();
+            let column = |names: &[&str]| {
+                titles
+                    .iter()
+                    .position(|t| names.iter().any(|n| t.eq_ignore_ascii_case(n)))
+            };
+            let pid_at = column(&["PID"]);
+            let parent_at = column(&["PPID"]);
+            let user_at = column(&["UID", "USER"]);
+            let cpu_at = column(&["%CPU", "C"]);
+            let rss_at = column(&["RSS"]);
+            let cmd_at = column(&["CMD", "COMMAND"]);
 
+            for proc in procs {
                 processes.push(ProcessInfo {
-                    pid,
-                    ppid: 0,
-                    cpu_percent: 0.0,
-                    memory_bytes: 0,
-                    command: cmd,
+                    pid: number(&proc, pid_at).unwrap_or(0),
+                    ppid: number(&proc, parent_at).unwrap_or(0),
+                    uid: number(&proc, user_at).unwrap_or(0),
+                    cpu_percent: number(&proc, cpu_at).unwrap_or(0.0),
+                    memory_bytes: number(&proc, rss_at).map_or(0, |kib: u64| kib * 1024),
+                    started_at: None,
+                    command: cmd_at.and_then(|i| proc.get(i)).cloned().unwrap_or_default(),
                 });
             }
         }
@@ -745,36 +1050,182 @@ impl ContainerRuntime for DockerRuntime {
         Ok(processes)
     }
 
-    async fn pull_image(&self, image: &ImageRef) -> Result<()> {
-        let options = CreateImageOptions {
-            from_image: format!("{}/{}", image.registry, image.repository),
-            tag: image.tag.clone(),
-            ..Default::default()
+    async fn archive_path(
+        &self,
+        id: &ContainerId,
+        path: &Path,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let container_name = self.container_name(id);
+        let options = DownloadFromContainerOptions {
+            path: path.to_string_lossy().into_owned(),
         };
+        let mut stream = self
+            .client
+            .download_from_container(&container_name, Some(options))
+            .boxed();
+
+        // Errors only show up on the first poll; surface them here rather
+        // than as a failed read.
+        let first = match stream.next().await {
+            Some(Ok(bytes)) => Some(Ok(bytes)),
+            Some(Err(e)) => return Err(Self::archive_error(id, path, &e)),
+            None => None,
+        };
+        let stream = futures::stream::iter(first)
+            .chain(stream)
+            .map(|result| result.map_err(std::io::Error::other));
 
-        info!(image = %image, "Pulling image");
+        Ok(Box::new(tokio_util::io::StreamReader::new(stream)))
+    }
 
-        let mut stream = self.client.create_image(Some(options), None, None);
+    async fn extract_archive(
+        &self,
+        id: &ContainerId,
+        path: &Path,
+        mut archive: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> Result<()> {
+        let container_name = self.container_name(id);
+        let mut tar = Vec::new();
+        archive.read_to_end(&mut tar).await?;
 
-        while let Some(result) = stream.next().await {
-            match result {
-                Ok(info) => {
-                    if let Some(status) = info.status {
-                        debug!(status = %status, "Image pull progress");
-                    }
-                    if let Some(err) = info.error {
-                        error!(error = %err, "Image pull error");
-                        return Err(CoreError::Runtime(format!("Pull failed: {}", err)));
-                    }
+        let options = UploadToContainerOptions {
+            path: path.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        self.client
+            .upload_to_container(&container_name, Some(options), tar.into())
+            .await
+            .map_err(|e| Self::archive_error(id, path, &e))
+    }
+
+    async fn diff(&self, id: &ContainerId) -> Result<Vec<FilesystemChange>> {
+        let container_name = self.container_name(id);
+
+        let changes = self
+            .client
+            .container_changes(&container_name)
+            .await
+            .map_err(|e| {
+                if e.to_string().contains("No such container") {
+                    CoreError::ContainerNotFound(id.to_string())
+                } else {
+                    CoreError::Runtime(format!("Failed to get changes: {e}"))
                 }
-                Err(e) => {
-                    return Err(CoreError::Runtime(format!("Pull failed: {}", e)));
+            })?;
+
+        Ok(changes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|change| FilesystemChange {
+                path: change.path,
+                kind: match change.kind {
+                    bollard::service::ChangeType::_0 => ChangeKind::Modified,
+                    bollard::service::ChangeType::_1 => ChangeKind::Added,
+                    bollard::service::ChangeType::_2 => ChangeKind::Deleted,
+                },
+            })
+            .collect())
+    }
+
+    async fn commit(&self, id: &ContainerId, options: CommitOptions) -> Result<String> {
+        let container_name = self.container_name(id);
+        let (repo, tag) = options
+            .reference
+            .as_ref()
+            .map_or_else(Default::default, |r| {
+                (format!("{}/{}", r.registry, r.repository), r.tag.clone())
+            });
+        let keys = |map: Option<HashMap<String, serde_json::Value>>| {
+            map.map(|m| m.into_keys().map(|k| (k, HashMap::new())).collect())
+        };
+
+        let changes = options.config;
+        let config = Config {
+            user: changes.user,
+            exposed_ports: keys(changes.exposed_ports),
+            env: changes.env,
+            entrypoint: changes.entrypoint,
+            cmd: changes.cmd,
+            volumes: keys(changes.volumes),
+            working_dir: changes.working_dir,
+            labels: changes.labels,
+            stop_signal: changes.stop_signal,
+            ..Default::default()
+        };
+        let commit_options = CommitContainerOptions {
+            container: container_name,
+            repo,
+            tag,
+            comment: options.comment.unwrap_or_default(),
+            author: options.author.unwrap_or_default(),
+            pause: options.pause,
+            changes: None,
+        };
+
+        let commit = self
+            .client
+            .commit_container(commit_options, config)
+            .await
+            .map_err(|e| {
+                if e.to_string().contains("No such container") {
+                    CoreError::ContainerNotFound(id.to_string())
+                } else {
+                    CoreError::Runtime(format!("Failed to commit container: {e}"))
                 }
-            }
+            })?;
+        if let Some(image_id) = commit.id {
+            return Ok(image_id);
         }
 
-        info!(image = %image, "Image pulled successfully");
-        Ok(())
+        // Docker answers with `Id`, which the response model does not
+        // capture; look the image up by its reference instead.
+        let reference = options.reference.ok_or_else(|| {
+            CoreError::Runtime("Docker did not report the committed image ID".to_string())
+        })?;
+        let image = self
+            .client
+            .inspect_image(&reference.full_name())
+            .await
+            .map_err(|e| CoreError::Runtime(format!("Failed to inspect committed image: {e}")))?;
+        image.id.ok_or_else(|| {
+            CoreError::Runtime("Docker did not report the committed image ID".to_string())
+        })
+    }
+
+    async fn export(&self, id: &ContainerId) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let container_name = self.container_name(id);
+        let mut stream = self.client.export_container(&container_name).boxed();
+
+        // As for archives, errors only show up on the first poll.
+        let first = match stream.next().await {
+            Some(Ok(bytes)) => Some(Ok(bytes)),
+            Some(Err(e)) if e.to_string().contains("No such container") => {
+                return Err(CoreError::ContainerNotFound(id.to_string()));
+            }
+            Some(Err(e)) => {
+                return Err(CoreError::Runtime(format!("Failed to export container: {e}")));
+            }
+            None => None,
+        };
+        let stream = futures::stream::iter(first)
+            .chain(stream)
+            .map(|result| result.map_err(std::io::Error::other));
+
+        Ok(Box::new(tokio_util::io::StreamReader::new(stream)))
+    }
+
+    async fn pull_image(&self, image: &ImageRef) -> Result<()> {
+        self.pull(image, &PullOptions::default()).await
+    }
+
+    async fn pull_image_with(&self, image: &ImageRef, options: PullOptions) -> Result<()> {
+        self.pull(image, &options).await
+    }
+
+    async fn push_image(&self, image: &ImageRef, options: PushOptions) -> Result<String> {
+        // Docker's push messages carry no layer IDs, see `push`.
+        let _ = options;
+        self.push(image).await
     }
 
     async fn image_exists(&self, image: &str) -> Result<bool> {
@@ -817,6 +1268,52 @@ impl ContainerRuntime for DockerRuntime {
             })
             .collect())
     }
+
+    async fn import_image(
+        &self,
+        mut archive: Box<dyn AsyncRead + Send + Unpin>,
+        options: ImportOptions,
+    ) -> Result<String> {
+        let mut tar = Vec::new();
+        archive.read_to_end(&mut tar).await?;
+
+        let (repo, tag) = options
+            .reference
+            .as_ref()
+            .map_or_else(Default::default, |r| {
+                (format!("{}/{}", r.registry, r.repository), r.tag.clone())
+            });
+        // The import message is not exposed by the API client; only the
+        // configuration is applied.
+        let changes = options.config.to_changes();
+        let create_options = CreateImageOptions {
+            from_src: "-".to_string(),
+            repo,
+            tag,
+            changes: changes.iter().map(String::as_str).collect(),
+            ..Default::default()
+        };
+
+        // Docker reports the new image ID as the final status.
+        let mut image_id = None;
+        let mut stream = self
+            .client
+            .create_image(Some(create_options), Some(tar.into()), None);
+        while let Some(result) = stream.next().await {
+            let info =
+                result.map_err(|e| CoreError::Runtime(format!("Failed to import image: {e}")))?;
+            if let Some(err) = info.error {
+                return Err(CoreError::Runtime(format!("Failed to import image: {err}")));
+            }
+            if let Some(status) = info.status.filter(|s| s.starts_with("sha256:")) {
+                image_id = Some(status);
+            }
+        }
+
+        image_id.ok_or_else(|| {
+            CoreError::Runtime("Docker did not report the imported image ID".to_string())
+        })
+    }
 }
 
 #[cfg(test)]
@@ -838,4 +1335,37 @@ mod tests {
             ContainerState::Exited
         ));
     }
+
+    #[test]
+    fn test_extended_spec_to_docker_config() {
+        let runtime = DockerRuntime::new().unwrap();
+        let spec = ContainerSpec::builder()
+            .image("chromedp/headless-shell:latest")
+            .cap_add("SYS_ADMIN")
+            .cap_drop("NET_RAW")
+            .device("/dev/dri/renderD128".parse().unwrap())
+            .ulimit("nofile=1024:-1".parse().unwrap())
+            .sysctl("net.ipv4.ip_unprivileged_port_start", "0")
+            .shm_size(2 * 1024 * 1024 * 1024)
+            .group_add("video")
+            .stop_signal("SIGINT")
+            .init(true)
+            .build();
+
+        let config = runtime.spec_to_docker_config(&spec).unwrap();
+        assert_eq!(config.stop_signal.as_deref(), Some("SIGINT"));
+
+        let host = config.host_config.unwrap();
+        assert_eq!(host.cap_add, Some(vec!["SYS_ADMIN".to_string()]));
+        assert_eq!(host.cap_drop, Some(vec!["NET_RAW".to_string()]));
+        let device = &host.devices.unwrap()[0];
+        assert_eq!(device.path_in_container.as_deref(), Some("/dev/dri/renderD128"));
+        assert_eq!(device.cgroup_permissions.as_deref(), Some("rwm"));
+        let ulimit = &host.ulimits.unwrap()[0];
+        assert_eq!((ulimit.soft, ulimit.hard), (Some(1024), Some(-1)));
+        assert_eq!(host.sysctls.unwrap()["net.ipv4.ip_unprivileged_port_start"], "0");
+        assert_eq!(host.shm_size, Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(host.group_add, Some(vec!["video".to_string()]));
+        assert_eq!(host.init, Some(true));
+    }
 }
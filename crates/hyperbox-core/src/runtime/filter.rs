@@ -0,0 +1,372 @@
+//! Container list filters.
+//!
+//! A [`ContainerFilter`] is built from Docker-style `key=value` filters
+//! (`label=env=prod`, `name=web-*`, `status=running`, `ancestor=nginx`,
+//! `project=shop`, `before=2024-01-15T00:00:00Z`, `since=...`). Values of the
+//! same key are alternatives, except labels, which must all match; different
+//! keys must all match.
+
+use crate::error::{CoreError, Result};
+use crate::types::{ContainerMetadata, ContainerState, ImageRef};
+use chrono::{DateTime, TimeZone, Utc};
+use std::collections::HashMap;
+
+/// Label naming the project a container belongs to.
+pub const PROJECT_LABEL: &str = "hyperbox.project";
+
+/// A label filter: the label must exist and, if a value is given, equal it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelFilter {
+    /// Label key
+    pub key: String,
+    /// Required value, or `None` to only require the key
+    pub value: Option<String>,
+}
+
+impl LabelFilter {
+    fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        labels
+            .get(&self.key)
+            .is_some_and(|value| self.value.as_ref().map_or(true, |want| want == value))
+    }
+}
+
+impl std::fmt::Display for LabelFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={}", self.key, value),
+            None => write!(f, "{}", self.key),
+        }
+    }
+}
+
+/// The fields of a container that filters look at.
+#[derive(Debug, Clone)]
+pub struct FilterTarget<'a> {
+    /// Container name
+    pub name: Option<&'a str>,
+    /// Image the container was created from
+    pub image: ImageRef,
+    /// Container labels
+    pub labels: &'a HashMap<String, String>,
+    /// Project the container belongs to
+    pub project: Option<&'a str>,
+    /// Current state
+    pub state: ContainerState,
+    /// When the container was created
+    pub created_at: DateTime<Utc>,
+}
+
+impl<'a> FilterTarget<'a> {
+    /// The filter view of a container's recorded metadata.
+    #[must_use]
+    pub fn from_metadata(meta: &'a ContainerMetadata, state: ContainerState) -> Self {
+        Self {
+            name: meta.name(),
+            image: meta.spec.image.clone(),
+            labels: meta.labels(),
+            project: meta.labels().get(PROJECT_LABEL).map(String::as_str),
+            state,
+            created_at: meta.created_at,
+        }
+    }
+}
+
+/// Filter for container listings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContainerFilter {
+    /// Labels that must all match
+    pub labels: Vec<LabelFilter>,
+    /// Name globs (`*` and `?`)
+    pub names: Vec<String>,
+    /// Accepted states
+    pub statuses: Vec<ContainerState>,
+    /// Accepted images, with or without a tag
+    pub ancestors: Vec<String>,
+    /// Accepted projects
+    pub projects: Vec<String>,
+    /// Only containers created before this time
+    pub created_before: Option<DateTime<Utc>>,
+    /// Only containers created after this time
+    pub created_after: Option<DateTime<Utc>>,
+}
+
+impl ContainerFilter {
+    /// Parse `key=value` filters, e.g. the values of `--filter` flags.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::InvalidSpec`] for an unknown key or a bad value.
+    pub fn parse<S: AsRef<str>>(filters: &[S]) -> Result<Self> {
+        let mut filter = Self::default();
+        for raw in filters {
+            let raw = raw.as_ref();
+            let (key, value) = raw
+                .split_once('=')
+                .ok_or_else(|| invalid(raw, "expected key=value"))?;
+            filter.add(key, value)?;
+        }
+        Ok(filter)
+    }
+
+    /// Build a filter from the Docker API form, a map from key to values.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::InvalidSpec`] for an unknown key or a bad value.
+    pub fn from_map(filters: &HashMap<String, Vec<String>>) -> Result<Self> {
+        let mut filter = Self::default();
+        for (key, values) in filters {
+            for value in values {
+                filter.add(key, value)?;
+            }
+        }
+        Ok(filter)
+    }
+
+    /// The Docker API form of this filter, a map from key to values.
+    #[must_use]
+    pub fn to_map(&self) -> HashMap<String, Vec<String>> {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        let mut insert = |key: &str, values: Vec<String>| {
+            if !values.is_empty() {
+                map.insert(key.to_string(), values);
+            }
+        };
+        insert("label", self.labels.iter().map(ToString::to_string).collect());
+        insert("name", self.names.clone());
+        insert("status", self.statuses.iter().map(ToString::to_string).collect());
+        insert("ancestor", self.ancestors.clone());
+        insert("project", self.projects.clone());
+        insert(
+            "before",
+            self.created_before
+                .iter()
+                .map(DateTime::to_rfc3339)
+                .collect(),
+        );
+        insert(
+            "since",
+            self.created_after
+                .iter()
+                .map(DateTime::to_rfc3339)
+                .collect(),
+        );
+        map
+    }
+
+    /// Add one filter.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::InvalidSpec`] for an unknown key or a bad value.
+    pub fn add(&mut self, key: &str, value: &str) -> Result<()> {
+        let raw = format!("{key}={value}");
+        if value.is_empty() {
+            return Err(invalid(&raw, "missing value"));
+        }
+        match key {
+            "label" => {
+                let (key, value) = match value.split_once('=') {
+                    Some((key, value)) => (key, Some(value.to_string())),
+                    None => (value, None),
+                };
+                self.labels.push(LabelFilter {
+                    key: key.to_string(),
+                    value,
+                });
+            }
+            "name" => self.names.push(value.to_string()),
+            "status" => self.statuses.push(value.parse()?),
+            "ancestor" => self.ancestors.push(value.to_string()),
+            "project" => self.projects.push(value.to_string()),
+            "before" => self.created_before = Some(parse_time(&raw, value)?),
+            "since" | "after" => self.created_after = Some(parse_time(&raw, value)?),
+            _ => return Err(invalid(&raw, "unknown filter")),
+        }
+        Ok(())
+    }
+
+    /// Whether the filter accepts every container.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether a container passes the filter.
+    #[must_use]
+    pub fn matches(&self, target: &FilterTarget<'_>) -> bool {
+        self.labels.iter().all(|label| label.matches(target.labels))
+            && any_or_empty(&self.names, |pattern| {
+                target.name.is_some_and(|name| glob_match(pattern, name))
+            })
+            && any_or_empty(&self.statuses, |status| same_status(*status, target.state))
+            && any_or_empty(&self.ancestors, |image| is_ancestor(image, &target.image))
+            && any_or_empty(&self.projects, |project| target.project == Some(project.as_str()))
+            && self
+                .created_before
+                .map_or(true, |before| target.created_at < before)
+            && self
+                .created_after
+                .map_or(true, |after| target.created_at > after)
+    }
+}
+
+fn invalid(filter: &str, reason: &str) -> CoreError {
+    CoreError::InvalidSpec {
+        field: "filter".to_string(),
+        reason: format!("{filter}: {reason}"),
+    }
+}
+
+fn any_or_empty<T>(values: &[T], accept: impl Fn(&T) -> bool) -> bool {
+    values.is_empty() || values.iter().any(accept)
+}
+
+/// Parse an RFC 3339 time or Unix seconds.
+fn parse_time(filter: &str, value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .ok()
+        .or_else(|| Utc.timestamp_opt(value.parse().ok()?, 0).single())
+        .ok_or_else(|| invalid(filter, "expected an RFC 3339 time or Unix seconds"))
+}
+
+/// Runtimes disagree on whether a finished container is stopped or exited.
+fn same_status(want: ContainerState, state: ContainerState) -> bool {
+    use ContainerState::{Exited, Stopped};
+
+    want == state || matches!((want, state), (Exited, Stopped) | (Stopped, Exited))
+}
+
+/// Whether `image` was created from `ancestor`; without a tag, any tag of
+/// the repository matches.
+fn is_ancestor(ancestor: &str, image: &ImageRef) -> bool {
+    let want = ImageRef::parse(ancestor);
+    let tagged = ancestor
+        .rsplit('/')
+        .next()
+        .is_some_and(|last| last.contains(':'));
+    want.registry == image.registry
+        && want.repository == image.repository
+        && (!tagged || want.tag == image.tag)
+}
+
+/// Match `name` against a glob where `*` is any run of characters and `?`
+/// any single character.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it was tried at.
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, n));
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ContainerSpec;
+
+    fn metadata() -> ContainerMetadata {
+        let spec = ContainerSpec::builder()
+            .image("docker.io/library/nginx:1.25")
+            .name("web-1")
+            .label("env", "prod")
+            .label(PROJECT_LABEL, "shop")
+            .build();
+        ContainerMetadata::new(crate::types::ContainerId::new(), spec)
+    }
+
+    fn matches(filters: &[&str], meta: &ContainerMetadata, state: ContainerState) -> bool {
+        ContainerFilter::parse(filters)
+            .unwrap()
+            .matches(&FilterTarget::from_metadata(meta, state))
+    }
+
+    #[test]
+    fn test_filter_parse() {
+        let filter =
+            ContainerFilter::parse(&["label=env=prod", "label=tier", "status=running"]).unwrap();
+        assert_eq!(
+            filter.labels,
+            vec![
+                LabelFilter {
+                    key: "env".to_string(),
+                    value: Some("prod".to_string()),
+                },
+                LabelFilter {
+                    key: "tier".to_string(),
+                    value: None,
+                },
+            ]
+        );
+        assert_eq!(filter.statuses, vec![ContainerState::Running]);
+        assert_eq!(
+            ContainerFilter::from_map(&filter.to_map())
+                .unwrap()
+                .labels
+                .len(),
+            2
+        );
+
+        assert!(ContainerFilter::parse(&["label"]).is_err());
+        assert!(ContainerFilter::parse(&["color=red"]).is_err());
+        assert!(ContainerFilter::parse(&["status=sleeping"]).is_err());
+        assert!(ContainerFilter::parse(&["before=yesterday"]).is_err());
+        assert!(ContainerFilter::parse::<&str>(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let meta = metadata();
+        let running = ContainerState::Running;
+
+        assert!(matches(&[], &meta, running));
+        assert!(matches(&["label=env=prod", "label=env"], &meta, running));
+        assert!(!matches(&["label=env=prod", "label=tier"], &meta, running));
+        assert!(matches(&["name=web-*"], &meta, running));
+        assert!(matches(&["name=db", "name=web-?"], &meta, running));
+        assert!(!matches(&["name=web"], &meta, running));
+        assert!(matches(&["status=exited"], &meta, ContainerState::Stopped));
+        assert!(!matches(&["status=exited"], &meta, running));
+        assert!(matches(&["ancestor=nginx"], &meta, running));
+        assert!(matches(&["ancestor=nginx:1.25"], &meta, running));
+        assert!(!matches(&["ancestor=nginx:1.24"], &meta, running));
+        assert!(matches(&["project=shop"], &meta, running));
+        assert!(!matches(&["project=blog"], &meta, running));
+        assert!(matches(&["since=0", "before=4102444800"], &meta, running));
+        assert!(!matches(&["before=2020-01-01T00:00:00Z"], &meta, running));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*c", "abbbc"));
+        assert!(glob_match("*b*", "abc"));
+        assert!(glob_match("a?c", "abc"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(!glob_match("a*d", "abc"));
+    }
+}
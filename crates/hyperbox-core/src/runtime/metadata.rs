@@ -0,0 +1,372 @@
+//! Persistent container metadata.
+//!
+//! OCI runtimes only remember a container's bundle and status, and lose even
+//! that when their state directory (usually on tmpfs) goes away. The
+//! [`MetadataStore`] keeps what HyperBox knows about each container: the spec
+//! it was created from, its name and labels, lifecycle timestamps, exit code
+//! and bundle path. Records are written to `<dir>/<id>.json` through a
+//! temporary file and a rename, so a crash never leaves a torn record.
+
+use crate::error::{CoreError, Result};
+use crate::types::{ContainerExit, ContainerId, ContainerMetadata, ContainerState};
+use chrono::Utc;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// Container metadata persisted on disk, indexed by id and by name.
+pub struct MetadataStore {
+    dir: PathBuf,
+    records: DashMap<String, ContainerMetadata>,
+    /// Container name to id.
+    names: DashMap<String, ContainerId>,
+    /// Serialises record writes so the last change is the one on disk.
+    write_lock: Mutex<()>,
+}
+
+impl MetadataStore {
+    /// Open the store in `dir`, loading the records of earlier runs.
+    ///
+    /// Records that cannot be read are skipped with a warning.
+    #[must_use]
+    pub fn open(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        let records = DashMap::new();
+        let names = DashMap::new();
+
+        for meta in load_records(&dir) {
+            if let Some(name) = meta.name() {
+                names.insert(name.to_string(), meta.id.clone());
+            }
+            records.insert(meta.id.to_string(), meta);
+        }
+        debug!(dir = %dir.display(), containers = records.len(), "Loaded container metadata");
+
+        Self {
+            dir,
+            records,
+            names,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Record a new container and reserve its name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::ContainerAlreadyExists`] if the id is known or
+    /// the name is taken, [`CoreError::InvalidSpec`] for an invalid name, or
+    /// an error if the record cannot be written.
+    pub async fn insert(&self, meta: ContainerMetadata) -> Result<()> {
+        let id = meta.id.clone();
+        if self.records.contains_key(id.as_str()) {
+            return Err(CoreError::ContainerAlreadyExists(id.to_string()));
+        }
+        if let Some(name) = meta.name() {
+            validate_name(name)?;
+            match self.names.entry(name.to_string()) {
+                Entry::Occupied(_) => {
+                    return Err(CoreError::ContainerAlreadyExists(name.to_string()));
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(id.clone());
+                }
+            }
+        }
+
+        self.records.insert(id.to_string(), meta);
+        if let Err(e) = self.persist(&id).await {
+            self.forget(&id);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Metadata of a container.
+    #[must_use]
+    pub fn get(&self, id: &ContainerId) -> Option<ContainerMetadata> {
+        self.records.get(id.as_str()).map(|meta| meta.clone())
+    }
+
+    /// Id of the container called `name`.
+    #[must_use]
+    pub fn lookup(&self, name: &str) -> Option<ContainerId> {
+        self.names.get(name).map(|id| id.clone())
+    }
+
+    /// Metadata of every container, oldest first.
+    #[must_use]
+    pub fn list(&self) -> Vec<ContainerMetadata> {
+        let mut records: Vec<_> = self.records.iter().map(|meta| meta.clone()).collect();
+        records.sort_by_key(|meta| meta.created_at);
+        records
+    }
+
+    /// Add the recorded containers missing from a runtime's `listed` ones.
+    ///
+    /// A runtime forgets containers when its state directory is lost, e.g.
+    /// on reboot; those are reported as exited.
+    #[must_use]
+    pub fn with_recorded(
+        &self,
+        mut listed: Vec<(ContainerId, ContainerState)>,
+    ) -> Vec<(ContainerId, ContainerState)> {
+        for meta in self.list() {
+            if !listed.iter().any(|(id, _)| *id == meta.id) {
+                listed.push((meta.id, ContainerState::Exited));
+            }
+        }
+        listed
+    }
+
+    /// Record that a container started, clearing its last exit.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::ContainerNotFound`] for an unknown container, or
+    /// an error if the record cannot be written.
+    pub async fn mark_started(&self, id: &ContainerId) -> Result<()> {
+        self.update(id, |meta| {
+            meta.started_at = Some(Utc::now());
+            meta.finished_at = None;
+            meta.exit_code = None;
+            true
+        })
+        .await
+    }
+
+    /// Record how a container exited.
+    ///
+    /// Recording the same exit again does not rewrite the record.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::ContainerNotFound`] for an unknown container, or
+    /// an error if the record cannot be written.
+    pub async fn record_exit(&self, id: &ContainerId, exit: &ContainerExit) -> Result<()> {
+        self.update(id, |meta| {
+            if meta.finished_at == Some(exit.finished_at) {
+                return false;
+            }
+            meta.finished_at = Some(exit.finished_at);
+            meta.exit_code = Some(exit.exit_code);
+            true
+        })
+        .await
+    }
+
+    /// Forget a container, freeing its name, and delete its record.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the record exists but cannot be deleted.
+    pub async fn remove(&self, id: &ContainerId) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        self.forget(id);
+        match tokio::fs::remove_file(self.record_path(id.as_str())).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Apply `change` to a record and write it if `change` returns true.
+    async fn update(
+        &self,
+        id: &ContainerId,
+        change: impl FnOnce(&mut ContainerMetadata) -> bool,
+    ) -> Result<()> {
+        let changed = {
+            let mut meta = self
+                .records
+                .get_mut(id.as_str())
+                .ok_or_else(|| CoreError::ContainerNotFound(id.to_string()))?;
+            change(meta.value_mut())
+        };
+        if changed {
+            self.persist(id).await?;
+        }
+        Ok(())
+    }
+
+    /// Write the current record of `id`, if it still exists.
+    async fn persist(&self, id: &ContainerId) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let data = match self.records.get(id.as_str()) {
+            Some(meta) => serde_json::to_vec_pretty(meta.value())?,
+            None => return Ok(()),
+        };
+        write_atomic(&self.record_path(id.as_str()), &data).await
+    }
+
+    fn forget(&self, id: &ContainerId) {
+        if let Some((_, meta)) = self.records.remove(id.as_str()) {
+            if let Some(name) = meta.name() {
+                self.names.remove_if(name, |_, owner| *owner == meta.id);
+            }
+        }
+    }
+
+    fn record_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+}
+
+/// Check a container name: an alphanumeric first character followed by
+/// alphanumerics, `_`, `.` or `-`.
+fn validate_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(CoreError::InvalidSpec {
+            field: "name".to_string(),
+            reason: format!("'{name}' must match [a-zA-Z0-9][a-zA-Z0-9_.-]*"),
+        })
+    }
+}
+
+/// Read the records in `dir`, skipping unreadable ones and removing
+/// temporary files left by interrupted writes.
+fn load_records(dir: &Path) -> Vec<ContainerMetadata> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            warn!(dir = %dir.display(), "Cannot read container metadata: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut records = Vec::new();
+    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => {}
+            Some("tmp") => {
+                let _ = std::fs::remove_file(&path);
+                continue;
+            }
+            _ => continue,
+        }
+        let meta = std::fs::read(&path)
+            .map_err(CoreError::from)
+            .and_then(|data| Ok(serde_json::from_slice::<ContainerMetadata>(&data)?));
+        match meta {
+            Ok(meta) => records.push(meta),
+            Err(e) => warn!(path = %path.display(), "Skipping container metadata: {}", e),
+        }
+    }
+    records
+}
+
+/// Replace `path` with `data` so readers see either the old or the new file.
+async fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let mut file = tokio::fs::File::create(&tmp).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    drop(file);
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ContainerSpec;
+    use tempfile::TempDir;
+
+    fn named(name: &str) -> ContainerMetadata {
+        let spec = ContainerSpec {
+            name: Some(name.to_string()),
+            ..ContainerSpec::default()
+        };
+        ContainerMetadata::new(ContainerId::new(), spec)
+    }
+
+    #[tokio::test]
+    async fn test_names_are_unique() {
+        let dir = TempDir::new().unwrap();
+        let store = MetadataStore::open(dir.path());
+
+        let web = named("web");
+        store.insert(web.clone()).await.unwrap();
+        assert_eq!(store.lookup("web"), Some(web.id.clone()));
+        assert!(matches!(
+            store.insert(named("web")).await,
+            Err(CoreError::ContainerAlreadyExists(_))
+        ));
+        assert!(matches!(store.insert(named("-web")).await, Err(CoreError::InvalidSpec { .. })));
+
+        store.remove(&web.id).await.unwrap();
+        assert_eq!(store.lookup("web"), None);
+        assert!(store.get(&web.id).is_none());
+        store.insert(named("web")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_records_survive_reopen() {
+        let dir = TempDir::new().unwrap();
+        let meta = named("db").with_bundle("/var/lib/hyperbox/bundles/db");
+        let id = meta.id.clone();
+        let exit = ContainerExit {
+            exit_code: 3,
+            signal: None,
+            oom_killed: false,
+            finished_at: Utc::now(),
+        };
+        {
+            let store = MetadataStore::open(dir.path());
+            store.insert(meta).await.unwrap();
+            store
+                .insert(ContainerMetadata::new(ContainerId::new(), ContainerSpec::default()))
+                .await
+                .unwrap();
+            store.mark_started(&id).await.unwrap();
+            store.record_exit(&id, &exit).await.unwrap();
+        }
+        // A write interrupted before its rename
+        std::fs::write(dir.path().join("partial.json.tmp"), "{").unwrap();
+
+        let store = MetadataStore::open(dir.path());
+        assert_eq!(store.list().len(), 2);
+        assert_eq!(store.lookup("db"), Some(id.clone()));
+        let meta = store.get(&id).unwrap();
+        assert!(meta.started_at.is_some());
+        assert_eq!(meta.finished_at, Some(exit.finished_at));
+        assert_eq!(meta.exit_code, Some(3));
+        assert_eq!(meta.bundle, Some(PathBuf::from("/var/lib/hyperbox/bundles/db")));
+        assert!(!dir.path().join("partial.json.tmp").exists());
+
+        store.mark_started(&id).await.unwrap();
+        let meta = store.get(&id).unwrap();
+        assert_eq!((meta.finished_at, meta.exit_code), (None, None));
+    }
+
+    #[tokio::test]
+    async fn test_with_recorded_adds_lost_containers() {
+        let dir = TempDir::new().unwrap();
+        let store = MetadataStore::open(dir.path());
+        let running = named("running");
+        let lost = named("lost");
+        store.insert(running.clone()).await.unwrap();
+        store.insert(lost.clone()).await.unwrap();
+
+        let listed = store.with_recorded(vec![(running.id.clone(), ContainerState::Running)]);
+        assert_eq!(
+            listed,
+            vec![
+                (running.id, ContainerState::Running),
+                (lost.id, ContainerState::Exited)
+            ]
+        );
+    }
+}
@@ -19,24 +19,53 @@
 //! - Linux: Native OCI runtimes (crun, youki, runc)
 //! - macOS: Docker Desktop via Bollard API
 
+mod archive;
+mod bundle;
+#[cfg(unix)]
+mod console;
 mod crun;
 mod docker;
+mod filter;
+mod metadata;
+mod monitor;
+mod procfs;
 mod registry;
+mod runc;
+mod spec;
+mod stdio;
 mod traits;
 #[cfg(feature = "wasm")]
 mod wasm;
 #[cfg(feature = "youki")]
 mod youki;
 
+pub(crate) use archive::while_frozen;
+pub use archive::ContainerFs;
+pub use bundle::{merge_image_config, resolve_group, resolve_user, BundleManager, PreparedBundle};
+#[cfg(unix)]
+pub use console::{ConsoleSocket, Pty};
 pub use crun::CrunRuntime;
 pub use docker::DockerRuntime;
+pub use filter::{ContainerFilter, FilterTarget, LabelFilter, PROJECT_LABEL};
+pub use metadata::MetadataStore;
+pub use monitor::ExitMonitor;
+pub use procfs::{container_processes, ps_table, DEFAULT_PS_COLUMNS};
 pub use registry::RuntimeRegistry;
-pub use traits::{ContainerRuntime, ImageInfo, ProcessInfo};
+pub use runc::RuncRuntime;
+pub(crate) use spec::{cpu_quota, CPU_PERIOD_US, DEFAULT_PATH};
+pub use spec::{
+    linux_resources, read_config, seccomp_to_oci, stop_signal, update_resources, validate,
+    write_config, OciSpecBuilder, ALL_CAPABILITIES, DEFAULT_CAPABILITIES, DEFAULT_HOSTNAME,
+    DEFAULT_STOP_SIGNAL, OCI_VERSION, STOP_SIGNAL_ANNOTATION,
+};
+pub use stdio::StdioManager;
+pub use traits::{ContainerRuntime, ExecSession, ImageInfo, ProcessInfo};
 #[cfg(feature = "wasm")]
 pub use wasm::WasmRuntime;
 #[cfg(feature = "youki")]
 pub use youki::YoukiRuntime;
 
+use crate::error::{CoreError, Result};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -135,6 +164,25 @@ impl std::fmt::Display for RuntimeType {
     }
 }
 
+impl std::str::FromStr for RuntimeType {
+    type Err = CoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "docker" => Ok(Self::Docker),
+            "crun" => Ok(Self::Crun),
+            "youki" => Ok(Self::Youki),
+            "runc" => Ok(Self::Runc),
+            "firecracker" => Ok(Self::Firecracker),
+            "wasm" | "wasmtime" => Ok(Self::Wasm),
+            _ => Err(CoreError::InvalidSpec {
+                field: "runtime".to_string(),
+                reason: format!("unknown runtime '{s}'"),
+            }),
+        }
+    }
+}
+
 /// Runtime configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RuntimeConfig {
@@ -0,0 +1,507 @@
+//! Container exit monitoring.
+//!
+//! OCI runtimes fork the container's init process and exit, leaving nobody to
+//! collect its exit status. Like conmon, [`ExitMonitor`] marks this process as
+//! a child subreaper so that orphaned init processes are re-parented to it,
+//! reaps them and records how they exited: exit code, signal, whether the OOM
+//! killer fired, and when. Records are written to `<state_dir>/<id>.json` so
+//! they outlive the monitor task.
+//!
+//! Other container processes are re-parented to us too, e.g. those left
+//! behind by `exec`. A reaper thread shared by all monitors collects them on
+//! `SIGCHLD`: it reaps watched processes and hands their status to the
+//! monitor, and reaps any other child that runs in a nested PID namespace.
+//! Children in our own namespace are left alone, since they are the runtime
+//! commands we spawned and wait for ourselves.
+
+use crate::error::Result;
+use crate::runtime::procfs::cgroup_dir;
+use crate::types::{ContainerExit, ContainerId};
+use chrono::Utc;
+use dashmap::DashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, watch};
+use tracing::{debug, warn};
+
+/// How often the reaper looks for exited children without a `SIGCHLD`.
+const REAP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the exit of an unwatched container process is kept for a
+/// monitor that starts watching it late.
+const UNCLAIMED_EXIT_TTL: Duration = Duration::from_secs(60);
+
+/// Exit code and terminating signal of a reaped process.
+type ExitStatus = (i32, Option<i32>);
+
+/// Reaps container init processes and records their exit status.
+pub struct ExitMonitor {
+    state_dir: PathBuf,
+    exits: DashMap<String, watch::Receiver<Option<ContainerExit>>>,
+}
+
+impl ExitMonitor {
+    /// Create a monitor that records exits in `state_dir`.
+    ///
+    /// On Linux this also makes the current process a child subreaper.
+    #[must_use]
+    pub fn new(state_dir: impl Into<PathBuf>) -> Self {
+        enable_subreaper();
+        Self {
+            state_dir: state_dir.into(),
+            exits: DashMap::new(),
+        }
+    }
+
+    /// Start monitoring `pid`, the init process of container `id`.
+    ///
+    /// The runtime command that forked `pid` must already have exited, so the
+    /// process has been re-parented to us.
+    pub async fn watch(&self, id: &ContainerId, pid: u32) {
+        let (tx, rx) = watch::channel(None);
+        self.exits.insert(id.to_string(), rx);
+
+        // Resolve the cgroup now; it cannot be read from /proc after exit.
+        let cgroup = cgroup_dir(pid).await;
+        let record = self.record_path(id.as_str());
+        let id = id.clone();
+
+        let status = reaper().watch(pid);
+
+        tokio::spawn(async move {
+            let Ok((exit_code, signal)) = status.await else {
+                // Dropping the sender tells waiters to fall back to the runtime.
+                return;
+            };
+
+            let oom_killed = match cgroup {
+                Some(dir) => oom_killed(&dir).await,
+                None => false,
+            };
+            let exit = ContainerExit {
+                exit_code,
+                signal,
+                oom_killed,
+                finished_at: Utc::now(),
+            };
+            debug!(container_id = %id, ?exit, "Container exited");
+
+            if let Err(e) = write_record(&record, &exit).await {
+                warn!(container_id = %id, "Failed to record exit status: {}", e);
+            }
+            let _ = tx.send(Some(exit));
+        });
+    }
+
+    /// Wait for a container to exit.
+    ///
+    /// Returns `None` if the container is not being monitored and no exit
+    /// was recorded for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a recorded exit status cannot be read.
+    pub async fn wait(&self, id: &ContainerId) -> Result<Option<ContainerExit>> {
+        let rx = self.exits.get(id.as_str()).map(|rx| rx.clone());
+        if let Some(mut rx) = rx {
+            if let Ok(exit) = rx.wait_for(Option::is_some).await {
+                return Ok(*exit);
+            }
+        }
+        self.exit_status(id).await
+    }
+
+    /// Exit status of a container, if it has exited.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a recorded exit status cannot be read.
+    pub async fn exit_status(&self, id: &ContainerId) -> Result<Option<ContainerExit>> {
+        if let Some(exit) = self.exits.get(id.as_str()).and_then(|rx| *rx.borrow()) {
+            return Ok(Some(exit));
+        }
+
+        match tokio::fs::read(self.record_path(id.as_str())).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Forget a container and delete its exit record.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the record exists but cannot be deleted.
+    pub async fn remove(&self, id: &ContainerId) -> Result<()> {
+        self.exits.remove(id.as_str());
+        match tokio::fs::remove_file(self.record_path(id.as_str())).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn record_path(&self, id: &str) -> PathBuf {
+        self.state_dir.join(format!("{id}.json"))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn enable_subreaper() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| {
+        if let Err(e) = nix::sys::prctl::set_child_subreaper(true) {
+            warn!("Cannot become a child subreaper, exit codes will be unavailable: {}", e);
+        }
+        reaper();
+    });
+}
+
+#[cfg(not(target_os = "linux"))]
+const fn enable_subreaper() {}
+
+/// Collects exited children for all monitors.
+struct Reaper {
+    /// Watched processes and where their exit status goes.
+    watchers: DashMap<u32, oneshot::Sender<ExitStatus>>,
+    /// Container processes that exited before anyone watched them.
+    unclaimed: DashMap<u32, (Instant, ExitStatus)>,
+}
+
+/// The process-wide reaper, started on first use.
+fn reaper() -> &'static Reaper {
+    static REAPER: OnceLock<Reaper> = OnceLock::new();
+    REAPER.get_or_init(|| {
+        let spawned = std::thread::Builder::new()
+            .name("hyperbox-reaper".to_string())
+            .spawn(|| {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build();
+                match runtime {
+                    Ok(runtime) => runtime.block_on(run_reaper()),
+                    Err(e) => warn!("Cannot start the child reaper: {}", e),
+                }
+            });
+        if let Err(e) = spawned {
+            warn!("Cannot start the child reaper: {}", e);
+        }
+        Reaper {
+            watchers: DashMap::new(),
+            unclaimed: DashMap::new(),
+        }
+    })
+}
+
+/// Reap on every `SIGCHLD`, and now and then in case one was missed.
+#[cfg(unix)]
+async fn run_reaper() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigchld = match signal(SignalKind::child()) {
+        Ok(sigchld) => Some(sigchld),
+        Err(e) => {
+            warn!("Cannot listen for SIGCHLD, reaping every {:?}: {}", REAP_INTERVAL, e);
+            None
+        }
+    };
+    let mut interval = tokio::time::interval(REAP_INTERVAL);
+    loop {
+        match sigchld.as_mut() {
+            Some(sigchld) => {
+                tokio::select! {
+                    _ = sigchld.recv() => {}
+                    _ = interval.tick() => {}
+                }
+            }
+            None => {
+                interval.tick().await;
+            }
+        }
+        reaper().sweep();
+    }
+}
+
+#[cfg(not(unix))]
+async fn run_reaper() {}
+
+impl Reaper {
+    /// Deliver the exit status of `pid` once it has been reaped.
+    ///
+    /// The sender is dropped if `pid` is not our child.
+    fn watch(&self, pid: u32) -> oneshot::Receiver<ExitStatus> {
+        let (tx, rx) = oneshot::channel();
+        if let Some((_, (_, status))) = self.unclaimed.remove(&pid) {
+            let _ = tx.send(status);
+        } else {
+            self.watchers.insert(pid, tx);
+            // It may have exited before it was watched.
+            self.sweep();
+        }
+        rx
+    }
+
+    /// Reap every exited watched process and container orphan.
+    fn sweep(&self) {
+        let watched: Vec<u32> = self.watchers.iter().map(|entry| *entry.key()).collect();
+        for pid in watched {
+            match try_reap(pid) {
+                Ok(None) => {}
+                Ok(Some(status)) => {
+                    if let Some((_, tx)) = self.watchers.remove(&pid) {
+                        let _ = tx.send(status);
+                    }
+                }
+                Err(e) => {
+                    debug!(%pid, "Cannot reap process: {}", e);
+                    self.watchers.remove(&pid);
+                }
+            }
+        }
+
+        for pid in container_zombies() {
+            if self.watchers.contains_key(&pid) {
+                continue;
+            }
+            if let Ok(Some(status)) = try_reap(pid) {
+                debug!(%pid, ?status, "Reaped orphaned container process");
+                self.unclaimed.insert(pid, (Instant::now(), status));
+            }
+        }
+        self.unclaimed
+            .retain(|_, (reaped_at, _)| reaped_at.elapsed() < UNCLAIMED_EXIT_TTL);
+    }
+}
+
+/// Reap `pid` if it has exited.
+#[cfg(unix)]
+fn try_reap(pid: u32) -> std::io::Result<Option<ExitStatus>> {
+    use nix::errno::Errno;
+    use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+    use nix::unistd::Pid;
+
+    let pid = i32::try_from(pid).map_err(|_| Errno::ESRCH)?;
+    loop {
+        match waitpid(Pid::from_raw(pid), Some(WaitPidFlag::WNOHANG)) {
+            Ok(WaitStatus::Exited(_, code)) => return Ok(Some((code, None))),
+            Ok(WaitStatus::Signaled(_, signal, _)) => {
+                let signal = signal as i32;
+                return Ok(Some((128 + signal, Some(signal))));
+            }
+            Ok(_) => return Ok(None),
+            Err(Errno::EINTR) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn try_reap(_pid: u32) -> std::io::Result<Option<ExitStatus>> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Our zombie children that ran in a nested PID namespace.
+#[cfg(target_os = "linux")]
+fn container_zombies() -> Vec<u32> {
+    let Ok(tasks) = std::fs::read_dir("/proc/self/task") else {
+        return Vec::new();
+    };
+    tasks
+        .flatten()
+        .filter_map(|task| std::fs::read_to_string(task.path().join("children")).ok())
+        .flat_map(|children| {
+            children
+                .split_whitespace()
+                .filter_map(|pid| pid.parse().ok())
+                .collect::<Vec<u32>>()
+        })
+        .filter(|pid| {
+            std::fs::read_to_string(format!("/proc/{pid}/status"))
+                .is_ok_and(|status| is_container_zombie(&status))
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn container_zombies() -> Vec<u32> {
+    Vec::new()
+}
+
+/// Whether a `/proc/<pid>/status` file describes a zombie whose PID
+/// namespace is nested in ours.
+fn is_container_zombie(status: &str) -> bool {
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .map(str::trim)
+    };
+    let zombie = field("State").is_some_and(|state| state.starts_with('Z'));
+    let nested = field("NSpid").is_some_and(|pids| pids.split_whitespace().count() > 1);
+    zombie && nested
+}
+
+async fn oom_killed(cgroup: &Path) -> bool {
+    tokio::fs::read_to_string(cgroup.join("memory.events"))
+        .await
+        .is_ok_and(|events| oom_kills(&events) > 0)
+}
+
+/// The `oom_kill` counter of a `memory.events` file.
+fn oom_kills(events: &str) -> u64 {
+    events
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|count| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+async fn write_record(path: &Path, exit: &ContainerExit) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec(exit)?).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Spawn a shell without waiting for it; the monitor reaps it.
+    #[allow(clippy::zombie_processes)]
+    fn spawn_shell(script: &str) -> u32 {
+        let child = std::process::Command::new("/bin/sh")
+            .arg("-c")
+            .arg(script)
+            .spawn()
+            .unwrap();
+        child.id()
+    }
+
+    async fn wait(monitor: &ExitMonitor, id: &ContainerId) -> Option<ContainerExit> {
+        tokio::time::timeout(Duration::from_secs(10), monitor.wait(id))
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_wait_returns_exit_code() {
+        let dir = TempDir::new().unwrap();
+        let monitor = ExitMonitor::new(dir.path());
+        let id = ContainerId::new();
+
+        monitor.watch(&id, spawn_shell("exit 7")).await;
+        let exit = wait(&monitor, &id).await.unwrap();
+        assert_eq!(exit.exit_code, 7);
+        assert_eq!(exit.signal, None);
+        assert!(!exit.oom_killed);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_signal_exit_code() {
+        let dir = TempDir::new().unwrap();
+        let monitor = ExitMonitor::new(dir.path());
+        let id = ContainerId::new();
+
+        monitor.watch(&id, spawn_shell("kill -KILL $$")).await;
+        let exit = wait(&monitor, &id).await.unwrap();
+        assert_eq!(exit.exit_code, 137);
+        assert_eq!(exit.signal, Some(9));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_exit_is_recorded_on_disk() {
+        let dir = TempDir::new().unwrap();
+        let id = ContainerId::new();
+        {
+            let monitor = ExitMonitor::new(dir.path());
+            monitor.watch(&id, spawn_shell("exit 3")).await;
+            wait(&monitor, &id).await.unwrap();
+        }
+
+        let monitor = ExitMonitor::new(dir.path());
+        let exit = monitor.exit_status(&id).await.unwrap().unwrap();
+        assert_eq!(exit.exit_code, 3);
+
+        monitor.remove(&id).await.unwrap();
+        assert!(monitor.exit_status(&id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_container() {
+        let dir = TempDir::new().unwrap();
+        let monitor = ExitMonitor::new(dir.path());
+        assert!(wait(&monitor, &ContainerId::new()).await.is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_reaps_orphaned_container_processes() {
+        let dir = TempDir::new().unwrap();
+        let _monitor = ExitMonitor::new(dir.path());
+
+        // The shell runs as init of a new PID namespace; killing `unshare`
+        // re-parents it to us.
+        let Ok(mut unshare) = std::process::Command::new("unshare")
+            .args(["--pid", "--fork", "sh", "-c", "sleep 0.5"])
+            .spawn()
+        else {
+            return;
+        };
+        let children = format!("/proc/{0}/task/{0}/children", unshare.id());
+        let mut orphan = None;
+        for _ in 0..50 {
+            orphan = std::fs::read_to_string(&children)
+                .ok()
+                .and_then(|pids| pids.split_whitespace().next()?.parse::<u32>().ok());
+            if orphan.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        unshare.kill().unwrap();
+        let status = unshare.wait().unwrap();
+        let Some(orphan) = orphan else {
+            // No permission to create PID namespaces.
+            assert!(!status.success());
+            return;
+        };
+
+        let proc_dir = PathBuf::from(format!("/proc/{orphan}"));
+        for _ in 0..100 {
+            if !proc_dir.exists() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("orphan {orphan} was not reaped");
+    }
+
+    #[test]
+    fn test_is_container_zombie() {
+        let status =
+            |state: &str, nspid: &str| format!("Name:\tsh\nState:\t{state}\nNSpid:\t{nspid}\n");
+        assert!(is_container_zombie(&status("Z (zombie)", "4242\t1")));
+        assert!(!is_container_zombie(&status("Z (zombie)", "4242")));
+        assert!(!is_container_zombie(&status("S (sleeping)", "4242\t1")));
+        assert!(!is_container_zombie("Name:\tsh\n"));
+    }
+
+    #[test]
+    fn test_oom_kills() {
+        let events = "low 0\nhigh 4\nmax 12\noom 2\noom_kill 1\noom_group_kill 0\n";
+        assert_eq!(oom_kills(events), 1);
+        assert_eq!(oom_kills("low 0\n"), 0);
+    }
+}
@@ -0,0 +1,422 @@
+//! Process inspection through `/proc`.
+//!
+//! Native runtimes have no daemon to ask for a container's processes, so
+//! [`container_processes`] lists the members of the container's cgroup and
+//! reads each one's `/proc/<pid>/{stat,status,cmdline}`. [`ps_table`] renders
+//! the result with `ps`-style column selection, like `docker top`.
+
+use crate::error::{CoreError, Result};
+use crate::runtime::traits::ProcessInfo;
+use chrono::{DateTime, Local, Utc};
+use std::path::{Path, PathBuf};
+
+/// Root of the cgroup v2 hierarchy.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Clock ticks per second used by `/proc/<pid>/stat` (`USER_HZ`, which is
+/// 100 on every architecture Linux exports to user space).
+const USER_HZ: f64 = 100.0;
+
+/// Columns shown when no `ps` arguments are given.
+pub const DEFAULT_PS_COLUMNS: &str = "uid,pid,ppid,pcpu,rss,stime,args";
+
+/// cgroup v2 directory of a process.
+pub async fn cgroup_dir(pid: u32) -> Option<PathBuf> {
+    let content = tokio::fs::read_to_string(format!("/proc/{pid}/cgroup"))
+        .await
+        .ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| Path::new(CGROUP_ROOT).join(path.trim_start_matches('/')))
+}
+
+/// List the processes of a container, given its init process.
+///
+/// All processes in the init process's cgroup and its descendants are
+/// returned, sorted by PID. Processes that exit while being read are skipped.
+///
+/// # Errors
+///
+/// Returns error if the init process does not exist.
+pub async fn container_processes(init_pid: u32) -> Result<Vec<ProcessInfo>> {
+    let clock = Clock::read().await?;
+
+    // A process in the root cgroup is not confined; list only itself.
+    let mut pids = match cgroup_dir(init_pid).await {
+        Some(cgroup) if cgroup != Path::new(CGROUP_ROOT) => cgroup_pids(&cgroup).await,
+        _ => Vec::new(),
+    };
+    if pids.is_empty() {
+        pids.push(init_pid);
+    }
+    pids.sort_unstable();
+    pids.dedup();
+
+    let mut processes = Vec::with_capacity(pids.len());
+    for pid in pids {
+        match read_process(pid, &clock).await {
+            Ok(process) => processes.push(process),
+            Err(_) if pid != init_pid => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(processes)
+}
+
+/// Render processes as a table with `ps`-style column selection.
+///
+/// `ps_args` may be a column list such as `pid,comm` or `-o pid,comm`;
+/// `None` or an empty string selects [`DEFAULT_PS_COLUMNS`]. Supported
+/// columns are `pid`, `ppid`, `uid` (`user`), `pcpu` (`%cpu`), `rss`,
+/// `stime` (`start`), `comm` and `args` (`cmd`, `command`).
+///
+/// # Errors
+///
+/// Returns [`CoreError::InvalidSpec`] for unknown columns.
+pub fn ps_table(
+    processes: &[ProcessInfo],
+    ps_args: Option<&str>,
+) -> Result<(Vec<String>, Vec<Vec<String>>)> {
+    let columns = parse_columns(ps_args.unwrap_or_default())?;
+    let titles = columns.iter().map(|c| c.title().to_string()).collect();
+    let rows = processes
+        .iter()
+        .map(|process| columns.iter().map(|c| c.format(process)).collect())
+        .collect();
+    Ok((titles, rows))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Pid,
+    Ppid,
+    Uid,
+    Cpu,
+    Rss,
+    StartTime,
+    Comm,
+    Args,
+}
+
+impl Column {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "pid" => Self::Pid,
+            "ppid" => Self::Ppid,
+            "uid" | "euid" | "user" | "euser" => Self::Uid,
+            "pcpu" | "%cpu" | "c" => Self::Cpu,
+            "rss" | "rsz" | "rssize" => Self::Rss,
+            "stime" | "start" | "start_time" => Self::StartTime,
+            "comm" | "ucomm" => Self::Comm,
+            "args" | "cmd" | "command" => Self::Args,
+            _ => return None,
+        })
+    }
+
+    const fn title(self) -> &'static str {
+        match self {
+            Self::Pid => "PID",
+            Self::Ppid => "PPID",
+            Self::Uid => "UID",
+            Self::Cpu => "%CPU",
+            Self::Rss => "RSS",
+            Self::StartTime => "STIME",
+            Self::Comm => "COMMAND",
+            Self::Args => "CMD",
+        }
+    }
+
+    fn format(self, process: &ProcessInfo) -> String {
+        match self {
+            Self::Pid => process.pid.to_string(),
+            Self::Ppid => process.ppid.to_string(),
+            Self::Uid => process.uid.to_string(),
+            Self::Cpu => format!("{:.1}", process.cpu_percent),
+            Self::Rss => (process.memory_bytes / 1024).to_string(),
+            Self::StartTime => process
+                .started_at
+                .map_or_else(|| "?".to_string(), format_stime),
+            Self::Comm => process
+                .command
+                .split_whitespace()
+                .next()
+                .and_then(|arg0| arg0.rsplit('/').next())
+                .unwrap_or_default()
+                .to_string(),
+            Self::Args => process.command.clone(),
+        }
+    }
+}
+
+fn parse_columns(ps_args: &str) -> Result<Vec<Column>> {
+    let spec = ps_args.trim();
+    let spec = spec
+        .strip_prefix("-o")
+        .or_else(|| spec.strip_prefix("o "))
+        .unwrap_or(spec);
+    let spec = if spec.trim().is_empty() {
+        DEFAULT_PS_COLUMNS
+    } else {
+        spec
+    };
+
+    spec.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            Column::parse(name).ok_or_else(|| CoreError::InvalidSpec {
+                field: "ps_args".to_string(),
+                reason: format!("unsupported column '{name}'"),
+            })
+        })
+        .collect()
+}
+
+/// Start time as `ps` shows it: the time of day for processes started
+/// today, the date otherwise.
+fn format_stime(started_at: DateTime<Utc>) -> String {
+    let local = started_at.with_timezone(&Local);
+    if local.date_naive() == Local::now().date_naive() {
+        local.format("%H:%M").to_string()
+    } else {
+        local.format("%b%d").to_string()
+    }
+}
+
+/// PIDs in a cgroup and all of its descendants.
+async fn cgroup_pids(cgroup: &Path) -> Vec<u32> {
+    let mut pids = Vec::new();
+    let mut pending = vec![cgroup.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        if let Ok(procs) = tokio::fs::read_to_string(dir.join("cgroup.procs")).await {
+            pids.extend(
+                procs
+                    .lines()
+                    .filter_map(|line| line.trim().parse::<u32>().ok()),
+            );
+        }
+        if let Ok(mut entries) = tokio::fs::read_dir(&dir).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                if entry.file_type().await.is_ok_and(|t| t.is_dir()) {
+                    pending.push(entry.path());
+                }
+            }
+        }
+    }
+
+    pids
+}
+
+/// System clock values needed to interpret `/proc/<pid>/stat` times.
+struct Clock {
+    /// Boot time.
+    boot_time: DateTime<Utc>,
+    /// Seconds since boot.
+    uptime: f64,
+}
+
+impl Clock {
+    async fn read() -> Result<Self> {
+        let stat = tokio::fs::read_to_string("/proc/stat").await?;
+        let uptime = tokio::fs::read_to_string("/proc/uptime").await?;
+
+        let boot_time = stat
+            .lines()
+            .find_map(|line| line.strip_prefix("btime "))
+            .and_then(|secs| secs.trim().parse::<i64>().ok())
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .ok_or_else(|| CoreError::Internal("/proc/stat has no btime".to_string()))?;
+        let uptime = uptime
+            .split_whitespace()
+            .next()
+            .and_then(|secs| secs.parse().ok())
+            .ok_or_else(|| CoreError::Internal("malformed /proc/uptime".to_string()))?;
+
+        Ok(Self { boot_time, uptime })
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+async fn read_process(pid: u32, clock: &Clock) -> Result<ProcessInfo> {
+    let proc_dir = PathBuf::from(format!("/proc/{pid}"));
+    let stat = tokio::fs::read_to_string(proc_dir.join("stat")).await?;
+    let status = tokio::fs::read_to_string(proc_dir.join("status")).await?;
+    let cmdline = tokio::fs::read(proc_dir.join("cmdline")).await?;
+
+    let stat = Stat::parse(&stat)
+        .ok_or_else(|| CoreError::Internal(format!("malformed /proc/{pid}/stat")))?;
+    let (uid, rss_kib) = parse_status(&status);
+
+    let started = stat.start_ticks as f64 / USER_HZ;
+    let cpu_seconds = (stat.utime + stat.stime) as f64 / USER_HZ;
+    let elapsed = clock.uptime - started;
+    let cpu_percent = if elapsed > 0.0 {
+        cpu_seconds / elapsed * 100.0
+    } else {
+        0.0
+    };
+    let started_at = clock.boot_time + chrono::Duration::milliseconds((started * 1000.0) as i64);
+
+    let command = parse_cmdline(&cmdline);
+    Ok(ProcessInfo {
+        pid,
+        ppid: stat.ppid,
+        uid,
+        cpu_percent,
+        memory_bytes: rss_kib * 1024,
+        started_at: Some(started_at),
+        // Kernel threads and zombies have no command line.
+        command: if command.is_empty() {
+            format!("[{}]", stat.comm)
+        } else {
+            command
+        },
+    })
+}
+
+/// Fields of `/proc/<pid>/stat`.
+#[derive(Debug, PartialEq, Eq)]
+struct Stat {
+    comm: String,
+    ppid: u32,
+    utime: u64,
+    stime: u64,
+    start_ticks: u64,
+}
+
+impl Stat {
+    fn parse(content: &str) -> Option<Self> {
+        // `comm` may itself contain spaces and parentheses.
+        let open = content.find('(')?;
+        let close = content.rfind(')')?;
+        let comm = content.get(open + 1..close)?.to_string();
+
+        // Fields after `comm`, starting with field 3 (`state`).
+        let fields: Vec<&str> = content.get(close + 1..)?.split_whitespace().collect();
+        let field = |n: usize| fields.get(n - 3).and_then(|f| f.parse().ok());
+
+        Some(Self {
+            comm,
+            ppid: u32::try_from(field(4)?).ok()?,
+            utime: field(14)?,
+            stime: field(15)?,
+            start_ticks: field(22)?,
+        })
+    }
+}
+
+/// Effective user ID and resident set size in kibibytes, from
+/// `/proc/<pid>/status`.
+fn parse_status(content: &str) -> (u32, u64) {
+    let mut uid = 0;
+    let mut rss = 0;
+    for line in content.lines() {
+        if let Some(ids) = line.strip_prefix("Uid:") {
+            uid = ids
+                .split_whitespace()
+                .nth(1)
+                .and_then(|id| id.parse().ok())
+                .unwrap_or(0);
+        } else if let Some(value) = line.strip_prefix("VmRSS:") {
+            rss = value
+                .split_whitespace()
+                .next()
+                .and_then(|kib| kib.parse().ok())
+                .unwrap_or(0);
+        }
+    }
+    (uid, rss)
+}
+
+fn parse_cmdline(raw: &[u8]) -> String {
+    raw.split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, command: &str) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            ppid: 1,
+            uid: 1000,
+            cpu_percent: 12.345,
+            memory_bytes: 8 * 1024 * 1024,
+            started_at: None,
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_stat() {
+        let content = "4242 (my (weird) proc) S 1 4242 4242 0 -1 4194560 500 0 0 0 \
+                       25 10 0 0 20 0 1 0 123456 10000000 200 18446744073709551615";
+        let stat = Stat::parse(content).unwrap();
+        assert_eq!(
+            stat,
+            Stat {
+                comm: "my (weird) proc".to_string(),
+                ppid: 1,
+                utime: 25,
+                stime: 10,
+                start_ticks: 123_456,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_status_and_cmdline() {
+        let status = "Name:\tsh\nUid:\t0\t1000\t1000\t1000\nVmRSS:\t    2048 kB\n";
+        assert_eq!(parse_status(status), (1000, 2048));
+        assert_eq!(parse_cmdline(b"/bin/sh\0-c\0sleep 5\0"), "/bin/sh -c sleep 5");
+        assert_eq!(parse_cmdline(b""), "");
+    }
+
+    #[test]
+    fn test_ps_table_default_columns() {
+        let (titles, rows) = ps_table(&[process(7, "/usr/bin/redis-server *:6379")], None).unwrap();
+        assert_eq!(titles, ["UID", "PID", "PPID", "%CPU", "RSS", "STIME", "CMD"]);
+        assert_eq!(
+            rows,
+            [[
+                "1000",
+                "7",
+                "1",
+                "12.3",
+                "8192",
+                "?",
+                "/usr/bin/redis-server *:6379"
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_ps_table_column_selection() {
+        let processes = [process(7, "/usr/bin/redis-server *:6379")];
+        let (titles, rows) = ps_table(&processes, Some("-o pid,comm")).unwrap();
+        assert_eq!(titles, ["PID", "COMMAND"]);
+        assert_eq!(rows, [["7", "redis-server"]]);
+
+        assert!(matches!(
+            ps_table(&processes, Some("pid,bogus")),
+            Err(CoreError::InvalidSpec { .. })
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_container_processes_reads_proc() {
+        let pid = std::process::id();
+        let processes = container_processes(pid).await.unwrap();
+        let me = processes.iter().find(|p| p.pid == pid).unwrap();
+        assert!(me.memory_bytes > 0);
+        assert!(me.started_at.is_some());
+        assert!(!me.command.is_empty());
+    }
+}
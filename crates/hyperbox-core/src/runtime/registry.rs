@@ -1,10 +1,22 @@
 //! Runtime registry for managing multiple container runtimes.
 
 use crate::error::{CoreError, Result};
-use crate::runtime::{ContainerRuntime, CrunRuntime, RuntimeConfig, RuntimeType};
+#[cfg(feature = "wasm")]
+use crate::runtime::WasmRuntime;
+#[cfg(feature = "youki")]
+use crate::runtime::YoukiRuntime;
+use crate::runtime::{
+    BundleManager, ContainerRuntime, CrunRuntime, RuncRuntime, RuntimeConfig, RuntimeType,
+};
+use crate::types::ContainerSpec;
 use dashmap::DashMap;
+use parking_lot::RwLock;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{info, warn};
+
+/// Label selecting the runtime for a container, e.g. `hyperbox.runtime=youki`.
+pub const RUNTIME_LABEL: &str = "hyperbox.runtime";
 
 /// Registry for managing container runtime implementations.
 ///
@@ -14,6 +26,10 @@ use tracing::info;
 pub struct RuntimeRegistry {
     runtimes: DashMap<RuntimeType, Arc<dyn ContainerRuntime>>,
     default_runtime: RuntimeType,
+    /// Binaries configured for individual runtimes.
+    binary_paths: DashMap<RuntimeType, PathBuf>,
+    /// Image store shared by the native runtimes.
+    bundles: RwLock<Option<Arc<BundleManager>>>,
 }
 
 impl RuntimeRegistry {
@@ -23,25 +39,73 @@ impl RuntimeRegistry {
         Self {
             runtimes: DashMap::new(),
             default_runtime,
+            binary_paths: DashMap::new(),
+            bundles: RwLock::new(None),
         }
     }
 
+    /// Use `path` as the binary of `runtime_type` when initializing.
+    pub fn set_binary_path(&self, runtime_type: RuntimeType, path: impl Into<PathBuf>) {
+        self.binary_paths.insert(runtime_type, path.into());
+    }
+
     /// Initialize the registry with available runtimes.
+    ///
+    /// Every runtime compiled into this build (crun, runc, and youki and
+    /// wasm when their features are enabled) is registered if its binary is
+    /// available. Each runtime uses the binary set with
+    /// [`set_binary_path`](Self::set_binary_path), falling back to
+    /// `config.binary_path` for `config.runtime_type`; the others are looked
+    /// up in their standard locations. The native
+    /// runtimes share one image store and bundle directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the image store cannot be opened or no runtime is
+    /// available.
     pub async fn initialize(&self, config: &RuntimeConfig) -> Result<()> {
         info!("Initializing runtime registry");
 
-        // Try to initialize crun
-        match CrunRuntime::new(config.clone()).await {
-            Ok(runtime) => {
-                self.register(RuntimeType::Crun, Arc::new(runtime));
-                info!("Registered crun runtime");
-            }
-            Err(e) => {
-                tracing::warn!("crun not available: {}", e);
-            }
+        let bundles = Arc::new(BundleManager::new(&config.root_dir)?);
+        *self.bundles.write() = Some(Arc::clone(&bundles));
+
+        match CrunRuntime::with_kind(
+            self.config_for(config, RuntimeType::Crun),
+            RuntimeType::Crun,
+            Arc::clone(&bundles),
+        )
+        .await
+        {
+            Ok(runtime) => self.register(RuntimeType::Crun, Arc::new(runtime)),
+            Err(e) => warn!("crun not available: {}", e),
         }
 
-        // Would also try youki, runc, firecracker here
+        match RuncRuntime::with_bundles(
+            self.config_for(config, RuntimeType::Runc),
+            Arc::clone(&bundles),
+        )
+        .await
+        {
+            Ok(runtime) => self.register(RuntimeType::Runc, Arc::new(runtime)),
+            Err(e) => warn!("runc not available: {}", e),
+        }
+
+        #[cfg(feature = "youki")]
+        self.register_if_available(
+            RuntimeType::Youki,
+            Arc::new(YoukiRuntime::with_bundles(
+                self.config_for(config, RuntimeType::Youki),
+                Arc::clone(&bundles),
+            )),
+        )
+        .await;
+
+        #[cfg(feature = "wasm")]
+        self.register_if_available(
+            RuntimeType::Wasm,
+            Arc::new(WasmRuntime::new(self.config_for(config, RuntimeType::Wasm))),
+        )
+        .await;
 
         if self.runtimes.is_empty() {
             return Err(CoreError::RuntimeNotAvailable {
@@ -50,11 +114,51 @@ impl RuntimeRegistry {
             });
         }
 
+        info!("Available runtimes: {:?}", self.available());
         Ok(())
     }
 
+    /// Configuration for one runtime, derived from the registry's.
+    fn config_for(&self, config: &RuntimeConfig, runtime_type: RuntimeType) -> RuntimeConfig {
+        RuntimeConfig {
+            runtime_type,
+            binary_path: self
+                .binary_paths
+                .get(&runtime_type)
+                .map(|path| path.clone())
+                .or_else(|| {
+                    config
+                        .binary_path
+                        .clone()
+                        .filter(|_| config.runtime_type == runtime_type)
+                }),
+            ..config.clone()
+        }
+    }
+
+    /// Register a runtime whose constructor cannot detect a missing binary.
+    #[cfg(any(feature = "youki", feature = "wasm"))]
+    async fn register_if_available(
+        &self,
+        runtime_type: RuntimeType,
+        runtime: Arc<dyn ContainerRuntime>,
+    ) {
+        if runtime.is_available().await {
+            self.register(runtime_type, runtime);
+        } else {
+            warn!("{} not available", runtime_type);
+        }
+    }
+
+    /// Image store and registry client of the native runtimes, once
+    /// [`initialize`](Self::initialize) has run.
+    pub fn bundles(&self) -> Option<Arc<BundleManager>> {
+        self.bundles.read().clone()
+    }
+
     /// Register a runtime implementation.
     pub fn register(&self, runtime_type: RuntimeType, runtime: Arc<dyn ContainerRuntime>) {
+        info!("Registered {} runtime", runtime_type);
         self.runtimes.insert(runtime_type, runtime);
     }
 
@@ -65,8 +169,61 @@ impl RuntimeRegistry {
 
     /// Get the default runtime.
     pub fn default(&self) -> Option<Arc<dyn ContainerRuntime>> {
-        self.get(self.default_runtime)
-            .or_else(|| self.runtimes.iter().next().map(|r| Arc::clone(r.value())))
+        self.default_type().and_then(|runtime_type| self.get(runtime_type))
+    }
+
+    /// Type of the default runtime, or of any registered runtime if the
+    /// configured default is unavailable.
+    #[must_use]
+    pub fn default_type(&self) -> Option<RuntimeType> {
+        if self.has(self.default_runtime) {
+            Some(self.default_runtime)
+        } else {
+            self.runtimes.iter().next().map(|r| *r.key())
+        }
+    }
+
+    /// Select the runtime for a container.
+    ///
+    /// In order of precedence: the spec's `runtime` field, the
+    /// [`RUNTIME_LABEL`] label, the wasm runtime for wasm modules, and
+    /// finally the default runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the label names an unknown runtime or the selected
+    /// runtime is not registered.
+    pub fn select(&self, spec: &ContainerSpec) -> Result<(RuntimeType, Arc<dyn ContainerRuntime>)> {
+        let requested = match (spec.runtime, spec.labels.get(RUNTIME_LABEL)) {
+            (Some(runtime_type), _) => Some(runtime_type),
+            (None, Some(label)) => Some(label.parse()?),
+            (None, None) => Self::detect(spec),
+        };
+
+        let runtime_type = match requested {
+            Some(runtime_type) => runtime_type,
+            None => self.default_type().ok_or_else(|| CoreError::RuntimeNotAvailable {
+                runtime: self.default_runtime.to_string(),
+                path: std::path::PathBuf::new(),
+            })?,
+        };
+
+        let runtime = self.get(runtime_type).ok_or_else(|| CoreError::RuntimeNotAvailable {
+            runtime: runtime_type.to_string(),
+            path: std::path::PathBuf::from(runtime_type.binary_name()),
+        })?;
+        Ok((runtime_type, runtime))
+    }
+
+    /// Runtime implied by the workload itself.
+    #[cfg(feature = "wasm")]
+    fn detect(spec: &ContainerSpec) -> Option<RuntimeType> {
+        WasmRuntime::is_wasm_image(&spec.image).then_some(RuntimeType::Wasm)
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn detect(_spec: &ContainerSpec) -> Option<RuntimeType> {
+        None
     }
 
     /// List available runtimes.
@@ -97,10 +254,130 @@ mod tests {
         assert!(registry.available().is_empty());
     }
 
+    #[test]
+    fn test_config_for_keeps_binary_path_for_selected_runtime() {
+        let config = RuntimeConfig {
+            runtime_type: RuntimeType::Runc,
+            binary_path: Some("/opt/runc".into()),
+            ..RuntimeConfig::default()
+        };
+
+        let registry = RuntimeRegistry::new(RuntimeType::Runc);
+
+        let runc = registry.config_for(&config, RuntimeType::Runc);
+        assert_eq!(runc.binary_path, Some("/opt/runc".into()));
+
+        let crun = registry.config_for(&config, RuntimeType::Crun);
+        assert_eq!(crun.runtime_type, RuntimeType::Crun);
+        assert_eq!(crun.binary_path, None);
+        assert_eq!(crun.root_dir, config.root_dir);
+    }
+
+    #[test]
+    fn test_config_for_uses_binary_path_of_each_runtime() {
+        let config = RuntimeConfig {
+            runtime_type: RuntimeType::Crun,
+            binary_path: Some("/opt/default".into()),
+            ..RuntimeConfig::default()
+        };
+        let registry = RuntimeRegistry::new(RuntimeType::Crun);
+        registry.set_binary_path(RuntimeType::Crun, "/opt/crun");
+        registry.set_binary_path(RuntimeType::Runc, "/opt/runc");
+
+        let crun = registry.config_for(&config, RuntimeType::Crun);
+        assert_eq!(crun.binary_path, Some("/opt/crun".into()));
+
+        let runc = registry.config_for(&config, RuntimeType::Runc);
+        assert_eq!(runc.binary_path, Some("/opt/runc".into()));
+
+        let youki = registry.config_for(&config, RuntimeType::Youki);
+        assert_eq!(youki.binary_path, None);
+    }
+
     #[test]
     fn test_default_registry() {
         let registry = <RuntimeRegistry as Default>::default();
         assert!(registry.available().is_empty());
         assert!(registry.default().is_none()); // No runtimes registered yet
     }
+
+    /// Registry with `types` registered; selection only looks at the keys.
+    fn registry_with(default: RuntimeType, types: &[RuntimeType]) -> RuntimeRegistry {
+        let registry = RuntimeRegistry::new(default);
+        let runtime: Arc<dyn ContainerRuntime> =
+            Arc::new(crate::runtime::DockerRuntime::new().unwrap());
+        for runtime_type in types {
+            registry.register(*runtime_type, Arc::clone(&runtime));
+        }
+        registry
+    }
+
+    fn selected(registry: &RuntimeRegistry, spec: &ContainerSpec) -> RuntimeType {
+        registry.select(spec).unwrap().0
+    }
+
+    #[test]
+    fn test_select_precedence() {
+        let registry = registry_with(
+            RuntimeType::Crun,
+            &[RuntimeType::Crun, RuntimeType::Youki, RuntimeType::Runc],
+        );
+
+        let spec = ContainerSpec::builder().image("alpine").build();
+        assert_eq!(selected(&registry, &spec), RuntimeType::Crun);
+
+        let spec = ContainerSpec::builder()
+            .image("alpine")
+            .label(RUNTIME_LABEL, "youki")
+            .build();
+        assert_eq!(selected(&registry, &spec), RuntimeType::Youki);
+
+        let spec = ContainerSpec::builder()
+            .image("alpine")
+            .label(RUNTIME_LABEL, "youki")
+            .runtime(RuntimeType::Runc)
+            .build();
+        assert_eq!(selected(&registry, &spec), RuntimeType::Runc);
+    }
+
+    #[test]
+    fn test_select_falls_back_when_default_missing() {
+        let registry = registry_with(RuntimeType::Crun, &[RuntimeType::Runc]);
+        let spec = ContainerSpec::builder().image("alpine").build();
+        assert_eq!(selected(&registry, &spec), RuntimeType::Runc);
+        assert_eq!(registry.default_type(), Some(RuntimeType::Runc));
+    }
+
+    #[test]
+    fn test_select_errors() {
+        let registry = registry_with(RuntimeType::Crun, &[RuntimeType::Crun]);
+
+        let spec = ContainerSpec::builder().image("alpine").runtime(RuntimeType::Youki).build();
+        assert!(matches!(
+            registry.select(&spec),
+            Err(CoreError::RuntimeNotAvailable { runtime, .. }) if runtime == "youki"
+        ));
+
+        let spec = ContainerSpec::builder()
+            .image("alpine")
+            .label(RUNTIME_LABEL, "lxc")
+            .build();
+        assert!(matches!(registry.select(&spec), Err(CoreError::InvalidSpec { .. })));
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_select_detects_wasm_images() {
+        let registry = registry_with(RuntimeType::Crun, &[RuntimeType::Crun, RuntimeType::Wasm]);
+        let spec = ContainerSpec::builder().image("./app.wasm").build();
+        assert_eq!(selected(&registry, &spec), RuntimeType::Wasm);
+    }
+
+    #[test]
+    fn test_runtime_type_from_str() {
+        assert_eq!("crun".parse::<RuntimeType>().unwrap(), RuntimeType::Crun);
+        assert_eq!("RunC".parse::<RuntimeType>().unwrap(), RuntimeType::Runc);
+        assert_eq!("wasmtime".parse::<RuntimeType>().unwrap(), RuntimeType::Wasm);
+        assert!("lxc".parse::<RuntimeType>().is_err());
+    }
 }
@@ -0,0 +1,239 @@
+//! runc runtime implementation.
+//!
+//! runc is the reference OCI runtime. Its command line matches crun's, so
+//! [`RuncRuntime`] drives it through the crun implementation: the same image
+//! bundles, log driver, console sockets and exit monitor, with runc's own
+//! state directory.
+
+use crate::error::Result;
+use crate::runtime::traits::{ExecSession, ImageInfo, ProcessInfo};
+use crate::runtime::{BundleManager, ContainerRuntime, CrunRuntime, RuntimeConfig, RuntimeType};
+use crate::types::{
+    CheckpointId, CommitOptions, ContainerExit, ContainerId, ContainerMetadata, ContainerSpec,
+    ContainerState, ContainerStats, ExecResult, ExecSpec, FilesystemChange, ImageRef,
+    ImportOptions, LogOptions, PullOptions, PushOptions, ResourceEvents, ResourceLimits,
+    ResourceUpdateReport, TtySize,
+};
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// runc runtime implementation.
+pub struct RuncRuntime {
+    inner: CrunRuntime,
+}
+
+impl RuncRuntime {
+    /// Create a new runc runtime instance.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if runc binary is not found.
+    pub async fn new(config: RuntimeConfig) -> Result<Self> {
+        let bundles = Arc::new(BundleManager::new(&config.root_dir)?);
+        Self::with_bundles(config, bundles).await
+    }
+
+    /// Create an instance keeping images and bundles in `bundles`.
+    pub(crate) async fn with_bundles(
+        config: RuntimeConfig,
+        bundles: Arc<BundleManager>,
+    ) -> Result<Self> {
+        Ok(Self {
+            inner: CrunRuntime::with_kind(config, RuntimeType::Runc, bundles).await?,
+        })
+    }
+}
+
+#[async_trait]
+impl ContainerRuntime for RuncRuntime {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn version(&self) -> Result<String> {
+        self.inner.version().await
+    }
+
+    async fn is_available(&self) -> bool {
+        self.inner.is_available().await
+    }
+
+    async fn create(&self, spec: ContainerSpec) -> Result<ContainerId> {
+        self.inner.create(spec).await
+    }
+
+    async fn start(&self, id: &ContainerId) -> Result<()> {
+        self.inner.start(id).await
+    }
+
+    async fn stop(&self, id: &ContainerId, timeout: Duration) -> Result<()> {
+        self.inner.stop(id, timeout).await
+    }
+
+    async fn kill(&self, id: &ContainerId, signal: &str) -> Result<()> {
+        self.inner.kill(id, signal).await
+    }
+
+    async fn remove(&self, id: &ContainerId) -> Result<()> {
+        self.inner.remove(id).await
+    }
+
+    async fn pause(&self, id: &ContainerId) -> Result<()> {
+        self.inner.pause(id).await
+    }
+
+    async fn resume(&self, id: &ContainerId) -> Result<()> {
+        self.inner.resume(id).await
+    }
+
+    async fn exec(&self, id: &ContainerId, spec: ExecSpec) -> Result<ExecResult> {
+        self.inner.exec(id, spec).await
+    }
+
+    async fn state(&self, id: &ContainerId) -> Result<ContainerState> {
+        self.inner.state(id).await
+    }
+
+    async fn stats(&self, id: &ContainerId) -> Result<ContainerStats> {
+        self.inner.stats(id).await
+    }
+
+    async fn logs(
+        &self,
+        id: &ContainerId,
+        opts: LogOptions,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        self.inner.logs(id, opts).await
+    }
+
+    async fn attach(
+        &self,
+        id: &ContainerId,
+    ) -> Result<(
+        Box<dyn AsyncWrite + Send + Unpin>,
+        Box<dyn AsyncRead + Send + Unpin>,
+        Box<dyn AsyncRead + Send + Unpin>,
+    )> {
+        self.inner.attach(id).await
+    }
+
+    async fn exec_interactive(&self, id: &ContainerId, spec: ExecSpec) -> Result<ExecSession> {
+        self.inner.exec_interactive(id, spec).await
+    }
+
+    async fn resize(&self, id: &ContainerId, exec_id: Option<&str>, size: TtySize) -> Result<()> {
+        self.inner.resize(id, exec_id, size).await
+    }
+
+    async fn list(&self) -> Result<Vec<(ContainerId, ContainerState)>> {
+        self.inner.list().await
+    }
+
+    async fn inspect(&self, id: &ContainerId) -> Result<ContainerMetadata> {
+        self.inner.inspect(id).await
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<ContainerMetadata>> {
+        self.inner.list_metadata().await
+    }
+
+    async fn lookup(&self, name: &str) -> Result<Option<ContainerId>> {
+        self.inner.lookup(name).await
+    }
+
+    async fn wait(&self, id: &ContainerId) -> Result<i32> {
+        self.inner.wait(id).await
+    }
+
+    async fn exit_status(&self, id: &ContainerId) -> Result<Option<ContainerExit>> {
+        self.inner.exit_status(id).await
+    }
+
+    async fn resource_events(
+        &self,
+        id: &ContainerId,
+    ) -> Result<BoxStream<'static, ResourceEvents>> {
+        self.inner.resource_events(id).await
+    }
+
+    async fn checkpoint(&self, id: &ContainerId, checkpoint_path: &Path) -> Result<CheckpointId> {
+        self.inner.checkpoint(id, checkpoint_path).await
+    }
+
+    async fn restore(&self, checkpoint_path: &Path, spec: ContainerSpec) -> Result<ContainerId> {
+        self.inner.restore(checkpoint_path, spec).await
+    }
+
+    async fn update(
+        &self,
+        id: &ContainerId,
+        resources: ResourceLimits,
+    ) -> Result<ResourceUpdateReport> {
+        self.inner.update(id, resources).await
+    }
+
+    async fn top(&self, id: &ContainerId) -> Result<Vec<ProcessInfo>> {
+        self.inner.top(id).await
+    }
+
+    async fn archive_path(
+        &self,
+        id: &ContainerId,
+        path: &Path,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        self.inner.archive_path(id, path).await
+    }
+
+    async fn extract_archive(
+        &self,
+        id: &ContainerId,
+        path: &Path,
+        archive: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> Result<()> {
+        self.inner.extract_archive(id, path, archive).await
+    }
+
+    async fn diff(&self, id: &ContainerId) -> Result<Vec<FilesystemChange>> {
+        self.inner.diff(id).await
+    }
+
+    async fn commit(&self, id: &ContainerId, options: CommitOptions) -> Result<String> {
+        self.inner.commit(id, options).await
+    }
+
+    async fn export(&self, id: &ContainerId) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        self.inner.export(id).await
+    }
+
+    async fn pull_image(&self, image: &ImageRef) -> Result<()> {
+        self.inner.pull_image(image).await
+    }
+
+    async fn pull_image_with(&self, image: &ImageRef, options: PullOptions) -> Result<()> {
+        self.inner.pull_image_with(image, options).await
+    }
+
+    async fn push_image(&self, image: &ImageRef, options: PushOptions) -> Result<String> {
+        self.inner.push_image(image, options).await
+    }
+
+    async fn image_exists(&self, image: &str) -> Result<bool> {
+        self.inner.image_exists(image).await
+    }
+
+    async fn list_images(&self) -> Result<Vec<ImageInfo>> {
+        self.inner.list_images().await
+    }
+
+    async fn import_image(
+        &self,
+        archive: Box<dyn AsyncRead + Send + Unpin>,
+        options: ImportOptions,
+    ) -> Result<String> {
+        self.inner.import_image(archive, options).await
+    }
+}
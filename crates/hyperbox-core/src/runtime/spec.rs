@@ -0,0 +1,1587 @@
+//! OCI runtime-spec generation.
+//!
+//! [`OciSpecBuilder`] turns a [`ContainerSpec`] into a typed
+//! [`oci_spec::runtime::Spec`] that every native runtime (crun, youki, runc)
+//! writes as the bundle's `config.json`. It covers:
+//!
+//! - process: argv, env, cwd, numeric user and supplementary groups,
+//!   terminal, capabilities (with `cap_add`/`cap_drop`), rlimits,
+//!   `noNewPrivileges`;
+//! - root filesystem path and `readonly`;
+//! - the standard `/proc`, `/dev`, `/sys` mounts (with the spec's `/dev/shm`
+//!   size) plus the spec's mounts;
+//! - namespaces, masked/read-only paths, sysctls, host devices, device cgroup
+//!   and resource limits, with IO throttles set on the disks backing the
+//!   root filesystem;
+//! - an init process as PID 1, bind-mounted from the host, when `init` is set;
+//! - the stop signal, recorded as the [`STOP_SIGNAL_ANNOTATION`] annotation.
+//!
+//! Every generated spec is checked with [`validate`] before it is returned.
+
+use crate::error::{CoreError, Result};
+use crate::isolation::block_device::{BlockDevice, BlockDeviceResolver};
+use crate::isolation::seccomp::{SeccompAction, SeccompOperator, SeccompProfile};
+use crate::types::{ContainerSpec, MountType, ResourceLimits, ResourceUpdateReport};
+use oci_spec::runtime::{
+    Capabilities, Capability, LinuxBlockIo, LinuxBlockIoBuilder, LinuxBuilder,
+    LinuxCapabilitiesBuilder, LinuxCpuBuilder, LinuxDevice, LinuxDeviceBuilder,
+    LinuxDeviceCgroupBuilder, LinuxDeviceType, LinuxMemoryBuilder, LinuxNamespace,
+    LinuxNamespaceBuilder, LinuxNamespaceType, LinuxPidsBuilder, LinuxResources,
+    LinuxResourcesBuilder, LinuxSeccomp, LinuxSeccompAction, LinuxSeccompArgBuilder,
+    LinuxSeccompBuilder, LinuxSeccompOperator, LinuxSyscallBuilder, LinuxThrottleDevice,
+    LinuxThrottleDeviceBuilder, Mount, MountBuilder, PosixRlimitBuilder, PosixRlimitType,
+    ProcessBuilder, RootBuilder, Spec, SpecBuilder, UserBuilder,
+};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// OCI runtime-spec version emitted in `ociVersion`.
+pub const OCI_VERSION: &str = "1.0.2";
+
+/// Hostname used when the container spec does not set one.
+pub const DEFAULT_HOSTNAME: &str = "hyperbox";
+
+/// `PATH` used when neither the image nor the container spec sets one.
+pub const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// CFS period used when converting millicores to a CPU quota.
+pub const CPU_PERIOD_US: u64 = 100_000;
+
+/// Smallest CFS quota the kernel accepts, in microseconds.
+const MIN_CPU_QUOTA_US: u64 = 1_000;
+
+/// Valid range of the cgroup v2 `cpu.weight` in [`ResourceLimits`].
+const CPU_WEIGHT_RANGE: std::ops::RangeInclusive<u16> = 1..=10_000;
+
+/// Valid range of the OCI (cgroup v1) CPU shares.
+const CPU_SHARES_RANGE: std::ops::RangeInclusive<u64> = 2..=262_144;
+
+/// Valid range of the cgroup v2 `io.weight` in [`ResourceLimits`].
+const IO_WEIGHT_RANGE: std::ops::RangeInclusive<u16> = 1..=10_000;
+
+/// Valid range of the OCI (blkio) weight.
+const BLKIO_WEIGHT_RANGE: std::ops::RangeInclusive<u16> = 10..=1000;
+
+/// Default open-files limit for the container process.
+const DEFAULT_NOFILE: u64 = 1024;
+
+/// Default size of `/dev/shm`.
+const DEFAULT_SHM_SIZE: &str = "65536k";
+
+/// Annotation holding the signal that stops the container.
+pub const STOP_SIGNAL_ANNOTATION: &str = "org.opencontainers.image.stopSignal";
+
+/// Signal that stops a container without a stop signal.
+pub const DEFAULT_STOP_SIGNAL: &str = "SIGTERM";
+
+/// Where the init binary is mounted in the container.
+const INIT_PATH: &str = "/dev/init";
+
+/// Host init binaries tried, in order, when none is configured. They must be
+/// statically linked to run in any image.
+const INIT_BINARIES: &[&str] = &[
+    "/usr/libexec/podman/catatonit",
+    "/usr/bin/catatonit",
+    "/usr/bin/tini-static",
+    "/usr/bin/docker-init",
+    "/usr/libexec/docker/docker-init",
+];
+
+/// Capabilities granted to non-privileged containers.
+pub const DEFAULT_CAPABILITIES: &[&str] = &[
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_FSETID",
+    "CAP_FOWNER",
+    "CAP_MKNOD",
+    "CAP_NET_RAW",
+    "CAP_SETGID",
+    "CAP_SETUID",
+    "CAP_SETFCAP",
+    "CAP_SETPCAP",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_SYS_CHROOT",
+    "CAP_KILL",
+    "CAP_AUDIT_WRITE",
+];
+
+/// Every capability known to the runtime spec, granted to privileged containers.
+pub const ALL_CAPABILITIES: &[&str] = &[
+    "CAP_AUDIT_CONTROL",
+    "CAP_AUDIT_READ",
+    "CAP_AUDIT_WRITE",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_KILL",
+    "CAP_LEASE",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_MAC_ADMIN",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MKNOD",
+    "CAP_NET_ADMIN",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_RAW",
+    "CAP_PERFMON",
+    "CAP_SETGID",
+    "CAP_SETFCAP",
+    "CAP_SETPCAP",
+    "CAP_SETUID",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_NICE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_SYSLOG",
+    "CAP_WAKE_ALARM",
+];
+
+/// Paths hidden from non-privileged containers.
+const MASKED_PATHS: &[&str] = &[
+    "/proc/acpi",
+    "/proc/asound",
+    "/proc/kcore",
+    "/proc/keys",
+    "/proc/latency_stats",
+    "/proc/timer_list",
+    "/proc/timer_stats",
+    "/proc/sched_debug",
+    "/proc/scsi",
+    "/sys/firmware",
+];
+
+/// Paths mounted read-only in non-privileged containers.
+const READONLY_PATHS: &[&str] = &[
+    "/proc/bus",
+    "/proc/fs",
+    "/proc/irq",
+    "/proc/sys",
+    "/proc/sysrq-trigger",
+];
+
+/// Builder for OCI runtime specs.
+///
+/// # Example
+///
+/// ```rust
+/// use hyperbox_core::runtime::OciSpecBuilder;
+/// use hyperbox_core::types::ContainerSpec;
+///
+/// let spec = ContainerSpec::builder()
+///     .image("alpine:latest")
+///     .command(vec!["echo", "hello"])
+///     .build();
+///
+/// let oci = OciSpecBuilder::new(&spec)
+///     .rootfs("/var/lib/hyperbox/rootfs")
+///     .build()
+///     .unwrap();
+/// assert_eq!(oci.version(), "1.0.2");
+/// ```
+pub struct OciSpecBuilder<'a> {
+    spec: &'a ContainerSpec,
+    rootfs: PathBuf,
+    cgroups_path: Option<PathBuf>,
+    init_binary: Option<PathBuf>,
+}
+
+impl<'a> OciSpecBuilder<'a> {
+    /// Start building a spec for `spec`.
+    ///
+    /// The root filesystem defaults to `rootfs` relative to the bundle.
+    #[must_use]
+    pub fn new(spec: &'a ContainerSpec) -> Self {
+        Self {
+            spec,
+            rootfs: PathBuf::from("rootfs"),
+            cgroups_path: None,
+            init_binary: None,
+        }
+    }
+
+    /// Set the root filesystem path.
+    #[must_use]
+    pub fn rootfs(mut self, rootfs: impl Into<PathBuf>) -> Self {
+        self.rootfs = rootfs.into();
+        self
+    }
+
+    /// Place the container in a specific cgroup.
+    #[must_use]
+    pub fn cgroups_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cgroups_path = Some(path.into());
+        self
+    }
+
+    /// Host binary run as PID 1 when the container spec sets `init`.
+    ///
+    /// Defaults to the first of catatonit, tini-static or docker-init found
+    /// on the host.
+    #[must_use]
+    pub fn init_binary(mut self, path: impl Into<PathBuf>) -> Self {
+        self.init_binary = Some(path.into());
+        self
+    }
+
+    /// Build and validate the spec.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::InvalidSpec`] if the container spec cannot be
+    /// expressed (e.g. a non-numeric user, a missing device or no init
+    /// binary) or the result fails validation.
+    pub fn build(self) -> Result<Spec> {
+        let spec = self.spec;
+        let privileged = spec.privileged;
+
+        let init = if spec.init {
+            let binary = self
+                .init_binary
+                .clone()
+                .or_else(find_init_binary)
+                .ok_or_else(|| CoreError::InvalidSpec {
+                    field: "init".to_string(),
+                    reason: "no init binary found; install catatonit or tini-static".to_string(),
+                })?;
+            Some(binary)
+        } else {
+            None
+        };
+
+        let devices = self.devices()?;
+        let mut linux = LinuxBuilder::default()
+            .namespaces(Self::namespaces()?)
+            .resources(self.resources(&devices)?);
+
+        if !devices.is_empty() {
+            linux = linux.devices(devices);
+        }
+        if !spec.sysctls.is_empty() {
+            linux = linux.sysctl(spec.sysctls.clone());
+        }
+
+        if !privileged {
+            linux = linux
+                .masked_paths(
+                    MASKED_PATHS
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>(),
+                )
+                .readonly_paths(
+                    READONLY_PATHS
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>(),
+                );
+        }
+
+        if let Some(path) = self.cgroups_path.as_ref() {
+            linux = linux.cgroups_path(path.clone());
+        }
+
+        let builder = SpecBuilder::default()
+            .version(OCI_VERSION)
+            .root(
+                RootBuilder::default()
+                    .path(self.rootfs.clone())
+                    .readonly(spec.read_only_rootfs)
+                    .build()
+                    .map_err(oci_error)?,
+            )
+            .process(self.process(init.is_some())?)
+            .mounts(self.mounts(init.as_deref())?)
+            .linux(linux.build().map_err(oci_error)?);
+
+        let mut oci = builder.build().map_err(oci_error)?;
+
+        // Unset builder fields fall back to `Spec::default()`, which carries
+        // a placeholder hostname and empty annotations; set both explicitly.
+        oci.set_hostname(Some(
+            spec.hostname
+                .as_deref()
+                .unwrap_or(DEFAULT_HOSTNAME)
+                .to_string(),
+        ));
+        let mut annotations = spec.labels.clone();
+        if let Some(signal) = spec.stop_signal.as_ref() {
+            annotations.insert(STOP_SIGNAL_ANNOTATION.to_string(), signal.clone());
+        }
+        oci.set_annotations((!annotations.is_empty()).then_some(annotations));
+        validate(&oci)?;
+        Ok(oci)
+    }
+
+    fn process(&self, init: bool) -> Result<oci_spec::runtime::Process> {
+        let spec = self.spec;
+
+        let mut args: Vec<String> = spec.command.iter().chain(&spec.args).cloned().collect();
+        if args.is_empty() {
+            args.push("/bin/sh".to_string());
+        }
+        if init {
+            args.splice(0..0, [INIT_PATH.to_string(), "--".to_string()]);
+        }
+
+        let mut env: Vec<String> = spec.env.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        if !spec.env.contains_key("PATH") {
+            env.push(format!("PATH={DEFAULT_PATH}"));
+        }
+        if spec.tty && !spec.env.contains_key("TERM") {
+            env.push("TERM=xterm".to_string());
+        }
+        env.sort();
+
+        let (uid, gid) = parse_user(spec.user.as_deref())?;
+        let mut user = UserBuilder::default().uid(uid).gid(gid);
+        if !spec.group_add.is_empty() {
+            user = user.additional_gids(parse_groups(&spec.group_add)?);
+        }
+
+        let caps = capability_set(&capability_names(spec))?;
+        let capabilities = LinuxCapabilitiesBuilder::default()
+            .bounding(caps.clone())
+            .effective(caps.clone())
+            .permitted(caps)
+            .inheritable(Capabilities::new())
+            .ambient(Capabilities::new())
+            .build()
+            .map_err(oci_error)?;
+
+        let mut rlimits = Vec::with_capacity(spec.ulimits.len() + 1);
+        if !spec.ulimits.iter().any(|u| u.name == "nofile") {
+            rlimits.push(
+                PosixRlimitBuilder::default()
+                    .typ(PosixRlimitType::RlimitNofile)
+                    .hard(DEFAULT_NOFILE)
+                    .soft(DEFAULT_NOFILE)
+                    .build()
+                    .map_err(oci_error)?,
+            );
+        }
+        for ulimit in &spec.ulimits {
+            let typ = serde_json::from_value::<PosixRlimitType>(serde_json::Value::String(
+                format!("RLIMIT_{}", ulimit.name.to_uppercase()),
+            ))
+            .map_err(|_| CoreError::InvalidSpec {
+                field: "ulimits".to_string(),
+                reason: format!("unknown resource {}", ulimit.name),
+            })?;
+            rlimits.push(
+                PosixRlimitBuilder::default()
+                    .typ(typ)
+                    .hard(ulimit.hard)
+                    .soft(ulimit.soft)
+                    .build()
+                    .map_err(oci_error)?,
+            );
+        }
+
+        ProcessBuilder::default()
+            .terminal(spec.tty)
+            .user(user.build().map_err(oci_error)?)
+            .args(args)
+            .env(env)
+            .cwd(
+                spec.working_dir
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from("/")),
+            )
+            .capabilities(capabilities)
+            .rlimits(rlimits)
+            .no_new_privileges(!spec.privileged)
+            .build()
+            .map_err(oci_error)
+    }
+
+    fn mounts(&self, init: Option<&Path>) -> Result<Vec<Mount>> {
+        let privileged = self.spec.privileged;
+        let sys_mode = if privileged { "rw" } else { "ro" };
+        let shm_size = format!(
+            "size={}",
+            self.spec
+                .shm_size
+                .map_or_else(|| DEFAULT_SHM_SIZE.to_string(), |bytes| bytes.to_string())
+        );
+
+        let mut mounts = vec![
+            mount("/proc", "proc", "proc", &[])?,
+            mount("/dev", "tmpfs", "tmpfs", &["nosuid", "strictatime", "mode=755", "size=65536k"])?,
+            mount(
+                "/dev/pts",
+                "devpts",
+                "devpts",
+                &[
+                    "nosuid",
+                    "noexec",
+                    "newinstance",
+                    "ptmxmode=0666",
+                    "mode=0620",
+                    "gid=5",
+                ],
+            )?,
+            mount(
+                "/dev/shm",
+                "tmpfs",
+                "shm",
+                &["nosuid", "noexec", "nodev", "mode=1777", &shm_size],
+            )?,
+            mount("/dev/mqueue", "mqueue", "mqueue", &["nosuid", "noexec", "nodev"])?,
+            mount("/sys", "sysfs", "sysfs", &["nosuid", "noexec", "nodev", sys_mode])?,
+            mount(
+                "/sys/fs/cgroup",
+                "cgroup",
+                "cgroup",
+                &["nosuid", "noexec", "nodev", "relatime", sys_mode],
+            )?,
+        ];
+
+        for m in &self.spec.mounts {
+            let target = m.target.to_string_lossy();
+            let mode = if m.read_only { "ro" } else { "rw" };
+            let oci_mount = match m.mount_type {
+                MountType::Bind | MountType::Volume => {
+                    if !m.source.is_absolute() {
+                        return Err(CoreError::InvalidSpec {
+                            field: "mounts".to_string(),
+                            reason: format!("bind source {} must be absolute", m.source.display()),
+                        });
+                    }
+                    mount(
+                        &target,
+                        "bind",
+                        &m.source.to_string_lossy(),
+                        &["rbind", "rprivate", mode],
+                    )?
+                }
+                MountType::Tmpfs => {
+                    mount(&target, "tmpfs", "tmpfs", &["nosuid", "nodev", "mode=1777", mode])?
+                }
+            };
+            mounts.push(oci_mount);
+        }
+
+        if let Some(init) = init {
+            mounts.push(mount(
+                INIT_PATH,
+                "bind",
+                &init.to_string_lossy(),
+                &["bind", "ro", "nosuid", "nodev"],
+            )?);
+        }
+
+        Ok(mounts)
+    }
+
+    /// Device nodes for the spec's host devices, in the same order.
+    fn devices(&self) -> Result<Vec<LinuxDevice>> {
+        self.spec
+            .devices
+            .iter()
+            .map(|device| {
+                let (typ, major, minor, mode) = host_device(&device.host_path)?;
+                LinuxDeviceBuilder::default()
+                    .path(device.container_path.clone())
+                    .typ(typ)
+                    .major(major)
+                    .minor(minor)
+                    .file_mode(mode)
+                    .build()
+                    .map_err(oci_error)
+            })
+            .collect()
+    }
+
+    fn namespaces() -> Result<Vec<LinuxNamespace>> {
+        [
+            LinuxNamespaceType::Pid,
+            LinuxNamespaceType::Network,
+            LinuxNamespaceType::Ipc,
+            LinuxNamespaceType::Uts,
+            LinuxNamespaceType::Mount,
+            LinuxNamespaceType::Cgroup,
+        ]
+        .into_iter()
+        .map(|typ| {
+            LinuxNamespaceBuilder::default()
+                .typ(typ)
+                .build()
+                .map_err(oci_error)
+        })
+        .collect()
+    }
+
+    fn resources(&self, devices: &[LinuxDevice]) -> Result<LinuxResources> {
+        let limits = &self.spec.resources;
+        let mut builder = linux_resources_builder(limits)?;
+        if let Some(block_io) = linux_block_io(limits, &throttled_devices(limits, &self.rootfs)?)? {
+            builder = builder.block_io(block_io);
+        }
+
+        let mut rules = vec![LinuxDeviceCgroupBuilder::default()
+            .allow(self.spec.privileged)
+            .access("rwm")
+            .build()
+            .map_err(oci_error)?];
+        for (device, mapping) in devices.iter().zip(&self.spec.devices) {
+            rules.push(
+                LinuxDeviceCgroupBuilder::default()
+                    .allow(true)
+                    .typ(device.typ())
+                    .major(device.major())
+                    .minor(device.minor())
+                    .access(mapping.permissions.clone())
+                    .build()
+                    .map_err(oci_error)?,
+            );
+        }
+        builder = builder.devices(rules);
+
+        builder.build().map_err(oci_error)
+    }
+}
+
+/// Convert resource limits to OCI `linux.resources`.
+///
+/// # Errors
+///
+/// Returns [`CoreError::InvalidSpec`] if a limit is out of range.
+pub fn linux_resources(limits: &ResourceLimits) -> Result<LinuxResources> {
+    linux_resources_builder(limits)?.build().map_err(oci_error)
+}
+
+fn linux_resources_builder(limits: &ResourceLimits) -> Result<LinuxResourcesBuilder> {
+    let mut builder = LinuxResourcesBuilder::default();
+
+    if limits.memory_bytes.is_some() || limits.memory_swap_bytes.is_some() {
+        let mut mem = LinuxMemoryBuilder::default();
+        if let Some(memory) = limits.memory_bytes {
+            mem = mem.limit(to_i64("memory_bytes", memory)?);
+        }
+        if let Some(swap) = limits.memory_swap_bytes {
+            mem = mem.swap(to_i64("memory_swap_bytes", swap)?);
+        }
+        builder = builder.memory(mem.build().map_err(oci_error)?);
+    }
+
+    if limits.cpu_millicores.is_some()
+        || limits.cpu_weight.is_some()
+        || limits.cpuset_cpus.is_some()
+    {
+        let mut cpu = LinuxCpuBuilder::default();
+        if let Some(millicores) = limits.cpu_millicores {
+            cpu = cpu.quota(cpu_quota(millicores)?).period(CPU_PERIOD_US);
+        }
+        if let Some(weight) = limits.cpu_weight {
+            cpu = cpu.shares(cpu_shares(weight)?);
+        }
+        if let Some(cpus) = &limits.cpuset_cpus {
+            cpu = cpu.cpus(cpus.clone());
+        }
+        builder = builder.cpu(cpu.build().map_err(oci_error)?);
+    }
+
+    if let Some(pids) = limits.pids_limit {
+        let pids = LinuxPidsBuilder::default()
+            .limit(to_i64("pids_limit", pids)?)
+            .build()
+            .map_err(oci_error)?;
+        builder = builder.pids(pids);
+    }
+
+    Ok(builder)
+}
+
+/// OCI resources for a live update of a container rooted at `rootfs`.
+///
+/// Only limits set in `limits` are included. IO throttles are reported as
+/// unsupported when no disk backs `rootfs`.
+///
+/// # Errors
+///
+/// Returns [`CoreError::InvalidSpec`] if a limit is out of range, or
+/// [`CoreError::CgroupOperation`] if the mount table cannot be read.
+pub fn update_resources(
+    limits: &ResourceLimits,
+    rootfs: &Path,
+) -> Result<(LinuxResources, ResourceUpdateReport)> {
+    let devices = throttled_devices(limits, rootfs)?;
+    let unsupported = if limits.has_io_throttles() && devices.is_empty() {
+        ResourceLimits::IO_THROTTLE_FIELDS
+    } else {
+        &[]
+    };
+
+    let mut builder = linux_resources_builder(limits)?;
+    if let Some(block_io) = linux_block_io(limits, &devices)? {
+        builder = builder.block_io(block_io);
+    }
+    let resources = builder.build().map_err(oci_error)?;
+    Ok((resources, ResourceUpdateReport::new(limits, unsupported)))
+}
+
+/// The disks backing `rootfs`, if `limits` throttles IO.
+fn throttled_devices(limits: &ResourceLimits, rootfs: &Path) -> Result<Vec<BlockDevice>> {
+    if limits.has_io_throttles() {
+        BlockDeviceResolver::new().resolve(rootfs)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Convert the IO limits to OCI `linux.resources.blockIO`.
+///
+/// Throttles are set on each of `devices`; the weight is scaled from
+/// `io.weight` to the blkio range runtimes expect.
+fn linux_block_io(
+    limits: &ResourceLimits,
+    devices: &[BlockDevice],
+) -> Result<Option<LinuxBlockIo>> {
+    if limits.io_weight.is_none() && !limits.has_io_throttles() {
+        return Ok(None);
+    }
+
+    let mut builder = LinuxBlockIoBuilder::default();
+    if let Some(weight) = limits.io_weight {
+        builder = builder.weight(blkio_weight(weight)?);
+    }
+
+    if !devices.is_empty() {
+        let throttle = |rate: u64| -> Result<Vec<LinuxThrottleDevice>> {
+            devices
+                .iter()
+                .map(|device| {
+                    LinuxThrottleDeviceBuilder::default()
+                        .major(i64::from(device.major))
+                        .minor(i64::from(device.minor))
+                        .rate(rate)
+                        .build()
+                        .map_err(oci_error)
+                })
+                .collect()
+        };
+
+        if let Some(rate) = limits.io_read_bps {
+            builder = builder.throttle_read_bps_device(throttle(rate)?);
+        }
+        if let Some(rate) = limits.io_write_bps {
+            builder = builder.throttle_write_bps_device(throttle(rate)?);
+        }
+        if let Some(rate) = limits.io_read_iops {
+            builder = builder.throttle_read_iops_device(throttle(rate)?);
+        }
+        if let Some(rate) = limits.io_write_iops {
+            builder = builder.throttle_write_iops_device(throttle(rate)?);
+        }
+    }
+
+    builder.build().map(Some).map_err(oci_error)
+}
+
+/// Scale a cgroup v2 `io.weight` (1-10000) to the blkio weight (10-1000).
+///
+/// Runtimes apply the inverse when writing `io.weight`.
+///
+/// # Errors
+///
+/// Returns [`CoreError::InvalidSpec`] if `weight` is outside 1-10000.
+pub fn blkio_weight(weight: u16) -> Result<u16> {
+    if !IO_WEIGHT_RANGE.contains(&weight) {
+        return Err(CoreError::InvalidSpec {
+            field: "io_weight".to_string(),
+            reason: format!(
+                "{weight} is outside {}-{}",
+                IO_WEIGHT_RANGE.start(),
+                IO_WEIGHT_RANGE.end()
+            ),
+        });
+    }
+
+    let blkio_span = u32::from(BLKIO_WEIGHT_RANGE.end() - BLKIO_WEIGHT_RANGE.start());
+    let io_span = u32::from(IO_WEIGHT_RANGE.end() - IO_WEIGHT_RANGE.start());
+    let offset = u32::from(weight - IO_WEIGHT_RANGE.start()) * blkio_span / io_span;
+    Ok(BLKIO_WEIGHT_RANGE.start() + u16::try_from(offset).unwrap_or(0))
+}
+
+/// Convert a cgroup v2 `cpu.weight` (1-10000) to OCI CPU shares (2-262144).
+///
+/// This inverts the conversion runtimes apply when writing `cpu.weight`, so
+/// the weight survives the round trip exactly.
+///
+/// # Errors
+///
+/// Returns [`CoreError::InvalidSpec`] if `weight` is outside 1-10000.
+pub fn cpu_shares(weight: u16) -> Result<u64> {
+    if !CPU_WEIGHT_RANGE.contains(&weight) {
+        return Err(CoreError::InvalidSpec {
+            field: "cpu_weight".to_string(),
+            reason: format!(
+                "{weight} is outside {}-{}",
+                CPU_WEIGHT_RANGE.start(),
+                CPU_WEIGHT_RANGE.end()
+            ),
+        });
+    }
+
+    let shares_span = CPU_SHARES_RANGE.end() - CPU_SHARES_RANGE.start();
+    let weight_span = u64::from(CPU_WEIGHT_RANGE.end() - CPU_WEIGHT_RANGE.start());
+    let offset = u64::from(weight - CPU_WEIGHT_RANGE.start()) * shares_span;
+    Ok(CPU_SHARES_RANGE.start() + offset.div_ceil(weight_span))
+}
+
+/// CFS quota per [`CPU_PERIOD_US`] for a CPU limit in millicores.
+///
+/// # Errors
+///
+/// Returns [`CoreError::InvalidSpec`] if the quota overflows or is below
+/// the kernel minimum of 1ms (10 millicores).
+pub fn cpu_quota(millicores: u64) -> Result<i64> {
+    let invalid = |reason: String| CoreError::InvalidSpec {
+        field: "cpu_millicores".to_string(),
+        reason,
+    };
+    let quota = millicores
+        .checked_mul(CPU_PERIOD_US)
+        .map(|quota| quota / 1000)
+        .ok_or_else(|| invalid(format!("{millicores} is out of range")))?;
+    if quota < MIN_CPU_QUOTA_US {
+        return Err(invalid(format!(
+            "{millicores} is below the minimum of {} millicores",
+            MIN_CPU_QUOTA_US * 1000 / CPU_PERIOD_US
+        )));
+    }
+    i64::try_from(quota).map_err(|_| invalid(format!("{millicores} is out of range")))
+}
+
+/// Convert a HyperBox seccomp profile to the OCI representation.
+///
+/// # Errors
+///
+/// Returns [`CoreError::InvalidSpec`] for unknown architectures.
+pub fn seccomp_to_oci(profile: &SeccompProfile) -> Result<LinuxSeccomp> {
+    let architectures = profile
+        .architectures
+        .iter()
+        .map(|arch| {
+            serde_json::from_value(serde_json::Value::String(arch.clone())).map_err(|_| {
+                CoreError::InvalidSpec {
+                    field: "seccomp.architectures".to_string(),
+                    reason: format!("unknown architecture {arch}"),
+                }
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let syscalls = profile
+        .syscalls
+        .iter()
+        .map(|rule| {
+            let args = rule
+                .args
+                .iter()
+                .map(|arg| {
+                    LinuxSeccompArgBuilder::default()
+                        .index(arg.index as usize)
+                        .value(arg.value)
+                        .value_two(arg.value_two.unwrap_or(0))
+                        .op(seccomp_operator(arg.op))
+                        .build()
+                        .map_err(oci_error)
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let mut syscall = LinuxSyscallBuilder::default()
+                .names(rule.names.clone())
+                .action(seccomp_action(rule.action));
+            if let Some(errno) = rule.errno_ret {
+                syscall = syscall.errno_ret(errno);
+            }
+            if !args.is_empty() {
+                syscall = syscall.args(args);
+            }
+            syscall.build().map_err(oci_error)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    LinuxSeccompBuilder::default()
+        .default_action(seccomp_action(profile.default_action))
+        .architectures(architectures)
+        .syscalls(syscalls)
+        .build()
+        .map_err(oci_error)
+}
+
+/// Check a spec against the rules of the OCI runtime specification.
+///
+/// # Errors
+///
+/// Returns [`CoreError::InvalidSpec`] naming the first offending field.
+pub fn validate(spec: &Spec) -> Result<()> {
+    let invalid = |field: &str, reason: String| CoreError::InvalidSpec {
+        field: field.to_string(),
+        reason,
+    };
+
+    if !spec.version().starts_with("1.") {
+        return Err(invalid("ociVersion", format!("unsupported version {}", spec.version())));
+    }
+
+    let root = spec
+        .root()
+        .as_ref()
+        .ok_or_else(|| invalid("root", "missing".to_string()))?;
+    if root.path().as_os_str().is_empty() {
+        return Err(invalid("root.path", "must not be empty".to_string()));
+    }
+
+    if let Some(process) = spec.process() {
+        if process.args().as_ref().map_or(true, Vec::is_empty) {
+            return Err(invalid("process.args", "must not be empty".to_string()));
+        }
+        if !process.cwd().is_absolute() {
+            return Err(invalid(
+                "process.cwd",
+                format!("{} is not absolute", process.cwd().display()),
+            ));
+        }
+        for entry in process.env().iter().flatten() {
+            if !entry.contains('=') {
+                return Err(invalid("process.env", format!("{entry} is not KEY=VALUE")));
+            }
+        }
+        if let Some(rlimits) = process.rlimits() {
+            let mut seen = HashSet::new();
+            for rlimit in rlimits {
+                if !seen.insert(rlimit.typ().to_string()) {
+                    return Err(invalid("process.rlimits", format!("duplicate {}", rlimit.typ())));
+                }
+                if rlimit.soft() > rlimit.hard() {
+                    return Err(invalid(
+                        "process.rlimits",
+                        format!("{} soft > hard", rlimit.typ()),
+                    ));
+                }
+            }
+        }
+    }
+
+    for m in spec.mounts().iter().flatten() {
+        if !m.destination().is_absolute() {
+            return Err(invalid(
+                "mounts",
+                format!("{} is not absolute", m.destination().display()),
+            ));
+        }
+    }
+
+    if let Some(linux) = spec.linux() {
+        let namespaces = linux.namespaces().clone().unwrap_or_default();
+        let mut seen = HashSet::new();
+        for ns in &namespaces {
+            if !seen.insert(ns.typ().to_string()) {
+                return Err(invalid(
+                    "linux.namespaces",
+                    format!("duplicate {} namespace", ns.typ()),
+                ));
+            }
+        }
+        let has = |typ| namespaces.iter().any(|ns| ns.typ() == typ);
+
+        if spec.hostname().is_some() && !has(LinuxNamespaceType::Uts) {
+            return Err(invalid("hostname", "requires a UTS namespace".to_string()));
+        }
+        for key in linux.sysctl().iter().flat_map(HashMap::keys) {
+            match sysctl_namespace(key) {
+                Some(typ) if has(typ) => {}
+                Some(typ) => {
+                    return Err(invalid(
+                        "linux.sysctl",
+                        format!("{key} requires a {typ} namespace"),
+                    ))
+                }
+                None => return Err(invalid("linux.sysctl", format!("{key} is not namespaced"))),
+            }
+        }
+        if has(LinuxNamespaceType::User)
+            && (linux.uid_mappings().as_ref().map_or(true, Vec::is_empty)
+                || linux.gid_mappings().as_ref().map_or(true, Vec::is_empty))
+        {
+            return Err(invalid(
+                "linux.uidMappings",
+                "user namespace requires id mappings".to_string(),
+            ));
+        }
+
+        if let Some(resources) = linux.resources() {
+            if let Some(cpu) = resources.cpu() {
+                if cpu.quota().is_some_and(|q| q > 0) && cpu.period().map_or(true, |p| p == 0) {
+                    return Err(invalid(
+                        "linux.resources.cpu",
+                        "quota requires a period".to_string(),
+                    ));
+                }
+            }
+            if let Some(memory) = resources.memory() {
+                if let (Some(limit), Some(swap)) = (memory.limit(), memory.swap()) {
+                    if swap >= 0 && swap < limit {
+                        return Err(invalid(
+                            "linux.resources.memory.swap",
+                            "memory+swap must not be lower than the memory limit".to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a numeric `uid[:gid]` user string.
+fn parse_user(user: Option<&str>) -> Result<(u32, u32)> {
+    let Some(user) = user.filter(|u| !u.is_empty()) else {
+        return Ok((0, 0));
+    };
+
+    let (uid, gid) = user.split_once(':').unwrap_or((user, "0"));
+    match (uid.parse(), gid.parse()) {
+        (Ok(uid), Ok(gid)) => Ok((uid, gid)),
+        _ => Err(CoreError::InvalidSpec {
+            field: "user".to_string(),
+            reason: format!(
+                "{user} must be numeric uid[:gid]; resolve names against the rootfs first"
+            ),
+        }),
+    }
+}
+
+/// Parse numeric supplementary group ids.
+fn parse_groups(groups: &[String]) -> Result<Vec<u32>> {
+    groups
+        .iter()
+        .map(|group| {
+            group.parse().map_err(|_| CoreError::InvalidSpec {
+                field: "group_add".to_string(),
+                reason: format!(
+                    "{group} must be a numeric gid; resolve names against the rootfs first"
+                ),
+            })
+        })
+        .collect()
+}
+
+/// The namespace a sysctl belongs to, or `None` if it is not namespaced.
+fn sysctl_namespace(key: &str) -> Option<LinuxNamespaceType> {
+    const IPC: &[&str] = &[
+        "kernel.msgmax",
+        "kernel.msgmnb",
+        "kernel.msgmni",
+        "kernel.sem",
+        "kernel.shmall",
+        "kernel.shmmax",
+        "kernel.shmmni",
+        "kernel.shm_rmid_forced",
+    ];
+
+    if key.starts_with("net.") {
+        Some(LinuxNamespaceType::Network)
+    } else if IPC.contains(&key) || key.starts_with("fs.mqueue.") {
+        Some(LinuxNamespaceType::Ipc)
+    } else if key == "kernel.hostname" || key == "kernel.domainname" {
+        Some(LinuxNamespaceType::Uts)
+    } else {
+        None
+    }
+}
+
+/// Capabilities of the container process, with `cap_add` and `cap_drop`
+/// applied as Docker does: `ALL` in `cap_drop` leaves only the added
+/// capabilities, `ALL` in `cap_add` grants every capability not dropped.
+fn capability_names(spec: &ContainerSpec) -> Vec<String> {
+    let normalize = |names: &[String]| -> Vec<String> {
+        names
+            .iter()
+            .map(|name| {
+                let name = name.to_uppercase();
+                if name == "ALL" || name.starts_with("CAP_") {
+                    name
+                } else {
+                    format!("CAP_{name}")
+                }
+            })
+            .collect()
+    };
+    let add = normalize(&spec.cap_add);
+    let drop = normalize(&spec.cap_drop);
+
+    let base = if spec.privileged || add.iter().any(|c| c == "ALL") {
+        ALL_CAPABILITIES
+    } else {
+        DEFAULT_CAPABILITIES
+    };
+    let mut caps: Vec<String> = if drop.iter().any(|c| c == "ALL") {
+        Vec::new()
+    } else {
+        base.iter()
+            .map(ToString::to_string)
+            .filter(|cap| !drop.contains(cap))
+            .collect()
+    };
+    for cap in add {
+        if cap != "ALL" && !caps.contains(&cap) {
+            caps.push(cap);
+        }
+    }
+    caps
+}
+
+fn capability_set(names: &[impl AsRef<str>]) -> Result<Capabilities> {
+    names
+        .iter()
+        .map(|name| {
+            let name = name.as_ref();
+            serde_json::from_value::<Capability>(serde_json::Value::String(name.to_string()))
+                .map_err(|_| CoreError::InvalidSpec {
+                    field: "capabilities".to_string(),
+                    reason: format!("unknown capability {name}"),
+                })
+        })
+        .collect()
+}
+
+/// Type, major and minor number and permission bits of a host device.
+#[cfg(target_os = "linux")]
+fn host_device(path: &Path) -> Result<(LinuxDeviceType, i64, i64, u32)> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let invalid = |reason: String| CoreError::InvalidSpec {
+        field: "devices".to_string(),
+        reason,
+    };
+    let metadata =
+        std::fs::metadata(path).map_err(|e| invalid(format!("{}: {e}", path.display())))?;
+    let typ = if metadata.file_type().is_char_device() {
+        LinuxDeviceType::C
+    } else if metadata.file_type().is_block_device() {
+        LinuxDeviceType::B
+    } else {
+        return Err(invalid(format!("{} is not a device", path.display())));
+    };
+
+    let rdev = metadata.rdev();
+    Ok((
+        typ,
+        to_i64("devices", nix::sys::stat::major(rdev))?,
+        to_i64("devices", nix::sys::stat::minor(rdev))?,
+        metadata.mode() & 0o7777,
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn host_device(path: &Path) -> Result<(LinuxDeviceType, i64, i64, u32)> {
+    Err(CoreError::InvalidSpec {
+        field: "devices".to_string(),
+        reason: format!("{}: host devices require a Linux host", path.display()),
+    })
+}
+
+fn find_init_binary() -> Option<PathBuf> {
+    INIT_BINARIES
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.is_file())
+}
+
+fn mount(destination: &str, typ: &str, source: &str, options: &[&str]) -> Result<Mount> {
+    let mut builder = MountBuilder::default()
+        .destination(destination)
+        .typ(typ)
+        .source(source);
+    if !options.is_empty() {
+        builder = builder.options(options.iter().map(ToString::to_string).collect::<Vec<_>>());
+    }
+    builder.build().map_err(oci_error)
+}
+
+const fn seccomp_action(action: SeccompAction) -> LinuxSeccompAction {
+    match action {
+        SeccompAction::Allow => LinuxSeccompAction::ScmpActAllow,
+        SeccompAction::Errno => LinuxSeccompAction::ScmpActErrno,
+        SeccompAction::Kill => LinuxSeccompAction::ScmpActKill,
+        SeccompAction::KillProcess => LinuxSeccompAction::ScmpActKillProcess,
+        SeccompAction::Trap => LinuxSeccompAction::ScmpActTrap,
+        SeccompAction::Log => LinuxSeccompAction::ScmpActLog,
+        SeccompAction::Trace => LinuxSeccompAction::ScmpActTrace,
+    }
+}
+
+const fn seccomp_operator(op: SeccompOperator) -> LinuxSeccompOperator {
+    match op {
+        SeccompOperator::NotEqual => LinuxSeccompOperator::ScmpCmpNe,
+        SeccompOperator::LessThan => LinuxSeccompOperator::ScmpCmpLt,
+        SeccompOperator::LessOrEqual => LinuxSeccompOperator::ScmpCmpLe,
+        SeccompOperator::EqualTo => LinuxSeccompOperator::ScmpCmpEq,
+        SeccompOperator::GreaterOrEqual => LinuxSeccompOperator::ScmpCmpGe,
+        SeccompOperator::GreaterThan => LinuxSeccompOperator::ScmpCmpGt,
+        SeccompOperator::MaskedEqual => LinuxSeccompOperator::ScmpCmpMaskedEq,
+    }
+}
+
+fn to_i64(field: &str, value: u64) -> Result<i64> {
+    i64::try_from(value).map_err(|_| CoreError::InvalidSpec {
+        field: field.to_string(),
+        reason: format!("{value} is out of range"),
+    })
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn oci_error(e: oci_spec::OciSpecError) -> CoreError {
+    CoreError::InvalidSpec {
+        field: "oci".to_string(),
+        reason: e.to_string(),
+    }
+}
+
+/// The signal that stops a container, from its [`STOP_SIGNAL_ANNOTATION`].
+#[must_use]
+pub fn stop_signal(spec: &Spec) -> &str {
+    spec.annotations()
+        .as_ref()
+        .and_then(|annotations| annotations.get(STOP_SIGNAL_ANNOTATION))
+        .map_or(DEFAULT_STOP_SIGNAL, String::as_str)
+}
+
+/// Write a spec as `config.json` into a bundle directory.
+///
+/// # Errors
+///
+/// Returns error if the file cannot be written.
+pub async fn write_config(bundle: &Path, spec: &Spec) -> Result<()> {
+    let json = serde_json::to_string_pretty(spec)?;
+    tokio::fs::write(bundle.join("config.json"), json).await?;
+    Ok(())
+}
+
+/// Read the `config.json` of a bundle directory.
+///
+/// # Errors
+///
+/// Returns error if the file cannot be read or is not a valid spec.
+pub async fn read_config(bundle: &Path) -> Result<Spec> {
+    let json = tokio::fs::read(bundle.join("config.json")).await?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Mount as SpecMount;
+
+    /// Set to regenerate the golden files instead of comparing against them.
+    const UPDATE_ENV: &str = "HYPERBOX_UPDATE_GOLDEN";
+
+    const ROOTFS: &str = "/var/lib/hyperbox/runtime/storage/layers/merged/abc";
+
+    fn golden_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/oci")
+    }
+
+    /// Sort capability sets, which serialize in hash order.
+    fn normalize(mut value: serde_json::Value) -> serde_json::Value {
+        if let Some(caps) = value
+            .pointer_mut("/process/capabilities")
+            .and_then(|c| c.as_object_mut())
+        {
+            for set in caps.values_mut() {
+                if let Some(list) = set.as_array_mut() {
+                    list.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+                }
+            }
+        }
+        value
+    }
+
+    fn assert_golden(name: &str, oci: &Spec) {
+        let actual = normalize(serde_json::to_value(oci).unwrap());
+        let path = golden_dir().join(format!("{name}.json"));
+
+        if std::env::var_os(UPDATE_ENV).is_some() {
+            std::fs::create_dir_all(golden_dir()).unwrap();
+            let mut json = serde_json::to_string_pretty(&actual).unwrap();
+            json.push('\n');
+            std::fs::write(&path, json).unwrap();
+            return;
+        }
+
+        let expected: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("{}: {e} (run with {UPDATE_ENV}=1)", path.display())),
+        )
+        .unwrap();
+        assert_eq!(actual, expected, "{name} differs from {}", path.display());
+    }
+
+    fn base_spec() -> ContainerSpec {
+        ContainerSpec::builder()
+            .image("alpine:latest")
+            .command(vec!["/bin/echo", "hello"])
+            .env("PATH", "/usr/bin:/bin")
+            .build()
+    }
+
+    fn build(spec: &ContainerSpec) -> Spec {
+        OciSpecBuilder::new(spec).rootfs(ROOTFS).build().unwrap()
+    }
+
+    #[test]
+    fn golden_minimal() {
+        assert_golden("minimal", &build(&base_spec()));
+    }
+
+    #[test]
+    fn golden_user() {
+        let mut spec = base_spec();
+        spec.user = Some("1000:100".to_string());
+        assert_golden("user", &build(&spec));
+    }
+
+    #[test]
+    fn golden_mounts() {
+        let spec = base_spec();
+        let spec = ContainerSpec {
+            mounts: vec![
+                SpecMount {
+                    source: PathBuf::from("/srv/data"),
+                    target: PathBuf::from("/data"),
+                    read_only: true,
+                    mount_type: MountType::Bind,
+                },
+                SpecMount {
+                    source: PathBuf::from("/var/lib/hyperbox/volumes/cache"),
+                    target: PathBuf::from("/cache"),
+                    read_only: false,
+                    mount_type: MountType::Volume,
+                },
+                SpecMount {
+                    source: PathBuf::new(),
+                    target: PathBuf::from("/tmp"),
+                    read_only: false,
+                    mount_type: MountType::Tmpfs,
+                },
+            ],
+            ..spec
+        };
+        assert_golden("mounts", &build(&spec));
+    }
+
+    #[test]
+    fn golden_read_only_rootfs() {
+        let mut spec = base_spec();
+        spec.read_only_rootfs = true;
+        assert_golden("read_only_rootfs", &build(&spec));
+    }
+
+    #[test]
+    fn golden_privileged() {
+        let mut spec = base_spec();
+        spec.privileged = true;
+        assert_golden("privileged", &build(&spec));
+    }
+
+    #[test]
+    fn golden_hostname() {
+        let mut spec = base_spec();
+        spec.hostname = Some("web-1".to_string());
+        spec.tty = true;
+        assert_golden("hostname", &build(&spec));
+    }
+
+    #[test]
+    fn golden_resources() {
+        let spec = ContainerSpec {
+            resources: ResourceLimits {
+                cpu_millicores: Some(1500),
+                memory_bytes: Some(512 * 1024 * 1024),
+                memory_swap_bytes: Some(1024 * 1024 * 1024),
+                pids_limit: Some(256),
+                ..ResourceLimits::default()
+            },
+            ..base_spec()
+        };
+        assert_golden("resources", &build(&spec));
+    }
+
+    #[test]
+    fn golden_extended() {
+        let spec = ContainerSpec::builder()
+            .image("postgres:16")
+            .command(vec!["postgres"])
+            .env("PATH", "/usr/bin:/bin")
+            .cap_add("SYS_NICE")
+            .cap_drop("cap_mknod")
+            .ulimit("nofile=4096:8192".parse().unwrap())
+            .ulimit("memlock=-1".parse().unwrap())
+            .sysctl("net.core.somaxconn", "1024")
+            .shm_size(256 * 1024 * 1024)
+            .group_add("10")
+            .stop_signal("SIGINT")
+            .init(true)
+            .build();
+        let oci = OciSpecBuilder::new(&spec)
+            .rootfs(ROOTFS)
+            .init_binary("/usr/bin/catatonit")
+            .build()
+            .unwrap();
+        assert_golden("extended", &oci);
+    }
+
+    #[test]
+    fn test_non_numeric_user_is_rejected() {
+        let mut spec = base_spec();
+        spec.user = Some("nginx".to_string());
+        let err = OciSpecBuilder::new(&spec).build().unwrap_err();
+        assert!(matches!(err, CoreError::InvalidSpec { ref field, .. } if field == "user"));
+    }
+
+    #[test]
+    fn test_relative_bind_source_is_rejected() {
+        let spec = base_spec();
+        let spec = ContainerSpec {
+            mounts: vec![SpecMount {
+                source: PathBuf::from("data"),
+                target: PathBuf::from("/data"),
+                read_only: false,
+                mount_type: MountType::Bind,
+            }],
+            ..spec
+        };
+        assert!(OciSpecBuilder::new(&spec).build().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_relative_cwd() {
+        let mut spec = base_spec();
+        spec.working_dir = Some(PathBuf::from("app"));
+        let err = OciSpecBuilder::new(&spec).build().unwrap_err();
+        assert!(matches!(err, CoreError::InvalidSpec { ref field, .. } if field == "process.cwd"));
+    }
+
+    #[test]
+    fn test_validate_rejects_swap_below_memory() {
+        let spec = ContainerSpec {
+            resources: ResourceLimits {
+                memory_bytes: Some(1024),
+                memory_swap_bytes: Some(512),
+                ..ResourceLimits::default()
+            },
+            ..base_spec()
+        };
+        assert!(OciSpecBuilder::new(&spec).build().is_err());
+    }
+
+    #[test]
+    fn test_io_weight_scales_to_blkio() {
+        assert_eq!(blkio_weight(1).unwrap(), 10);
+        assert_eq!(blkio_weight(100).unwrap(), 19);
+        assert_eq!(blkio_weight(10_000).unwrap(), 1000);
+        assert!(blkio_weight(0).is_err());
+
+        let spec = ContainerSpec {
+            resources: ResourceLimits {
+                io_weight: Some(10_000),
+                ..ResourceLimits::default()
+            },
+            ..base_spec()
+        };
+        let oci = build(&spec);
+        let resources = oci.linux().as_ref().unwrap().resources().as_ref().unwrap();
+        let block_io = resources.block_io().as_ref().unwrap();
+        assert_eq!(block_io.weight(), Some(1000));
+        assert!(block_io.throttle_read_bps_device().is_none());
+    }
+
+    #[test]
+    fn test_cpu_quota_bounds() {
+        assert_eq!(cpu_quota(10).unwrap(), 1_000);
+        assert_eq!(cpu_quota(1500).unwrap(), 150_000);
+        assert!(matches!(cpu_quota(9), Err(CoreError::InvalidSpec { .. })));
+        assert!(matches!(cpu_quota(u64::MAX), Err(CoreError::InvalidSpec { .. })));
+
+        let limits = ResourceLimits {
+            cpu_millicores: Some(u64::MAX / 1000),
+            ..Default::default()
+        };
+        assert!(linux_resources(&limits).is_err());
+    }
+
+    #[test]
+    fn test_cpu_weight_round_trips_through_shares() {
+        // The conversion runtimes use to write cpu.weight from shares.
+        let to_weight = |shares: u64| 1 + (shares - 2) * 9999 / 262_142;
+        for weight in [1, 2, 39, 100, 1024, 9_999, 10_000] {
+            assert_eq!(to_weight(cpu_shares(weight).unwrap()), u64::from(weight));
+        }
+        assert_eq!(cpu_shares(1).unwrap(), 2);
+        assert_eq!(cpu_shares(10_000).unwrap(), 262_144);
+        assert!(cpu_shares(0).is_err());
+
+        let spec = ContainerSpec {
+            resources: ResourceLimits {
+                cpu_millicores: Some(500),
+                cpuset_cpus: Some("0-1".to_string()),
+                ..ResourceLimits::default()
+            },
+            ..base_spec()
+        };
+        let oci = build(&spec);
+        let resources = oci.linux().as_ref().unwrap().resources().as_ref().unwrap();
+        let cpu = resources.cpu().as_ref().unwrap();
+        assert_eq!((cpu.quota(), cpu.period()), (Some(50_000), Some(CPU_PERIOD_US)));
+        assert_eq!(cpu.shares(), None);
+        assert_eq!(cpu.cpus().as_deref(), Some("0-1"));
+    }
+
+    #[test]
+    fn test_default_capabilities_are_known() {
+        assert_eq!(capability_set(DEFAULT_CAPABILITIES).unwrap().len(), DEFAULT_CAPABILITIES.len());
+        assert_eq!(capability_set(ALL_CAPABILITIES).unwrap().len(), ALL_CAPABILITIES.len());
+    }
+
+    #[test]
+    fn test_capability_add_and_drop() {
+        let mut spec = base_spec();
+        spec.cap_drop = vec!["ALL".to_string()];
+        spec.cap_add = vec!["net_bind_service".to_string(), "CAP_CHOWN".to_string()];
+        let mut caps = capability_names(&spec);
+        caps.sort();
+        assert_eq!(caps, vec!["CAP_CHOWN", "CAP_NET_BIND_SERVICE"]);
+
+        spec.cap_drop = vec!["NET_RAW".to_string()];
+        spec.cap_add = vec!["ALL".to_string()];
+        let caps = capability_names(&spec);
+        assert_eq!(caps.len(), ALL_CAPABILITIES.len() - 1);
+        assert!(!caps.iter().any(|c| c == "CAP_NET_RAW"));
+
+        spec.cap_add = vec!["NOT_A_CAP".to_string()];
+        let err = OciSpecBuilder::new(&spec).build().unwrap_err();
+        assert!(matches!(err, CoreError::InvalidSpec { ref field, .. } if field == "capabilities"));
+    }
+
+    #[test]
+    fn test_ulimit_replaces_default_nofile() {
+        let spec = ContainerSpec {
+            ulimits: vec!["nofile=65536".parse().unwrap()],
+            ..base_spec()
+        };
+        let oci = build(&spec);
+        let rlimits = oci.process().as_ref().unwrap().rlimits().clone().unwrap();
+        assert_eq!(rlimits.len(), 1);
+        assert_eq!(rlimits[0].soft(), 65536);
+
+        let spec = ContainerSpec {
+            ulimits: vec!["bogus=1".parse().unwrap()],
+            ..base_spec()
+        };
+        let err = OciSpecBuilder::new(&spec).build().unwrap_err();
+        assert!(matches!(err, CoreError::InvalidSpec { ref field, .. } if field == "ulimits"));
+    }
+
+    #[test]
+    fn test_sysctl_must_be_namespaced() {
+        let spec = ContainerSpec::builder()
+            .command(vec!["/bin/true"])
+            .sysctl("kernel.panic", "1")
+            .build();
+        let err = OciSpecBuilder::new(&spec).build().unwrap_err();
+        assert!(matches!(err, CoreError::InvalidSpec { ref field, .. } if field == "linux.sysctl"));
+
+        let spec = ContainerSpec::builder()
+            .command(vec!["/bin/true"])
+            .sysctl("kernel.shmmax", "68719476736")
+            .build();
+        assert!(OciSpecBuilder::new(&spec).build().is_ok());
+    }
+
+    #[test]
+    fn test_non_numeric_group_is_rejected() {
+        let mut spec = base_spec();
+        spec.group_add = vec!["video".to_string()];
+        let err = OciSpecBuilder::new(&spec).build().unwrap_err();
+        assert!(matches!(err, CoreError::InvalidSpec { ref field, .. } if field == "group_add"));
+    }
+
+    #[test]
+    fn test_stop_signal() {
+        assert_eq!(stop_signal(&build(&base_spec())), DEFAULT_STOP_SIGNAL);
+
+        let spec = ContainerSpec {
+            stop_signal: Some("SIGQUIT".to_string()),
+            ..base_spec()
+        };
+        assert_eq!(stop_signal(&build(&spec)), "SIGQUIT");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_host_device() {
+        let spec = ContainerSpec {
+            devices: vec!["/dev/null:/dev/sink:rw".parse().unwrap()],
+            ..base_spec()
+        };
+        let oci = build(&spec);
+        let linux = oci.linux().as_ref().unwrap();
+
+        let device = &linux.devices().as_ref().unwrap()[0];
+        assert_eq!(device.path(), Path::new("/dev/sink"));
+        assert_eq!(device.typ(), LinuxDeviceType::C);
+        assert_eq!((device.major(), device.minor()), (1, 3));
+
+        let rules = linux.resources().as_ref().unwrap().devices().clone().unwrap();
+        let rule = rules.last().unwrap();
+        assert!(rule.allow());
+        assert_eq!((rule.major(), rule.minor()), (Some(1), Some(3)));
+        assert_eq!(rule.access().as_deref(), Some("rw"));
+
+        let spec = ContainerSpec {
+            devices: vec!["/etc/hostname".parse().unwrap()],
+            ..base_spec()
+        };
+        assert!(OciSpecBuilder::new(&spec).build().is_err());
+    }
+
+    #[test]
+    fn test_seccomp_default_profile_converts() {
+        let seccomp = seccomp_to_oci(&SeccompProfile::default_profile()).unwrap();
+        assert_eq!(seccomp.default_action(), LinuxSeccompAction::ScmpActErrno);
+        assert_eq!(seccomp.architectures().as_ref().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_config_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let spec = ContainerSpec {
+            tty: true,
+            ..base_spec()
+        };
+        let oci = OciSpecBuilder::new(&spec).rootfs(Path::new(ROOTFS)).build().unwrap();
+
+        write_config(dir.path(), &oci).await.unwrap();
+        let read = read_config(dir.path()).await.unwrap();
+        assert_eq!(read, oci);
+        assert_eq!(read.process().as_ref().unwrap().terminal(), Some(true));
+    }
+}
@@ -0,0 +1,673 @@
+//! Container and exec stdio for OCI runtimes.
+//!
+//! [`StdioManager`] spawns the runtime's `create` and `exec` commands with
+//! the right stdio wiring:
+//!
+//! - containers without a terminal get pipes; their output is copied to the
+//!   [`LogDriver`] and fanned out to any attached clients, and stdin stays
+//!   writable when `stdin_open` is set;
+//! - with a terminal, a [`ConsoleSocket`] receives the PTY master, which then
+//!   serves as both output (logged as stdout) and stdin;
+//! - interactive execs stream the process stdio directly, through a PTY when
+//!   `tty` is requested.
+//!
+//! Terminals are remembered so they can be resized later.
+
+use crate::error::{CoreError, Result};
+use crate::logs::LogDriver;
+#[cfg(unix)]
+use crate::runtime::console::{ConsoleSocket, Pty};
+use crate::runtime::traits::ExecSession;
+use crate::types::{ContainerId, ContainerSpec, ExecSpec, TtySize};
+use dashmap::DashMap;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::process::Command;
+use tokio::sync::{broadcast, Mutex};
+use tracing::debug;
+
+/// Chunks buffered per attached client before it starts missing output.
+const HUB_CAPACITY: usize = 256;
+
+/// Buffer size of the pipes handed to attached clients.
+const ATTACH_BUFFER: usize = 64 * 1024;
+
+type SharedWriter = Arc<Mutex<Box<dyn AsyncWrite + Send + Unpin>>>;
+
+/// Stdin, stdout and stderr handed to an attached client.
+type AttachedStdio = (
+    Box<dyn AsyncWrite + Send + Unpin>,
+    Box<dyn AsyncRead + Send + Unpin>,
+    Box<dyn AsyncRead + Send + Unpin>,
+);
+
+/// Tracks stdio and terminals of containers and exec sessions.
+pub struct StdioManager {
+    /// Directory for console sockets.
+    sockets_dir: PathBuf,
+    /// Stdio of containers created by this process.
+    containers: DashMap<String, ContainerStdio>,
+    /// Terminals keyed by container ID, or `<container>/<exec>` for execs.
+    #[cfg(unix)]
+    terminals: Arc<DashMap<String, Pty>>,
+}
+
+impl StdioManager {
+    /// Create a manager that places console sockets in `sockets_dir`.
+    #[must_use]
+    pub fn new(sockets_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            sockets_dir: sockets_dir.into(),
+            containers: DashMap::new(),
+            #[cfg(unix)]
+            terminals: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Run a runtime `create` command and capture the container's stdio.
+    ///
+    /// `cmd` must be the complete create invocation except for the container
+    /// ID, which is appended after the console socket option.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the command cannot be spawned, times out, or does not
+    /// hand over a terminal when `spec.tty` is set.
+    pub async fn run_create(
+        &self,
+        id: &ContainerId,
+        mut cmd: Command,
+        spec: &ContainerSpec,
+        logs: &LogDriver,
+        timeout: Duration,
+    ) -> Result<ExitStatus> {
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        if spec.tty {
+            return self.run_create_tty(id, cmd, logs, timeout).await;
+        }
+
+        if spec.stdin_open {
+            cmd.stdin(Stdio::piped());
+        }
+        cmd.arg(id.as_str());
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| CoreError::RuntimeExecution(e.to_string()))?;
+
+        let stdout = OutputHub::new();
+        let stderr = OutputHub::new();
+        logs.attach(
+            id,
+            child
+                .stdout
+                .take()
+                .map(|r| TeeReader::new(r, stdout.clone())),
+            child
+                .stderr
+                .take()
+                .map(|r| TeeReader::new(r, stderr.clone())),
+        )
+        .await?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .map(|w| Arc::new(Mutex::new(Box::new(w) as Box<dyn AsyncWrite + Send + Unpin>)));
+
+        let status = wait_with_timeout(&mut child, timeout).await?;
+        self.containers.insert(
+            id.to_string(),
+            ContainerStdio {
+                stdin,
+                stdout,
+                stderr,
+            },
+        );
+        Ok(status)
+    }
+
+    #[cfg(unix)]
+    async fn run_create_tty(
+        &self,
+        id: &ContainerId,
+        mut cmd: Command,
+        logs: &LogDriver,
+        timeout: Duration,
+    ) -> Result<ExitStatus> {
+        let socket = ConsoleSocket::bind(self.socket_path(id.as_str()))?;
+        cmd.arg("--console-socket")
+            .arg(socket.path())
+            .arg(id.as_str());
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| CoreError::RuntimeExecution(e.to_string()))?;
+        let status = wait_with_timeout(&mut child, timeout).await?;
+        if !status.success() {
+            return Ok(status);
+        }
+
+        // The runtime has already sent the terminal; it is queued on the socket.
+        let master = tokio::time::timeout(timeout, socket.receive())
+            .await
+            .map_err(|_| CoreError::Timeout {
+                operation: "console socket".to_string(),
+                duration_ms: u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX),
+            })??;
+        let pty = Pty::new(master)?;
+
+        let stdout = OutputHub::new();
+        logs.attach(
+            id,
+            Some(TeeReader::new(pty.try_clone()?, stdout.clone())),
+            None::<tokio::io::Empty>,
+        )
+        .await?;
+
+        let stdin: Box<dyn AsyncWrite + Send + Unpin> = Box::new(pty.try_clone()?);
+        self.terminals.insert(id.to_string(), pty);
+        self.containers.insert(
+            id.to_string(),
+            ContainerStdio {
+                stdin: Some(Arc::new(Mutex::new(stdin))),
+                stdout,
+                stderr: OutputHub::closed(),
+            },
+        );
+        Ok(status)
+    }
+
+    #[cfg(not(unix))]
+    async fn run_create_tty(
+        &self,
+        _id: &ContainerId,
+        _cmd: Command,
+        _logs: &LogDriver,
+        _timeout: Duration,
+    ) -> Result<ExitStatus> {
+        Err(terminals_unsupported())
+    }
+
+    /// Run a runtime `exec` command with live stdio.
+    ///
+    /// `cmd` must be the exec invocation up to (but excluding) the terminal
+    /// options, container ID and process arguments, which are appended here.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the command cannot be spawned or exits before handing
+    /// over a terminal.
+    pub async fn run_exec(
+        &self,
+        id: &ContainerId,
+        mut cmd: Command,
+        spec: &ExecSpec,
+    ) -> Result<ExecSession> {
+        let exec_id = uuid::Uuid::new_v4().simple().to_string();
+
+        if spec.tty {
+            return self.run_exec_tty(id, cmd, spec, exec_id).await;
+        }
+
+        let piped = |attach: bool| {
+            if attach {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            }
+        };
+        cmd.arg(id.as_str())
+            .args(&spec.command)
+            .stdin(piped(spec.attach_stdin))
+            .stdout(piped(spec.attach_stdout))
+            .stderr(piped(spec.attach_stderr));
+
+        debug!(container_id = %id, exec_id = %exec_id, "Starting exec");
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| CoreError::RuntimeExecution(e.to_string()))?;
+
+        let stdin: Box<dyn AsyncWrite + Send + Unpin> = match child.stdin.take() {
+            Some(w) => Box::new(w),
+            None => Box::new(tokio::io::sink()),
+        };
+        let stdout: Box<dyn AsyncRead + Send + Unpin> = match child.stdout.take() {
+            Some(r) => Box::new(r),
+            None => Box::new(tokio::io::empty()),
+        };
+        let stderr: Box<dyn AsyncRead + Send + Unpin> = match child.stderr.take() {
+            Some(r) => Box::new(r),
+            None => Box::new(tokio::io::empty()),
+        };
+
+        let exit = tokio::spawn(async move { child.wait().await });
+        Ok(ExecSession {
+            id: exec_id,
+            stdin,
+            stdout,
+            stderr: Some(stderr),
+            exit_code: Box::pin(exit_code(exit)),
+        })
+    }
+
+    #[cfg(unix)]
+    async fn run_exec_tty(
+        &self,
+        id: &ContainerId,
+        mut cmd: Command,
+        spec: &ExecSpec,
+        exec_id: String,
+    ) -> Result<ExecSession> {
+        let socket = ConsoleSocket::bind(self.socket_path(&exec_id))?;
+        cmd.arg("--tty")
+            .arg("--console-socket")
+            .arg(socket.path())
+            .arg(id.as_str())
+            .args(&spec.command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+
+        debug!(container_id = %id, exec_id = %exec_id, "Starting exec with terminal");
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| CoreError::RuntimeExecution(e.to_string()))?;
+
+        let master = tokio::select! {
+            master = socket.receive() => master?,
+            status = child.wait() => {
+                let mut reason = String::new();
+                if let Some(mut stderr) = child.stderr.take() {
+                    let _ = stderr.read_to_string(&mut reason).await;
+                }
+                return Err(CoreError::RuntimeExecution(format!(
+                    "exec exited before allocating a terminal ({}): {}",
+                    status.map(|s| s.to_string()).unwrap_or_default(),
+                    reason.trim()
+                )));
+            }
+        };
+        let pty = Pty::new(master)?;
+
+        let key = format!("{id}/{exec_id}");
+        self.terminals.insert(key.clone(), pty.try_clone()?);
+        let stdin: Box<dyn AsyncWrite + Send + Unpin> = Box::new(pty.try_clone()?);
+
+        let terminals = self.terminals.clone();
+        let exit = tokio::spawn(async move {
+            let status = child.wait().await;
+            terminals.remove(&key);
+            status
+        });
+
+        Ok(ExecSession {
+            id: exec_id,
+            stdin,
+            stdout: Box::new(pty),
+            stderr: None,
+            exit_code: Box::pin(exit_code(exit)),
+        })
+    }
+
+    #[cfg(not(unix))]
+    async fn run_exec_tty(
+        &self,
+        _id: &ContainerId,
+        _cmd: Command,
+        _spec: &ExecSpec,
+        _exec_id: String,
+    ) -> Result<ExecSession> {
+        Err(terminals_unsupported())
+    }
+
+    /// Attach to a container's stdio.
+    ///
+    /// Output is live from the moment of attaching. Writes go to the
+    /// container's stdin, or are discarded if stdin was not kept open.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::ContainerNotFound`] if the container was not
+    /// created by this manager.
+    pub fn attach(&self, id: &ContainerId) -> Result<AttachedStdio> {
+        let entry = self
+            .containers
+            .get(id.as_str())
+            .ok_or_else(|| CoreError::ContainerNotFound(id.to_string()))?;
+
+        let stdin: Box<dyn AsyncWrite + Send + Unpin> = match &entry.stdin {
+            Some(shared) => forward_input(shared.clone()),
+            None => Box::new(tokio::io::sink()),
+        };
+        Ok((stdin, entry.stdout.subscribe(), entry.stderr.subscribe()))
+    }
+
+    /// Resize a container's terminal, or an exec session's.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the target has no terminal.
+    pub fn resize(&self, id: &ContainerId, exec_id: Option<&str>, size: TtySize) -> Result<()> {
+        let key = exec_id.map_or_else(|| id.to_string(), |exec| format!("{id}/{exec}"));
+
+        #[cfg(unix)]
+        {
+            let pty = self
+                .terminals
+                .get(&key)
+                .ok_or_else(|| CoreError::Internal(format!("{key} has no terminal")))?;
+            pty.resize(size)
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (key, size);
+            Err(terminals_unsupported())
+        }
+    }
+
+    /// Forget a container's stdio and terminals.
+    pub fn remove(&self, id: &ContainerId) {
+        self.containers.remove(id.as_str());
+        #[cfg(unix)]
+        {
+            let prefix = format!("{id}/");
+            self.terminals
+                .retain(|key, _| key != id.as_str() && !key.starts_with(&prefix));
+        }
+    }
+
+    fn socket_path(&self, name: &str) -> PathBuf {
+        self.sockets_dir.join(format!("{name}.sock"))
+    }
+}
+
+#[cfg(not(unix))]
+fn terminals_unsupported() -> CoreError {
+    CoreError::Internal("terminals require a Unix host".to_string())
+}
+
+/// Stdio of a running container.
+struct ContainerStdio {
+    stdin: Option<SharedWriter>,
+    stdout: Arc<OutputHub>,
+    stderr: Arc<OutputHub>,
+}
+
+/// Fans one output stream out to attached clients.
+struct OutputHub {
+    tx: broadcast::Sender<Vec<u8>>,
+    closed: AtomicBool,
+}
+
+impl OutputHub {
+    fn new() -> Arc<Self> {
+        let (tx, _) = broadcast::channel(HUB_CAPACITY);
+        Arc::new(Self {
+            tx,
+            closed: AtomicBool::new(false),
+        })
+    }
+
+    fn closed() -> Arc<Self> {
+        let hub = Self::new();
+        hub.close();
+        hub
+    }
+
+    fn publish(&self, data: &[u8]) {
+        // Having no attached clients is not an error.
+        let _ = self.tx.send(data.to_vec());
+    }
+
+    /// Mark the end of the stream; an empty chunk tells clients to stop.
+    fn close(&self) {
+        if !self.closed.swap(true, Ordering::SeqCst) {
+            let _ = self.tx.send(Vec::new());
+        }
+    }
+
+    fn subscribe(&self) -> Box<dyn AsyncRead + Send + Unpin> {
+        let mut rx = self.tx.subscribe();
+        if self.closed.load(Ordering::SeqCst) {
+            return Box::new(tokio::io::empty());
+        }
+
+        let (mut writer, reader) = tokio::io::duplex(ATTACH_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(chunk) if chunk.is_empty() => break,
+                    Ok(chunk) => {
+                        if writer.write_all(&chunk).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!("Attached client missed {} output chunks", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        Box::new(reader)
+    }
+}
+
+/// Give a client its own writer that feeds a shared stdin.
+fn forward_input(stdin: SharedWriter) -> Box<dyn AsyncWrite + Send + Unpin> {
+    let (writer, mut reader) = tokio::io::duplex(ATTACH_BUFFER);
+    tokio::spawn(async move {
+        let mut buf = vec![0_u8; 8192];
+        loop {
+            match reader.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    let mut stdin = stdin.lock().await;
+                    let written =
+                        stdin.write_all(&buf[..n]).await.is_ok() && stdin.flush().await.is_ok();
+                    drop(stdin);
+                    if !written {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    Box::new(writer)
+}
+
+/// Reader that publishes everything it reads to an [`OutputHub`].
+struct TeeReader<R> {
+    inner: R,
+    hub: Arc<OutputHub>,
+}
+
+impl<R> TeeReader<R> {
+    const fn new(inner: R, hub: Arc<OutputHub>) -> Self {
+        Self { inner, hub }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for TeeReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        match &result {
+            Poll::Ready(Ok(())) => {
+                let data = &buf.filled()[before..];
+                if data.is_empty() {
+                    self.hub.close();
+                } else {
+                    self.hub.publish(data);
+                }
+            }
+            Poll::Ready(Err(_)) => self.hub.close(),
+            Poll::Pending => {}
+        }
+        result
+    }
+}
+
+impl<R> Drop for TeeReader<R> {
+    fn drop(&mut self) {
+        self.hub.close();
+    }
+}
+
+async fn wait_with_timeout(
+    child: &mut tokio::process::Child,
+    timeout: Duration,
+) -> Result<ExitStatus> {
+    tokio::time::timeout(timeout, child.wait())
+        .await
+        .map_err(|_| CoreError::Timeout {
+            operation: "runtime create".to_string(),
+            duration_ms: u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX),
+        })?
+        .map_err(|e| CoreError::RuntimeExecution(e.to_string()))
+}
+
+/// Exit code of a finished exec, using the shell convention of 128 + signal
+/// for processes killed by a signal.
+async fn exit_code(exit: tokio::task::JoinHandle<std::io::Result<ExitStatus>>) -> Result<i32> {
+    let status = exit
+        .await
+        .map_err(|e| CoreError::Internal(e.to_string()))??;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return Ok(128 + signal);
+        }
+    }
+    Ok(status.code().unwrap_or(-1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_tee_reader_fans_out_until_eof() {
+        let hub = OutputHub::new();
+        let mut client = hub.subscribe();
+
+        let mut tee = TeeReader::new(&b"hello world"[..], hub.clone());
+        let mut copied = Vec::new();
+        tee.read_to_end(&mut copied).await.unwrap();
+        assert_eq!(copied, b"hello world");
+
+        let mut seen = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), client.read_to_end(&mut seen))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(seen, b"hello world");
+
+        // Late subscribers see an already finished stream.
+        let mut late = Vec::new();
+        hub.subscribe().read_to_end(&mut late).await.unwrap();
+        assert!(late.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_forward_input_reaches_shared_stdin() {
+        let (sink, mut source) = tokio::io::duplex(1024);
+        let shared: SharedWriter = Arc::new(Mutex::new(Box::new(sink)));
+
+        let mut a = forward_input(shared.clone());
+        let mut b = forward_input(shared);
+        a.write_all(b"one ").await.unwrap();
+        a.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        b.write_all(b"two").await.unwrap();
+        b.flush().await.unwrap();
+
+        let mut buf = vec![0_u8; 7];
+        tokio::time::timeout(Duration::from_secs(5), source.read_exact(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf, b"one two");
+    }
+
+    #[tokio::test]
+    async fn test_attach_unknown_container() {
+        let manager = StdioManager::new(std::env::temp_dir());
+        assert!(matches!(
+            manager.attach(&ContainerId::new()),
+            Err(CoreError::ContainerNotFound(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_exec_streams_and_exit_code() {
+        // Stand in for `crun exec`: the shell receives the container ID and
+        // the command as positional arguments.
+        let mut cmd = Command::new("/bin/sh");
+        cmd.arg("-c").arg("cat; echo err >&2; exit 3").arg("sh");
+
+        let manager = StdioManager::new(std::env::temp_dir());
+        let spec = ExecSpec {
+            command: Vec::new(),
+            env: std::collections::HashMap::new(),
+            working_dir: None,
+            user: None,
+            tty: false,
+            attach_stdin: true,
+            attach_stdout: true,
+            attach_stderr: true,
+            privileged: false,
+        };
+        let mut session = manager
+            .run_exec(&ContainerId::from_string("abc"), cmd, &spec)
+            .await
+            .unwrap();
+
+        session.stdin.write_all(b"ping\n").await.unwrap();
+        session.stdin.shutdown().await.unwrap();
+        drop(session.stdin);
+
+        let mut out = String::new();
+        session.stdout.read_to_string(&mut out).await.unwrap();
+        let mut err = String::new();
+        session
+            .stderr
+            .unwrap()
+            .read_to_string(&mut err)
+            .await
+            .unwrap();
+
+        assert_eq!(out, "ping\n");
+        assert_eq!(err, "err\n");
+        assert_eq!(session.exit_code.await.unwrap(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resize_without_terminal() {
+        let manager = StdioManager::new(std::env::temp_dir());
+        let size = TtySize { rows: 24, cols: 80 };
+        assert!(manager
+            .resize(&ContainerId::from_string("abc"), None, size)
+            .is_err());
+    }
+}
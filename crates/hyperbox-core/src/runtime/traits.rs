@@ -1,8 +1,12 @@
 //! Container runtime trait definition.
 
-use crate::error::Result;
+use crate::error::{CoreError, Result};
+use crate::runtime::{ContainerFilter, FilterTarget};
 use crate::types::*;
 use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use std::collections::HashMap;
 use std::path::Path;
 use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncWrite};
@@ -161,6 +165,34 @@ pub trait ContainerRuntime: Send + Sync {
         Box<dyn AsyncRead + Send + Unpin>,
     )>;
 
+    /// Run a command in a container with live stdio.
+    ///
+    /// With `spec.tty` the process gets a pseudo-terminal and all of its
+    /// output arrives on `stdout`; otherwise stdout and stderr are separate.
+    /// Runtimes that cannot stream exec I/O return an error.
+    async fn exec_interactive(&self, id: &ContainerId, spec: ExecSpec) -> Result<ExecSession> {
+        let _ = (id, spec);
+        Err(CoreError::Internal(format!(
+            "{} does not support interactive exec",
+            self.name()
+        )))
+    }
+
+    /// Resize the terminal of a container, or of one of its exec sessions.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Container ID
+    /// * `exec_id` - Exec session ID, or `None` for the container's own console
+    /// * `size` - New terminal size
+    async fn resize(&self, id: &ContainerId, exec_id: Option<&str>, size: TtySize) -> Result<()> {
+        let _ = (id, exec_id, size);
+        Err(CoreError::Internal(format!(
+            "{} does not support terminal resize",
+            self.name()
+        )))
+    }
+
     /// List all containers managed by this runtime.
     ///
     /// # Returns
@@ -168,6 +200,76 @@ pub trait ContainerRuntime: Send + Sync {
     /// List of container IDs and their states.
     async fn list(&self) -> Result<Vec<(ContainerId, ContainerState)>>;
 
+    /// List the containers that pass a filter.
+    ///
+    /// The default implementation matches the recorded metadata of each
+    /// listed container; containers without a record only pass an empty
+    /// filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Filter to apply
+    async fn list_filtered(
+        &self,
+        filter: &ContainerFilter,
+    ) -> Result<Vec<(ContainerId, ContainerState)>> {
+        let listed = self.list().await?;
+        if filter.is_empty() {
+            return Ok(listed);
+        }
+
+        let metadata: HashMap<ContainerId, ContainerMetadata> = self
+            .list_metadata()
+            .await?
+            .into_iter()
+            .map(|meta| (meta.id.clone(), meta))
+            .collect();
+        Ok(listed
+            .into_iter()
+            .filter(|(id, state)| {
+                metadata
+                    .get(id)
+                    .is_some_and(|meta| filter.matches(&FilterTarget::from_metadata(meta, *state)))
+            })
+            .collect())
+    }
+
+    /// Recorded metadata of a container: the spec it was created from, its
+    /// name and labels, lifecycle timestamps and last exit code.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Container ID
+    async fn inspect(&self, id: &ContainerId) -> Result<ContainerMetadata> {
+        let _ = id;
+        Err(CoreError::Internal(format!(
+            "{} does not record container metadata",
+            self.name()
+        )))
+    }
+
+    /// Recorded metadata of every container, oldest first.
+    async fn list_metadata(&self) -> Result<Vec<ContainerMetadata>> {
+        Err(CoreError::Internal(format!(
+            "{} does not record container metadata",
+            self.name()
+        )))
+    }
+
+    /// Find a container by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Container name
+    ///
+    /// # Returns
+    ///
+    /// The container's ID, or `None` if no container has that name.
+    async fn lookup(&self, name: &str) -> Result<Option<ContainerId>> {
+        let _ = name;
+        Ok(None)
+    }
+
     /// Wait for a container to exit.
     ///
     /// # Arguments
@@ -179,6 +281,33 @@ pub trait ContainerRuntime: Send + Sync {
     /// Exit code of the container.
     async fn wait(&self, id: &ContainerId) -> Result<i32>;
 
+    /// How a container exited, if it has exited and the runtime recorded it.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Container ID
+    async fn exit_status(&self, id: &ContainerId) -> Result<Option<ContainerExit>> {
+        let _ = id;
+        Ok(None)
+    }
+
+    /// Watch the resource limit events of a running container.
+    ///
+    /// The stream yields the current counters first and then every time they
+    /// change, e.g. when the OOM killer kills a process or a fork fails at
+    /// the PIDs limit. It ends when the container's cgroup goes away.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Container ID
+    async fn resource_events(
+        &self,
+        id: &ContainerId,
+    ) -> Result<BoxStream<'static, ResourceEvents>> {
+        let _ = id;
+        Err(CoreError::Internal(format!("{} does not report resource events", self.name())))
+    }
+
     /// Checkpoint a running container (CRIU).
     ///
     /// # Arguments
@@ -205,11 +334,23 @@ pub trait ContainerRuntime: Send + Sync {
 
     /// Update container resource limits.
     ///
+    /// Only limits set in `resources` change; the others keep their current
+    /// value.
+    ///
     /// # Arguments
     ///
     /// * `id` - Container ID
     /// * `resources` - New resource limits
-    async fn update(&self, id: &ContainerId, resources: ResourceLimits) -> Result<()>;
+    ///
+    /// # Returns
+    ///
+    /// Which of the given limits were applied and which the runtime cannot
+    /// change.
+    async fn update(
+        &self,
+        id: &ContainerId,
+        resources: ResourceLimits,
+    ) -> Result<ResourceUpdateReport>;
 
     /// Get container processes.
     ///
@@ -222,6 +363,95 @@ pub trait ContainerRuntime: Send + Sync {
     /// List of processes running in the container.
     async fn top(&self, id: &ContainerId) -> Result<Vec<ProcessInfo>>;
 
+    /// Stream a tar archive of a path in a container's filesystem.
+    ///
+    /// Directories are archived recursively under their own name, keeping
+    /// ownership, permissions and symlinks.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Container ID
+    /// * `path` - Absolute path inside the container
+    async fn archive_path(
+        &self,
+        id: &ContainerId,
+        path: &Path,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let _ = (id, path);
+        Err(CoreError::Internal(format!(
+            "{} does not support copying files out of containers",
+            self.name()
+        )))
+    }
+
+    /// Extract a tar archive into a directory in a container's filesystem.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Container ID
+    /// * `path` - Absolute path of an existing directory inside the container
+    /// * `archive` - Tar stream to extract
+    async fn extract_archive(
+        &self,
+        id: &ContainerId,
+        path: &Path,
+        archive: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> Result<()> {
+        let _ = (id, path, archive);
+        Err(CoreError::Internal(format!(
+            "{} does not support copying files into containers",
+            self.name()
+        )))
+    }
+
+    /// List the paths a container changed relative to its image.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Container ID
+    async fn diff(&self, id: &ContainerId) -> Result<Vec<FilesystemChange>> {
+        let _ = id;
+        Err(CoreError::Internal(format!(
+            "{} does not support filesystem diffs",
+            self.name()
+        )))
+    }
+
+    /// Snapshot a container's filesystem into a new image.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Container ID
+    /// * `options` - Reference, history and configuration of the new image
+    ///
+    /// # Returns
+    ///
+    /// ID of the new image.
+    async fn commit(&self, id: &ContainerId, options: CommitOptions) -> Result<String> {
+        let _ = (id, options);
+        Err(CoreError::Internal(format!(
+            "{} does not support committing containers",
+            self.name()
+        )))
+    }
+
+    /// Export a container's root filesystem.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Container ID
+    ///
+    /// # Returns
+    ///
+    /// A tar stream of the flattened filesystem, without mounted volumes.
+    async fn export(&self, id: &ContainerId) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let _ = id;
+        Err(CoreError::Internal(format!(
+            "{} does not support exporting containers",
+            self.name()
+        )))
+    }
+
     /// Pull an image from a registry.
     ///
     /// # Arguments
@@ -233,6 +463,43 @@ pub trait ContainerRuntime: Send + Sync {
     /// Ok(()) on successful pull.
     async fn pull_image(&self, image: &crate::types::ImageRef) -> Result<()>;
 
+    /// Pull an image for a given platform, reporting layer progress.
+    ///
+    /// Multi-platform images resolve to the manifest for
+    /// `options.platform`. The default accepts only the host platform and
+    /// reports no progress.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - Image reference to pull
+    /// * `options` - Platform and progress channel
+    async fn pull_image_with(&self, image: &ImageRef, options: PullOptions) -> Result<()> {
+        if let Some(platform) = &options.platform {
+            if !platform.matches(&Platform::host()) {
+                return Err(CoreError::Runtime(format!(
+                    "{} cannot pull images for {platform}",
+                    self.name()
+                )));
+            }
+        }
+        self.pull_image(image).await
+    }
+
+    /// Push a local image to its registry, reporting blob progress.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - Image reference to push
+    /// * `options` - Progress channel
+    ///
+    /// # Returns
+    ///
+    /// Digest of the pushed manifest.
+    async fn push_image(&self, image: &ImageRef, options: PushOptions) -> Result<String> {
+        let _ = (image, options);
+        Err(CoreError::Internal(format!("{} does not support pushing images", self.name())))
+    }
+
     /// Check if an image exists locally.
     ///
     /// # Arguments
@@ -250,6 +517,51 @@ pub trait ContainerRuntime: Send + Sync {
     ///
     /// List of image references.
     async fn list_images(&self) -> Result<Vec<ImageInfo>>;
+
+    /// Create a single-layer image from a root filesystem tarball.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - Tar stream, optionally gzip-compressed
+    /// * `options` - Reference, history and configuration of the new image
+    ///
+    /// # Returns
+    ///
+    /// ID of the new image.
+    async fn import_image(
+        &self,
+        archive: Box<dyn AsyncRead + Send + Unpin>,
+        options: ImportOptions,
+    ) -> Result<String> {
+        let _ = (archive, options);
+        Err(CoreError::Internal(format!(
+            "{} does not support importing images",
+            self.name()
+        )))
+    }
+}
+
+/// Live stdio of an interactive exec.
+pub struct ExecSession {
+    /// Exec session ID, used to resize its terminal.
+    pub id: String,
+    /// Process stdin.
+    pub stdin: Box<dyn AsyncWrite + Send + Unpin>,
+    /// Process stdout (the terminal output when a TTY was allocated).
+    pub stdout: Box<dyn AsyncRead + Send + Unpin>,
+    /// Process stderr; `None` when a TTY was allocated.
+    pub stderr: Option<Box<dyn AsyncRead + Send + Unpin>>,
+    /// Resolves to the exit code once the process exits.
+    pub exit_code: BoxFuture<'static, Result<i32>>,
+}
+
+impl std::fmt::Debug for ExecSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExecSession")
+            .field("id", &self.id)
+            .field("tty", &self.stderr.is_none())
+            .finish_non_exhaustive()
+    }
 }
 
 /// Image information.
@@ -272,10 +584,14 @@ pub struct ProcessInfo {
     pub pid: u32,
     /// Parent process ID
     pub ppid: u32,
+    /// Effective user ID
+    pub uid: u32,
     /// CPU usage percentage
     pub cpu_percent: f64,
-    /// Memory usage in bytes
+    /// Memory usage (resident set size) in bytes
     pub memory_bytes: u64,
+    /// When the process started, if known
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
     /// Command
     pub command: String,
 }
@@ -34,7 +34,7 @@
 //! # Example
 //!
 //! ```no_run
-//! use hyperbox_core::runtime::wasm::WasmRuntime;
+//! use hyperbox_core::runtime::WasmRuntime;
 //! use hyperbox_core::runtime::{RuntimeConfig, RuntimeType};
 //!
 //! let config = RuntimeConfig {
@@ -50,6 +50,7 @@ use async_trait::async_trait;
 use dashmap::DashMap;
 use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::process::Command;
@@ -57,11 +58,11 @@ use tracing::{debug, info, instrument, warn};
 
 use crate::error::{CoreError, Result};
 use crate::runtime::traits::{ContainerRuntime, ImageInfo, ProcessInfo};
-use crate::runtime::RuntimeConfig;
+use crate::runtime::{MetadataStore, RuntimeConfig};
 use crate::types::{
-    BlockIoStats, CheckpointId, ContainerId, ContainerSpec, ContainerState, ContainerStats,
-    CpuStats, ExecResult, ExecSpec, ImageRef, LogOptions, MemoryStats, NetworkStats,
-    ResourceLimits,
+    BlockIoStats, CheckpointId, ContainerExit, ContainerId, ContainerMetadata, ContainerSpec,
+    ContainerState, ContainerStats, CpuStats, ExecResult, ExecSpec, ImageRef, LogOptions,
+    MemoryStats, NetworkStats, PullOptions, ResourceEvents, ResourceLimits, ResourceUpdateReport,
 };
 
 /// Default fuel budget (~1 second of CPU-equivalent execution).
@@ -76,6 +77,22 @@ const MAX_FUEL: u64 = 100_000_000_000;
 /// AOT-compiled module file extension (Cranelift ahead-of-time compiled WASM).
 const COMPILED_EXT: &str = "cwasm";
 
+/// How long to wait for the wasmtime process to stop or continue.
+const SIGNAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Limits wasmtime has no flag for.
+const WASM_UNSUPPORTED_LIMITS: &[&str] = &[
+    "cpu_weight",
+    "cpuset_cpus",
+    "memory_swap_bytes",
+    "pids_limit",
+    "io_read_bps",
+    "io_write_bps",
+    "io_read_iops",
+    "io_write_iops",
+    "io_weight",
+];
+
 // ---------------------------------------------------------------------------
 // Internal state types
 // ---------------------------------------------------------------------------
@@ -140,7 +157,9 @@ pub struct WasmRuntime {
     /// Directory for cached AOT-compiled modules.
     cache_dir: PathBuf,
     /// Active WASM instances tracked by container ID string.
-    instances: DashMap<String, WasmInstance>,
+    instances: Arc<DashMap<String, WasmInstance>>,
+    /// Specs, names and lifecycle times of instances, kept across restarts.
+    metadata: Arc<MetadataStore>,
     /// Directory for instance log capture.
     log_dir: PathBuf,
 }
@@ -151,6 +170,9 @@ impl WasmRuntime {
     /// Searches standard locations and `PATH` for the `wasmtime` binary.
     /// Falls back to the bare name `"wasmtime"` so that `is_available()`
     /// can report the binary as missing rather than panicking.
+    ///
+    /// Instances recorded by an earlier run are restored as exited, or as
+    /// created if they never started.
     pub fn new(config: RuntimeConfig) -> Self {
         let binary_path = config
             .binary_path
@@ -159,14 +181,21 @@ impl WasmRuntime {
 
         let cache_dir = config.root_dir.join("wasm-cache");
         let log_dir = config.root_dir.join("wasm-logs");
+        let metadata = MetadataStore::open(config.root_dir.join("metadata").join("wasm"));
 
-        Self {
+        let runtime = Self {
             config,
             binary_path,
             cache_dir,
-            instances: DashMap::new(),
+            instances: Arc::new(DashMap::new()),
+            metadata: Arc::new(metadata),
             log_dir,
+        };
+        for meta in runtime.metadata.list() {
+            let instance = runtime.recorded_instance(meta);
+            runtime.instances.insert(instance.id.to_string(), instance);
         }
+        runtime
     }
 
     // ------------------------------------------------------------------
@@ -223,7 +252,8 @@ impl WasmRuntime {
     /// Detect whether an image reference points to a WASM module.
     #[must_use]
     pub fn is_wasm_image(image: &ImageRef) -> bool {
-        let name = image.full_name();
+        // The parser appends a default tag, so look at the repository only.
+        let name = &image.repository;
         name.ends_with(".wasm") || name.ends_with(".wat") || name.ends_with(".cwasm")
     }
 
@@ -231,7 +261,64 @@ impl WasmRuntime {
     fn resolve_module_path(image: &ImageRef) -> PathBuf {
         // Image references for WASM are local filesystem paths in the
         // simple case. (OCI-based WASM pulling is handled by `pull_image`.)
-        PathBuf::from(image.full_name())
+        // Undo what `ImageRef::parse` adds to a path: the default registry
+        // and `library/` namespace, or the split of `./` or `../` into a
+        // "registry".
+        if image.registry == "docker.io" {
+            PathBuf::from(image.repository.strip_prefix("library/").unwrap_or(&image.repository))
+        } else {
+            PathBuf::from(format!("{}/{}", image.registry, image.repository))
+        }
+    }
+
+    /// A new instance of `spec` in the created state.
+    fn new_instance(
+        &self,
+        id: ContainerId,
+        spec: ContainerSpec,
+        compiled_path: Option<PathBuf>,
+    ) -> WasmInstance {
+        let module_path = Self::resolve_module_path(&spec.image);
+        let fuel_limit = Self::limits_to_fuel(&spec.resources);
+        let stdout_log = self.log_dir.join(format!("{}-stdout.log", id.short()));
+        let stderr_log = self.log_dir.join(format!("{}-stderr.log", id.short()));
+
+        WasmInstance {
+            id,
+            spec,
+            state: ContainerState::Created,
+            module_path,
+            compiled_path,
+            created_at: chrono::Utc::now(),
+            started_at: None,
+            stopped_at: None,
+            fuel_limit,
+            process_id: None,
+            exit_code: None,
+            stdout_log,
+            stderr_log,
+        }
+    }
+
+    /// The instance of a container recorded by an earlier run.
+    fn recorded_instance(&self, meta: ContainerMetadata) -> WasmInstance {
+        let module_path = Self::resolve_module_path(&meta.spec.image);
+        let compiled_path =
+            if module_path.extension().and_then(|e| e.to_str()) == Some(COMPILED_EXT) {
+                Some(module_path)
+            } else {
+                Some(self.compiled_cache_path(&module_path)).filter(|path| path.exists())
+            };
+
+        let mut instance = self.new_instance(meta.id, meta.spec, compiled_path);
+        if meta.started_at.is_some() {
+            instance.state = ContainerState::Exited;
+        }
+        instance.created_at = meta.created_at;
+        instance.started_at = meta.started_at;
+        instance.stopped_at = meta.finished_at;
+        instance.exit_code = meta.exit_code;
+        instance
     }
 
     /// Generate a deterministic cache path for an AOT-compiled module.
@@ -450,6 +537,61 @@ impl WasmRuntime {
                 write_ops: 0,
             },
             pids: if instance.process_id.is_some() { 1 } else { 0 },
+            events: ResourceEvents::default(),
+        }
+    }
+
+    /// Process state letter from the contents of `/proc/<pid>/stat`.
+    fn proc_stat_state(stat: &str) -> Option<char> {
+        // The command name is parenthesised and may contain spaces or
+        // parentheses itself, so the state follows the last `)`.
+        let (_, rest) = stat.rsplit_once(')')?;
+        rest.split_whitespace().next()?.chars().next()
+    }
+
+    /// States of every thread of a process, from `/proc/<pid>/task/*/stat`.
+    ///
+    /// Returns `None` once the process is gone. Threads that exit while
+    /// they are listed are skipped.
+    #[cfg(target_os = "linux")]
+    async fn thread_states(pid: u32) -> Option<Vec<char>> {
+        let mut tasks = tokio::fs::read_dir(format!("/proc/{pid}/task"))
+            .await
+            .ok()?;
+        let mut states = Vec::new();
+        while let Ok(Some(task)) = tasks.next_entry().await {
+            if let Ok(stat) = tokio::fs::read_to_string(task.path().join("stat")).await {
+                states.extend(Self::proc_stat_state(&stat));
+            }
+        }
+        Some(states)
+    }
+
+    /// Wait until the kernel reports the process as stopped (or running
+    /// again after `SIGCONT`).
+    ///
+    /// Signals are delivered asynchronously, so a pause is only reported
+    /// once every thread of the wasmtime process has actually stopped. A
+    /// process that exits meanwhile is left to the exit watcher.
+    #[cfg(target_os = "linux")]
+    async fn wait_for_stopped(pid: u32, stopped: bool) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + SIGNAL_TIMEOUT;
+        loop {
+            let Some(states) = Self::thread_states(pid).await else {
+                return Ok(());
+            };
+            let settled =
+                |state: &char| matches!(state, 'T' | 't') == stopped || matches!(state, 'Z' | 'X');
+            if states.iter().all(settled) {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(CoreError::Timeout {
+                    operation: if stopped { "pause" } else { "resume" }.to_string(),
+                    duration_ms: SIGNAL_TIMEOUT.as_millis() as u64,
+                });
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
         }
     }
 }
@@ -517,30 +659,14 @@ impl ContainerRuntime for WasmRuntime {
             None
         };
 
-        let fuel_limit = Self::limits_to_fuel(&spec.resources);
-
-        let stdout_log = self.log_dir.join(format!("{}-stdout.log", id.short()));
-        let stderr_log = self.log_dir.join(format!("{}-stderr.log", id.short()));
-
-        let instance = WasmInstance {
-            id: id.clone(),
-            spec,
-            state: ContainerState::Created,
-            module_path,
-            compiled_path,
-            created_at: chrono::Utc::now(),
-            started_at: None,
-            stopped_at: None,
-            fuel_limit,
-            process_id: None,
-            exit_code: None,
-            stdout_log,
-            stderr_log,
-        };
+        self.metadata
+            .insert(ContainerMetadata::new(id.clone(), spec.clone()))
+            .await?;
+        let instance = self.new_instance(id.clone(), spec, compiled_path);
 
         info!(
             container_id = %id.short(),
-            fuel_limit,
+            fuel_limit = instance.fuel_limit,
             aot = instance.compiled_path.is_some(),
             "WASM container created"
         );
@@ -598,37 +724,43 @@ impl ContainerRuntime for WasmRuntime {
             pid = ?instance.process_id,
             "WASM container started (sub-ms instantiation with AOT)"
         );
+        drop(entry);
+
+        if let Err(e) = self.metadata.mark_started(id).await {
+            warn!(error = %e, "Cannot record container start");
+        }
 
         // Spawn a background task to wait for exit and update state
-        let instances = self.instances.clone();
-        let container_id = id.as_str().to_string();
+        let instances = Arc::clone(&self.instances);
+        let metadata = Arc::clone(&self.metadata);
+        let container_id = id.clone();
         tokio::spawn(async move {
             let mut child = child;
-            match child.wait().await {
-                Ok(status) => {
-                    if let Some(mut entry) = instances.get_mut(&container_id) {
-                        let inst = entry.value_mut();
-                        inst.exit_code = status.code();
-                        inst.state = ContainerState::Exited;
-                        inst.stopped_at = Some(chrono::Utc::now());
-                        inst.process_id = None;
-                        debug!(
-                            container_id,
-                            exit_code = ?inst.exit_code,
-                            "WASM container exited"
-                        );
-                    }
-                }
+            let exit_code = match child.wait().await {
+                Ok(status) => status.code(),
                 Err(e) => {
-                    warn!(container_id, error = %e, "Failed to wait on wasmtime process");
-                    if let Some(mut entry) = instances.get_mut(&container_id) {
-                        let inst = entry.value_mut();
-                        inst.state = ContainerState::Exited;
-                        inst.exit_code = Some(-1);
-                        inst.stopped_at = Some(chrono::Utc::now());
-                        inst.process_id = None;
-                    }
+                    warn!(%container_id, error = %e, "Failed to wait on wasmtime process");
+                    Some(-1)
                 }
+            };
+            let finished_at = chrono::Utc::now();
+            if let Some(mut entry) = instances.get_mut(container_id.as_str()) {
+                let inst = entry.value_mut();
+                inst.exit_code = exit_code;
+                inst.state = ContainerState::Exited;
+                inst.stopped_at = Some(finished_at);
+                inst.process_id = None;
+                debug!(%container_id, ?exit_code, "WASM container exited");
+            }
+
+            let exit = ContainerExit {
+                exit_code: exit_code.unwrap_or(-1),
+                signal: None,
+                oom_killed: false,
+                finished_at,
+            };
+            if let Err(e) = metadata.record_exit(&container_id, &exit).await {
+                debug!(%container_id, "Cannot record exit in metadata: {}", e);
             }
         });
 
@@ -644,7 +776,7 @@ impl ContainerRuntime for WasmRuntime {
 
         let instance = entry.value_mut();
 
-        if instance.state != ContainerState::Running {
+        if !matches!(instance.state, ContainerState::Running | ContainerState::Paused) {
             return Err(CoreError::ContainerNotRunning(id.to_string()));
         }
 
@@ -656,6 +788,13 @@ impl ContainerRuntime for WasmRuntime {
                     nix::unistd::Pid::from_raw(pid as i32),
                     nix::sys::signal::Signal::SIGTERM,
                 );
+                // A stopped process only acts on SIGTERM once continued.
+                if instance.state == ContainerState::Paused {
+                    let _ = nix::sys::signal::kill(
+                        nix::unistd::Pid::from_raw(pid as i32),
+                        nix::sys::signal::Signal::SIGCONT,
+                    );
+                }
             }
             #[cfg(windows)]
             {
@@ -744,7 +883,7 @@ impl ContainerRuntime for WasmRuntime {
             .ok_or_else(|| CoreError::ContainerNotFound(id.to_string()))?;
 
         // Cannot remove running containers
-        if entry.value().state == ContainerState::Running {
+        if matches!(entry.value().state, ContainerState::Running | ContainerState::Paused) {
             return Err(CoreError::RuntimeExecution(
                 "Cannot remove running WASM container; stop it first".into(),
             ));
@@ -758,6 +897,7 @@ impl ContainerRuntime for WasmRuntime {
 
         drop(entry);
         self.instances.remove(id.as_str());
+        self.metadata.remove(id).await?;
 
         info!("WASM container removed");
         Ok(())
@@ -765,18 +905,17 @@ impl ContainerRuntime for WasmRuntime {
 
     #[instrument(skip(self), fields(container_id = %id.short()))]
     async fn pause(&self, id: &ContainerId) -> Result<()> {
-        let mut entry = self
+        let (state, pid) = self
             .instances
-            .get_mut(id.as_str())
+            .get(id.as_str())
+            .map(|entry| (entry.state, entry.process_id))
             .ok_or_else(|| CoreError::ContainerNotFound(id.to_string()))?;
-
-        let instance = entry.value_mut();
-        if instance.state != ContainerState::Running {
+        if state != ContainerState::Running {
             return Err(CoreError::ContainerNotRunning(id.to_string()));
         }
 
         // Pause via SIGSTOP on Unix
-        if let Some(pid) = instance.process_id {
+        if let Some(pid) = pid {
             #[cfg(unix)]
             {
                 nix::sys::signal::kill(
@@ -785,6 +924,14 @@ impl ContainerRuntime for WasmRuntime {
                 )
                 .map_err(|e| CoreError::RuntimeExecution(format!("SIGSTOP failed: {e}")))?;
             }
+            #[cfg(target_os = "linux")]
+            if let Err(e) = Self::wait_for_stopped(pid, true).await {
+                let _ = nix::sys::signal::kill(
+                    nix::unistd::Pid::from_raw(pid as i32),
+                    nix::sys::signal::Signal::SIGCONT,
+                );
+                return Err(e);
+            }
             #[cfg(windows)]
             {
                 // Windows: suspend the process via NtSuspendProcess or debug API
@@ -794,27 +941,35 @@ impl ContainerRuntime for WasmRuntime {
             }
         }
 
+        // The process may have exited while it was being stopped.
+        let mut entry = self
+            .instances
+            .get_mut(id.as_str())
+            .ok_or_else(|| CoreError::ContainerNotFound(id.to_string()))?;
+        let instance = entry.value_mut();
+        if instance.state != ContainerState::Running {
+            return Err(CoreError::ContainerNotRunning(id.to_string()));
+        }
         instance.state = ContainerState::Paused;
+        drop(entry);
         debug!("WASM container paused");
         Ok(())
     }
 
     #[instrument(skip(self), fields(container_id = %id.short()))]
     async fn resume(&self, id: &ContainerId) -> Result<()> {
-        let mut entry = self
+        let (state, pid) = self
             .instances
-            .get_mut(id.as_str())
+            .get(id.as_str())
+            .map(|entry| (entry.state, entry.process_id))
             .ok_or_else(|| CoreError::ContainerNotFound(id.to_string()))?;
-
-        let instance = entry.value_mut();
-        if instance.state != ContainerState::Paused {
+        if state != ContainerState::Paused {
             return Err(CoreError::RuntimeExecution(format!(
-                "Cannot resume container in state {:?}",
-                instance.state
+                "Cannot resume container in state {state:?}"
             )));
         }
 
-        if let Some(pid) = instance.process_id {
+        if let Some(pid) = pid {
             #[cfg(unix)]
             {
                 nix::sys::signal::kill(
@@ -823,6 +978,8 @@ impl ContainerRuntime for WasmRuntime {
                 )
                 .map_err(|e| CoreError::RuntimeExecution(format!("SIGCONT failed: {e}")))?;
             }
+            #[cfg(target_os = "linux")]
+            Self::wait_for_stopped(pid, false).await?;
             #[cfg(windows)]
             {
                 let _ = pid;
@@ -830,7 +987,16 @@ impl ContainerRuntime for WasmRuntime {
             }
         }
 
+        let mut entry = self
+            .instances
+            .get_mut(id.as_str())
+            .ok_or_else(|| CoreError::ContainerNotFound(id.to_string()))?;
+        let instance = entry.value_mut();
+        if instance.state != ContainerState::Paused {
+            return Err(CoreError::ContainerNotRunning(id.to_string()));
+        }
         instance.state = ContainerState::Running;
+        drop(entry);
         debug!("WASM container resumed");
         Ok(())
     }
@@ -999,6 +1165,20 @@ impl ContainerRuntime for WasmRuntime {
         Ok(result)
     }
 
+    async fn inspect(&self, id: &ContainerId) -> Result<ContainerMetadata> {
+        self.metadata
+            .get(id)
+            .ok_or_else(|| CoreError::ContainerNotFound(id.to_string()))
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<ContainerMetadata>> {
+        Ok(self.metadata.list())
+    }
+
+    async fn lookup(&self, name: &str) -> Result<Option<ContainerId>> {
+        Ok(self.metadata.lookup(name))
+    }
+
     #[instrument(skip(self), fields(container_id = %id.short()))]
     async fn wait(&self, id: &ContainerId) -> Result<i32> {
         // Poll for exit status
@@ -1124,27 +1304,10 @@ impl ContainerRuntime for WasmRuntime {
         };
 
         let id = ContainerId::new();
-        let module_path = Self::resolve_module_path(&spec.image);
-        let fuel_limit = Self::limits_to_fuel(&spec.resources);
-
-        let stdout_log = self.log_dir.join(format!("{}-stdout.log", id.short()));
-        let stderr_log = self.log_dir.join(format!("{}-stderr.log", id.short()));
-
-        let instance = WasmInstance {
-            id: id.clone(),
-            spec,
-            state: ContainerState::Created,
-            module_path,
-            compiled_path: restored_compiled,
-            created_at: chrono::Utc::now(),
-            started_at: None,
-            stopped_at: None,
-            fuel_limit,
-            process_id: None,
-            exit_code: None,
-            stdout_log,
-            stderr_log,
-        };
+        self.metadata
+            .insert(ContainerMetadata::new(id.clone(), spec.clone()))
+            .await?;
+        let instance = self.new_instance(id.clone(), spec, restored_compiled);
 
         info!(
             container_id = %id.short(),
@@ -1156,28 +1319,33 @@ impl ContainerRuntime for WasmRuntime {
     }
 
     #[instrument(skip(self, resources), fields(container_id = %id.short()))]
-    async fn update(&self, id: &ContainerId, resources: ResourceLimits) -> Result<()> {
+    async fn update(
+        &self,
+        id: &ContainerId,
+        resources: ResourceLimits,
+    ) -> Result<ResourceUpdateReport> {
         let mut entry = self
             .instances
             .get_mut(id.as_str())
             .ok_or_else(|| CoreError::ContainerNotFound(id.to_string()))?;
 
         let instance = entry.value_mut();
-
-        // Update fuel limit based on new CPU allocation
-        if let Some(mc) = resources.cpu_millicores {
-            let new_fuel = (mc as u128 * DEFAULT_FUEL as u128 / 1000) as u64;
-            instance.fuel_limit = new_fuel.min(MAX_FUEL);
+        instance.spec.resources.merge(&resources);
+        if resources.cpu_millicores.is_some() {
+            instance.fuel_limit = Self::limits_to_fuel(&instance.spec.resources);
             debug!(new_fuel = instance.fuel_limit, "Updated fuel budget");
         }
 
-        // Note: memory limits for an already-running WASM process cannot be
-        // dynamically adjusted via the CLI. The new limits will apply on
-        // next start.
-        instance.spec.resources = resources;
-        debug!("Resource limits updated (effective on next start)");
+        // Fuel and memory are wasmtime flags, fixed for the life of the
+        // process; a running module keeps its limits until it is restarted.
+        let report = if instance.process_id.is_some() {
+            ResourceUpdateReport::new(&resources, ResourceLimits::FIELDS)
+        } else {
+            ResourceUpdateReport::new(&resources, WASM_UNSUPPORTED_LIMITS)
+        };
+        debug!(applied = ?report.applied, "Resource limits updated");
 
-        Ok(())
+        Ok(report)
     }
 
     async fn top(&self, id: &ContainerId) -> Result<Vec<ProcessInfo>> {
@@ -1193,8 +1361,10 @@ impl ContainerRuntime for WasmRuntime {
             Ok(vec![ProcessInfo {
                 pid,
                 ppid: std::process::id(),
+                uid: 0,
                 cpu_percent: 0.0,
                 memory_bytes: 0,
+                started_at: instance.started_at,
                 command: format!(
                     "wasmtime run {}",
                     instance
@@ -1260,6 +1430,11 @@ impl ContainerRuntime for WasmRuntime {
         Ok(())
     }
 
+    async fn pull_image_with(&self, image: &ImageRef, _options: PullOptions) -> Result<()> {
+        // WASM modules run on every platform.
+        self.pull_image(image).await
+    }
+
     async fn image_exists(&self, image: &str) -> Result<bool> {
         let path = PathBuf::from(image);
         // Check for source module
@@ -1314,13 +1489,15 @@ impl ContainerRuntime for WasmRuntime {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::runtime::RuntimeConfig;
+    use crate::runtime::{RuntimeConfig, RuntimeType};
 
     fn test_config() -> RuntimeConfig {
         RuntimeConfig {
             runtime_type: RuntimeType::Wasm,
             binary_path: None,
-            root_dir: std::env::temp_dir().join("hyperbox-wasm-test"),
+            // Runtimes reload persisted metadata, so each test needs its own root.
+            root_dir: std::env::temp_dir()
+                .join(format!("hyperbox-wasm-test-{}", uuid::Uuid::new_v4())),
             debug: true,
             timeout_seconds: 10,
         }
@@ -1352,6 +1529,14 @@ mod tests {
         assert!(!WasmRuntime::is_wasm_image(&oci_ref));
     }
 
+    #[test]
+    fn test_resolve_module_path() {
+        for path in ["./hello.wasm", "../mods/hello.wasm", "/srv/mods/hello.wasm", "hello.wasm"] {
+            let image = ImageRef::parse(path);
+            assert_eq!(WasmRuntime::resolve_module_path(&image), PathBuf::from(path));
+        }
+    }
+
     #[test]
     fn test_fuel_calculation_default() {
         let limits = ResourceLimits::default();
@@ -1644,4 +1829,41 @@ mod tests {
         assert!(runtime.image_exists(&wasm_path.to_string_lossy()).await.unwrap());
         assert!(!runtime.image_exists("/nonexistent/path.wasm").await.unwrap());
     }
+
+    #[test]
+    fn test_proc_stat_state() {
+        let stat = "4242 (wasm (worker) t) T 1 4242 4242 0 -1 4194560";
+        assert_eq!(WasmRuntime::proc_stat_state(stat), Some('T'));
+        assert_eq!(WasmRuntime::proc_stat_state("1 (init) S 0 1"), Some('S'));
+        assert_eq!(WasmRuntime::proc_stat_state("garbage"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_thread_states_lists_every_thread() {
+        let states = WasmRuntime::thread_states(std::process::id())
+            .await
+            .unwrap();
+        // The test thread plus at least the two runtime workers.
+        assert!(states.len() >= 3);
+        assert!(WasmRuntime::thread_states(u32::MAX).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pause_requires_running() {
+        let runtime = WasmRuntime::new(test_config());
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let wasm_path = tmp.path().join("paused.wasm");
+        std::fs::write(&wasm_path, b"\x00asm\x01\x00\x00\x00").unwrap();
+
+        let spec = ContainerSpec::builder()
+            .image(wasm_path.to_string_lossy())
+            .build();
+        let id = runtime.create(spec).await.unwrap();
+
+        assert!(matches!(runtime.pause(&id).await, Err(CoreError::ContainerNotRunning(_))));
+        assert!(runtime.resume(&id).await.is_err());
+        assert_eq!(runtime.state(&id).await.unwrap(), ContainerState::Created);
+    }
 }
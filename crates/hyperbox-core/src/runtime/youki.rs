@@ -13,19 +13,27 @@
 //! Feature-gated: `#[cfg(feature = "youki")]`
 
 use async_trait::async_trait;
+use futures::stream::BoxStream;
 use std::path::{Path, PathBuf};
 use std::process::Output;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tracing::{debug, info, instrument, warn};
 
 use crate::error::{CoreError, Result};
-use crate::runtime::traits::{ContainerRuntime, ImageInfo, ProcessInfo};
-use crate::runtime::{RuntimeConfig, RuntimeType};
+use crate::isolation::CgroupManager;
+use crate::logs::{LogConfig, LogDriver};
+use crate::runtime::traits::{ContainerRuntime, ExecSession, ImageInfo, ProcessInfo};
+use crate::runtime::{
+    container_processes, read_config, stop_signal, update_resources, while_frozen, write_config,
+    BundleManager, ContainerFs, ExitMonitor, MetadataStore, OciSpecBuilder, RuntimeConfig,
+    RuntimeType, StdioManager, DEFAULT_STOP_SIGNAL,
+};
 use crate::types::{
-    BlockIoStats, CheckpointId, ContainerId, ContainerSpec, ContainerState, ContainerStats,
-    CpuStats, ExecResult, ExecSpec, ImageRef, LogOptions, MemoryStats, NetworkStats,
-    ResourceLimits,
+    BlockIoStats, CheckpointId, ContainerExit, ContainerId, ContainerMetadata, ContainerSpec,
+    ContainerState, ContainerStats, CpuStats, ExecResult, ExecSpec, ImageRef, LogOptions,
+    MemoryStats, NetworkStats, ResourceEvents, ResourceLimits, ResourceUpdateReport, TtySize,
 };
 
 /// Cgroup v2 stats read from sysfs.
@@ -35,6 +43,7 @@ struct YoukiCgroupStats {
     memory_limit: u64,
     cpu_usage_usec: u64,
     pids_current: u64,
+    events: ResourceEvents,
 }
 
 /// Youki OCI runtime implementation.
@@ -54,17 +63,32 @@ struct YoukiCgroupStats {
 /// # Example
 ///
 /// ```no_run
-/// use hyperbox_core::runtime::youki::YoukiRuntime;
+/// use hyperbox_core::runtime::YoukiRuntime;
 /// use hyperbox_core::runtime::RuntimeConfig;
 ///
 /// let config = RuntimeConfig::default();
-/// let runtime = YoukiRuntime::new(config);
+/// let runtime = YoukiRuntime::new(config)?;
+/// # Ok::<(), hyperbox_core::error::CoreError>(())
 /// ```
 pub struct YoukiRuntime {
     /// Runtime configuration.
     config: RuntimeConfig,
     /// Resolved path to the youki binary.
     binary_path: PathBuf,
+    /// State directory passed to youki as `--root`.
+    state_dir: PathBuf,
+    /// Image store and bundles, shared with the registry's other runtimes.
+    bundles: Arc<BundleManager>,
+    /// Captures container stdout/stderr.
+    logs: LogDriver,
+    /// Container and exec stdio.
+    stdio: StdioManager,
+    /// Reaps init processes and records their exit status.
+    monitor: ExitMonitor,
+    /// Specs, names and lifecycle times of containers.
+    metadata: MetadataStore,
+    /// Freezes container cgroups for pause and resume.
+    cgroups: CgroupManager,
 }
 
 impl YoukiRuntime {
@@ -72,15 +96,38 @@ impl YoukiRuntime {
     ///
     /// Attempts to find the youki binary in standard locations or PATH.
     /// Falls back to `config.binary_path` if provided.
-    pub fn new(config: RuntimeConfig) -> Self {
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the image store cannot be opened.
+    pub fn new(config: RuntimeConfig) -> Result<Self> {
+        let bundles = Arc::new(BundleManager::new(&config.root_dir)?);
+        Ok(Self::with_bundles(config, bundles))
+    }
+
+    /// Create an instance keeping images and bundles in `bundles`.
+    pub(crate) fn with_bundles(config: RuntimeConfig, bundles: Arc<BundleManager>) -> Self {
         let binary_path = config
             .binary_path
             .clone()
             .unwrap_or_else(|| Self::find_binary().unwrap_or_else(|| PathBuf::from("youki")));
 
+        let state_dir = config.root_dir.join("state").join("youki");
+        let logs = LogDriver::new(config.root_dir.join("logs"), LogConfig::default());
+        let stdio = StdioManager::new(config.root_dir.join("console"));
+        let monitor = ExitMonitor::new(config.root_dir.join("exits"));
+        let metadata = MetadataStore::open(config.root_dir.join("metadata").join("youki"));
+
         Self {
             config,
             binary_path,
+            state_dir,
+            bundles,
+            logs,
+            stdio,
+            monitor,
+            metadata,
+            cgroups: CgroupManager::new(),
         }
     }
 
@@ -115,7 +162,7 @@ impl YoukiRuntime {
         let mut cmd = tokio::process::Command::new(&self.binary_path);
 
         // Set root directory
-        cmd.arg("--root").arg(&self.config.root_dir);
+        cmd.arg("--root").arg(&self.state_dir);
 
         // Enable debug logging if configured
         if self.config.debug {
@@ -147,263 +194,223 @@ impl YoukiRuntime {
         Ok(output)
     }
 
-    /// Generate an OCI bundle directory with config.json for the given spec.
-    async fn generate_bundle(&self, spec: &ContainerSpec) -> Result<PathBuf> {
-        let bundle_dir = std::env::temp_dir()
-            .join("hyperbox")
-            .join("bundles")
-            .join(uuid::Uuid::new_v4().to_string());
+    /// `youki exec` options for the environment, user and working directory.
+    fn exec_options(spec: &ExecSpec) -> Vec<String> {
+        let mut args = Vec::new();
 
-        tokio::fs::create_dir_all(&bundle_dir).await?;
+        for (key, value) in &spec.env {
+            args.push("--env".to_string());
+            args.push(format!("{key}={value}"));
+        }
 
-        let rootfs_dir = bundle_dir.join("rootfs");
-        tokio::fs::create_dir_all(&rootfs_dir).await?;
+        if let Some(ref user) = spec.user {
+            args.push("--user".to_string());
+            args.push(user.clone());
+        }
 
-        // Generate OCI runtime config
-        let oci_config = self.spec_to_oci(spec);
-        let config_json = serde_json::to_string_pretty(&oci_config)?;
-        tokio::fs::write(bundle_dir.join("config.json"), config_json).await?;
+        if let Some(ref cwd) = spec.working_dir {
+            args.push("--cwd".to_string());
+            args.push(cwd.to_string_lossy().to_string());
+        }
 
-        debug!(bundle = ?bundle_dir, "Generated OCI bundle");
-        Ok(bundle_dir)
+        args
     }
 
-    /// Convert a HyperBox ContainerSpec to an OCI Runtime Spec JSON value.
-    fn spec_to_oci(&self, spec: &ContainerSpec) -> serde_json::Value {
-        let mut env: Vec<String> = spec
-            .env
-            .iter()
-            .map(|(k, v)| format!("{k}={v}"))
-            .collect();
+    /// Run `youki create` with the container's stdio captured by the log
+    /// driver and available to attach. youki's own errors go to `youki.log`
+    /// in the bundle.
+    async fn create_container(
+        &self,
+        id: &ContainerId,
+        bundle: &Path,
+        spec: &ContainerSpec,
+    ) -> Result<()> {
+        let timeout = Duration::from_secs(self.config.timeout_seconds);
+        let log_file = bundle.join("youki.log");
 
-        // Ensure PATH is set
-        if !spec.env.contains_key("PATH") {
-            env.push("PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin".into());
-        }
-
-        let mut args: Vec<String> = spec.command.clone();
-        args.extend(spec.args.iter().cloned());
-        if args.is_empty() {
-            args.push("/bin/sh".to_string());
-        }
-
-        let mut mounts = vec![
-            serde_json::json!({
-                "destination": "/proc",
-                "type": "proc",
-                "source": "proc"
-            }),
-            serde_json::json!({
-                "destination": "/dev",
-                "type": "tmpfs",
-                "source": "tmpfs",
-                "options": ["nosuid", "strictatime", "mode=755", "size=65536k"]
-            }),
-            serde_json::json!({
-                "destination": "/dev/pts",
-                "type": "devpts",
-                "source": "devpts",
-                "options": ["nosuid", "noexec", "newinstance", "ptmxmode=0666", "mode=0620"]
-            }),
-            serde_json::json!({
-                "destination": "/dev/shm",
-                "type": "tmpfs",
-                "source": "shm",
-                "options": ["nosuid", "noexec", "nodev", "mode=1777", "size=65536k"]
-            }),
-            serde_json::json!({
-                "destination": "/sys",
-                "type": "sysfs",
-                "source": "sysfs",
-                "options": ["nosuid", "noexec", "nodev", "ro"]
-            }),
-        ];
+        let mut cmd = tokio::process::Command::new(&self.binary_path);
+        cmd.arg("--root").arg(&self.state_dir);
+        if self.config.debug {
+            cmd.arg("--debug");
+        }
+        cmd.arg("--log")
+            .arg(&log_file)
+            .arg("create")
+            .arg("--bundle")
+            .arg(bundle);
+
+        debug!(binary = ?self.binary_path, container_id = %id, "Executing youki create");
+
+        let status = self
+            .stdio
+            .run_create(id, cmd, spec, &self.logs, timeout)
+            .await?;
+
+        if !status.success() {
+            let reason = tokio::fs::read_to_string(&log_file).await.unwrap_or_default();
+            return Err(CoreError::RuntimeExecution(format!(
+                "youki create exited with {status}: {}",
+                reason.trim()
+            )));
+        }
 
-        // Add user-specified mounts
-        for mount in &spec.mounts {
-            let mut opts = vec!["rbind"];
-            if mount.read_only {
-                opts.push("ro");
-            }
-            mounts.push(serde_json::json!({
-                "destination": mount.target.to_string_lossy(),
-                "type": "bind",
-                "source": mount.source.to_string_lossy(),
-                "options": opts,
-            }));
-        }
-
-        // Build Linux-specific config
-        let linux = self.build_linux_config(spec);
-
-        let mut config = serde_json::json!({
-            "ociVersion": "1.0.2",
-            "process": {
-                "terminal": spec.tty,
-                "user": {
-                    "uid": 0,
-                    "gid": 0
-                },
-                "args": args,
-                "env": env,
-                "cwd": spec.working_dir.as_ref()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "/".to_string()),
-                "capabilities": {
-                    "bounding": self.default_capabilities(),
-                    "effective": self.default_capabilities(),
-                    "inheritable": self.default_capabilities(),
-                    "permitted": self.default_capabilities(),
-                    "ambient": self.default_capabilities(),
-                },
-                "rlimits": [{
-                    "type": "RLIMIT_NOFILE",
-                    "hard": 1024u64,
-                    "soft": 1024u64
-                }],
-                "noNewPrivileges": !spec.privileged,
-            },
-            "root": {
-                "path": "rootfs",
-                "readonly": spec.read_only_rootfs,
-            },
-            "hostname": spec.hostname.as_deref().unwrap_or("hyperbox"),
-            "mounts": mounts,
-            "linux": linux,
-        });
+        Ok(())
+    }
 
-        // Set user if specified
-        if let Some(ref user) = spec.user {
-            if let Some((uid_str, gid_str)) = user.split_once(':') {
-                if let (Ok(uid), Ok(gid)) = (uid_str.parse::<u32>(), gid_str.parse::<u32>()) {
-                    config["process"]["user"]["uid"] = serde_json::json!(uid);
-                    config["process"]["user"]["gid"] = serde_json::json!(gid);
-                }
-            }
-        }
+    /// PID of the container's init process, as seen from the host.
+    async fn init_pid(&self, id: &ContainerId) -> Result<u32> {
+        let output = self.run_youki(&["state", id.as_str()]).await?;
+        let state: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+        state["pid"]
+            .as_u64()
+            .and_then(|pid| u32::try_from(pid).ok())
+            .filter(|pid| *pid > 0)
+            .ok_or_else(|| {
+                CoreError::RuntimeExecution(format!("youki state reported no pid for {id}"))
+            })
+    }
 
-        config
-    }
-
-    /// Build Linux-specific OCI config section.
-    fn build_linux_config(&self, spec: &ContainerSpec) -> serde_json::Value {
-        let mut linux = serde_json::json!({
-            "namespaces": [
-                { "type": "pid" },
-                { "type": "network" },
-                { "type": "ipc" },
-                { "type": "uts" },
-                { "type": "mount" },
-                { "type": "cgroup" },
-            ],
-            "maskedPaths": [
-                "/proc/acpi",
-                "/proc/asound",
-                "/proc/kcore",
-                "/proc/keys",
-                "/proc/latency_stats",
-                "/proc/timer_list",
-                "/proc/timer_stats",
-                "/proc/sched_debug",
-                "/sys/firmware",
-                "/proc/scsi",
-            ],
-            "readonlyPaths": [
-                "/proc/bus",
-                "/proc/fs",
-                "/proc/irq",
-                "/proc/sys",
-                "/proc/sysrq-trigger",
-            ],
-        });
+    /// Hand the container's init process to the exit monitor.
+    async fn monitor_init(&self, id: &ContainerId) -> Result<()> {
+        let pid = self.init_pid(id).await?;
+        self.monitor.watch(id, pid).await;
+        Ok(())
+    }
 
-        // Apply resource limits via cgroup v2
-        let resources = self.resources_to_oci(&spec.resources);
-        if !resources.is_null() {
-            linux["resources"] = resources;
+    /// Bundle directory of a container, as reported by the runtime.
+    async fn bundle_path(&self, id: &ContainerId) -> Result<PathBuf> {
+        if let Some(bundle) = self.metadata.get(id).and_then(|meta| meta.bundle) {
+            return Ok(bundle);
         }
-
-        linux
+        let output = self.run_youki(&["state", id.as_str()]).await?;
+        let state: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+        state["bundle"].as_str().map(PathBuf::from).ok_or_else(|| {
+            CoreError::RuntimeExecution(format!("youki state reported no bundle for {id}"))
+        })
     }
 
-    /// Convert ResourceLimits to OCI cgroup v2 resource config.
-    fn resources_to_oci(&self, limits: &ResourceLimits) -> serde_json::Value {
-        let mut resources = serde_json::json!({});
+    /// The signal that stops a container, recorded in its `config.json`.
+    async fn stop_signal(&self, id: &ContainerId) -> String {
+        let signal = match self.bundle_path(id).await {
+            Ok(bundle) => read_config(&bundle)
+                .await
+                .map(|spec| stop_signal(&spec).to_string()),
+            Err(e) => Err(e),
+        };
+        signal.unwrap_or_else(|e| {
+            debug!(container_id = %id, "Cannot read stop signal: {}", e);
+            DEFAULT_STOP_SIGNAL.to_string()
+        })
+    }
 
-        if let Some(cpu_millicores) = limits.cpu_millicores {
-            // Convert millicores to cgroup v2 cpu.max format
-            // 1000 millicores = 1 CPU = period 100000, quota 100000
-            let period = 100_000u64;
-            let quota = (cpu_millicores * period) / 1000;
-            resources["cpu"] = serde_json::json!({
-                "quota": quota,
-                "period": period,
-            });
-        }
+    /// Recreate a stopped container from its bundle so it can be started
+    /// again; like other OCI runtimes, youki cannot restart it in place.
+    async fn recreate(&self, id: &ContainerId) -> Result<()> {
+        let bundle = self.bundle_path(id).await?;
+        let spec = if let Some(meta) = self.metadata.get(id) {
+            meta.spec
+        } else {
+            let tty = read_config(&bundle)
+                .await?
+                .process()
+                .as_ref()
+                .and_then(oci_spec::runtime::Process::terminal)
+                .unwrap_or(false);
+            ContainerSpec {
+                tty,
+                ..ContainerSpec::default()
+            }
+        };
 
-        if let Some(memory) = limits.memory_bytes {
-            resources["memory"] = serde_json::json!({
-                "limit": memory,
-            });
-            if let Some(swap) = limits.memory_swap_bytes {
-                resources["memory"]["swap"] = serde_json::json!(swap);
+        debug!(container_id = %id, "Recreating stopped container via youki");
+        if let Err(e) = self.run_youki(&["delete", id.as_str()]).await {
+            if !self.lost_runtime_state(id).await {
+                return Err(e);
             }
         }
+        self.stdio.remove(id);
+        self.monitor.remove(id).await?;
 
-        if let Some(pids) = limits.pids_limit {
-            resources["pids"] = serde_json::json!({
-                "limit": pids,
-            });
+        self.create_container(id, &bundle, &spec).await?;
+        if let Err(e) = self.monitor_init(id).await {
+            warn!(container_id = %id, "Exit status will not be recorded: {}", e);
         }
+        Ok(())
+    }
 
-        if limits.io_read_bps.is_some() || limits.io_write_bps.is_some() {
-            let mut io = serde_json::json!({});
-            if let Some(read_bps) = limits.io_read_bps {
-                io["weight"] = serde_json::json!(read_bps.min(10000));
-            }
-            resources["blockIO"] = io;
+    /// Whether a container is recorded but unknown to youki, whose state
+    /// directory was lost, e.g. on reboot.
+    async fn lost_runtime_state(&self, id: &ContainerId) -> bool {
+        self.metadata.get(id).is_some() && self.run_youki(&["state", id.as_str()]).await.is_err()
+    }
+
+    /// Recorded metadata of a container, including its exit once the
+    /// monitor has seen it.
+    async fn recorded(&self, id: &ContainerId) -> Result<ContainerMetadata> {
+        if let Ok(Some(exit)) = self.monitor.exit_status(id).await {
+            self.record_exit(id, &exit).await;
         }
+        self.metadata
+            .get(id)
+            .ok_or_else(|| CoreError::ContainerNotFound(id.to_string()))
+    }
 
-        resources
+    async fn record_exit(&self, id: &ContainerId, exit: &ContainerExit) {
+        if let Err(e) = self.metadata.record_exit(id, exit).await {
+            debug!(container_id = %id, "Cannot record exit in metadata: {}", e);
+        }
     }
 
-    /// Default container capabilities (non-privileged).
-    fn default_capabilities(&self) -> Vec<&'static str> {
-        vec![
-            "CAP_CHOWN",
-            "CAP_DAC_OVERRIDE",
-            "CAP_FSETID",
-            "CAP_FOWNER",
-            "CAP_MKNOD",
-            "CAP_NET_RAW",
-            "CAP_SETGID",
-            "CAP_SETUID",
-            "CAP_SETFCAP",
-            "CAP_SETPCAP",
-            "CAP_NET_BIND_SERVICE",
-            "CAP_SYS_CHROOT",
-            "CAP_KILL",
-            "CAP_AUDIT_WRITE",
-        ]
+    /// Resolve the image, assemble the root filesystem and write
+    /// `config.json` for a new container.
+    async fn generate_bundle(&self, id: &ContainerId, spec: &ContainerSpec) -> Result<PathBuf> {
+        let bundle = self.bundles.prepare(id, spec).await?;
+
+        let oci_spec = OciSpecBuilder::new(&bundle.spec).rootfs(&bundle.rootfs).build();
+        let written = match oci_spec {
+            Ok(oci_spec) => write_config(&bundle.path, &oci_spec).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            let _ = self.bundles.cleanup(id).await;
+            return Err(e);
+        }
+
+        debug!(bundle = ?bundle.path, "Generated OCI bundle");
+        Ok(bundle.path)
     }
 
-    /// Read cgroup v2 stats for a container.
-    async fn read_cgroup_stats(&self, id: &ContainerId) -> Result<YoukiCgroupStats> {
+    /// Locate a container's cgroup v2 directory.
+    fn cgroup_path(&self, id: &ContainerId) -> Option<PathBuf> {
         let possible_paths = [
-            PathBuf::from("/sys/fs/cgroup/hyperbox.slice")
-                .join(format!("container-{}", id.as_str())),
+            self.cgroups.container_cgroup_path(id.as_str()),
             PathBuf::from("/sys/fs/cgroup/system.slice")
                 .join(format!("youki-{}.scope", id.as_str())),
             PathBuf::from("/sys/fs/cgroup").join(id.as_str()),
         ];
 
-        for cgroup_path in &possible_paths {
-            if cgroup_path.exists() {
-                return self.read_stats_from_path(cgroup_path).await;
+        possible_paths.into_iter().find(|path| path.exists())
+    }
+
+    /// Thaw a paused container so a signal just sent to it is delivered.
+    async fn thaw_for_signal(&self, id: &ContainerId) {
+        let Some(cgroup_path) = self.cgroup_path(id) else {
+            return;
+        };
+        if matches!(self.cgroups.is_frozen(&cgroup_path).await, Ok(true)) {
+            debug!(container_id = %id, "Thawing paused container to deliver stop signal");
+            if let Err(e) = self.cgroups.thaw(&cgroup_path).await {
+                warn!(container_id = %id, "Failed to thaw container: {}", e);
             }
         }
+    }
 
-        Ok(YoukiCgroupStats::default())
+    /// Read cgroup v2 stats for a container.
+    async fn read_cgroup_stats(&self, id: &ContainerId) -> Result<YoukiCgroupStats> {
+        match self.cgroup_path(id) {
+            Some(cgroup_path) => self.read_stats_from_path(&cgroup_path).await,
+            None => Ok(YoukiCgroupStats::default()),
+        }
     }
 
     /// Read cgroup v2 stats from a specific path.
@@ -432,6 +439,8 @@ impl YoukiRuntime {
             stats.pids_current = content.trim().parse().unwrap_or(0);
         }
 
+        stats.events = self.cgroups.read_events(path).await;
+
         Ok(stats)
     }
 }
@@ -457,15 +466,29 @@ impl ContainerRuntime for YoukiRuntime {
         let id = ContainerId::new();
         info!(container_id = %id, "Creating container via youki");
 
-        let bundle = self.generate_bundle(&spec).await?;
+        let meta = ContainerMetadata::new(id.clone(), spec.clone())
+            .with_bundle(self.bundles.bundle_dir(&id));
+        self.metadata.insert(meta).await?;
 
-        self.run_youki(&[
-            "create",
-            "--bundle",
-            bundle.to_str().unwrap(),
-            id.as_str(),
-        ])
-        .await?;
+        let bundle = match self.generate_bundle(&id, &spec).await {
+            Ok(bundle) => bundle,
+            Err(e) => {
+                let _ = self.metadata.remove(&id).await;
+                return Err(e);
+            }
+        };
+
+        if let Err(e) = self.create_container(&id, &bundle, &spec).await {
+            self.stdio.remove(&id);
+            let _ = self.logs.remove(&id).await;
+            let _ = self.bundles.cleanup(&id).await;
+            let _ = self.metadata.remove(&id).await;
+            return Err(e);
+        }
+
+        if let Err(e) = self.monitor_init(&id).await {
+            warn!(container_id = %id, "Exit status will not be recorded: {}", e);
+        }
 
         info!(container_id = %id, "Container created via youki");
         Ok(id)
@@ -474,7 +497,13 @@ impl ContainerRuntime for YoukiRuntime {
     #[instrument(skip(self))]
     async fn start(&self, id: &ContainerId) -> Result<()> {
         info!(container_id = %id, "Starting container via youki");
+        if matches!(self.state(id).await?, ContainerState::Stopped | ContainerState::Exited) {
+            self.recreate(id).await?;
+        }
         self.run_youki(&["start", id.as_str()]).await?;
+        if let Err(e) = self.metadata.mark_started(id).await {
+            warn!(container_id = %id, "Cannot record container start: {}", e);
+        }
         info!(container_id = %id, "Container started");
         Ok(())
     }
@@ -483,8 +512,9 @@ impl ContainerRuntime for YoukiRuntime {
     async fn stop(&self, id: &ContainerId, timeout: Duration) -> Result<()> {
         info!(container_id = %id, "Stopping container via youki");
 
-        // SIGTERM first
-        let _ = self.kill(id, "SIGTERM").await;
+        let signal = self.stop_signal(id).await;
+        let _ = self.kill(id, &signal).await;
+        self.thaw_for_signal(id).await;
 
         // Wait for graceful shutdown
         let start = std::time::Instant::now();
@@ -509,16 +539,36 @@ impl ContainerRuntime for YoukiRuntime {
 
     async fn remove(&self, id: &ContainerId) -> Result<()> {
         info!(container_id = %id, "Removing container via youki");
-        self.run_youki(&["delete", "--force", id.as_str()]).await?;
-        Ok(())
+        if let Err(e) = self.run_youki(&["delete", "--force", id.as_str()]).await {
+            if !self.lost_runtime_state(id).await {
+                return Err(e);
+            }
+        }
+        self.stdio.remove(id);
+        self.monitor.remove(id).await?;
+        self.logs.remove(id).await?;
+        self.bundles.cleanup(id).await?;
+        self.metadata.remove(id).await
     }
 
     async fn pause(&self, id: &ContainerId) -> Result<()> {
+        if !matches!(self.state(id).await?, ContainerState::Running) {
+            return Err(CoreError::ContainerNotRunning(id.to_string()));
+        }
+        info!(container_id = %id, "Pausing container via youki");
+        if let Some(cgroup_path) = self.cgroup_path(id) {
+            return self.cgroups.freeze(&cgroup_path).await;
+        }
+        // No cgroup v2 hierarchy; let youki use its own freezer.
         self.run_youki(&["pause", id.as_str()]).await?;
         Ok(())
     }
 
     async fn resume(&self, id: &ContainerId) -> Result<()> {
+        info!(container_id = %id, "Resuming container via youki");
+        if let Some(cgroup_path) = self.cgroup_path(id) {
+            return self.cgroups.thaw(&cgroup_path).await;
+        }
         self.run_youki(&["resume", id.as_str()]).await?;
         Ok(())
     }
@@ -531,21 +581,7 @@ impl ContainerRuntime for YoukiRuntime {
             args.push("--tty".to_string());
         }
 
-        for (key, value) in &spec.env {
-            args.push("--env".to_string());
-            args.push(format!("{key}={value}"));
-        }
-
-        if let Some(ref user) = spec.user {
-            args.push("--user".to_string());
-            args.push(user.clone());
-        }
-
-        if let Some(ref cwd) = spec.working_dir {
-            args.push("--cwd".to_string());
-            args.push(cwd.to_string_lossy().to_string());
-        }
-
+        args.extend(Self::exec_options(&spec));
         args.push(id.to_string());
         args.extend(spec.command.iter().cloned());
 
@@ -560,7 +596,15 @@ impl ContainerRuntime for YoukiRuntime {
     }
 
     async fn state(&self, id: &ContainerId) -> Result<ContainerState> {
-        let output = self.run_youki(&["state", id.as_str()]).await?;
+        let output = match self.run_youki(&["state", id.as_str()]).await {
+            Ok(output) => output,
+            // youki lost its state, e.g. on reboot, but the container is
+            // still recorded.
+            Err(CoreError::RuntimeExecution(_)) if self.metadata.get(id).is_some() => {
+                return Ok(ContainerState::Exited);
+            }
+            Err(e) => return Err(e),
+        };
         let state_json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
 
         let status = state_json["status"].as_str().unwrap_or("unknown");
@@ -578,14 +622,15 @@ impl ContainerRuntime for YoukiRuntime {
     async fn stats(&self, id: &ContainerId) -> Result<ContainerStats> {
         let cgroup_stats = self.read_cgroup_stats(id).await;
 
-        let (memory_usage, memory_limit, cpu_usage_usec, pids) = match cgroup_stats {
+        let (memory_usage, memory_limit, cpu_usage_usec, pids, events) = match cgroup_stats {
             Ok(stats) => (
                 stats.memory_usage,
                 stats.memory_limit,
                 stats.cpu_usage_usec,
                 stats.pids_current,
+                stats.events,
             ),
-            Err(_) => (0, 0, 0, 0),
+            Err(_) => (0, 0, 0, 0, ResourceEvents::default()),
         };
 
         let memory_percent = if memory_limit > 0 && memory_limit < u64::MAX {
@@ -625,35 +670,42 @@ impl ContainerRuntime for YoukiRuntime {
                 write_ops: 0,
             },
             pids,
+            events,
         })
     }
 
     async fn logs(
         &self,
-        _id: &ContainerId,
-        _opts: LogOptions,
+        id: &ContainerId,
+        opts: LogOptions,
     ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
-        // Youki logs are typically handled by the container log driver.
-        // Return empty reader; real log retrieval would come from the
-        // configured log path (root_dir/<id>/log).
-        let empty: &[u8] = &[];
-        Ok(Box::new(std::io::Cursor::new(empty.to_vec())))
+        self.logs.read(id, &opts).await
     }
 
     async fn attach(
         &self,
-        _id: &ContainerId,
+        id: &ContainerId,
     ) -> Result<(
         Box<dyn AsyncWrite + Send + Unpin>,
         Box<dyn AsyncRead + Send + Unpin>,
         Box<dyn AsyncRead + Send + Unpin>,
     )> {
-        // Attach requires console socket integration with youki.
-        // This would be implemented via the youki `--console-socket` flag
-        // and Unix domain socket communication.
-        Err(CoreError::Internal(
-            "youki attach requires console socket setup (not yet implemented)".to_string(),
-        ))
+        self.stdio.attach(id)
+    }
+
+    #[instrument(skip(self, spec))]
+    async fn exec_interactive(&self, id: &ContainerId, spec: ExecSpec) -> Result<ExecSession> {
+        let mut cmd = tokio::process::Command::new(&self.binary_path);
+        cmd.arg("--root")
+            .arg(&self.state_dir)
+            .arg("exec")
+            .args(Self::exec_options(&spec));
+
+        self.stdio.run_exec(id, cmd, &spec).await
+    }
+
+    async fn resize(&self, id: &ContainerId, exec_id: Option<&str>, size: TtySize) -> Result<()> {
+        self.stdio.resize(id, exec_id, size)
     }
 
     async fn list(&self) -> Result<Vec<(ContainerId, ContainerState)>> {
@@ -675,15 +727,39 @@ impl ContainerRuntime for YoukiRuntime {
             }
         }
 
-        Ok(result)
+        Ok(self.metadata.with_recorded(result))
+    }
+
+    async fn inspect(&self, id: &ContainerId) -> Result<ContainerMetadata> {
+        self.recorded(id).await
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<ContainerMetadata>> {
+        let mut records = Vec::new();
+        for meta in self.metadata.list() {
+            records.push(self.recorded(&meta.id).await.unwrap_or(meta));
+        }
+        Ok(records)
+    }
+
+    async fn lookup(&self, name: &str) -> Result<Option<ContainerId>> {
+        Ok(self.metadata.lookup(name))
     }
 
     async fn wait(&self, id: &ContainerId) -> Result<i32> {
+        if let Some(exit) = self.monitor.wait(id).await? {
+            self.record_exit(id, &exit).await;
+            return Ok(exit.exit_code);
+        }
+
+        // Not monitored by this process, e.g. created before a daemon restart.
         loop {
             let state = self.state(id).await?;
             match state {
                 ContainerState::Exited | ContainerState::Stopped => {
-                    return Ok(0);
+                    return Err(CoreError::Internal(format!(
+                        "exit status of container {id} was not recorded"
+                    )));
                 }
                 ContainerState::Unknown => {
                     return Err(CoreError::ContainerNotFound(id.to_string()));
@@ -695,6 +771,24 @@ impl ContainerRuntime for YoukiRuntime {
         }
     }
 
+    async fn exit_status(&self, id: &ContainerId) -> Result<Option<ContainerExit>> {
+        self.monitor.exit_status(id).await
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn resource_events(
+        &self,
+        id: &ContainerId,
+    ) -> Result<BoxStream<'static, ResourceEvents>> {
+        let cgroup_path = self
+            .cgroup_path(id)
+            .ok_or_else(|| CoreError::CgroupOperation {
+                operation: "watch cgroup events".to_string(),
+                reason: format!("no cgroup found for container {id}"),
+            })?;
+        self.cgroups.watch_events(&cgroup_path)
+    }
+
     #[instrument(skip(self))]
     async fn checkpoint(&self, id: &ContainerId, checkpoint_path: &Path) -> Result<CheckpointId> {
         tokio::fs::create_dir_all(checkpoint_path).await?;
@@ -720,52 +814,93 @@ impl ContainerRuntime for YoukiRuntime {
 
     async fn restore(&self, checkpoint_path: &Path, spec: ContainerSpec) -> Result<ContainerId> {
         let id = ContainerId::new();
-        let bundle = self.generate_bundle(&spec).await?;
+        let meta = ContainerMetadata::new(id.clone(), spec.clone())
+            .with_bundle(self.bundles.bundle_dir(&id));
+        self.metadata.insert(meta).await?;
+
+        let bundle = match self.generate_bundle(&id, &spec).await {
+            Ok(bundle) => bundle,
+            Err(e) => {
+                let _ = self.metadata.remove(&id).await;
+                return Err(e);
+            }
+        };
 
-        self.run_youki(&[
-            "restore",
-            "--image-path",
-            checkpoint_path.to_str().unwrap(),
-            "--bundle",
-            bundle.to_str().unwrap(),
-            id.as_str(),
-        ])
-        .await?;
+        if let Err(e) = self
+            .run_youki(&[
+                "restore",
+                "--image-path",
+                checkpoint_path.to_str().unwrap(),
+                "--bundle",
+                bundle.to_str().unwrap(),
+                id.as_str(),
+            ])
+            .await
+        {
+            let _ = self.bundles.cleanup(&id).await;
+            let _ = self.metadata.remove(&id).await;
+            return Err(e);
+        }
+        if let Err(e) = self.metadata.mark_started(&id).await {
+            warn!(container_id = %id, "Cannot record container start: {}", e);
+        }
+        if let Err(e) = self.monitor_init(&id).await {
+            warn!(container_id = %id, "Exit status will not be recorded: {}", e);
+        }
 
         info!(container_id = %id, "Restored from checkpoint via youki");
         Ok(id)
     }
 
-    async fn update(&self, id: &ContainerId, resources: ResourceLimits) -> Result<()> {
-        let mut args = vec!["update".to_string()];
-
-        if let Some(memory) = resources.memory_bytes {
-            args.push("--memory".to_string());
-            args.push(memory.to_string());
-        }
-
-        if let Some(cpu) = resources.cpu_millicores {
-            // Convert millicores to cpu-shares (1000mc = 1024 shares)
-            args.push("--cpu-shares".to_string());
-            args.push((cpu * 1024 / 1000).to_string());
-        }
-
-        if let Some(pids) = resources.pids_limit {
-            args.push("--pids-limit".to_string());
-            args.push(pids.to_string());
-        }
-
-        args.push(id.to_string());
+    async fn update(
+        &self,
+        id: &ContainerId,
+        resources: ResourceLimits,
+    ) -> Result<ResourceUpdateReport> {
+        let bundle = self.bundle_path(id).await?;
+        let rootfs = read_config(&bundle)
+            .await?
+            .root()
+            .as_ref()
+            .map_or_else(|| bundle.join("rootfs"), |root| bundle.join(root.path()));
+        let (oci, report) = update_resources(&resources, &rootfs)?;
+
+        // The new limits are passed as an OCI `linux.resources` document.
+        let file = bundle.join("update-resources.json");
+        let path = file.to_string_lossy();
+        tokio::fs::write(&file, serde_json::to_vec(&oci)?).await?;
+        let result = self
+            .run_youki(&["update", "--resources", &path, id.as_str()])
+            .await;
+        let _ = tokio::fs::remove_file(&file).await;
+        result?;
+
+        debug!(container_id = %id, applied = ?report.applied, "Updated resource limits");
+        Ok(report)
+    }
 
-        let args_refs: Vec<&str> = args.iter().map(String::as_str).collect();
-        self.run_youki(&args_refs).await?;
+    async fn top(&self, id: &ContainerId) -> Result<Vec<ProcessInfo>> {
+        let pid = self.init_pid(id).await?;
+        container_processes(pid).await
+    }
 
-        Ok(())
+    async fn archive_path(
+        &self,
+        id: &ContainerId,
+        path: &Path,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let fs = ContainerFs::from_bundle(&self.bundle_path(id).await?).await?;
+        while_frozen(self, id, fs.archive(path)).await
     }
 
-    async fn top(&self, _id: &ContainerId) -> Result<Vec<ProcessInfo>> {
-        // Would read /proc/<pid>/task for the container's init PID
-        Ok(Vec::new())
+    async fn extract_archive(
+        &self,
+        id: &ContainerId,
+        path: &Path,
+        archive: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> Result<()> {
+        let fs = ContainerFs::from_bundle(&self.bundle_path(id).await?).await?;
+        while_frozen(self, id, fs.extract(path, archive)).await
     }
 
     async fn pull_image(&self, _image: &ImageRef) -> Result<()> {
@@ -795,10 +930,15 @@ mod tests {
     use super::*;
     use crate::types::ImageRef;
 
+    /// The OCI spec youki receives for `spec`, as JSON.
+    fn oci_json(spec: &ContainerSpec) -> serde_json::Value {
+        serde_json::to_value(OciSpecBuilder::new(spec).build().unwrap()).unwrap()
+    }
+
     #[test]
     fn test_youki_runtime_creation() {
         let config = RuntimeConfig::default();
-        let runtime = YoukiRuntime::new(config);
+        let runtime = YoukiRuntime::new(config).unwrap();
         assert_eq!(runtime.name(), "youki");
     }
 
@@ -808,21 +948,31 @@ mod tests {
             binary_path: Some(PathBuf::from("/custom/path/youki")),
             ..Default::default()
         };
-        let runtime = YoukiRuntime::new(config);
+        let runtime = YoukiRuntime::new(config).unwrap();
         assert_eq!(runtime.binary_path, PathBuf::from("/custom/path/youki"));
     }
 
     #[test]
-    fn test_spec_to_oci_basic() {
-        let config = RuntimeConfig::default();
-        let runtime = YoukiRuntime::new(config);
+    fn test_bundles_live_under_root_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let config = RuntimeConfig {
+            root_dir: root.path().to_path_buf(),
+            ..Default::default()
+        };
+        let runtime = YoukiRuntime::new(config).unwrap();
+
+        let id = ContainerId::new();
+        assert!(runtime.bundles.bundle_dir(&id).starts_with(root.path()));
+    }
 
+    #[test]
+    fn test_spec_to_oci_basic() {
         let spec = ContainerSpec::builder()
             .image("alpine:latest")
             .command(vec!["echo", "hello"])
             .build();
 
-        let oci = runtime.spec_to_oci(&spec);
+        let oci = oci_json(&spec);
 
         assert_eq!(oci["ociVersion"], "1.0.2");
         assert_eq!(oci["process"]["args"][0], "echo");
@@ -832,9 +982,6 @@ mod tests {
 
     #[test]
     fn test_spec_to_oci_with_resources() {
-        let config = RuntimeConfig::default();
-        let runtime = YoukiRuntime::new(config);
-
         let spec = ContainerSpec::builder()
             .image("alpine:latest")
             .resources(ResourceLimits {
@@ -845,7 +992,7 @@ mod tests {
             })
             .build();
 
-        let oci = runtime.spec_to_oci(&spec);
+        let oci = oci_json(&spec);
         let resources = &oci["linux"]["resources"];
 
         // 500 millicores = quota 50000 / period 100000
@@ -857,25 +1004,19 @@ mod tests {
 
     #[test]
     fn test_spec_to_oci_with_user() {
-        let config = RuntimeConfig::default();
-        let runtime = YoukiRuntime::new(config);
-
         let mut spec = ContainerSpec::builder()
             .image("alpine:latest")
             .command(vec!["/bin/sh"])
             .build();
         spec.user = Some("1000:1000".to_string());
 
-        let oci = runtime.spec_to_oci(&spec);
+        let oci = oci_json(&spec);
         assert_eq!(oci["process"]["user"]["uid"], 1000);
         assert_eq!(oci["process"]["user"]["gid"], 1000);
     }
 
     #[test]
     fn test_spec_to_oci_with_mounts() {
-        let config = RuntimeConfig::default();
-        let runtime = YoukiRuntime::new(config);
-
         let spec = ContainerSpec::builder()
             .image("alpine:latest")
             .mount(crate::types::Mount {
@@ -886,7 +1027,7 @@ mod tests {
             })
             .build();
 
-        let oci = runtime.spec_to_oci(&spec);
+        let oci = oci_json(&spec);
         let mounts = oci["mounts"].as_array().unwrap();
 
         // Should have default mounts + 1 user mount
@@ -903,11 +1044,8 @@ mod tests {
 
     #[test]
     fn test_spec_to_oci_namespaces() {
-        let config = RuntimeConfig::default();
-        let runtime = YoukiRuntime::new(config);
-
         let spec = ContainerSpec::builder().image("alpine:latest").build();
-        let oci = runtime.spec_to_oci(&spec);
+        let oci = oci_json(&spec);
 
         let namespaces = oci["linux"]["namespaces"].as_array().unwrap();
         let ns_types: Vec<&str> = namespaces
@@ -923,10 +1061,15 @@ mod tests {
 
     #[test]
     fn test_default_capabilities() {
-        let config = RuntimeConfig::default();
-        let runtime = YoukiRuntime::new(config);
+        let spec = ContainerSpec::builder().image("alpine:latest").build();
+        let oci = oci_json(&spec);
 
-        let caps = runtime.default_capabilities();
+        let caps: Vec<&str> = oci["process"]["capabilities"]["bounding"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|cap| cap.as_str())
+            .collect();
         assert!(caps.contains(&"CAP_NET_BIND_SERVICE"));
         assert!(caps.contains(&"CAP_SETUID"));
         assert!(caps.contains(&"CAP_SETGID"));
@@ -937,14 +1080,14 @@ mod tests {
 
     #[test]
     fn test_resources_to_oci_empty() {
-        let config = RuntimeConfig::default();
-        let runtime = YoukiRuntime::new(config);
-
-        let limits = ResourceLimits::default();
-        let resources = runtime.resources_to_oci(&limits);
+        let spec = ContainerSpec::builder()
+            .image("alpine:latest")
+            .resources(ResourceLimits::default())
+            .build();
+        let oci = oci_json(&spec);
 
         // Should still have pids (from ResourceLimits default)
-        assert!(resources["pids"]["limit"].is_number());
+        assert!(oci["linux"]["resources"]["pids"]["limit"].is_number());
     }
 
     #[test]
@@ -959,7 +1102,7 @@ mod tests {
             binary_path: Some(PathBuf::from("/nonexistent/youki")),
             ..Default::default()
         };
-        let runtime = YoukiRuntime::new(config);
+        let runtime = YoukiRuntime::new(config).unwrap();
         assert!(!runtime.is_available().await);
     }
 }
@@ -0,0 +1,422 @@
+//! Container filesystem changes.
+//!
+//! A container's writes land in the upper directory of its overlay mount
+//! (see [`LayerStore::mount_overlay`](super::LayerStore::mount_overlay)).
+//! Overlayfs records deletions there as whiteouts: a `0:0` character device
+//! in place of a removed path, and an `overlay.opaque` extended attribute on
+//! a directory that replaces the lower one entirely.
+//!
+//! [`changes`] compares the upper directory against the image layers, and
+//! [`pack`] turns it into an OCI layer tarball, where whiteouts are empty
+//! `.wh.<name>` files and opaque directories contain `.wh..wh..opq`.
+//! [`export`] archives the merged view instead, flattening the container's
+//! filesystem into a single tree.
+
+use crate::error::{CoreError, Result};
+use crate::types::{ChangeKind, FilesystemChange};
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Prefix of OCI whiteout files.
+pub const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// OCI marker for an opaque directory.
+pub const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+/// Extended attributes overlayfs sets on opaque directories. The `user.`
+/// variant is used by unprivileged (`userxattr`) mounts.
+const OPAQUE_XATTRS: [&str; 2] = ["trusted.overlay.opaque", "user.overlay.opaque"];
+
+/// List the paths an overlay upper directory changes.
+///
+/// `lowers` are the unpacked image layers, top layer first. Paths that do
+/// not exist in any lower layer are added, others are modified. Entries of
+/// an opaque directory that only exist in the layers below are reported as
+/// deleted.
+///
+/// # Errors
+///
+/// Returns error if the upper directory cannot be read.
+pub fn changes(upper: &Path, lowers: &[PathBuf]) -> Result<Vec<FilesystemChange>> {
+    let mut changes = Vec::new();
+    for entry in walk(upper) {
+        let entry = entry.map_err(|e| CoreError::StorageOperation(format!("diff: {e}")))?;
+        let relative = entry
+            .path()
+            .strip_prefix(upper)
+            .unwrap_or_else(|_| entry.path());
+        let path = container_path(relative);
+
+        if is_whiteout(entry.path())? {
+            changes.push(FilesystemChange {
+                path,
+                kind: ChangeKind::Deleted,
+            });
+            continue;
+        }
+
+        let kind = if exists_in(lowers, relative) {
+            ChangeKind::Modified
+        } else {
+            ChangeKind::Added
+        };
+        changes.push(FilesystemChange { path, kind });
+
+        if entry.file_type().is_dir() && kind == ChangeKind::Modified && is_opaque(entry.path()) {
+            for name in lower_entries(lowers, relative) {
+                if std::fs::symlink_metadata(entry.path().join(&name)).is_err() {
+                    changes.push(FilesystemChange {
+                        path: container_path(&relative.join(name)),
+                        kind: ChangeKind::Deleted,
+                    });
+                }
+            }
+        }
+    }
+
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
+
+/// Write an overlay upper directory as an uncompressed OCI layer tarball.
+///
+/// # Errors
+///
+/// Returns error if the directory cannot be read or archived.
+pub fn pack<W: Write>(upper: &Path, writer: W) -> Result<W> {
+    write_tree(upper, writer, true)
+}
+
+/// Write a directory tree, such as a container's merged root filesystem,
+/// as a tarball.
+///
+/// # Errors
+///
+/// Returns error if the directory cannot be read or archived.
+pub fn export<W: Write>(root: &Path, writer: W) -> Result<W> {
+    write_tree(root, writer, false)
+}
+
+/// Archive a directory tree, converting overlay whiteouts to OCI whiteout
+/// files if `whiteouts` is set.
+fn write_tree<W: Write>(root: &Path, writer: W, whiteouts: bool) -> Result<W> {
+    let pack_err = |e: std::io::Error| CoreError::StorageOperation(format!("archive: {e}"));
+
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    for entry in walk(root) {
+        let entry = entry.map_err(|e| CoreError::StorageOperation(format!("archive: {e}")))?;
+        let relative = entry
+            .path()
+            .strip_prefix(root)
+            .unwrap_or_else(|_| entry.path());
+
+        if whiteouts && is_whiteout(entry.path())? {
+            let mut name = OsString::from(WHITEOUT_PREFIX);
+            name.push(relative.file_name().unwrap_or_default());
+            append_marker(&mut builder, &relative.with_file_name(name)).map_err(pack_err)?;
+            continue;
+        }
+
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            builder
+                .append_dir(relative, entry.path())
+                .map_err(pack_err)?;
+            if whiteouts && is_opaque(entry.path()) {
+                append_marker(&mut builder, &relative.join(OPAQUE_WHITEOUT)).map_err(pack_err)?;
+            }
+        } else if !is_socket(file_type) {
+            // Sockets cannot be archived and are meaningless in an image.
+            builder
+                .append_path_with_name(entry.path(), relative)
+                .map_err(pack_err)?;
+        }
+    }
+
+    builder.into_inner().map_err(pack_err)
+}
+
+/// Walk a directory below its root, parents before children, in name order.
+fn walk(root: &Path) -> walkdir::IntoIter {
+    WalkDir::new(root)
+        .min_depth(1)
+        .follow_links(false)
+        .sort_by_file_name()
+        .into_iter()
+}
+
+fn container_path(relative: &Path) -> String {
+    Path::new("/").join(relative).to_string_lossy().into_owned()
+}
+
+/// Append an empty whiteout marker file.
+fn append_marker<W: Write>(builder: &mut tar::Builder<W>, path: &Path) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_size(0);
+    header.set_mode(0o644);
+    builder.append_data(&mut header, path, std::io::empty())
+}
+
+/// Whether a lower layer stack has a path.
+///
+/// Honours both overlay whiteouts and the OCI whiteout files that unpacked
+/// layers may still carry.
+fn exists_in(lowers: &[PathBuf], relative: &Path) -> bool {
+    let parent = relative.parent().unwrap_or_else(|| Path::new(""));
+    for lower in lowers {
+        let path = lower.join(relative);
+        if std::fs::symlink_metadata(&path).is_ok() {
+            return !is_whiteout(&path).unwrap_or(false);
+        }
+        if let Some(name) = relative.file_name() {
+            let mut whiteout = OsString::from(WHITEOUT_PREFIX);
+            whiteout.push(name);
+            if lower.join(parent).join(whiteout).exists() {
+                return false;
+            }
+        }
+        let dir = lower.join(parent);
+        if dir.join(OPAQUE_WHITEOUT).exists() || is_opaque(&dir) {
+            return false;
+        }
+    }
+    false
+}
+
+/// Names visible in a directory of a lower layer stack.
+fn lower_entries(lowers: &[PathBuf], relative: &Path) -> Vec<OsString> {
+    let mut names: Vec<OsString> = lowers
+        .iter()
+        .filter_map(|lower| std::fs::read_dir(lower.join(relative)).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.file_name()))
+        .filter(|name| !name.to_string_lossy().starts_with(WHITEOUT_PREFIX))
+        .collect();
+    names.sort();
+    names.dedup();
+    names.retain(|name| exists_in(lowers, &relative.join(name)));
+    names
+}
+
+#[cfg(unix)]
+fn is_whiteout(path: &Path) -> Result<bool> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let metadata = std::fs::symlink_metadata(path)?;
+    Ok(metadata.file_type().is_char_device() && metadata.rdev() == 0)
+}
+
+#[cfg(not(unix))]
+fn is_whiteout(_path: &Path) -> Result<bool> {
+    Ok(false)
+}
+
+#[cfg(unix)]
+fn is_socket(file_type: std::fs::FileType) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    file_type.is_socket()
+}
+
+#[cfg(not(unix))]
+const fn is_socket(_file_type: std::fs::FileType) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn is_opaque(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    OPAQUE_XATTRS.iter().any(|attr| {
+        let name = std::ffi::CString::new(*attr).unwrap_or_default();
+        let mut value = [0u8; 1];
+        // SAFETY: both strings are NUL-terminated and the buffer length
+        // matches the buffer.
+        #[allow(unsafe_code)]
+        let len = unsafe {
+            libc::lgetxattr(path.as_ptr(), name.as_ptr(), value.as_mut_ptr().cast(), value.len())
+        };
+        len == 1 && value[0] == b'y'
+    })
+}
+
+#[cfg(not(unix))]
+const fn is_opaque(_dir: &Path) -> bool {
+    false
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, path: &str, contents: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    /// A base layer, and an upper directory that edits, adds and deletes.
+    fn layers() -> (TempDir, PathBuf, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let lower = dir.path().join("lower");
+        let upper = dir.path().join("upper");
+        write(&lower, "etc/hosts", "127.0.0.1 localhost\n");
+        write(&lower, "etc/motd", "hello\n");
+        write(&lower, "var/cache/a", "");
+        write(&lower, "var/cache/b", "");
+
+        write(&upper, "etc/hosts", "10.0.0.1 db\n");
+        write(&upper, "srv/app/main.py", "print()\n");
+        std::fs::create_dir_all(upper.join("var/cache")).unwrap();
+        write(&upper, "var/cache/b", "");
+        (dir, lower, upper)
+    }
+
+    #[test]
+    fn test_changes_added_and_modified() {
+        let (_dir, lower, upper) = layers();
+
+        let changes = changes(&upper, &[lower]).unwrap();
+        let listed: Vec<String> = changes
+            .iter()
+            .map(|c| format!("{} {}", c.kind, c.path))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                "C /etc",
+                "C /etc/hosts",
+                "A /srv",
+                "A /srv/app",
+                "A /srv/app/main.py",
+                "C /var",
+                "C /var/cache",
+                "C /var/cache/b",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exists_in_honours_oci_whiteouts() {
+        let dir = TempDir::new().unwrap();
+        let base = dir.path().join("base");
+        let top = dir.path().join("top");
+        write(&base, "etc/motd", "hello\n");
+        write(&base, "opt/tool/bin", "");
+        write(&top, "etc/.wh.motd", "");
+        write(&top, "opt/tool/.wh..wh..opq", "");
+
+        let lowers = [top, base.clone()];
+        assert!(!exists_in(&lowers, Path::new("etc/motd")));
+        assert!(!exists_in(&lowers, Path::new("opt/tool/bin")));
+        assert!(exists_in(&lowers, Path::new("opt/tool")));
+        assert!(exists_in(&[base], Path::new("etc/motd")));
+    }
+
+    #[test]
+    fn test_pack_writes_upper_dir() {
+        let (_dir, _lower, upper) = layers();
+
+        let layer = pack(&upper, Vec::new()).unwrap();
+        let mut archive = tar::Archive::new(layer.as_slice());
+        let names: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|e| {
+                e.unwrap()
+                    .path()
+                    .unwrap()
+                    .to_string_lossy()
+                    .trim_end_matches('/')
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "etc",
+                "etc/hosts",
+                "srv",
+                "srv/app",
+                "srv/app/main.py",
+                "var",
+                "var/cache",
+                "var/cache/b",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_writes_tree() {
+        let (_dir, lower, _upper) = layers();
+
+        let archive = export(&lower, Vec::new()).unwrap();
+        let mut archive = tar::Archive::new(archive.as_slice());
+        let names: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|e| {
+                e.unwrap()
+                    .path()
+                    .unwrap()
+                    .to_string_lossy()
+                    .trim_end_matches('/')
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "etc",
+                "etc/hosts",
+                "etc/motd",
+                "var",
+                "var/cache",
+                "var/cache/a",
+                "var/cache/b",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_whiteouts() {
+        // Creating overlay whiteout devices needs CAP_MKNOD.
+        let (_dir, lower, upper) = layers();
+        if nix::sys::stat::mknod(
+            &upper.join("etc/motd"),
+            nix::sys::stat::SFlag::S_IFCHR,
+            nix::sys::stat::Mode::empty(),
+            0,
+        )
+        .is_err()
+        {
+            eprintln!("skipping: cannot create whiteout devices");
+            return;
+        }
+
+        let changes = changes(&upper, &[lower]).unwrap();
+        assert!(changes.contains(&FilesystemChange {
+            path: "/etc/motd".to_string(),
+            kind: ChangeKind::Deleted,
+        }));
+
+        let layer = pack(&upper, Vec::new()).unwrap();
+        let mut archive = tar::Archive::new(layer.as_slice());
+        let whiteout = archive
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap())
+            .find(|e| e.path().unwrap() == Path::new("etc/.wh.motd"))
+            .expect("whiteout missing from layer");
+        assert_eq!(whiteout.header().entry_type(), tar::EntryType::Regular);
+        assert_eq!(whiteout.size(), 0);
+    }
+}
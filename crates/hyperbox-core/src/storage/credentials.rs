@@ -0,0 +1,451 @@
+//! Registry credentials.
+//!
+//! Credentials live where the Docker CLI keeps them, in `config.json` under
+//! `$DOCKER_CONFIG` or `~/.docker`, so a `docker login` also works for
+//! HyperBox and the other way round. A registry's credentials come from the
+//! first of:
+//!
+//! 1. its entry in `credHelpers`,
+//! 2. the `credsStore` used for every registry,
+//! 3. its entry in `auths` (base64 `user:password` or an identity token).
+//!
+//! Helpers are the `docker-credential-<name>` binaries of the Docker
+//! credential helper protocol.
+
+use crate::error::{CoreError, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::debug;
+
+/// Key Docker uses for Docker Hub credentials.
+pub const DOCKER_HUB_SERVER: &str = "https://index.docker.io/v1/";
+
+/// Username credential helpers return for an identity token.
+const TOKEN_USERNAME: &str = "<token>";
+
+/// Credentials for a registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// Username and password (or personal access token)
+    Basic {
+        /// Username
+        username: String,
+        /// Password
+        password: String,
+    },
+    /// `OAuth2` refresh token issued by the registry's token service
+    IdentityToken(String),
+}
+
+/// The parts of the Docker CLI's `config.json` that hold credentials.
+///
+/// Other settings are kept as they are when the file is rewritten.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DockerConfig {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    auths: BTreeMap<String, AuthEntry>,
+    #[serde(rename = "credsStore", skip_serializing_if = "Option::is_none")]
+    creds_store: Option<String>,
+    #[serde(
+        rename = "credHelpers",
+        default,
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    cred_helpers: HashMap<String, String>,
+    #[serde(flatten)]
+    other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// An entry of `auths`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AuthEntry {
+    /// Base64 of `username:password`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth: Option<String>,
+    #[serde(rename = "identitytoken", skip_serializing_if = "Option::is_none")]
+    identity_token: Option<String>,
+    #[serde(flatten)]
+    other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Credentials as exchanged with a credential helper.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HelperCredentials {
+    #[serde(rename = "ServerURL")]
+    server_url: String,
+    username: String,
+    secret: String,
+}
+
+/// Registry credentials backed by the Docker CLI configuration.
+#[derive(Debug, Clone)]
+pub struct CredentialStore {
+    path: PathBuf,
+    /// Directory of the credential helpers, or `None` to search `PATH`
+    helper_dir: Option<PathBuf>,
+}
+
+impl CredentialStore {
+    /// Use the configuration file at `path`.
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            helper_dir: None,
+        }
+    }
+
+    /// Look for credential helpers in `dir` instead of `PATH`.
+    #[must_use]
+    pub fn with_helper_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.helper_dir = Some(dir.into());
+        self
+    }
+
+    /// Use the Docker CLI's configuration: `$DOCKER_CONFIG/config.json`, or
+    /// `.docker/config.json` in the home directory.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let dir = std::env::var_os("DOCKER_CONFIG").map_or_else(
+            || {
+                std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+                    .map(PathBuf::from)
+                    .unwrap_or_default()
+                    .join(".docker")
+            },
+            PathBuf::from,
+        );
+        Self::new(dir.join("config.json"))
+    }
+
+    /// Path of the configuration file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Look up the credentials for a registry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration cannot be read or a credential
+    /// helper fails.
+    pub async fn get(&self, registry: &str) -> Result<Option<Credentials>> {
+        let config = self.load().await?;
+        let server = server_key(registry);
+
+        if let Some(helper) = config.helper(&server) {
+            let Some(output) = self.run_helper(helper, "get", &server).await? else {
+                return Ok(None);
+            };
+            let creds: HelperCredentials = serde_json::from_str(&output)?;
+            return Ok(Some(if creds.username == TOKEN_USERNAME {
+                Credentials::IdentityToken(creds.secret)
+            } else {
+                Credentials::Basic {
+                    username: creds.username,
+                    password: creds.secret,
+                }
+            }));
+        }
+
+        let Some(entry) = config
+            .auths
+            .iter()
+            .find(|(key, _)| server_key(key) == server)
+            .map(|(_, entry)| entry)
+        else {
+            return Ok(None);
+        };
+        if let Some(token) = &entry.identity_token {
+            return Ok(Some(Credentials::IdentityToken(token.clone())));
+        }
+        entry.auth.as_deref().map(decode_auth).transpose()
+    }
+
+    /// Save the credentials for a registry, in its credential helper if it
+    /// has one and in `auths` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration cannot be read or written or
+    /// the credential helper fails.
+    pub async fn store(&self, registry: &str, credentials: &Credentials) -> Result<()> {
+        let mut config = self.load().await?;
+        let server = server_key(registry);
+
+        if let Some(helper) = config.helper(&server) {
+            let (username, secret) = match credentials {
+                Credentials::Basic { username, password } => (username.clone(), password.clone()),
+                Credentials::IdentityToken(token) => (TOKEN_USERNAME.to_string(), token.clone()),
+            };
+            let input = serde_json::to_string(&HelperCredentials {
+                server_url: server.clone(),
+                username,
+                secret,
+            })?;
+            self.run_helper(helper, "store", &input).await?;
+            // Docker keeps an empty entry so the registry shows up as logged in.
+            config.auths.entry(server).or_default();
+        } else {
+            let entry = config.auths.entry(server).or_default();
+            match credentials {
+                Credentials::Basic { username, password } => {
+                    entry.auth = Some(BASE64.encode(format!("{username}:{password}")));
+                    entry.identity_token = None;
+                }
+                Credentials::IdentityToken(token) => {
+                    entry.auth = None;
+                    entry.identity_token = Some(token.clone());
+                }
+            }
+        }
+        self.save(&config).await
+    }
+
+    /// Remove the credentials for a registry.
+    ///
+    /// # Returns
+    ///
+    /// Whether there were credentials to remove.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration cannot be read or written or
+    /// the credential helper fails.
+    pub async fn erase(&self, registry: &str) -> Result<bool> {
+        let mut config = self.load().await?;
+        let server = server_key(registry);
+
+        let mut erased = match config.helper(&server) {
+            Some(helper) => self.run_helper(helper, "erase", &server).await?.is_some(),
+            None => false,
+        };
+        let before = config.auths.len();
+        config.auths.retain(|key, _| server_key(key) != server);
+        if config.auths.len() != before {
+            erased = true;
+            self.save(&config).await?;
+        }
+        Ok(erased)
+    }
+
+    async fn load(&self) -> Result<DockerConfig> {
+        match tokio::fs::read(&self.path).await {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(DockerConfig::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the configuration through a temporary file and a rename,
+    /// readable only by its owner.
+    async fn save(&self, config: &DockerConfig) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&tmp).await?;
+        file.write_all(&serde_json::to_vec_pretty(config)?).await?;
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&tmp, &self.path).await?;
+        Ok(())
+    }
+
+    /// Run `docker-credential-<helper> <action>` with `input` on stdin.
+    ///
+    /// Returns its output, or `None` if it has no credentials for the server.
+    async fn run_helper(&self, helper: &str, action: &str, input: &str) -> Result<Option<String>> {
+        let binary = format!("docker-credential-{helper}");
+        debug!(helper = %binary, action, "Running credential helper");
+
+        let program = self
+            .helper_dir
+            .as_ref()
+            .map_or_else(|| PathBuf::from(&binary), |dir| dir.join(&binary));
+        let mut child = Command::new(program)
+            .arg(action)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| CoreError::Configuration(format!("credential helper {binary}: {e}")))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input.as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() {
+            return Ok(Some(stdout));
+        }
+        // Helpers report a missing entry on stdout and exit with 1.
+        if stdout.contains("credentials not found") {
+            return Ok(None);
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(CoreError::Configuration(format!(
+            "credential helper {binary} {action} failed: {}",
+            if stdout.is_empty() {
+                stderr.trim()
+            } else {
+                &stdout
+            }
+        )))
+    }
+}
+
+impl DockerConfig {
+    /// The credential helper for a server, if any.
+    fn helper(&self, server: &str) -> Option<&str> {
+        self.cred_helpers
+            .iter()
+            .find(|(key, _)| server_key(key) == server)
+            .map(|(_, helper)| helper.as_str())
+            .or(self.creds_store.as_deref())
+            .filter(|helper| !helper.is_empty())
+    }
+}
+
+/// The key credentials for `registry` are stored under: the host (and
+/// port), or [`DOCKER_HUB_SERVER`] for Docker Hub.
+#[must_use]
+pub fn server_key(registry: &str) -> String {
+    let host = registry
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    match host {
+        "docker.io" | "index.docker.io" | "registry-1.docker.io" => DOCKER_HUB_SERVER.to_string(),
+        _ => host.to_string(),
+    }
+}
+
+fn decode_auth(auth: &str) -> Result<Credentials> {
+    let decoded = BASE64
+        .decode(auth.trim())
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| CoreError::Configuration("invalid base64 in registry auth".to_string()))?;
+    let (username, password) = decoded.split_once(':').ok_or_else(|| {
+        CoreError::Configuration("registry auth is not user:password".to_string())
+    })?;
+    Ok(Credentials::Basic {
+        username: username.to_string(),
+        password: password.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn basic(username: &str, password: &str) -> Credentials {
+        Credentials::Basic {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_server_key() {
+        assert_eq!(server_key("https://registry-1.docker.io"), DOCKER_HUB_SERVER);
+        assert_eq!(server_key("docker.io"), DOCKER_HUB_SERVER);
+        assert_eq!(server_key(DOCKER_HUB_SERVER), DOCKER_HUB_SERVER);
+        assert_eq!(
+            server_key("https://registry.example.com:5000/v2/"),
+            "registry.example.com:5000"
+        );
+        assert_eq!(server_key("registry.example.com"), "registry.example.com");
+    }
+
+    #[tokio::test]
+    async fn test_store_get_erase() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{"auths":{"https://registry.example.com":{"auth":"dTpw"}},"psFormat":"table"}"#,
+        )
+        .unwrap();
+        let store = CredentialStore::new(&path);
+
+        assert_eq!(store.get("registry.example.com").await.unwrap(), Some(basic("u", "p")));
+        assert_eq!(store.get("docker.io").await.unwrap(), None);
+
+        store
+            .store("https://registry-1.docker.io", &basic("me", "s3:cret"))
+            .await
+            .unwrap();
+        assert_eq!(store.get("docker.io").await.unwrap(), Some(basic("me", "s3:cret")));
+
+        // Settings HyperBox does not know about survive the rewrite.
+        let config: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(config["psFormat"], "table");
+        assert_eq!(config["auths"][DOCKER_HUB_SERVER]["auth"], BASE64.encode("me:s3:cret"));
+
+        assert!(store.erase("registry.example.com").await.unwrap());
+        assert!(!store.erase("registry.example.com").await.unwrap());
+        assert_eq!(store.get("registry.example.com").await.unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_credential_helper() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        // A helper that keeps a single entry in a file next to it.
+        let script = format!(
+            r#"#!/bin/sh
+db="{db}"
+case "$1" in
+  store) cat > "$db" ;;
+  get) read server; if [ -f "$db" ]; then cat "$db"; else echo "credentials not found in native keychain"; exit 1; fi ;;
+  erase) read server; if [ -f "$db" ]; then rm "$db"; else echo "credentials not found in native keychain"; exit 1; fi ;;
+esac
+"#,
+            db = dir.path().join("db.json").display()
+        );
+        let helper = dir.path().join("docker-credential-hbtest");
+        std::fs::write(&helper, script).unwrap();
+        std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = dir.path().join("config.json");
+        std::fs::write(&config, r#"{"credHelpers":{"registry.example.com":"hbtest"}}"#).unwrap();
+        let store = CredentialStore::new(&config).with_helper_dir(dir.path());
+
+        assert_eq!(store.get("registry.example.com").await.unwrap(), None);
+        store
+            .store("registry.example.com", &Credentials::IdentityToken("refresh".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get("registry.example.com").await.unwrap(),
+            Some(Credentials::IdentityToken("refresh".to_string()))
+        );
+        // The secret stays with the helper.
+        assert!(!std::fs::read_to_string(&config)
+            .unwrap()
+            .contains("refresh"));
+
+        assert!(store.erase("registry.example.com").await.unwrap());
+        assert_eq!(store.get("registry.example.com").await.unwrap(), None);
+    }
+}
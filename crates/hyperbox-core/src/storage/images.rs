@@ -0,0 +1,596 @@
+//! Local image store.
+//!
+//! Keeps track of images that have been pulled and unpacked into the
+//! [`LayerStore`](super::LayerStore). Each entry records the manifest,
+//! the image configuration and the ordered list of layer digests needed
+//! to assemble a root filesystem.
+//!
+//! The index is persisted as `index.json` under the store root and is
+//! rewritten atomically (write to a temporary file, then rename) on every
+//! change, so a crash never leaves a half-written index behind.
+
+use crate::error::{CoreError, Result};
+use crate::storage::layers::LayerInfo;
+use crate::storage::{
+    canonical_json, Descriptor, HistoryEntry, ImageConfig, ImageManifest, RootFs,
+    CONFIG_MEDIA_TYPE, MANIFEST_MEDIA_TYPE,
+};
+use crate::types::{CommitOptions, ImageRef, ImportOptions, Platform};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::{debug, info};
+
+/// Name of the persisted index file.
+const INDEX_FILE: &str = "index.json";
+
+/// An image that is available locally.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredImage {
+    /// Image ID (digest of the image configuration).
+    pub id: String,
+    /// References (`registry/repository:tag`) pointing at this image.
+    pub references: Vec<String>,
+    /// Image manifest.
+    pub manifest: ImageManifest,
+    /// Image configuration.
+    pub config: ImageConfig,
+    /// Layer digests, ordered from the base layer to the top layer.
+    pub layers: Vec<String>,
+    /// Total compressed size of all layers in bytes.
+    pub size: u64,
+    /// When the image was pulled.
+    pub pulled_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl StoredImage {
+    /// Image creation time, falling back to the pull time.
+    #[must_use]
+    pub fn created(&self) -> chrono::DateTime<chrono::Utc> {
+        self.config
+            .created
+            .as_deref()
+            .and_then(|c| chrono::DateTime::parse_from_rfc3339(c).ok())
+            .map_or(self.pulled_at, |c| c.with_timezone(&chrono::Utc))
+    }
+
+    /// Create a single-layer image for the host platform, as imported from
+    /// a root filesystem tarball.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the configuration cannot be serialized.
+    pub fn from_layer(layer: &LayerInfo, options: &ImportOptions) -> Result<Self> {
+        let platform = Platform::host();
+        let scratch = Self {
+            id: String::new(),
+            references: Vec::new(),
+            manifest: ImageManifest {
+                schema_version: 2,
+                media_type: MANIFEST_MEDIA_TYPE.to_string(),
+                config: Descriptor {
+                    media_type: CONFIG_MEDIA_TYPE.to_string(),
+                    digest: String::new(),
+                    size: 0,
+                    annotations: std::collections::HashMap::new(),
+                    platform: None,
+                },
+                layers: Vec::new(),
+                annotations: std::collections::HashMap::new(),
+            },
+            config: ImageConfig {
+                architecture: platform.architecture,
+                os: platform.os,
+                variant: platform.variant,
+                created: None,
+                author: None,
+                config: None,
+                rootfs: RootFs {
+                    fs_type: "layers".to_string(),
+                    diff_ids: Vec::new(),
+                },
+                history: Vec::new(),
+            },
+            layers: Vec::new(),
+            size: 0,
+            pulled_at: chrono::Utc::now(),
+        };
+
+        let commit = CommitOptions {
+            comment: options.comment.clone(),
+            config: options.config.clone(),
+            ..Default::default()
+        };
+        scratch.with_layer(layer, &commit)
+    }
+
+    /// Derive a new image by adding `layer` on top of this one.
+    ///
+    /// The configuration is updated from `options` and gets a history
+    /// entry; the new image ID is the digest of the resulting configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the configuration cannot be serialized.
+    pub fn with_layer(&self, layer: &LayerInfo, options: &CommitOptions) -> Result<Self> {
+        let now = chrono::Utc::now();
+        let created = now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+
+        let mut config = self.config.clone();
+        config.created = Some(created.clone());
+        if options.author.is_some() {
+            config.author.clone_from(&options.author);
+        }
+        config
+            .config
+            .get_or_insert_with(Default::default)
+            .apply(&options.config);
+        config.rootfs.diff_ids.push(layer.diff_id.clone());
+        config.history.push(HistoryEntry {
+            created: Some(created),
+            created_by: None,
+            empty_layer: None,
+            comment: options.comment.clone(),
+        });
+
+        let config_json = canonical_json(&config)?;
+        let id = format!("sha256:{:x}", Sha256::digest(&config_json));
+
+        let mut manifest = self.manifest.clone();
+        manifest.media_type = MANIFEST_MEDIA_TYPE.to_string();
+        manifest.config = Descriptor {
+            media_type: CONFIG_MEDIA_TYPE.to_string(),
+            digest: id.clone(),
+            size: config_json.len() as u64,
+            annotations: std::collections::HashMap::new(),
+            platform: None,
+        };
+        manifest.layers.push(Descriptor {
+            media_type: layer.media_type.clone(),
+            digest: layer.digest.clone(),
+            size: layer.compressed_size,
+            annotations: std::collections::HashMap::new(),
+            platform: None,
+        });
+
+        let mut layers = self.layers.clone();
+        layers.push(layer.digest.clone());
+
+        Ok(Self {
+            id,
+            references: Vec::new(),
+            manifest,
+            config,
+            layers,
+            size: self.size + layer.compressed_size,
+            pulled_at: now,
+        })
+    }
+}
+
+/// Persistent index of locally available images.
+pub struct ImageStore {
+    /// Root directory for the store.
+    root_dir: PathBuf,
+    /// Images keyed by image ID.
+    images: DashMap<String, StoredImage>,
+    /// Reference to image ID lookup.
+    references: DashMap<String, String>,
+    /// Serialises index writes.
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+impl ImageStore {
+    /// Create a new image store rooted at `root_dir`.
+    #[must_use]
+    pub fn new(root_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            root_dir: root_dir.into(),
+            images: DashMap::new(),
+            references: DashMap::new(),
+            write_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Initialize the store and load the persisted index, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the root directory cannot be created or the index
+    /// cannot be read or parsed.
+    pub async fn initialize(&self) -> Result<()> {
+        fs::create_dir_all(&self.root_dir).await?;
+
+        let index_path = self.index_path();
+        if index_path.exists() {
+            let data = fs::read(&index_path).await?;
+            let images: Vec<StoredImage> = serde_json::from_slice(&data)?;
+            for image in images {
+                self.index(image);
+            }
+        }
+
+        info!("Loaded {} images from {:?}", self.images.len(), self.root_dir);
+        Ok(())
+    }
+
+    /// Get the root directory.
+    #[must_use]
+    pub fn root_dir(&self) -> &Path {
+        &self.root_dir
+    }
+
+    /// Look up an image by reference.
+    #[must_use]
+    pub fn get(&self, image: &ImageRef) -> Option<StoredImage> {
+        let id = self.references.get(&image.full_name())?;
+        self.images.get(id.value()).map(|r| r.value().clone())
+    }
+
+    /// Look up an image by ID.
+    #[must_use]
+    pub fn get_by_id(&self, id: &str) -> Option<StoredImage> {
+        self.images.get(id).map(|r| r.value().clone())
+    }
+
+    /// Check if an image is available locally.
+    #[must_use]
+    pub fn has(&self, image: &ImageRef) -> bool {
+        self.references.contains_key(&image.full_name())
+    }
+
+    /// List all local images.
+    #[must_use]
+    pub fn list(&self) -> Vec<StoredImage> {
+        self.images.iter().map(|r| r.value().clone()).collect()
+    }
+
+    /// Record an image under `reference` and persist the index.
+    ///
+    /// If the reference previously pointed at another image, it is moved to
+    /// the new one. Images left without references stay in the store until
+    /// they are removed explicitly.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the index cannot be written.
+    pub async fn insert(&self, reference: &ImageRef, mut image: StoredImage) -> Result<()> {
+        let name = reference.full_name();
+
+        if let Some((_, old_id)) = self.references.remove(&name) {
+            if old_id != image.id {
+                if let Some(mut old) = self.images.get_mut(&old_id) {
+                    old.references.retain(|r| r != &name);
+                }
+            }
+        }
+
+        if let Some(existing) = self.images.get(&image.id) {
+            for r in &existing.references {
+                if !image.references.contains(r) {
+                    image.references.push(r.clone());
+                }
+            }
+        }
+        if !image.references.contains(&name) {
+            image.references.push(name);
+        }
+
+        debug!("Storing image {} as {:?}", image.id, image.references);
+        self.index(image);
+        self.persist().await
+    }
+
+    /// Record an image without adding a reference and persist the index.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the index cannot be written.
+    pub async fn add(&self, image: StoredImage) -> Result<()> {
+        if let Some(existing) = self.images.get(&image.id) {
+            debug!("Image {} already stored", existing.id);
+            return Ok(());
+        }
+
+        debug!("Storing untagged image {}", image.id);
+        self.index(image);
+        self.persist().await
+    }
+
+    /// Remove an image by reference.
+    ///
+    /// Returns the removed image once its last reference is gone.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::ImageNotFound`] if the reference is unknown, or
+    /// error if the index cannot be written.
+    pub async fn remove(&self, reference: &ImageRef) -> Result<Option<StoredImage>> {
+        let name = reference.full_name();
+        let Some((_, id)) = self.references.remove(&name) else {
+            return Err(CoreError::ImageNotFound(name));
+        };
+
+        let removed = {
+            let mut entry = self
+                .images
+                .get_mut(&id)
+                .ok_or_else(|| CoreError::ImageNotFound(id.clone()))?;
+            entry.references.retain(|r| r != &name);
+            entry.references.is_empty()
+        };
+
+        let removed = if removed {
+            self.images.remove(&id).map(|(_, image)| image)
+        } else {
+            None
+        };
+
+        self.persist().await?;
+        Ok(removed)
+    }
+
+    fn index(&self, image: StoredImage) {
+        for reference in &image.references {
+            self.references.insert(reference.clone(), image.id.clone());
+        }
+        self.images.insert(image.id.clone(), image);
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.root_dir.join(INDEX_FILE)
+    }
+
+    async fn persist(&self) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+
+        let mut images = self.list();
+        images.sort_by(|a, b| a.id.cmp(&b.id));
+        let data = serde_json::to_vec_pretty(&images)?;
+
+        fs::create_dir_all(&self.root_dir).await?;
+        let tmp_path = self.root_dir.join(format!("{INDEX_FILE}.tmp"));
+        fs::write(&tmp_path, data).await?;
+        fs::rename(&tmp_path, self.index_path()).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{Descriptor, RootFs};
+    use tempfile::TempDir;
+
+    fn test_image(id: &str) -> StoredImage {
+        StoredImage {
+            id: id.to_string(),
+            references: Vec::new(),
+            manifest: ImageManifest {
+                schema_version: 2,
+                media_type: "application/vnd.oci.image.manifest.v1+json".to_string(),
+                config: Descriptor {
+                    media_type: "application/vnd.oci.image.config.v1+json".to_string(),
+                    digest: id.to_string(),
+                    size: 0,
+                    annotations: std::collections::HashMap::new(),
+                    platform: None,
+                },
+                layers: Vec::new(),
+                annotations: std::collections::HashMap::new(),
+            },
+            config: ImageConfig {
+                architecture: "amd64".to_string(),
+                os: "linux".to_string(),
+                variant: None,
+                created: Some("2024-01-01T00:00:00Z".to_string()),
+                author: None,
+                config: None,
+                rootfs: RootFs {
+                    fs_type: "layers".to_string(),
+                    diff_ids: Vec::new(),
+                },
+                history: Vec::new(),
+            },
+            layers: vec!["sha256:base".to_string(), "sha256:top".to_string()],
+            size: 42,
+            pulled_at: chrono::Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_lookup() {
+        let dir = TempDir::new().unwrap();
+        let store = ImageStore::new(dir.path());
+        store.initialize().await.unwrap();
+
+        let reference = ImageRef::parse("alpine:3.19");
+        store
+            .insert(&reference, test_image("sha256:aaa"))
+            .await
+            .unwrap();
+
+        assert!(store.has(&reference));
+        assert!(!store.has(&ImageRef::parse("alpine:3.18")));
+        let image = store.get(&reference).unwrap();
+        assert_eq!(image.references, vec!["docker.io/library/alpine:3.19"]);
+        assert_eq!(image.layers.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_index_persists_across_instances() {
+        let dir = TempDir::new().unwrap();
+        {
+            let store = ImageStore::new(dir.path());
+            store.initialize().await.unwrap();
+            store
+                .insert(&ImageRef::parse("nginx:1.25"), test_image("sha256:bbb"))
+                .await
+                .unwrap();
+        }
+
+        let store = ImageStore::new(dir.path());
+        store.initialize().await.unwrap();
+        assert!(store.has(&ImageRef::parse("nginx:1.25")));
+        assert!(store.get_by_id("sha256:bbb").is_some());
+        assert!(!dir.path().join("index.json.tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn test_retag_moves_reference() {
+        let dir = TempDir::new().unwrap();
+        let store = ImageStore::new(dir.path());
+        let reference = ImageRef::parse("alpine:latest");
+
+        store
+            .insert(&reference, test_image("sha256:old"))
+            .await
+            .unwrap();
+        store
+            .insert(&reference, test_image("sha256:new"))
+            .await
+            .unwrap();
+
+        assert_eq!(store.get(&reference).unwrap().id, "sha256:new");
+        assert!(store.get_by_id("sha256:old").unwrap().references.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remove_last_reference() {
+        let dir = TempDir::new().unwrap();
+        let store = ImageStore::new(dir.path());
+        let a = ImageRef::parse("alpine:latest");
+        let b = ImageRef::parse("alpine:3.19");
+
+        store.insert(&a, test_image("sha256:ccc")).await.unwrap();
+        store.insert(&b, test_image("sha256:ccc")).await.unwrap();
+
+        assert!(store.remove(&a).await.unwrap().is_none());
+        assert!(store.has(&b));
+        assert!(store.remove(&b).await.unwrap().is_some());
+        assert!(store.list().is_empty());
+        assert!(store.remove(&b).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_add_untagged() {
+        let dir = TempDir::new().unwrap();
+        let store = ImageStore::new(dir.path());
+        let reference = ImageRef::parse("alpine:latest");
+
+        store
+            .insert(&reference, test_image("sha256:eee"))
+            .await
+            .unwrap();
+        store.add(test_image("sha256:eee")).await.unwrap();
+        store.add(test_image("sha256:fff")).await.unwrap();
+
+        assert_eq!(store.get(&reference).unwrap().id, "sha256:eee");
+        assert!(store.get_by_id("sha256:fff").unwrap().references.is_empty());
+        assert_eq!(store.list().len(), 2);
+    }
+
+    #[test]
+    fn test_with_layer() {
+        let base = test_image("sha256:base");
+        let layer = LayerInfo {
+            digest: "sha256:changes".to_string(),
+            diff_id: "sha256:changes".to_string(),
+            size: 8,
+            compressed_size: 8,
+            path: PathBuf::from("/layers/diff/sha256:changes"),
+            media_type: crate::storage::LAYER_MEDIA_TYPE.to_string(),
+            ref_count: 1,
+        };
+        let options = CommitOptions {
+            author: Some("dev".to_string()),
+            comment: Some("install deps".to_string()),
+            config: crate::storage::ContainerConfig {
+                cmd: Some(vec!["serve".to_string()]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let image = base.with_layer(&layer, &options).unwrap();
+        assert_ne!(image.id, base.id);
+        assert_eq!(image.manifest.config.digest, image.id);
+        assert_eq!(image.layers, ["sha256:base", "sha256:top", "sha256:changes"]);
+        assert_eq!(image.manifest.layers.last().unwrap().digest, "sha256:changes");
+        assert_eq!(image.config.rootfs.diff_ids, ["sha256:changes"]);
+        assert_eq!(image.config.author.as_deref(), Some("dev"));
+        assert_eq!(image.config.history[0].comment.as_deref(), Some("install deps"));
+        assert_eq!(image.config.config.unwrap().cmd.unwrap(), ["serve"]);
+        assert_eq!(image.size, 50);
+    }
+
+    #[test]
+    fn test_from_layer() {
+        let layer = LayerInfo {
+            digest: "sha256:rootfs".to_string(),
+            diff_id: "sha256:rootfs".to_string(),
+            size: 1024,
+            compressed_size: 1024,
+            path: PathBuf::from("/layers/diff/sha256:rootfs"),
+            media_type: crate::storage::LAYER_MEDIA_TYPE.to_string(),
+            ref_count: 1,
+        };
+        let options = ImportOptions {
+            comment: Some("imported".to_string()),
+            config: crate::storage::ContainerConfig {
+                env: Some(vec!["MODE=prod".to_string()]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let image = StoredImage::from_layer(&layer, &options).unwrap();
+        assert!(image.id.starts_with("sha256:"));
+        assert_eq!(image.manifest.config.digest, image.id);
+        assert_eq!(image.layers, ["sha256:rootfs"]);
+        assert_eq!(image.config.rootfs.diff_ids, ["sha256:rootfs"]);
+        assert_eq!(image.config.os, std::env::consts::OS);
+        assert_eq!(image.config.history.len(), 1);
+        assert_eq!(image.config.config.unwrap().env.unwrap(), ["MODE=prod"]);
+        assert_eq!(image.size, 1024);
+    }
+
+    #[test]
+    fn test_created_falls_back_to_pull_time() {
+        let mut image = test_image("sha256:ddd");
+        assert_eq!(image.created().to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        image.config.created = None;
+        assert_eq!(image.created(), image.pulled_at);
+    }
+
+    #[test]
+    fn test_with_layer_describes_the_compressed_blob() {
+        let layer = LayerInfo {
+            digest: "sha256:compressed".to_string(),
+            diff_id: "sha256:uncompressed".to_string(),
+            size: 4096,
+            compressed_size: 1024,
+            path: std::path::PathBuf::new(),
+            media_type: "application/vnd.oci.image.layer.v1.tar+gzip".to_string(),
+            ref_count: 0,
+        };
+        let image = test_image("sha256:base")
+            .with_layer(&layer, &CommitOptions::default())
+            .unwrap();
+
+        let descriptor = image.manifest.layers.last().unwrap();
+        assert_eq!(descriptor.size, 1024);
+        assert_eq!(image.size, 42 + 1024);
+
+        // The ID is the digest of the config as a registry receives it.
+        let config_json = canonical_json(&image.config).unwrap();
+        assert_eq!(image.id, format!("sha256:{:x}", Sha256::digest(&config_json)));
+        assert_eq!(image.manifest.config.size, config_json.len() as u64);
+
+        let manifest = serde_json::to_value(&image.manifest).unwrap();
+        assert!(manifest.get("annotations").is_none());
+        assert!(manifest["layers"][0].get("annotations").is_none());
+    }
+}
@@ -3,14 +3,24 @@
 //! Provides layer caching, deduplication, and overlay filesystem support.
 
 use crate::error::{CoreError, Result};
+use crate::storage::unpack::{self, Unpacked, Whiteouts};
+use crate::storage::{changes, LAYER_MEDIA_TYPE};
+use crate::types::FilesystemChange;
 use dashmap::DashMap;
 use flate2::read::GzDecoder;
 use sha2::{Digest, Sha256};
-use std::io::Read;
+use std::io::{BufRead, Cursor, Read, Write};
 use std::path::{Path, PathBuf};
-use tar::Archive;
 use tokio::fs;
-use tracing::{debug, info};
+use tokio::io::AsyncRead;
+use tokio::sync::mpsc;
+use tokio_util::io::{StreamReader, SyncIoBridge};
+use tracing::{debug, info, warn};
+
+/// Size of the chunks the archiving thread hands to the reader.
+const PIPE_SIZE: usize = 64 * 1024;
+/// Chunks buffered between the archiving thread and the reader.
+const PIPE_CHUNKS: usize = 4;
 
 /// Layer store for managing image layers.
 pub struct LayerStore {
@@ -29,7 +39,7 @@ pub struct LayerInfo {
     pub digest: String,
     /// Diff ID (uncompressed digest)
     pub diff_id: String,
-    /// Size in bytes
+    /// Uncompressed size in bytes
     pub size: u64,
     /// Compressed size
     pub compressed_size: u64,
@@ -61,11 +71,61 @@ impl LayerStore {
         fs::create_dir_all(&self.cas_dir).await?;
         fs::create_dir_all(self.root_dir.join("diff")).await?;
         fs::create_dir_all(self.root_dir.join("merged")).await?;
+        fs::create_dir_all(self.root_dir.join("meta")).await?;
+
+        // Re-register layers unpacked by a previous run. Extraction goes
+        // through a temporary directory, so anything under `diff/` with a
+        // matching blob is complete.
+        let mut entries = fs::read_dir(self.root_dir.join("diff")).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "tmp") {
+                let _ = fs::remove_dir_all(&path).await;
+                continue;
+            }
+            let digest = entry.file_name().to_string_lossy().to_string();
+            let Ok(meta) = fs::metadata(self.cas_dir.join(&digest)).await else {
+                continue;
+            };
+            // Layers unpacked before diff IDs were recorded were stored
+            // uncompressed, so their digest is their diff ID.
+            let unpacked = self
+                .read_unpacked(&digest)
+                .await
+                .unwrap_or_else(|| Unpacked {
+                    diff_id: digest.clone(),
+                    size: meta.len(),
+                });
+            self.layers.insert(
+                digest.clone(),
+                LayerInfo {
+                    digest,
+                    diff_id: unpacked.diff_id,
+                    size: unpacked.size,
+                    compressed_size: meta.len(),
+                    path,
+                    media_type: String::new(),
+                    ref_count: 0,
+                },
+            );
+        }
 
-        info!("Initialized layer store at {:?}", self.root_dir);
+        info!("Initialized layer store at {:?} ({} layers)", self.root_dir, self.layers.len());
         Ok(())
     }
 
+    /// Get the directory an unpacked layer lives in.
+    #[must_use]
+    pub fn layer_path(&self, digest: &str) -> PathBuf {
+        self.root_dir.join("diff").join(digest)
+    }
+
+    /// Get the stored blob of a layer.
+    #[must_use]
+    pub fn blob_path(&self, digest: &str) -> PathBuf {
+        self.cas_dir.join(digest)
+    }
+
     /// Get a layer by digest.
     #[must_use]
     pub fn get(&self, digest: &str) -> Option<LayerInfo> {
@@ -78,7 +138,7 @@ impl LayerStore {
         self.layers.contains_key(digest)
     }
 
-    /// Store a layer from a tar archive.
+    /// Store a layer from a tar archive, compressed as `media_type` says.
     pub async fn store_layer(&self, reader: impl Read, media_type: &str) -> Result<LayerInfo> {
         // Calculate digest while reading
         let mut hasher = Sha256::new();
@@ -103,15 +163,13 @@ impl LayerStore {
         fs::write(&blob_path, &data).await?;
 
         // Extract layer
-        let diff_dir = self.root_dir.join("diff").join(&digest);
-        fs::create_dir_all(&diff_dir).await?;
+        let diff_dir = self.layer_path(&digest);
+        let unpacked = self.unpack(&digest, media_type, &diff_dir).await?;
 
-        // Would extract tar here
-        // For now, just store the info
         let info = LayerInfo {
             digest: digest.clone(),
-            diff_id: digest.clone(), // Would be different for compressed layers
-            size: data.len() as u64,
+            diff_id: unpacked.diff_id,
+            size: unpacked.size,
             compressed_size: data.len() as u64,
             path: diff_dir,
             media_type: media_type.to_string(),
@@ -122,8 +180,140 @@ impl LayerStore {
         Ok(info)
     }
 
-    /// Extract a layer tar to a directory.
-    pub async fn extract_layer(&self, digest: &str, target: &Path) -> Result<()> {
+    /// Store an uncompressed layer tarball that `write` produces.
+    ///
+    /// The tarball streams into a temporary file in the blob directory,
+    /// hashed as it is written, and is renamed to its digest once complete.
+    async fn store_written<F>(&self, write: F) -> Result<LayerInfo>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<()> + Send + 'static,
+    {
+        fs::create_dir_all(&self.cas_dir).await?;
+        let partial = self
+            .cas_dir
+            .join(format!("{}.partial", uuid::Uuid::new_v4().simple()));
+
+        let path = partial.clone();
+        let written = tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::create(&path)?;
+            let mut writer = HashingWriter::new(std::io::BufWriter::new(file));
+            write(&mut writer)?;
+            writer.flush()?;
+            Ok((format!("sha256:{:x}", writer.hasher.finalize()), writer.written))
+        })
+        .await
+        .map_err(|e| CoreError::StorageOperation(format!("store layer: {e}")))
+        .and_then(|written| written);
+        let (digest, compressed_size) = match written {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = fs::remove_file(&partial).await;
+                return Err(e);
+            }
+        };
+
+        if let Some(info) = self.get(&digest) {
+            debug!("Layer {} already exists", digest);
+            let _ = fs::remove_file(&partial).await;
+            return Ok(info);
+        }
+        fs::rename(&partial, self.cas_dir.join(&digest)).await?;
+
+        let diff_dir = self.layer_path(&digest);
+        let unpacked = self.unpack(&digest, LAYER_MEDIA_TYPE, &diff_dir).await?;
+
+        let info = LayerInfo {
+            digest: digest.clone(),
+            diff_id: unpacked.diff_id,
+            size: unpacked.size,
+            compressed_size,
+            path: diff_dir,
+            media_type: LAYER_MEDIA_TYPE.to_string(),
+            ref_count: 1,
+        };
+
+        self.layers.insert(digest, info.clone());
+        Ok(info)
+    }
+
+    /// Import a downloaded layer blob whose digest is already known.
+    ///
+    /// The blob is linked (or copied) into the content-addressed store and
+    /// unpacked into its own diff directory. Importing a layer that is
+    /// already present is a no-op. Check the returned `diff_id` against the
+    /// image configuration with
+    /// [`ImageConfig::verify_diff_id`](super::ImageConfig::verify_diff_id).
+    pub async fn import_blob(
+        &self,
+        blob: &Path,
+        digest: &str,
+        media_type: &str,
+    ) -> Result<LayerInfo> {
+        if let Some(info) = self.get(digest) {
+            debug!("Layer {} already exists", digest);
+            return Ok(info);
+        }
+
+        fs::create_dir_all(&self.cas_dir).await?;
+        let blob_path = self.cas_dir.join(digest);
+        if !blob_path.exists() && fs::hard_link(blob, &blob_path).await.is_err() {
+            fs::copy(blob, &blob_path).await?;
+        }
+        let compressed_size = fs::metadata(&blob_path).await?.len();
+
+        let diff_dir = self.layer_path(digest);
+        let unpacked = match self.read_unpacked(digest).await {
+            Some(unpacked) if diff_dir.exists() => unpacked,
+            _ => {
+                let _ = fs::remove_dir_all(&diff_dir).await;
+                self.unpack(digest, media_type, &diff_dir).await?
+            }
+        };
+
+        let info = LayerInfo {
+            digest: digest.to_string(),
+            diff_id: unpacked.diff_id,
+            size: unpacked.size,
+            compressed_size,
+            path: diff_dir,
+            media_type: media_type.to_string(),
+            ref_count: 0,
+        };
+
+        self.layers.insert(digest.to_string(), info.clone());
+        Ok(info)
+    }
+
+    /// Unpack a stored blob into `target` via a temporary directory, so an
+    /// interrupted extraction never looks like a complete layer.
+    async fn unpack(&self, digest: &str, media_type: &str, target: &Path) -> Result<Unpacked> {
+        let tmp = target.with_extension("tmp");
+        let _ = fs::remove_dir_all(&tmp).await;
+        let unpacked = self.extract_layer(digest, media_type, &tmp).await?;
+        fs::create_dir_all(self.root_dir.join("meta")).await?;
+        fs::write(self.meta_path(digest), serde_json::to_vec(&unpacked)?).await?;
+        fs::rename(&tmp, target).await?;
+        Ok(unpacked)
+    }
+
+    /// What unpacking a layer recorded, if it has been unpacked.
+    async fn read_unpacked(&self, digest: &str) -> Option<Unpacked> {
+        let data = fs::read(self.meta_path(digest)).await.ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    fn meta_path(&self, digest: &str) -> PathBuf {
+        self.root_dir.join("meta").join(format!("{digest}.json"))
+    }
+
+    /// Extract a stored layer blob to a directory, with overlayfs
+    /// whiteouts.
+    pub async fn extract_layer(
+        &self,
+        digest: &str,
+        media_type: &str,
+        target: &Path,
+    ) -> Result<Unpacked> {
         let blob_path = self.cas_dir.join(digest);
 
         if !blob_path.exists() {
@@ -133,64 +323,61 @@ impl LayerStore {
             )));
         }
 
-        fs::create_dir_all(target).await?;
-
-        // Read the layer blob
-        let data = fs::read(&blob_path).await?;
+        let media_type = media_type.to_string();
+        let dir = target.to_path_buf();
+        let unpacked = tokio::task::spawn_blocking(move || {
+            unpack::apply_blob(&blob_path, &media_type, &dir, Whiteouts::Overlay)
+        })
+        .await
+        .map_err(|e| CoreError::StorageOperation(format!("extract layer: {e}")))??;
 
-        // Decompress if gzipped
-        let decompressed = if data.starts_with(&[0x1f, 0x8b]) {
-            // GZip magic bytes
-            let mut decoder = GzDecoder::new(&data[..]);
-            let mut decompressed = Vec::new();
-            decoder
-                .read_to_end(&mut decompressed)
-                .map_err(|e| CoreError::StorageOperation(format!("decompress layer: {}", e)))?;
-            decompressed
-        } else {
-            data
-        };
+        debug!("Extracted layer {} ({}) to {:?}", digest, unpacked.diff_id, target);
+        Ok(unpacked)
+    }
 
-        // Extract tar archive
-        let mut archive = Archive::new(&decompressed[..]);
+    /// Build the overlay `lowerdir` option for a set of layers.
+    ///
+    /// `layer_digests` are ordered base first, as in an image manifest.
+    /// Overlayfs stacks lower directories right to left, so the top layer
+    /// has to come first.
+    pub fn lowerdir(&self, layer_digests: &[String]) -> Result<String> {
+        if layer_digests.is_empty() {
+            return Err(CoreError::StorageOperation("mount overlay: No layers found".to_string()));
+        }
 
-        archive
-            .unpack(target)
-            .map_err(|e| CoreError::StorageOperation(format!("extract tar: {}", e)))?;
+        let lower_dirs = layer_digests
+            .iter()
+            .rev()
+            .map(|d| {
+                self.get(d)
+                    .map(|info| info.path.to_string_lossy().to_string())
+                    .ok_or_else(|| {
+                        CoreError::StorageOperation(format!("mount overlay: Layer {d} not found"))
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-        debug!("Extracted layer {} to {:?}", digest, target);
-        Ok(())
+        Ok(lower_dirs.join(":"))
     }
 
     /// Create an overlay mount for a container.
+    ///
+    /// `layer_digests` are ordered base first. Returns the merged directory.
     #[cfg(unix)]
     pub async fn mount_overlay(
         &self,
         layer_digests: &[String],
         container_id: &str,
     ) -> Result<PathBuf> {
-        let merged_dir = self.root_dir.join("merged").join(container_id);
+        let merged_dir = self.merged_dir(container_id);
         let work_dir = self.root_dir.join("work").join(container_id);
-        let upper_dir = self.root_dir.join("upper").join(container_id);
+        let upper_dir = self.upper_dir(container_id);
 
         fs::create_dir_all(&merged_dir).await?;
         fs::create_dir_all(&work_dir).await?;
         fs::create_dir_all(&upper_dir).await?;
 
-        // Build lower dirs string
-        let lower_dirs: Vec<String> = layer_digests
-            .iter()
-            .filter_map(|d| {
-                self.get(d)
-                    .map(|info| info.path.to_string_lossy().to_string())
-            })
-            .collect();
-
-        if lower_dirs.is_empty() {
-            return Err(CoreError::StorageOperation("mount overlay: No layers found".to_string()));
-        }
-
-        let lowerdir = lower_dirs.join(":");
+        let lowerdir = self.lowerdir(layer_digests)?;
 
         info!("Mounting overlay for container {}", container_id);
 
@@ -222,10 +409,127 @@ impl LayerStore {
         Ok(merged_dir)
     }
 
+    /// Get the merged overlay directory, a container's root filesystem.
+    #[must_use]
+    pub fn merged_dir(&self, container_id: &str) -> PathBuf {
+        self.root_dir.join("merged").join(container_id)
+    }
+
+    /// Get the overlay upper directory holding a container's writes.
+    #[must_use]
+    pub fn upper_dir(&self, container_id: &str) -> PathBuf {
+        self.root_dir.join("upper").join(container_id)
+    }
+
+    /// List the paths a container changed on top of its layers.
+    ///
+    /// `layer_digests` are ordered base first, as for
+    /// [`mount_overlay`](Self::mount_overlay).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the container has no overlay upper directory.
+    pub async fn diff(
+        &self,
+        layer_digests: &[String],
+        container_id: &str,
+    ) -> Result<Vec<FilesystemChange>> {
+        let upper = self.upper_container_dir(container_id)?;
+        let lowers: Vec<PathBuf> = layer_digests
+            .iter()
+            .rev()
+            .map(|d| self.layer_path(d))
+            .collect();
+
+        tokio::task::spawn_blocking(move || changes::changes(&upper, &lowers))
+            .await
+            .map_err(|e| CoreError::StorageOperation(format!("diff: {e}")))?
+    }
+
+    /// Store a container's changes as a new layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the container has no overlay upper directory or the
+    /// layer cannot be stored.
+    pub async fn commit(&self, container_id: &str) -> Result<LayerInfo> {
+        let upper = self.upper_container_dir(container_id)?;
+        let info = self
+            .store_written(move |writer| changes::pack(&upper, writer).map(drop))
+            .await?;
+        info!("Committed container {} as layer {}", container_id, info.digest);
+        Ok(info)
+    }
+
+    /// Stream a container's root filesystem as a flat tarball.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the container has no overlay root filesystem.
+    /// Errors while archiving are returned by the reader, so a failed export
+    /// never looks like a complete tarball.
+    pub fn export(&self, container_id: &str) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        self.upper_container_dir(container_id)?;
+        let merged = self.merged_dir(container_id);
+
+        let (tx, mut rx) = mpsc::channel(PIPE_CHUNKS);
+        tokio::task::spawn_blocking(move || {
+            let writer = std::io::BufWriter::with_capacity(PIPE_SIZE, ChannelWriter(tx.clone()));
+            let exported = changes::export(&merged, writer)
+                .and_then(|mut writer| writer.flush().map_err(CoreError::from));
+            if let Err(e) = exported {
+                warn!(path = %merged.display(), "Failed to export root filesystem: {}", e);
+                let _ = tx.blocking_send(Err(std::io::Error::other(e.to_string())));
+            }
+        });
+
+        let chunks = futures::stream::poll_fn(move |cx| rx.poll_recv(cx));
+        Ok(Box::new(StreamReader::new(chunks)))
+    }
+
+    /// Store a root filesystem tarball, optionally gzip-compressed, as a
+    /// new uncompressed layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the archive cannot be read, decompressed or stored.
+    pub async fn import(
+        &self,
+        archive: impl AsyncRead + Send + Unpin + 'static,
+    ) -> Result<LayerInfo> {
+        let archive = SyncIoBridge::new(archive);
+        let info = self
+            .store_written(move |writer| {
+                let mut archive = std::io::BufReader::new(archive);
+                if archive.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+                    std::io::copy(&mut GzDecoder::new(archive), writer).map_err(|e| {
+                        CoreError::StorageOperation(format!("decompress layer: {e}"))
+                    })?;
+                } else {
+                    std::io::copy(&mut archive, writer)?;
+                }
+                Ok(())
+            })
+            .await?;
+        info!("Imported layer {}", info.digest);
+        Ok(info)
+    }
+
+    fn upper_container_dir(&self, container_id: &str) -> Result<PathBuf> {
+        let upper = self.upper_dir(container_id);
+        if upper.is_dir() {
+            Ok(upper)
+        } else {
+            Err(CoreError::StorageOperation(format!(
+                "container {container_id} has no overlay root filesystem"
+            )))
+        }
+    }
+
     /// Unmount an overlay.
     #[cfg(unix)]
     pub async fn unmount_overlay(&self, container_id: &str) -> Result<()> {
-        let merged_dir = self.root_dir.join("merged").join(container_id);
+        let merged_dir = self.merged_dir(container_id);
 
         if merged_dir.exists() {
             let _ = std::process::Command::new("umount")
@@ -235,7 +539,7 @@ impl LayerStore {
 
         // Cleanup directories
         let _ = fs::remove_dir_all(self.root_dir.join("work").join(container_id)).await;
-        let _ = fs::remove_dir_all(self.root_dir.join("upper").join(container_id)).await;
+        let _ = fs::remove_dir_all(self.upper_dir(container_id)).await;
         let _ = fs::remove_dir_all(&merged_dir).await;
 
         Ok(())
@@ -262,6 +566,7 @@ impl LayerStore {
         if let Some((_, info)) = self.layers.remove(digest) {
             if info.ref_count == 0 {
                 let _ = fs::remove_file(self.cas_dir.join(digest)).await;
+                let _ = fs::remove_file(self.meta_path(digest)).await;
                 let _ = fs::remove_dir_all(&info.path).await;
                 return Ok(true);
             }
@@ -281,3 +586,212 @@ impl LayerStore {
         self.layers.iter().map(|r| r.value().clone()).collect()
     }
 }
+
+/// Hashes and counts the bytes written through it.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    written: u64,
+}
+
+impl<W> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            written: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Hands what is written through it to a channel-backed reader.
+struct ChannelWriter(mpsc::Sender<std::io::Result<Cursor<Vec<u8>>>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Cursor::new(buf.to_vec())))
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tempfile::TempDir;
+
+    fn layer_tarball(dir: &Path, name: &str, file: &str, contents: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        let encoder = GzEncoder::new(std::fs::File::create(&path).unwrap(), Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, file, contents).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_import_blob_unpacks_layer() {
+        let dir = TempDir::new().unwrap();
+        let store = LayerStore::new(dir.path().join("layers"));
+        store.initialize().await.unwrap();
+
+        let blob = layer_tarball(dir.path(), "blob", "etc/os-release", b"ID=test\n");
+        let info = store
+            .import_blob(&blob, "sha256:base", "application/vnd.oci.image.layer.v1.tar+gzip")
+            .await
+            .unwrap();
+
+        assert!(store.has("sha256:base"));
+        assert_eq!(info.path, store.layer_path("sha256:base"));
+        let mut tar = Vec::new();
+        GzDecoder::new(std::fs::File::open(&blob).unwrap())
+            .read_to_end(&mut tar)
+            .unwrap();
+        assert_eq!(info.diff_id, format!("sha256:{:x}", Sha256::digest(&tar)));
+        assert_eq!(info.size, tar.len() as u64);
+        assert_eq!(std::fs::read_to_string(info.path.join("etc/os-release")).unwrap(), "ID=test\n");
+    }
+
+    #[tokio::test]
+    async fn test_import_decompresses_archive() {
+        let dir = TempDir::new().unwrap();
+        let store = LayerStore::new(dir.path().join("layers"));
+        store.initialize().await.unwrap();
+
+        let tarball = layer_tarball(dir.path(), "rootfs.tar.gz", "bin/app", b"#!/bin/sh\n");
+        let archive = tokio::fs::File::open(&tarball).await.unwrap();
+        let info = store.import(archive).await.unwrap();
+
+        let data = std::fs::read(store.cas_dir.join(&info.digest)).unwrap();
+        assert!(!data.starts_with(&[0x1f, 0x8b]));
+        assert_eq!(info.digest, format!("sha256:{:x}", Sha256::digest(&data)));
+        assert_eq!(info.media_type, LAYER_MEDIA_TYPE);
+        assert_eq!(std::fs::read(info.path.join("bin/app")).unwrap(), b"#!/bin/sh\n");
+    }
+
+    #[tokio::test]
+    async fn test_commit_streams_upper_dir_into_blob() {
+        let dir = TempDir::new().unwrap();
+        let store = LayerStore::new(dir.path().join("layers"));
+        store.initialize().await.unwrap();
+
+        let upper = store.upper_dir("web");
+        std::fs::create_dir_all(upper.join("srv")).unwrap();
+        std::fs::write(upper.join("srv/index.html"), b"hello").unwrap();
+
+        let info = store.commit("web").await.unwrap();
+
+        let data = std::fs::read(store.cas_dir.join(&info.digest)).unwrap();
+        assert_eq!(info.digest, format!("sha256:{:x}", Sha256::digest(&data)));
+        assert_eq!(info.compressed_size, data.len() as u64);
+        assert_eq!(std::fs::read(info.path.join("srv/index.html")).unwrap(), b"hello");
+        let names: Vec<_> = std::fs::read_dir(&store.cas_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, [std::ffi::OsString::from(&info.digest)]);
+
+        // Committing the same changes again reuses the stored layer.
+        assert_eq!(store.commit("web").await.unwrap().digest, info.digest);
+        assert_eq!(std::fs::read_dir(&store.cas_dir).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_export_fails_the_reader_when_archiving_fails() {
+        use tokio::io::AsyncReadExt;
+
+        let dir = TempDir::new().unwrap();
+        let store = LayerStore::new(dir.path().join("layers"));
+        store.initialize().await.unwrap();
+        std::fs::create_dir_all(store.upper_dir("web")).unwrap();
+
+        // No merged root filesystem to archive.
+        let mut reader = store.export("web").unwrap();
+        let mut tarball = Vec::new();
+        assert!(reader.read_to_end(&mut tarball).await.is_err());
+
+        let merged = store.merged_dir("web");
+        std::fs::create_dir_all(&merged).unwrap();
+        std::fs::write(merged.join("hello"), b"world").unwrap();
+        let mut reader = store.export("web").unwrap();
+        let mut tarball = Vec::new();
+        reader.read_to_end(&mut tarball).await.unwrap();
+        let mut archive = tar::Archive::new(tarball.as_slice());
+        let names: Vec<_> = archive
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().into_owned())
+            .collect();
+        assert_eq!(names, [PathBuf::from("hello")]);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_reloads_layers() {
+        let dir = TempDir::new().unwrap();
+        let blob = layer_tarball(dir.path(), "blob", "hello", b"world");
+        let diff_id = {
+            let store = LayerStore::new(dir.path().join("layers"));
+            store.initialize().await.unwrap();
+            store
+                .import_blob(&blob, "sha256:one", "")
+                .await
+                .unwrap()
+                .diff_id
+        };
+
+        let store = LayerStore::new(dir.path().join("layers"));
+        store.initialize().await.unwrap();
+        assert!(store.has("sha256:one"));
+        assert_eq!(store.get("sha256:one").unwrap().diff_id, diff_id);
+        assert_ne!(diff_id, "sha256:one");
+    }
+
+    #[tokio::test]
+    async fn test_lowerdir_puts_top_layer_first() {
+        let dir = TempDir::new().unwrap();
+        let store = LayerStore::new(dir.path().join("layers"));
+        store.initialize().await.unwrap();
+
+        let base = layer_tarball(dir.path(), "base", "a", b"a");
+        let top = layer_tarball(dir.path(), "top", "b", b"b");
+        store.import_blob(&base, "sha256:base", "").await.unwrap();
+        store.import_blob(&top, "sha256:top", "").await.unwrap();
+
+        let lowerdir = store
+            .lowerdir(&["sha256:base".to_string(), "sha256:top".to_string()])
+            .unwrap();
+        let expected = format!(
+            "{}:{}",
+            store.layer_path("sha256:top").display(),
+            store.layer_path("sha256:base").display()
+        );
+        assert_eq!(lowerdir, expected);
+
+        assert!(store.lowerdir(&[]).is_err());
+        assert!(store.lowerdir(&["sha256:missing".to_string()]).is_err());
+    }
+}
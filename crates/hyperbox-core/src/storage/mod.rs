@@ -2,35 +2,71 @@
 //!
 //! Provides composefs integration, layer management, and image caching.
 
+pub mod changes;
 pub mod composefs;
+pub mod credentials;
+pub mod images;
 pub mod layers;
 pub mod registry;
+pub mod unpack;
 
 pub use composefs::ComposefsManager;
+pub use credentials::{CredentialStore, Credentials};
+pub use images::{ImageStore, StoredImage};
 pub use layers::LayerStore;
-pub use registry::ImageRegistry;
+pub use registry::{ImageRegistry, LocalImage};
 
+use crate::error::{CoreError, Result};
+use crate::types::Platform;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+/// Media type of OCI image manifests.
+pub const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+
+/// Media type of OCI image configurations.
+pub const CONFIG_MEDIA_TYPE: &str = "application/vnd.oci.image.config.v1+json";
+
+/// Media type of uncompressed OCI layers, as created from containers.
+pub const LAYER_MEDIA_TYPE: &str = "application/vnd.oci.image.layer.v1.tar";
+
+/// Media type of OCI image indexes (multi-platform images).
+pub const INDEX_MEDIA_TYPE: &str = "application/vnd.oci.image.index.v1+json";
+
+/// Media type of Docker image manifests.
+pub const DOCKER_MANIFEST_MEDIA_TYPE: &str = "application/vnd.docker.distribution.manifest.v2+json";
+
+/// Media type of Docker manifest lists (multi-platform images).
+pub const DOCKER_MANIFEST_LIST_MEDIA_TYPE: &str =
+    "application/vnd.docker.distribution.manifest.list.v2+json";
+
+/// Serialize a value with object keys sorted, so equal values always
+/// serialize, and hash, the same.
+pub(crate) fn canonical_json<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(&serde_json::to_value(value)?)?)
+}
+
 /// Image manifest (OCI Image Manifest).
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ImageManifest {
     /// Schema version
     pub schema_version: u32,
     /// Media type
+    #[serde(default)]
     pub media_type: String,
     /// Config descriptor
     pub config: Descriptor,
     /// Layer descriptors
     pub layers: Vec<Descriptor>,
     /// Annotations
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "std::collections::HashMap::is_empty")]
     pub annotations: std::collections::HashMap<String, String>,
 }
 
 /// Content descriptor.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Descriptor {
     /// Media type
     pub media_type: String,
@@ -39,10 +75,45 @@ pub struct Descriptor {
     /// Size in bytes
     pub size: u64,
     /// Annotations
+    #[serde(default, skip_serializing_if = "std::collections::HashMap::is_empty")]
+    pub annotations: std::collections::HashMap<String, String>,
+    /// Platform of the referenced manifest, in image indexes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+}
+
+/// Image index (OCI Image Index or Docker manifest list), pointing at one
+/// manifest per platform.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageIndex {
+    /// Schema version
+    pub schema_version: u32,
+    /// Media type
+    #[serde(default)]
+    pub media_type: String,
+    /// Manifest descriptors
+    pub manifests: Vec<Descriptor>,
+    /// Annotations
     #[serde(default)]
     pub annotations: std::collections::HashMap<String, String>,
 }
 
+impl ImageIndex {
+    /// The manifest for `platform`, if the index has one.
+    #[must_use]
+    pub fn select(&self, platform: &Platform) -> Option<&Descriptor> {
+        self.manifests
+            .iter()
+            .find(|m| m.platform.as_ref().is_some_and(|p| p.matches(platform)))
+    }
+
+    /// Platforms the index has manifests for.
+    pub fn platforms(&self) -> impl Iterator<Item = &Platform> {
+        self.manifests.iter().filter_map(|m| m.platform.as_ref())
+    }
+}
+
 /// Image configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageConfig {
@@ -50,6 +121,9 @@ pub struct ImageConfig {
     pub architecture: String,
     /// OS
     pub os: String,
+    /// CPU variant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
     /// Created timestamp
     pub created: Option<String>,
     /// Author
@@ -59,9 +133,37 @@ pub struct ImageConfig {
     /// Root filesystem
     pub rootfs: RootFs,
     /// History
+    #[serde(default)]
     pub history: Vec<HistoryEntry>,
 }
 
+impl ImageConfig {
+    /// Platform the image was built for.
+    #[must_use]
+    pub fn platform(&self) -> Platform {
+        Platform::new(&self.os, &self.architecture, self.variant.clone())
+    }
+
+    /// Check the `diff_id` of the unpacked layer at `index` against
+    /// `rootfs.diff_ids`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the configuration lists a different `diff_id`, or
+    /// none for the layer.
+    pub fn verify_diff_id(&self, index: usize, diff_id: &str) -> Result<()> {
+        match self.rootfs.diff_ids.get(index) {
+            Some(expected) if expected == diff_id => Ok(()),
+            Some(expected) => Err(CoreError::StorageOperation(format!(
+                "layer {index} has diff_id {diff_id}, image config expects {expected}"
+            ))),
+            None => Err(CoreError::StorageOperation(format!(
+                "image config lists no diff_id for layer {index}"
+            ))),
+        }
+    }
+}
+
 /// Container configuration from image.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ContainerConfig {
@@ -94,6 +196,202 @@ pub struct ContainerConfig {
     pub stop_signal: Option<String>,
 }
 
+impl ContainerConfig {
+    /// Build configuration changes from Dockerfile instructions, as given
+    /// to `docker commit --change`.
+    ///
+    /// Supports `CMD`, `ENTRYPOINT`, `ENV`, `EXPOSE`, `LABEL`, `STOPSIGNAL`,
+    /// `USER`, `VOLUME` and `WORKDIR`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::InvalidSpec`] for unsupported or malformed
+    /// instructions.
+    pub fn from_changes<S: AsRef<str>>(changes: &[S]) -> Result<Self> {
+        let mut config = Self::default();
+        for change in changes {
+            let change = change.as_ref().trim();
+            let invalid = |reason: &str| CoreError::InvalidSpec {
+                field: "change".to_string(),
+                reason: format!("{change}: {reason}"),
+            };
+            let (instruction, args) = change
+                .split_once(char::is_whitespace)
+                .map(|(instruction, args)| (instruction, args.trim()))
+                .filter(|(_, args)| !args.is_empty())
+                .ok_or_else(|| invalid("missing arguments"))?;
+
+            match instruction.to_ascii_uppercase().as_str() {
+                "CMD" => config.cmd = Some(instruction_command(args)),
+                "ENTRYPOINT" => config.entrypoint = Some(instruction_command(args)),
+                "ENV" => {
+                    let env = config.env.get_or_insert_with(Vec::new);
+                    for (key, value) in
+                        instruction_pairs(args).ok_or_else(|| invalid("expected KEY=VALUE"))?
+                    {
+                        env.push(format!("{key}={value}"));
+                    }
+                }
+                "LABEL" => {
+                    let labels = config.labels.get_or_insert_with(Default::default);
+                    labels.extend(
+                        instruction_pairs(args).ok_or_else(|| invalid("expected KEY=VALUE"))?,
+                    );
+                }
+                "EXPOSE" => {
+                    let ports = config.exposed_ports.get_or_insert_with(Default::default);
+                    for port in args.split_whitespace() {
+                        let port = if port.contains('/') {
+                            port.to_string()
+                        } else {
+                            format!("{port}/tcp")
+                        };
+                        ports.insert(port, serde_json::json!({}));
+                    }
+                }
+                "VOLUME" => {
+                    let volumes = config.volumes.get_or_insert_with(Default::default);
+                    let paths = serde_json::from_str::<Vec<String>>(args)
+                        .unwrap_or_else(|_| args.split_whitespace().map(str::to_string).collect());
+                    for path in paths {
+                        volumes.insert(path, serde_json::json!({}));
+                    }
+                }
+                "STOPSIGNAL" => config.stop_signal = Some(args.to_string()),
+                "USER" => config.user = Some(args.to_string()),
+                "WORKDIR" => config.working_dir = Some(args.to_string()),
+                _ => return Err(invalid("unsupported instruction")),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Express this configuration as Dockerfile instructions, the inverse
+    /// of [`from_changes`](Self::from_changes).
+    #[must_use]
+    pub fn to_changes(&self) -> Vec<String> {
+        fn pair(instruction: &str, key: &str, value: &str) -> String {
+            // The `KEY VALUE` form keeps values with spaces intact.
+            if value.is_empty() || !value.contains(char::is_whitespace) {
+                format!("{instruction} {key}={value}")
+            } else {
+                format!("{instruction} {key} {value}")
+            }
+        }
+        fn sorted_keys<V>(map: &std::collections::HashMap<String, V>) -> Vec<&str> {
+            let mut keys: Vec<&str> = map.keys().map(String::as_str).collect();
+            keys.sort_unstable();
+            keys
+        }
+        let json = |values: &[&str]| serde_json::to_string(values).unwrap_or_default();
+
+        let mut changes = Vec::new();
+        for var in self.env.iter().flatten() {
+            let (key, value) = var.split_once('=').unwrap_or((var, ""));
+            changes.push(pair("ENV", key, value));
+        }
+        if let Some(labels) = &self.labels {
+            for key in sorted_keys(labels) {
+                changes.push(pair("LABEL", key, &labels[key]));
+            }
+        }
+        if let Some(ports) = self.exposed_ports.as_ref().filter(|p| !p.is_empty()) {
+            changes.push(format!("EXPOSE {}", sorted_keys(ports).join(" ")));
+        }
+        if let Some(volumes) = self.volumes.as_ref().filter(|v| !v.is_empty()) {
+            changes.push(format!("VOLUME {}", json(&sorted_keys(volumes))));
+        }
+        for (instruction, value) in [
+            ("USER", &self.user),
+            ("WORKDIR", &self.working_dir),
+            ("STOPSIGNAL", &self.stop_signal),
+        ] {
+            if let Some(value) = value {
+                changes.push(format!("{instruction} {value}"));
+            }
+        }
+        for (instruction, argv) in [("ENTRYPOINT", &self.entrypoint), ("CMD", &self.cmd)] {
+            if let Some(argv) = argv {
+                let argv: Vec<&str> = argv.iter().map(String::as_str).collect();
+                changes.push(format!("{instruction} {}", json(&argv)));
+            }
+        }
+        changes
+    }
+
+    /// Apply `changes` on top of this configuration.
+    ///
+    /// Fields set in `changes` replace ours, except that `Env` is overridden
+    /// per variable and `ExposedPorts`, `Volumes` and `Labels` are merged.
+    pub fn apply(&mut self, changes: &Self) {
+        fn merge<V: Clone>(
+            base: &mut Option<std::collections::HashMap<String, V>>,
+            changes: Option<&std::collections::HashMap<String, V>>,
+        ) {
+            if let Some(changes) = changes {
+                base.get_or_insert_with(Default::default)
+                    .extend(changes.iter().map(|(k, v)| (k.clone(), v.clone())));
+            }
+        }
+
+        if let Some(env) = &changes.env {
+            let base = self.env.get_or_insert_with(Vec::new);
+            for var in env {
+                let key = var.split('=').next().unwrap_or_default();
+                match base.iter_mut().find(|v| v.split('=').next() == Some(key)) {
+                    Some(existing) => existing.clone_from(var),
+                    None => base.push(var.clone()),
+                }
+            }
+        }
+        merge(&mut self.exposed_ports, changes.exposed_ports.as_ref());
+        merge(&mut self.volumes, changes.volumes.as_ref());
+        merge(&mut self.labels, changes.labels.as_ref());
+
+        for (field, value) in [
+            (&mut self.user, &changes.user),
+            (&mut self.working_dir, &changes.working_dir),
+            (&mut self.stop_signal, &changes.stop_signal),
+        ] {
+            if value.is_some() {
+                field.clone_from(value);
+            }
+        }
+        for (field, value) in [
+            (&mut self.entrypoint, &changes.entrypoint),
+            (&mut self.cmd, &changes.cmd),
+        ] {
+            if value.is_some() {
+                field.clone_from(value);
+            }
+        }
+    }
+}
+
+/// Arguments of `CMD` or `ENTRYPOINT`: a JSON array, or a shell command.
+fn instruction_command(args: &str) -> Vec<String> {
+    serde_json::from_str(args)
+        .unwrap_or_else(|_| vec!["/bin/sh".to_string(), "-c".to_string(), args.to_string()])
+}
+
+/// Arguments of `ENV` or `LABEL`: `KEY=VALUE ...`, or the legacy
+/// `KEY VALUE` form.
+fn instruction_pairs(args: &str) -> Option<Vec<(String, String)>> {
+    let unquote = |value: &str| value.trim_matches('"').to_string();
+
+    let first = args.split_whitespace().next()?;
+    if !first.contains('=') {
+        let (key, value) = args.split_once(char::is_whitespace)?;
+        return Some(vec![(key.to_string(), unquote(value.trim()))]);
+    }
+    args.split_whitespace()
+        .map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            (!key.is_empty()).then(|| (unquote(key), unquote(value)))
+        })
+        .collect()
+}
+
 /// Root filesystem.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RootFs {
@@ -155,3 +453,81 @@ impl Default for StorageConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_config_changes() {
+        let mut config = ContainerConfig {
+            env: Some(vec!["PATH=/bin".to_string(), "MODE=dev".to_string()]),
+            cmd: Some(vec!["sh".to_string()]),
+            labels: Some([("team".to_string(), "infra".to_string())].into()),
+            ..ContainerConfig::default()
+        };
+        config.apply(&ContainerConfig {
+            env: Some(vec!["MODE=prod".to_string(), "PORT=80".to_string()]),
+            cmd: Some(vec!["serve".to_string()]),
+            labels: Some([("tier".to_string(), "web".to_string())].into()),
+            ..ContainerConfig::default()
+        });
+
+        assert_eq!(config.env.unwrap(), ["PATH=/bin", "MODE=prod", "PORT=80"]);
+        assert_eq!(config.cmd.unwrap(), ["serve"]);
+        assert_eq!(config.labels.unwrap().len(), 2);
+        assert!(config.entrypoint.is_none());
+    }
+
+    #[test]
+    fn test_config_from_changes() {
+        let config = ContainerConfig::from_changes(&[
+            r#"CMD ["python", "app.py"]"#,
+            "ENTRYPOINT exec serve",
+            "ENV MODE=prod PORT=8080",
+            "env DEBUG 0",
+            r#"LABEL team="infra""#,
+            "EXPOSE 8080 53/udp",
+            "WORKDIR /srv",
+        ])
+        .unwrap();
+
+        assert_eq!(config.cmd.unwrap(), ["python", "app.py"]);
+        assert_eq!(config.entrypoint.unwrap(), ["/bin/sh", "-c", "exec serve"]);
+        assert_eq!(config.env.unwrap(), ["MODE=prod", "PORT=8080", "DEBUG=0"]);
+        assert_eq!(config.labels.unwrap()["team"], "infra");
+        let ports = config.exposed_ports.unwrap();
+        assert!(ports.contains_key("8080/tcp") && ports.contains_key("53/udp"));
+        assert_eq!(config.working_dir.as_deref(), Some("/srv"));
+
+        assert!(ContainerConfig::from_changes(&["RUN make"]).is_err());
+        assert!(ContainerConfig::from_changes(&["CMD"]).is_err());
+        assert!(ContainerConfig::from_changes(&["ENV =x"]).is_err());
+    }
+
+    #[test]
+    fn test_config_to_changes_round_trips() {
+        let config = ContainerConfig::from_changes(&[
+            r#"CMD ["python", "app.py"]"#,
+            "ENV GREETING hello world",
+            "ENV EMPTY=",
+            "LABEL team=infra",
+            "EXPOSE 8080",
+            r#"VOLUME ["/data"]"#,
+            "USER app",
+        ])
+        .unwrap();
+
+        let changes = config.to_changes();
+        assert!(changes.contains(&"ENV GREETING hello world".to_string()));
+        assert!(changes.contains(&r#"CMD ["python","app.py"]"#.to_string()));
+
+        let parsed = ContainerConfig::from_changes(&changes).unwrap();
+        assert_eq!(parsed.cmd, config.cmd);
+        assert_eq!(parsed.env, config.env);
+        assert_eq!(parsed.labels, config.labels);
+        assert_eq!(parsed.exposed_ports, config.exposed_ports);
+        assert_eq!(parsed.volumes, config.volumes);
+        assert_eq!(parsed.user, config.user);
+    }
+}
@@ -1,45 +1,184 @@
 //! Container image registry client.
 //!
-//! Supports pulling images from OCI-compliant registries.
+//! Supports pulling images from and pushing images to OCI-compliant
+//! registries. For multi-platform images (OCI image indexes and Docker
+//! manifest lists) the manifest matching the requested [`Platform`] is
+//! pulled.
+//!
+//! # Authentication
+//!
+//! The first request to a registry pings `/v2/` to learn how it
+//! authenticates from its `WWW-Authenticate` challenge. `Basic` registries
+//! get the stored [`Credentials`] with every request; `Bearer` registries get
+//! a token from their token service for the scope of the request
+//! (`repository:<name>:pull`, or `repository:<name>:pull,push` to push),
+//! fetched with the credentials if there are any and anonymously otherwise.
+//! Tokens are cached until their `expires_in` runs out.
+//!
+//! # Downloads
+//!
+//! Layers download concurrently. Each blob streams to disk while it is
+//! hashed and only enters the cache once it matches its descriptor;
+//! interrupted downloads resume where they stopped. Progress is reported
+//! per layer through [`PullOptions::progress`].
+//!
+//! # Uploads
+//!
+//! A push skips blobs the repository already has (`HEAD`), mounts blobs
+//! from other repositories of the same registry where it can, and uploads
+//! the rest: in one request, or in chunks above the upload chunk size. The
+//! manifest goes last, and its digest is returned.
 
 use crate::error::{CoreError, Result};
-use crate::storage::{ImageConfig, ImageManifest};
-use flate2::read::GzDecoder;
-use reqwest::Client;
+use crate::storage::credentials::{CredentialStore, Credentials};
+use crate::storage::unpack::{self, Whiteouts};
+use crate::storage::{
+    canonical_json, Descriptor, ImageConfig, ImageIndex, ImageManifest, CONFIG_MEDIA_TYPE,
+    DOCKER_MANIFEST_LIST_MEDIA_TYPE, DOCKER_MANIFEST_MEDIA_TYPE, INDEX_MEDIA_TYPE,
+    MANIFEST_MEDIA_TYPE,
+};
+use crate::types::{
+    Platform, PullOptions, PullProgress, PullStatus, PushOptions, PushProgress, PushStatus,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use parking_lot::Mutex;
+use reqwest::header::{
+    ACCEPT, AUTHORIZATION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, LOCATION, RANGE,
+    WWW_AUTHENTICATE,
+};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::io::Read;
 use std::path::{Path, PathBuf};
-use tar::Archive;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
-use tracing::{debug, info};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, info, warn};
 
 /// Docker Hub registry URL.
 pub const DOCKER_HUB_REGISTRY: &str = "https://registry-1.docker.io";
 /// Docker Hub auth service.
 pub const DOCKER_HUB_AUTH: &str = "https://auth.docker.io";
 
+/// Lifetime of a token whose response has no `expires_in`, per the
+/// distribution token spec.
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(60);
+
+/// Tokens are renewed this long before they expire.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(5);
+
+/// Layers downloaded at the same time by default, as in Docker.
+const DEFAULT_CONCURRENT_DOWNLOADS: usize = 3;
+
+/// How often an interrupted blob download is resumed before giving up.
+const DOWNLOAD_ATTEMPTS: u32 = 5;
+
+/// Minimum time between two progress reports for the same blob.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Blobs larger than this are uploaded in chunks of this size.
+const DEFAULT_UPLOAD_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Header with the digest a registry computed for a manifest.
+const DOCKER_CONTENT_DIGEST: &str = "docker-content-digest";
+
 /// Image registry client.
 pub struct ImageRegistry {
     /// HTTP client
     client: Client,
     /// Cache directory
     cache_dir: PathBuf,
-    /// Authentication tokens
-    tokens: HashMap<String, String>,
+    /// Registry credentials
+    credentials: CredentialStore,
+    /// How each registry authenticates
+    challenges: Mutex<HashMap<String, Challenge>>,
+    /// Bearer tokens by registry and scope
+    tokens: Mutex<HashMap<String, CachedToken>>,
+    /// Locks serializing downloads into the same path
+    downloads: Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+    /// Layers downloaded at the same time during a pull
+    max_concurrent_downloads: usize,
+    /// Size of the chunks large blobs are uploaded in
+    upload_chunk_size: u64,
+}
+
+/// How a registry asks clients to authenticate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Challenge {
+    /// No authentication
+    Anonymous,
+    /// HTTP basic authentication with the registry credentials
+    Basic,
+    /// Tokens from a token service
+    Bearer {
+        realm: String,
+        service: Option<String>,
+    },
+}
+
+impl Challenge {
+    /// Parse a `WWW-Authenticate` header.
+    fn parse(header: &str) -> Option<Self> {
+        let (scheme, params) = header
+            .trim()
+            .split_once(' ')
+            .unwrap_or_else(|| (header.trim(), ""));
+        if scheme.eq_ignore_ascii_case("basic") {
+            return Some(Self::Basic);
+        }
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            return None;
+        }
+
+        let mut params = auth_params(params);
+        Some(Self::Bearer {
+            realm: params.remove("realm")?,
+            service: params.remove("service"),
+        })
+    }
+}
+
+/// Parse the `key="value"` parameters of a challenge.
+fn auth_params(params: &str) -> HashMap<String, String> {
+    let mut parsed = HashMap::new();
+    let mut rest = params.trim();
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key
+            .trim()
+            .trim_start_matches(',')
+            .trim()
+            .to_ascii_lowercase();
+        let (value, after) = after.strip_prefix('"').map_or_else(
+            || after.split_once(',').unwrap_or((after, "")),
+            |quoted| quoted.split_once('"').unwrap_or((quoted, "")),
+        );
+        parsed.insert(key, value.to_string());
+        rest = after.trim_start_matches(',').trim();
+    }
+    parsed
+}
+
+/// A bearer token and when to stop using it.
+#[derive(Debug)]
+struct CachedToken {
+    token: String,
+    expires_at: Instant,
 }
 
 /// Token response from registry auth.
 #[derive(Debug, Deserialize)]
 struct TokenResponse {
+    #[serde(alias = "access_token")]
     token: String,
-    #[allow(dead_code)]
     expires_in: Option<u64>,
 }
 
 impl ImageRegistry {
-    /// Create a new registry client.
+    /// Create a new registry client using the Docker CLI's credentials.
     pub fn new(cache_dir: impl Into<PathBuf>) -> Result<Self> {
         let client = Client::builder()
             .user_agent(format!("hyperbox/{}", env!("CARGO_PKG_VERSION")))
@@ -49,10 +188,58 @@ impl ImageRegistry {
         Ok(Self {
             client,
             cache_dir: cache_dir.into(),
-            tokens: HashMap::new(),
+            credentials: CredentialStore::from_env(),
+            challenges: Mutex::new(HashMap::new()),
+            tokens: Mutex::new(HashMap::new()),
+            downloads: Mutex::new(HashMap::new()),
+            max_concurrent_downloads: DEFAULT_CONCURRENT_DOWNLOADS,
+            upload_chunk_size: DEFAULT_UPLOAD_CHUNK_SIZE,
         })
     }
 
+    /// Use a different credential store.
+    #[must_use]
+    pub fn with_credentials(mut self, credentials: CredentialStore) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// The store registry credentials are read from and saved to.
+    #[must_use]
+    pub const fn credentials(&self) -> &CredentialStore {
+        &self.credentials
+    }
+
+    /// Download up to `max` layers at the same time.
+    #[must_use]
+    pub fn with_max_concurrent_downloads(mut self, max: usize) -> Self {
+        self.max_concurrent_downloads = max.max(1);
+        self
+    }
+
+    /// Upload blobs larger than `size` bytes in chunks of that size.
+    #[must_use]
+    pub fn with_upload_chunk_size(mut self, size: u64) -> Self {
+        self.upload_chunk_size = size.max(1);
+        self
+    }
+
+    /// The registry API URL for a server name as given to `hb login`:
+    /// Docker Hub when empty, `https://` unless a scheme is given.
+    #[must_use]
+    pub fn registry_url(server: &str) -> String {
+        let server = server.trim_end_matches('/');
+        match server {
+            "" | "docker.io" | "index.docker.io" | "registry-1.docker.io" => {
+                DOCKER_HUB_REGISTRY.to_string()
+            }
+            _ if server.starts_with("http://") || server.starts_with("https://") => {
+                server.to_string()
+            }
+            _ => format!("https://{server}"),
+        }
+    }
+
     /// Parse an image reference.
     pub fn parse_ref(image: &str) -> (String, String, String) {
         let mut registry = DOCKER_HUB_REGISTRY.to_string();
@@ -65,7 +252,10 @@ impl ImageRegistry {
                 || image.split('/').next().unwrap().contains(':'))
         {
             let parts: Vec<&str> = image.splitn(2, '/').collect();
-            registry = format!("https://{}", parts[0]);
+            // `docker.io` is only the canonical name; the API lives elsewhere.
+            if parts[0] != "docker.io" && parts[0] != "index.docker.io" {
+                registry = format!("https://{}", parts[0]);
+            }
             name = parts[1].to_string();
         }
 
@@ -89,104 +279,311 @@ impl ImageRegistry {
         (registry, name, tag)
     }
 
-    /// Get authentication token for a registry.
-    async fn get_token(&mut self, registry: &str, repo: &str) -> Result<String> {
-        let cache_key = format!("{registry}/{repo}");
+    /// Check credentials against a registry and store them.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::PermissionDenied`] if the registry rejects the
+    /// credentials, or an error if it cannot be reached or the credentials
+    /// cannot be stored.
+    pub async fn login(&self, registry: &str, credentials: &Credentials) -> Result<()> {
+        match self.challenge(registry).await? {
+            Challenge::Anonymous => {}
+            Challenge::Basic => {
+                let response = self
+                    .client
+                    .get(format!("{registry}/v2/"))
+                    .header(AUTHORIZATION, basic_auth(credentials)?)
+                    .send()
+                    .await
+                    .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?;
+                if !response.status().is_success() {
+                    return Err(unauthorized(registry));
+                }
+            }
+            Challenge::Bearer { realm, service } => {
+                self.fetch_token(registry, &realm, service.as_deref(), &[], Some(credentials))
+                    .await?;
+            }
+        }
+
+        self.credentials.store(registry, credentials).await?;
+        self.forget(registry);
+        info!("Logged in to {}", registry);
+        Ok(())
+    }
+
+    /// Remove the stored credentials for a registry.
+    ///
+    /// # Returns
+    ///
+    /// Whether there were credentials to remove.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credentials cannot be removed.
+    pub async fn logout(&self, registry: &str) -> Result<bool> {
+        self.forget(registry);
+        self.credentials.erase(registry).await
+    }
 
-        if let Some(token) = self.tokens.get(&cache_key) {
-            return Ok(token.clone());
+    /// Drop the cached challenge and tokens of a registry.
+    fn forget(&self, registry: &str) {
+        self.challenges.lock().remove(registry);
+        let prefix = format!("{registry}|");
+        self.tokens
+            .lock()
+            .retain(|key, _| !key.starts_with(&prefix));
+    }
+
+    /// How a registry authenticates, from the challenge of `/v2/`.
+    async fn challenge(&self, registry: &str) -> Result<Challenge> {
+        if let Some(challenge) = self.challenges.lock().get(registry) {
+            return Ok(challenge.clone());
         }
 
-        // For Docker Hub, use auth service
-        if registry.contains("docker.io") {
-            let auth_url = format!(
-                "{}/token?service=registry.docker.io&scope=repository:{}:pull",
-                DOCKER_HUB_AUTH, repo
-            );
+        let response = self
+            .client
+            .get(format!("{registry}/v2/"))
+            .send()
+            .await
+            .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?;
+        let challenge = if response.status() == StatusCode::UNAUTHORIZED {
+            response
+                .headers()
+                .get(WWW_AUTHENTICATE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Challenge::parse)
+                .ok_or_else(|| {
+                    CoreError::NetworkConfiguration(format!(
+                        "{registry} requires an unsupported authentication scheme"
+                    ))
+                })?
+        } else {
+            Challenge::Anonymous
+        };
+
+        debug!(registry, ?challenge, "Registry authentication");
+        self.challenges
+            .lock()
+            .insert(registry.to_string(), challenge.clone());
+        Ok(challenge)
+    }
 
-            debug!("Fetching token from {}", auth_url);
+    /// The `Authorization` header for a request needing `scopes`.
+    async fn authorization(&self, registry: &str, scopes: &[String]) -> Result<Option<String>> {
+        match self.challenge(registry).await? {
+            Challenge::Anonymous => Ok(None),
+            Challenge::Basic => {
+                let credentials = self
+                    .credentials
+                    .get(registry)
+                    .await?
+                    .ok_or_else(|| unauthorized(registry))?;
+                basic_auth(&credentials).map(Some)
+            }
+            Challenge::Bearer { realm, service } => {
+                let cache_key = format!("{registry}|{}", scopes.join(" "));
+                if let Some(cached) = self.tokens.lock().get(&cache_key) {
+                    if Instant::now() < cached.expires_at {
+                        return Ok(Some(format!("Bearer {}", cached.token)));
+                    }
+                }
+
+                let credentials = self.credentials.get(registry).await?;
+                let cached = self
+                    .fetch_token(
+                        registry,
+                        &realm,
+                        service.as_deref(),
+                        scopes,
+                        credentials.as_ref(),
+                    )
+                    .await?;
+                let header = format!("Bearer {}", cached.token);
+                self.tokens.lock().insert(cache_key, cached);
+                Ok(Some(header))
+            }
+        }
+    }
 
-            let response: TokenResponse = self
-                .client
-                .get(&auth_url)
+    /// Get a token from a registry's token service: with a refresh token
+    /// through the `OAuth2` endpoint, otherwise with basic credentials or
+    /// anonymously.
+    async fn fetch_token(
+        &self,
+        registry: &str,
+        realm: &str,
+        service: Option<&str>,
+        scopes: &[String],
+        credentials: Option<&Credentials>,
+    ) -> Result<CachedToken> {
+        debug!(realm, ?scopes, "Fetching registry token");
+
+        let request = if let Some(Credentials::IdentityToken(refresh_token)) = credentials {
+            let mut form = vec![
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token.as_str()),
+                ("client_id", "hyperbox"),
+            ];
+            form.extend(service.map(|service| ("service", service)));
+            form.extend(scopes.iter().map(|scope| ("scope", scope.as_str())));
+            self.client.post(realm).form(&form)
+        } else {
+            let mut query = Vec::new();
+            query.extend(service.map(|service| ("service", service)));
+            query.extend(scopes.iter().map(|scope| ("scope", scope.as_str())));
+            let mut request = self.client.get(realm).query(&query);
+            if let Some(credentials) = credentials {
+                request = request.header(AUTHORIZATION, basic_auth(credentials)?);
+            }
+            request
+        };
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?;
+        if matches!(response.status(), StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
+            return Err(unauthorized(registry));
+        }
+        if !response.status().is_success() {
+            return Err(CoreError::NetworkConfiguration(format!(
+                "token service of {registry}: Status {}",
+                response.status()
+            )));
+        }
+
+        let token: TokenResponse = response
+            .json()
+            .await
+            .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?;
+        let lifetime = token
+            .expires_in
+            .map_or(DEFAULT_TOKEN_LIFETIME, Duration::from_secs);
+        Ok(CachedToken {
+            token: token.token,
+            expires_at: Instant::now() + lifetime.saturating_sub(TOKEN_EXPIRY_MARGIN),
+        })
+    }
+
+    /// Send a request authenticated for `scopes`, such as
+    /// [`pull_scope`] of the repository.
+    ///
+    /// A token the registry rejects is dropped and the request retried once
+    /// with a fresh one.
+    async fn send(
+        &self,
+        registry: &str,
+        scopes: &[String],
+        request: impl Fn(&Client) -> RequestBuilder,
+    ) -> Result<Response> {
+        let mut retried = false;
+        loop {
+            let mut builder = request(&self.client);
+            if let Some(authorization) = self.authorization(registry, scopes).await? {
+                builder = builder.header(AUTHORIZATION, authorization);
+            }
+            let response = builder
                 .send()
                 .await
-                .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?
-                .json()
-                .await
                 .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?;
 
-            self.tokens.insert(cache_key, response.token.clone());
-            return Ok(response.token);
+            if response.status() != StatusCode::UNAUTHORIZED {
+                return Ok(response);
+            }
+            if retried {
+                return Err(unauthorized(registry));
+            }
+            retried = true;
+            self.forget(registry);
         }
-
-        // For other registries, return empty token (anonymous)
-        Ok(String::new())
     }
 
-    /// Fetch image manifest.
+    /// Fetch the image manifest for `platform`.
+    ///
+    /// When `reference` names a multi-platform image (an OCI image index or
+    /// a Docker manifest list), the manifest of the entry matching
+    /// `platform` is fetched from it.
     pub async fn get_manifest(
-        &mut self,
+        &self,
         registry: &str,
         name: &str,
         reference: &str,
+        platform: &Platform,
     ) -> Result<ImageManifest> {
-        let token = self.get_token(registry, name).await?;
+        let manifest = self.fetch_manifest(registry, name, reference).await?;
+        if manifest.get("manifests").is_none() {
+            let manifest: ImageManifest = serde_json::from_value(manifest)?;
+            return Ok(manifest);
+        }
 
-        let url = format!("{}/v2/{}/manifests/{}", registry, name, reference);
+        let index: ImageIndex = serde_json::from_value(manifest)?;
+        let Some(entry) = index.select(platform) else {
+            let available: Vec<String> = index.platforms().map(ToString::to_string).collect();
+            return Err(CoreError::ImageNotFound(format!(
+                "{name}:{reference} for {platform} (available: {})",
+                available.join(", ")
+            )));
+        };
 
-        info!("Fetching manifest from {}", url);
+        debug!("Selected manifest {} for {} from index", entry.digest, platform);
+        let manifest = self.fetch_manifest(registry, name, &entry.digest).await?;
+        let manifest: ImageManifest = serde_json::from_value(manifest)?;
+        Ok(manifest)
+    }
 
-        let mut request = self
-            .client
-            .get(&url)
-            .header("Accept", "application/vnd.oci.image.manifest.v1+json")
-            .header("Accept", "application/vnd.docker.distribution.manifest.v2+json");
+    /// Fetch a manifest or image index as JSON.
+    async fn fetch_manifest(
+        &self,
+        registry: &str,
+        name: &str,
+        reference: &str,
+    ) -> Result<serde_json::Value> {
+        let url = format!("{registry}/v2/{name}/manifests/{reference}");
 
-        if !token.is_empty() {
-            request = request.header("Authorization", format!("Bearer {token}"));
-        }
+        info!("Fetching manifest from {}", url);
 
-        let response = request
-            .send()
-            .await
-            .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?;
+        let accept = [
+            MANIFEST_MEDIA_TYPE,
+            DOCKER_MANIFEST_MEDIA_TYPE,
+            INDEX_MEDIA_TYPE,
+            DOCKER_MANIFEST_LIST_MEDIA_TYPE,
+        ]
+        .join(", ");
+        let response = self
+            .send(registry, &[pull_scope(name)], |client| {
+                client.get(&url).header(ACCEPT, &accept)
+            })
+            .await?;
 
         if !response.status().is_success() {
-            return Err(CoreError::ImageNotFound(format!("{}/{}", name, reference)));
+            return Err(CoreError::ImageNotFound(format!("{name}/{reference}")));
         }
 
-        let manifest: ImageManifest = response
+        response
             .json()
             .await
-            .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?;
-
-        Ok(manifest)
+            .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))
     }
 
     /// Fetch image config.
+    ///
+    /// The blob is verified against its digest and kept in the cache, so a
+    /// later push uploads it unchanged.
     pub async fn get_config(
-        &mut self,
+        &self,
         registry: &str,
         name: &str,
         config_digest: &str,
     ) -> Result<ImageConfig> {
-        let token = self.get_token(registry, name).await?;
-
         let url = format!("{}/v2/{}/blobs/{}", registry, name, config_digest);
 
         debug!("Fetching config from {}", url);
 
-        let mut request = self.client.get(&url);
-
-        if !token.is_empty() {
-            request = request.header("Authorization", format!("Bearer {token}"));
-        }
-
-        let response = request
-            .send()
-            .await
-            .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?;
+        let response = self
+            .send(registry, &[pull_scope(name)], |client| client.get(&url))
+            .await?;
 
         if !response.status().is_success() {
             return Err(CoreError::StorageOperation(format!(
@@ -195,92 +592,300 @@ impl ImageRegistry {
             )));
         }
 
-        let config: ImageConfig = response
-            .json()
+        let blob = response
+            .bytes()
             .await
             .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?;
+        if sha256_digest(&blob) != config_digest {
+            return Err(CoreError::StorageOperation(format!(
+                "config blob {config_digest} failed verification"
+            )));
+        }
+
+        let config: ImageConfig = serde_json::from_slice(&blob)?;
+        let cached = self.cache_dir.join("blobs").join(config_digest);
+        fs::create_dir_all(self.cache_dir.join("blobs")).await?;
+        fs::write(&cached, &blob).await?;
 
         Ok(config)
     }
 
-    /// Download a blob (layer).
+    /// Download a blob into `output`, verifying it against `descriptor`.
+    ///
+    /// The blob streams into a partial file of its own while it is hashed
+    /// and is renamed to `output` only once its size and digest match, so the
+    /// cache never holds a truncated or corrupt blob. A transfer that breaks
+    /// off is resumed with an HTTP range request up to [`DOWNLOAD_ATTEMPTS`]
+    /// times. Downloads into the same `output` run one at a time; one that
+    /// finds `output` already written by another returns its size.
     pub async fn download_blob(
-        &mut self,
+        &self,
         registry: &str,
         name: &str,
-        digest: &str,
+        descriptor: &Descriptor,
         output: &Path,
+        options: &PullOptions,
     ) -> Result<u64> {
-        let token = self.get_token(registry, name).await?;
+        let expected = descriptor.digest.strip_prefix("sha256:").ok_or_else(|| {
+            CoreError::StorageOperation(format!(
+                "unsupported digest algorithm in {}",
+                descriptor.digest
+            ))
+        })?;
+
+        let lock = self.download_lock(output);
+        let guard = lock.lock().await;
+        let result = self
+            .download_blob_locked(registry, name, descriptor, expected, output, options)
+            .await;
+        drop(guard);
+        drop(lock);
+        self.downloads
+            .lock()
+            .retain(|_, lock| Arc::strong_count(lock) > 1);
+        result
+    }
 
-        let url = format!("{}/v2/{}/blobs/{}", registry, name, digest);
+    /// The lock serializing downloads into `output`.
+    fn download_lock(&self, output: &Path) -> Arc<tokio::sync::Mutex<()>> {
+        Arc::clone(
+            self.downloads
+                .lock()
+                .entry(output.to_path_buf())
+                .or_default(),
+        )
+    }
 
-        info!("Downloading blob {} to {:?}", digest, output);
+    /// Download a blob while holding the lock on `output`.
+    async fn download_blob_locked(
+        &self,
+        registry: &str,
+        name: &str,
+        descriptor: &Descriptor,
+        expected: &str,
+        output: &Path,
+        options: &PullOptions,
+    ) -> Result<u64> {
+        let mut progress = Progress::new(descriptor, options);
+        if let Ok(metadata) = fs::metadata(output).await {
+            debug!("Blob {} was downloaded concurrently", descriptor.digest);
+            progress.report(PullStatus::Cached, metadata.len());
+            return Ok(metadata.len());
+        }
 
-        let mut request = self.client.get(&url);
+        info!("Downloading blob {} to {:?}", descriptor.digest, output);
 
-        if !token.is_empty() {
-            request = request.header("Authorization", format!("Bearer {token}"));
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent).await?;
         }
+        // A partial file nobody else writes to, so resuming cannot pick up
+        // bytes from another download.
+        let partial = output.with_extension(format!("{}.partial", uuid::Uuid::new_v4().simple()));
+
+        let mut attempt = 1;
+        let (size, digest) = loop {
+            match self
+                .fetch_blob(registry, name, descriptor, &partial, &mut progress)
+                .await
+            {
+                Ok(downloaded) => break downloaded,
+                Err(CoreError::NetworkConfiguration(e)) if attempt < DOWNLOAD_ATTEMPTS => {
+                    warn!("Download of {} interrupted, resuming: {}", descriptor.digest, e);
+                    attempt += 1;
+                }
+                Err(e) => {
+                    let _ = fs::remove_file(&partial).await;
+                    return Err(e);
+                }
+            }
+        };
 
-        let response = request
-            .send()
-            .await
-            .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?;
-
-        if !response.status().is_success() {
+        progress.report(PullStatus::Verifying, size);
+        if size != descriptor.size || digest != expected {
+            let _ = fs::remove_file(&partial).await;
             return Err(CoreError::StorageOperation(format!(
-                "download blob: Status {}",
-                response.status()
+                "blob {} failed verification: got {size} bytes with digest sha256:{digest}, expected {} bytes",
+                descriptor.digest, descriptor.size
             )));
         }
 
-        // Stream to file
-        if let Some(parent) = output.parent() {
-            fs::create_dir_all(parent).await?;
+        fs::rename(&partial, output).await?;
+        progress.report(PullStatus::Complete, size);
+        Ok(size)
+    }
+
+    /// Fetch the rest of a blob into `partial`, which holds what earlier
+    /// attempts of the same download wrote.
+    ///
+    /// # Returns
+    ///
+    /// The size and hex SHA-256 digest of the whole blob.
+    async fn fetch_blob(
+        &self,
+        registry: &str,
+        name: &str,
+        descriptor: &Descriptor,
+        partial: &Path,
+        progress: &mut Progress<'_>,
+    ) -> Result<(u64, String)> {
+        // Hash what an earlier attempt already downloaded.
+        let mut hasher = Sha256::new();
+        let mut offset = 0;
+        if let Ok(mut file) = fs::File::open(partial).await {
+            let mut buf = vec![0; 64 * 1024];
+            loop {
+                let n = file.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buf[..n]);
+                offset += n as u64;
+            }
+        }
+        if offset >= descriptor.size {
+            return Ok((offset, hex::encode(hasher.finalize())));
         }
 
-        let mut file = fs::File::create(output).await?;
-        let bytes = response
-            .bytes()
+        let url = format!("{}/v2/{}/blobs/{}", registry, name, descriptor.digest);
+        let mut response = self
+            .send(registry, &[pull_scope(name)], |client| {
+                let request = client.get(&url);
+                if offset > 0 {
+                    request.header(RANGE, format!("bytes={offset}-"))
+                } else {
+                    request
+                }
+            })
+            .await?;
+
+        let mut file = match response.status() {
+            StatusCode::PARTIAL_CONTENT if offset > 0 => {
+                debug!("Resuming {} at byte {}", descriptor.digest, offset);
+                fs::OpenOptions::new().append(true).open(partial).await?
+            }
+            status if status.is_success() => {
+                // The registry ignored the range, so start over.
+                hasher = Sha256::new();
+                offset = 0;
+                fs::File::create(partial).await?
+            }
+            status => {
+                return Err(CoreError::StorageOperation(format!("download blob: Status {status}")));
+            }
+        };
+
+        progress.report(PullStatus::Downloading, offset);
+        while let Some(chunk) = response
+            .chunk()
             .await
-            .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?;
+            .map_err(|e| CoreError::NetworkConfiguration(e.to_string()))?
+        {
+            file.write_all(&chunk).await?;
+            hasher.update(&chunk);
+            offset += chunk.len() as u64;
+            progress.downloading(offset);
+        }
+        file.flush().await?;
 
-        file.write_all(&bytes).await?;
+        if offset < descriptor.size {
+            return Err(CoreError::NetworkConfiguration(format!(
+                "connection closed after {offset} of {} bytes",
+                descriptor.size
+            )));
+        }
+        Ok((offset, hex::encode(hasher.finalize())))
+    }
+
+    /// The cached path of a layer, downloading it first if needed.
+    async fn fetch_layer(
+        &self,
+        registry: &str,
+        name: &str,
+        layer: &Descriptor,
+        options: &PullOptions,
+    ) -> Result<PathBuf> {
+        let layer_path = self.cache_dir.join("blobs").join(&layer.digest);
+
+        if layer_path.exists() {
+            debug!("Layer {} already cached", layer.digest);
+            options.report(PullProgress {
+                layer: layer.digest.clone(),
+                status: PullStatus::Cached,
+                current: layer.size,
+                total: layer.size,
+            });
+        } else {
+            self.download_blob(registry, name, layer, &layer_path, options)
+                .await?;
+        }
+
+        Ok(layer_path)
+    }
 
-        Ok(bytes.len() as u64)
+    /// Pull a complete image for the host platform.
+    pub async fn pull(&self, image: &str) -> Result<PulledImage> {
+        self.pull_with(image, &PullOptions::default()).await
     }
 
-    /// Pull a complete image.
-    pub async fn pull(&mut self, image: &str) -> Result<PulledImage> {
+    /// Pull a complete image, downloading up to
+    /// [`with_max_concurrent_downloads`](Self::with_max_concurrent_downloads)
+    /// layers at a time.
+    pub async fn pull_with(&self, image: &str, options: &PullOptions) -> Result<PulledImage> {
         let (registry, name, tag) = Self::parse_ref(image);
+        let pulled = self.pull_from(&registry, &name, &tag, options).await?;
+        info!("Successfully pulled {}", image);
+        Ok(pulled)
+    }
+
+    /// Pull `name:reference` from the registry at `registry`.
+    async fn pull_from(
+        &self,
+        registry: &str,
+        name: &str,
+        reference: &str,
+        options: &PullOptions,
+    ) -> Result<PulledImage> {
+        let platform = options.platform.clone().unwrap_or_else(Platform::host);
 
-        info!("Pulling image {} from {}", image, registry);
+        info!("Pulling image {}:{} ({}) from {}", name, reference, platform, registry);
 
         // Get manifest
-        let manifest = self.get_manifest(&registry, &name, &tag).await?;
+        let manifest = self
+            .get_manifest(registry, name, reference, &platform)
+            .await?;
 
         // Get config
         let config = self
-            .get_config(&registry, &name, &manifest.config.digest)
+            .get_config(registry, name, &manifest.config.digest)
             .await?;
+        if !config.platform().matches(&platform) {
+            warn!(
+                "Image {}:{} is built for {}, which does not match the requested platform {}",
+                name,
+                reference,
+                config.platform(),
+                platform
+            );
+        }
 
         // Download layers
-        let mut layer_paths = Vec::new();
         for layer in &manifest.layers {
-            let layer_path = self.cache_dir.join("blobs").join(&layer.digest);
-
-            if !layer_path.exists() {
-                self.download_blob(&registry, &name, &layer.digest, &layer_path)
-                    .await?;
-            } else {
-                debug!("Layer {} already cached", layer.digest);
-            }
-
-            layer_paths.push(layer_path);
+            options.report(PullProgress {
+                layer: layer.digest.clone(),
+                status: PullStatus::Waiting,
+                current: 0,
+                total: layer.size,
+            });
         }
-
-        info!("Successfully pulled {}", image);
+        let downloads: Vec<_> = manifest
+            .layers
+            .iter()
+            .map(|layer| self.fetch_layer(registry, name, layer, options))
+            .collect();
+        let layer_paths: Vec<PathBuf> = stream::iter(downloads)
+            .buffered(self.max_concurrent_downloads)
+            .try_collect()
+            .await?;
 
         Ok(PulledImage {
             manifest,
@@ -289,37 +894,373 @@ impl ImageRegistry {
         })
     }
 
+    /// Push a local image to the registry and repository of `image`.
+    ///
+    /// Returns the digest of the pushed manifest.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a blob cannot be read or the registry refuses an
+    /// upload or the manifest.
+    pub async fn push(
+        &self,
+        image: &str,
+        local: &LocalImage,
+        options: &PushOptions,
+    ) -> Result<String> {
+        let (registry, name, reference) = Self::parse_ref(image);
+        let digest = self
+            .push_to(&registry, &name, &reference, local, options)
+            .await?;
+        info!("Successfully pushed {}:{} ({})", name, reference, digest);
+        Ok(digest)
+    }
+
+    async fn push_to(
+        &self,
+        registry: &str,
+        name: &str,
+        reference: &str,
+        local: &LocalImage,
+        options: &PushOptions,
+    ) -> Result<String> {
+        info!("Pushing image {}:{} to {}", name, reference, registry);
+
+        if local.layer_paths.len() != local.manifest.layers.len() {
+            return Err(CoreError::StorageOperation(format!(
+                "push: {} layer blobs for {} layers in the manifest",
+                local.layer_paths.len(),
+                local.manifest.layers.len()
+            )));
+        }
+
+        let mut manifest = local.manifest.clone();
+        if manifest.media_type.is_empty() {
+            manifest.media_type = MANIFEST_MEDIA_TYPE.to_string();
+        }
+        let config_path = self.config_blob(local, &mut manifest.config).await?;
+
+        let mut blobs: Vec<(&Descriptor, &Path)> = manifest
+            .layers
+            .iter()
+            .zip(local.layer_paths.iter().map(PathBuf::as_path))
+            .collect();
+        blobs.push((&manifest.config, &config_path));
+        for (descriptor, _) in &blobs {
+            report_push(options, descriptor, PushStatus::Waiting, 0);
+        }
+        for (descriptor, path) in blobs {
+            self.push_blob(registry, name, descriptor, path, &local.mount_from, options)
+                .await?;
+        }
+
+        self.put_manifest(registry, name, reference, &manifest)
+            .await
+    }
+
+    /// The configuration blob of a local image, with `descriptor` updated
+    /// to match it.
+    ///
+    /// That is the blob pulled with the image, if it is in the cache, and
+    /// otherwise the configuration serialized with sorted keys, as images
+    /// are committed.
+    async fn config_blob(
+        &self,
+        local: &LocalImage,
+        descriptor: &mut Descriptor,
+    ) -> Result<PathBuf> {
+        let blobs = self.cache_dir.join("blobs");
+        let cached = blobs.join(&descriptor.digest);
+        if !descriptor.digest.is_empty() && cached.is_file() {
+            descriptor.size = fs::metadata(&cached).await?.len();
+            return Ok(cached);
+        }
+
+        let config = canonical_json(&local.config)?;
+        let digest = sha256_digest(&config);
+        if digest != descriptor.digest {
+            debug!("Configuration of {} is pushed as {}", descriptor.digest, digest);
+        }
+        let path = blobs.join(&digest);
+        fs::create_dir_all(&blobs).await?;
+        fs::write(&path, &config).await?;
+
+        if descriptor.media_type.is_empty() {
+            descriptor.media_type = CONFIG_MEDIA_TYPE.to_string();
+        }
+        descriptor.digest = digest;
+        descriptor.size = config.len() as u64;
+        Ok(path)
+    }
+
+    /// Whether a repository has a blob, checked before pushing it.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the registry answers other than found or not found.
+    pub async fn blob_exists(&self, registry: &str, name: &str, digest: &str) -> Result<bool> {
+        let url = format!("{registry}/v2/{name}/blobs/{digest}");
+        let response = self
+            .send(registry, &[push_scope(name)], |client| client.head(&url))
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            status => {
+                Err(CoreError::StorageOperation(format!("check blob {digest}: Status {status}")))
+            }
+        }
+    }
+
+    /// Make sure a repository has a blob: skip it if it is there already,
+    /// mount it from one of `mount_from`, or upload it from `path`.
+    async fn push_blob(
+        &self,
+        registry: &str,
+        name: &str,
+        descriptor: &Descriptor,
+        path: &Path,
+        mount_from: &[String],
+        options: &PushOptions,
+    ) -> Result<()> {
+        if self.blob_exists(registry, name, &descriptor.digest).await? {
+            debug!("Blob {} already in {}", descriptor.digest, name);
+            report_push(options, descriptor, PushStatus::Exists, descriptor.size);
+            return Ok(());
+        }
+
+        let mut location = None;
+        for from in mount_from.iter().filter(|from| *from != name) {
+            match self
+                .mount_blob(registry, name, &descriptor.digest, from)
+                .await?
+            {
+                Mount::Mounted => {
+                    debug!("Mounted blob {} from {}", descriptor.digest, from);
+                    report_push(options, descriptor, PushStatus::Mounted, descriptor.size);
+                    return Ok(());
+                }
+                Mount::Upload(url) => {
+                    location = Some(url);
+                    break;
+                }
+                Mount::Refused => {}
+            }
+        }
+        let location = match location {
+            Some(location) => location,
+            None => self.start_upload(registry, name).await?,
+        };
+
+        self.upload_blob(registry, name, descriptor, path, location, options)
+            .await?;
+        report_push(options, descriptor, PushStatus::Complete, descriptor.size);
+        Ok(())
+    }
+
+    /// Ask the registry to mount a blob from another repository.
+    ///
+    /// Registries that cannot mount the blob start an upload instead.
+    async fn mount_blob(
+        &self,
+        registry: &str,
+        name: &str,
+        digest: &str,
+        from: &str,
+    ) -> Result<Mount> {
+        let url = format!("{registry}/v2/{name}/blobs/uploads/");
+        let scopes = [push_scope(name), pull_scope(from)];
+        let response = self
+            .send(registry, &scopes, |client| {
+                client
+                    .post(&url)
+                    .query(&[("mount", digest), ("from", from)])
+                    .header(CONTENT_LENGTH, 0)
+            })
+            .await?;
+
+        match response.status() {
+            StatusCode::CREATED => Ok(Mount::Mounted),
+            StatusCode::ACCEPTED => Ok(Mount::Upload(upload_location(registry, &response)?)),
+            status => {
+                debug!("Cannot mount {} from {}: Status {}", digest, from, status);
+                Ok(Mount::Refused)
+            }
+        }
+    }
+
+    /// Start a blob upload and return its URL.
+    async fn start_upload(&self, registry: &str, name: &str) -> Result<String> {
+        let url = format!("{registry}/v2/{name}/blobs/uploads/");
+        let response = self
+            .send(registry, &[push_scope(name)], |client| {
+                client.post(&url).header(CONTENT_LENGTH, 0)
+            })
+            .await?;
+
+        if response.status() != StatusCode::ACCEPTED {
+            return Err(CoreError::StorageOperation(format!(
+                "start upload to {name}: Status {}",
+                response.status()
+            )));
+        }
+        upload_location(registry, &response)
+    }
+
+    /// Upload a blob from `path` to the upload at `location`: in one
+    /// request, or in chunks if it is larger than the upload chunk size.
+    async fn upload_blob(
+        &self,
+        registry: &str,
+        name: &str,
+        descriptor: &Descriptor,
+        path: &Path,
+        mut location: String,
+        options: &PushOptions,
+    ) -> Result<()> {
+        let scopes = [push_scope(name)];
+        let mut file = fs::File::open(path).await?;
+        let size = file.metadata().await?.len();
+        if size != descriptor.size {
+            return Err(CoreError::StorageOperation(format!(
+                "blob {} is {size} bytes, the manifest says {}",
+                descriptor.digest, descriptor.size
+            )));
+        }
+
+        let mut body = Vec::new();
+        if size > self.upload_chunk_size {
+            let mut offset = 0;
+            while offset < size {
+                let mut chunk = Vec::new();
+                (&mut file)
+                    .take(self.upload_chunk_size)
+                    .read_to_end(&mut chunk)
+                    .await?;
+                if chunk.is_empty() {
+                    return Err(CoreError::StorageOperation(format!(
+                        "blob {} shrank while it was uploaded",
+                        descriptor.digest
+                    )));
+                }
+                let end = offset + chunk.len() as u64;
+                let response = self
+                    .send(registry, &scopes, |client| {
+                        client
+                            .patch(&location)
+                            .header(CONTENT_TYPE, "application/octet-stream")
+                            .header(CONTENT_RANGE, format!("{offset}-{}", end - 1))
+                            .body(chunk.clone())
+                    })
+                    .await?;
+                if response.status() != StatusCode::ACCEPTED {
+                    return Err(CoreError::StorageOperation(format!(
+                        "upload blob {}: Status {}",
+                        descriptor.digest,
+                        response.status()
+                    )));
+                }
+                location = upload_location(registry, &response)?;
+                offset = end;
+                report_push(options, descriptor, PushStatus::Uploading, offset);
+            }
+        } else {
+            file.read_to_end(&mut body).await?;
+        }
+
+        let response = self
+            .send(registry, &scopes, |client| {
+                client
+                    .put(&location)
+                    .query(&[("digest", &descriptor.digest)])
+                    .header(CONTENT_TYPE, "application/octet-stream")
+                    .body(body.clone())
+            })
+            .await?;
+        if !response.status().is_success() {
+            return Err(CoreError::StorageOperation(format!(
+                "upload blob {}: Status {}",
+                descriptor.digest,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Upload a manifest as `reference` and return its digest.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the registry refuses the manifest or stores it
+    /// under a different digest.
+    pub async fn put_manifest(
+        &self,
+        registry: &str,
+        name: &str,
+        reference: &str,
+        manifest: &ImageManifest,
+    ) -> Result<String> {
+        let url = format!("{registry}/v2/{name}/manifests/{reference}");
+        let body = canonical_json(manifest)?;
+        let digest = sha256_digest(&body);
+        let media_type = if manifest.media_type.is_empty() {
+            MANIFEST_MEDIA_TYPE
+        } else {
+            &manifest.media_type
+        };
+
+        info!("Uploading manifest to {}", url);
+
+        let response = self
+            .send(registry, &[push_scope(name)], |client| {
+                client
+                    .put(&url)
+                    .header(CONTENT_TYPE, media_type)
+                    .body(body.clone())
+            })
+            .await?;
+        if !response.status().is_success() {
+            return Err(CoreError::StorageOperation(format!(
+                "upload manifest: Status {}",
+                response.status()
+            )));
+        }
+
+        let stored = response
+            .headers()
+            .get(DOCKER_CONTENT_DIGEST)
+            .and_then(|value| value.to_str().ok());
+        if let Some(stored) = stored.filter(|stored| *stored != digest) {
+            return Err(CoreError::StorageOperation(format!(
+                "registry stored manifest {digest} as {stored}"
+            )));
+        }
+        Ok(digest)
+    }
+
     /// Extract image layers to a rootfs directory.
+    ///
+    /// Layers are applied base first, honouring their whiteouts, and each
+    /// layer's `diff_id` is checked against the image configuration.
     pub async fn extract_to_rootfs(&self, pulled: &PulledImage, rootfs: &Path) -> Result<()> {
         fs::create_dir_all(rootfs).await?;
 
         info!("Extracting {} layers to {:?}", pulled.layer_paths.len(), rootfs);
 
-        for (idx, layer_path) in pulled.layer_paths.iter().enumerate() {
+        let layers = pulled.manifest.layers.iter().zip(&pulled.layer_paths);
+        for (idx, (descriptor, layer_path)) in layers.enumerate() {
             debug!("Extracting layer {}/{}: {:?}", idx + 1, pulled.layer_paths.len(), layer_path);
 
-            // Read the layer blob
-            let data = fs::read(layer_path).await?;
-
-            // Decompress if gzipped
-            let decompressed = if data.starts_with(&[0x1f, 0x8b]) {
-                // GZip magic bytes
-                let mut decoder = GzDecoder::new(&data[..]);
-                let mut decompressed = Vec::new();
-                decoder
-                    .read_to_end(&mut decompressed)
-                    .map_err(|e| CoreError::StorageOperation(format!("decompress layer: {}", e)))?;
-                decompressed
-            } else {
-                data
-            };
-
-            // Extract tar archive
-            let mut archive = Archive::new(&decompressed[..]);
-
-            archive.unpack(rootfs).map_err(|e| {
-                CoreError::StorageOperation(format!("extract tar layer {}: {}", idx, e))
-            })?;
+            let blob = layer_path.clone();
+            let media_type = descriptor.media_type.clone();
+            let target = rootfs.to_path_buf();
+            let unpacked = tokio::task::spawn_blocking(move || {
+                unpack::apply_blob(&blob, &media_type, &target, Whiteouts::Apply)
+            })
+            .await
+            .map_err(|e| CoreError::StorageOperation(format!("extract tar layer {idx}: {e}")))??;
+            pulled.config.verify_diff_id(idx, &unpacked.diff_id)?;
 
             debug!("Extracted layer {}/{}", idx + 1, pulled.layer_paths.len());
         }
@@ -329,6 +1270,112 @@ impl ImageRegistry {
     }
 }
 
+/// Outcome of a cross-repository blob mount.
+enum Mount {
+    /// The blob is in the repository now
+    Mounted,
+    /// The registry started an upload at this URL instead
+    Upload(String),
+    /// The registry did not mount the blob
+    Refused,
+}
+
+/// The upload URL from the `Location` header of an upload response, which
+/// may be relative to the registry.
+fn upload_location(registry: &str, response: &Response) -> Result<String> {
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| {
+            CoreError::StorageOperation("upload: registry sent no Location".to_string())
+        })?;
+    if location.starts_with("http://") || location.starts_with("https://") {
+        Ok(location.to_string())
+    } else {
+        Ok(format!("{registry}/{}", location.trim_start_matches('/')))
+    }
+}
+
+fn report_push(options: &PushOptions, descriptor: &Descriptor, status: PushStatus, current: u64) {
+    options.report(PushProgress {
+        layer: descriptor.digest.clone(),
+        status,
+        current,
+        total: descriptor.size,
+    });
+}
+
+fn sha256_digest(data: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(data))
+}
+
+/// Token scope for reading a repository.
+fn pull_scope(repo: &str) -> String {
+    format!("repository:{repo}:pull")
+}
+
+/// Token scope for reading and writing a repository.
+fn push_scope(repo: &str) -> String {
+    format!("repository:{repo}:pull,push")
+}
+
+/// `Authorization` header value for HTTP basic authentication.
+fn basic_auth(credentials: &Credentials) -> Result<String> {
+    match credentials {
+        Credentials::Basic { username, password } => {
+            Ok(format!("Basic {}", BASE64.encode(format!("{username}:{password}"))))
+        }
+        Credentials::IdentityToken(_) => Err(CoreError::Configuration(
+            "identity tokens only work with registries that use a token service".to_string(),
+        )),
+    }
+}
+
+fn unauthorized(registry: &str) -> CoreError {
+    CoreError::PermissionDenied {
+        operation: format!("authenticate to {registry}"),
+        required: "valid registry credentials (hb login)".to_string(),
+    }
+}
+
+/// Reports download progress of one blob, throttled to one update per
+/// [`PROGRESS_INTERVAL`].
+struct Progress<'a> {
+    options: &'a PullOptions,
+    layer: &'a Descriptor,
+    last: Option<Instant>,
+}
+
+impl<'a> Progress<'a> {
+    const fn new(layer: &'a Descriptor, options: &'a PullOptions) -> Self {
+        Self {
+            options,
+            layer,
+            last: None,
+        }
+    }
+
+    fn report(&mut self, status: PullStatus, current: u64) {
+        self.last = Some(Instant::now());
+        self.options.report(PullProgress {
+            layer: self.layer.digest.clone(),
+            status,
+            current,
+            total: self.layer.size,
+        });
+    }
+
+    fn downloading(&mut self, current: u64) {
+        if self
+            .last
+            .map_or(true, |last| last.elapsed() >= PROGRESS_INTERVAL)
+        {
+            self.report(PullStatus::Downloading, current);
+        }
+    }
+}
+
 /// Result of pulling an image.
 #[derive(Debug)]
 pub struct PulledImage {
@@ -339,3 +1386,928 @@ pub struct PulledImage {
     /// Paths to downloaded layers
     pub layer_paths: Vec<PathBuf>,
 }
+
+/// An image to push.
+#[derive(Debug, Clone)]
+pub struct LocalImage {
+    /// Image manifest
+    pub manifest: ImageManifest,
+    /// Image config
+    pub config: ImageConfig,
+    /// Paths to the layer blobs, in manifest order
+    pub layer_paths: Vec<PathBuf>,
+    /// Other repositories of the target registry that may have the layers,
+    /// to mount them from instead of uploading
+    pub mount_from: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LAYER_MEDIA_TYPE;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+
+    const MANIFEST: &str = r#"{
+        "schemaVersion": 2,
+        "config": {"mediaType": "application/vnd.oci.image.config.v1+json", "size": 2, "digest": "sha256:cfg"},
+        "layers": []
+    }"#;
+
+    /// Served for the `multi` tag; each entry's digest names the config
+    /// digest of the manifest it points at.
+    const INDEX: &str = r#"{
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.index.v1+json",
+        "manifests": [
+            {"mediaType": "application/vnd.oci.image.manifest.v1+json", "size": 2, "digest": "sha256:amd64",
+             "platform": {"architecture": "amd64", "os": "linux"}},
+            {"mediaType": "application/vnd.oci.image.manifest.v1+json", "size": 2, "digest": "sha256:arm64",
+             "platform": {"architecture": "arm64", "os": "linux", "variant": "v8"}},
+            {"mediaType": "application/vnd.oci.image.manifest.v1+json", "size": 2, "digest": "sha256:att",
+             "platform": {"architecture": "unknown", "os": "unknown"}}
+        ]
+    }"#;
+
+    /// A registry stand-in that checks basic credentials against an
+    /// htpasswd file (plain-text `user:password` lines). With `token_lifetime`
+    /// it sends clients to its token service like Docker Hub does;
+    /// otherwise it takes basic credentials on every request.
+    struct StandIn {
+        url: String,
+        tokens_issued: Arc<AtomicUsize>,
+    }
+
+    impl StandIn {
+        async fn start(htpasswd: &str, token_lifetime: Option<u64>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let users: HashSet<String> = htpasswd
+                .lines()
+                .map(|line| format!("Basic {}", BASE64.encode(line.trim())))
+                .collect();
+            let tokens_issued = Arc::new(AtomicUsize::new(0));
+            let valid_tokens = Arc::new(Mutex::new(HashSet::new()));
+
+            let issued = tokens_issued.clone();
+            let realm = format!("{url}/token");
+            tokio::spawn(async move {
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let mut request = Vec::new();
+                    let mut buf = [0; 4096];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        let n = stream.read(&mut buf).await.unwrap();
+                        if n == 0 {
+                            break;
+                        }
+                        request.extend_from_slice(&buf[..n]);
+                    }
+                    let request = String::from_utf8_lossy(&request).to_string();
+                    let path = request.split(' ').nth(1).unwrap_or_default().to_string();
+                    let authorization = request
+                        .lines()
+                        .find_map(|line| line.strip_prefix("authorization: "))
+                        .unwrap_or_default()
+                        .to_string();
+
+                    let response = match token_lifetime {
+                        None if users.contains(&authorization) => ok(&path, String::new()),
+                        None => challenge("Basic realm=\"stand-in\""),
+                        Some(lifetime) if path.starts_with("/token") => {
+                            if users.contains(&authorization) {
+                                let n = issued.fetch_add(1, Ordering::SeqCst);
+                                let token = format!("token-{n}");
+                                valid_tokens.lock().insert(format!("Bearer {token}"));
+                                ok(
+                                    &path,
+                                    format!(r#"{{"token":"{token}","expires_in":{lifetime}}}"#),
+                                )
+                            } else {
+                                challenge("Basic realm=\"stand-in\"")
+                            }
+                        }
+                        Some(_) if valid_tokens.lock().contains(&authorization) => {
+                            ok(&path, String::new())
+                        }
+                        Some(_) => {
+                            challenge(&format!(r#"Bearer realm="{realm}",service="stand-in""#))
+                        }
+                    };
+                    let _ = stream.write_all(response.as_bytes()).await;
+                }
+            });
+
+            Self { url, tokens_issued }
+        }
+
+        fn tokens_issued(&self) -> usize {
+            self.tokens_issued.load(Ordering::SeqCst)
+        }
+    }
+
+    fn ok(path: &str, body: String) -> String {
+        let body = match path.split_once("/manifests/") {
+            Some((_, "multi")) => INDEX.to_string(),
+            Some((_, digest)) if digest.starts_with("sha256:") => {
+                MANIFEST.replace("sha256:cfg", digest)
+            }
+            Some(_) => MANIFEST.to_string(),
+            None => body,
+        };
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn challenge(header: &str) -> String {
+        format!(
+            "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: {header}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )
+    }
+
+    /// An anonymous registry serving `team/app:latest`, an image with the
+    /// given layers. With `cut_first` the first response for each layer
+    /// breaks off halfway, like a dropped connection. The `Range` headers
+    /// of blob requests are recorded.
+    struct BlobServer {
+        url: String,
+        layers: Vec<Descriptor>,
+        blobs: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+        ranges: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl BlobServer {
+        async fn start(layers: &[&[u8]], cut_first: bool) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+
+            let host = Platform::host();
+            let config = serde_json::json!({
+                "architecture": host.architecture,
+                "os": host.os,
+                "rootfs": {"type": "layers", "diff_ids": []}
+            })
+            .to_string();
+            let mut blobs = HashMap::new();
+            let mut add = |data: &[u8]| {
+                let descriptor = blob(data);
+                blobs.insert(descriptor.digest.clone(), data.to_vec());
+                descriptor
+            };
+            let manifest = ImageManifest {
+                schema_version: 2,
+                media_type: MANIFEST_MEDIA_TYPE.to_string(),
+                config: add(config.as_bytes()),
+                layers: layers.iter().map(|data| add(data)).collect(),
+                annotations: HashMap::new(),
+            };
+            let layers = manifest.layers.clone();
+            // Only layers get cut off.
+            let mut cut = HashSet::from([manifest.config.digest.clone()]);
+            let manifest = serde_json::to_vec(&manifest).unwrap();
+
+            let blobs = Arc::new(Mutex::new(blobs));
+            let ranges = Arc::new(Mutex::new(Vec::new()));
+            let served = blobs.clone();
+            let recorded = ranges.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let request = read_request(&mut stream).await;
+                    let path = request.split(' ').nth(1).unwrap_or_default().to_string();
+                    let range = request
+                        .lines()
+                        .find_map(|line| line.strip_prefix("range: "))
+                        .map(str::to_string);
+
+                    let Some((_, digest)) = path.split_once("/blobs/") else {
+                        let body = if path.contains("/manifests/") {
+                            manifest.as_slice()
+                        } else {
+                            b""
+                        };
+                        reply(&mut stream, "200 OK", "", body, body.len()).await;
+                        continue;
+                    };
+                    let Some(data) = served.lock().get(digest).cloned() else {
+                        reply(&mut stream, "404 Not Found", "", b"", 0).await;
+                        continue;
+                    };
+
+                    if let Some(range) = range {
+                        let start: usize = range
+                            .trim_start_matches("bytes=")
+                            .trim_end_matches('-')
+                            .parse()
+                            .unwrap();
+                        recorded.lock().push(range);
+                        let headers = format!(
+                            "Content-Range: bytes {start}-{}/{}\r\n",
+                            data.len() - 1,
+                            data.len()
+                        );
+                        let body = &data[start..];
+                        reply(&mut stream, "206 Partial Content", &headers, body, body.len()).await;
+                    } else if cut_first && cut.insert(digest.to_string()) {
+                        let half = &data[..data.len() / 2];
+                        reply(&mut stream, "200 OK", "", half, data.len()).await;
+                    } else {
+                        reply(&mut stream, "200 OK", "", &data, data.len()).await;
+                    }
+                }
+            });
+
+            Self {
+                url,
+                layers,
+                blobs,
+                ranges,
+            }
+        }
+    }
+
+    fn blob(data: &[u8]) -> Descriptor {
+        Descriptor {
+            media_type: LAYER_MEDIA_TYPE.to_string(),
+            digest: format!("sha256:{:x}", Sha256::digest(data)),
+            size: data.len() as u64,
+            annotations: HashMap::new(),
+            platform: None,
+        }
+    }
+
+    async fn read_request(stream: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+        while !request.ends_with(b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+        String::from_utf8_lossy(&request).to_string()
+    }
+
+    /// Write a response announcing `length` bytes, of which `body` is sent.
+    async fn reply(
+        stream: &mut TcpStream,
+        status: &str,
+        headers: &str,
+        body: &[u8],
+        length: usize,
+    ) {
+        let head = format!(
+            "HTTP/1.1 {status}\r\n{headers}Content-Length: {length}\r\nConnection: close\r\n\r\n"
+        );
+        let _ = stream.write_all(head.as_bytes()).await;
+        let _ = stream.write_all(body).await;
+    }
+
+    /// An anonymous registry taking pushes. Blobs are kept per repository
+    /// and `team/base` starts with `base` in it. Every upload step moves
+    /// the upload to a new `Location`, and requests are recorded as
+    /// `METHOD path`.
+    struct PushServer {
+        url: String,
+        blobs: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+        manifests: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl PushServer {
+        async fn start(base: &[u8]) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let blobs = Arc::new(Mutex::new(HashMap::from([(
+                format!("team/base@{}", blob(base).digest),
+                base.to_vec(),
+            )])));
+            let manifests = Arc::new(Mutex::new(HashMap::new()));
+            let requests = Arc::new(Mutex::new(Vec::new()));
+
+            let (stored, pushed, recorded) = (blobs.clone(), manifests.clone(), requests.clone());
+            tokio::spawn(async move {
+                let mut uploads: HashMap<String, Vec<u8>> = HashMap::new();
+                let mut next_upload = 0;
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let (head, body) = read_request_with_body(&mut stream).await;
+                    let mut words = head.split(' ');
+                    let method = words.next().unwrap_or_default().to_string();
+                    let target = words.next().unwrap_or_default().replace("%3A", ":");
+                    let target = target.replace("%2F", "/");
+                    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+                    let param = |key: &str| {
+                        query
+                            .split('&')
+                            .find_map(|pair| pair.strip_prefix(&format!("{key}=")))
+                            .map(str::to_string)
+                    };
+                    recorded.lock().push(format!("{method} {path}"));
+
+                    let path = path.trim_start_matches("/v2/");
+                    let mut new_upload = |uploads: &mut HashMap<String, Vec<u8>>, data| {
+                        next_upload += 1;
+                        let id = next_upload.to_string();
+                        uploads.insert(id.clone(), data);
+                        id
+                    };
+                    if let Some((repo, digest)) = path.split_once("/blobs/sha256:") {
+                        let key = format!("{repo}@sha256:{digest}");
+                        let status = if stored.lock().contains_key(&key) {
+                            "200 OK"
+                        } else {
+                            "404 Not Found"
+                        };
+                        reply(&mut stream, status, "", b"", 0).await;
+                    } else if let Some((repo, id)) = path.split_once("/blobs/uploads/") {
+                        match method.as_str() {
+                            "POST" => {
+                                let mounted =
+                                    param("mount")
+                                        .zip(param("from"))
+                                        .and_then(|(digest, from)| {
+                                            let data = stored
+                                                .lock()
+                                                .get(&format!("{from}@{digest}"))?
+                                                .clone();
+                                            stored.lock().insert(format!("{repo}@{digest}"), data);
+                                            Some(())
+                                        });
+                                if mounted.is_some() {
+                                    reply(&mut stream, "201 Created", "", b"", 0).await;
+                                } else {
+                                    let id = new_upload(&mut uploads, Vec::new());
+                                    let location =
+                                        format!("Location: /v2/{repo}/blobs/uploads/{id}\r\n");
+                                    reply(&mut stream, "202 Accepted", &location, b"", 0).await;
+                                }
+                            }
+                            "PATCH" => {
+                                let mut data = uploads.remove(id).unwrap();
+                                data.extend_from_slice(&body);
+                                let id = new_upload(&mut uploads, data);
+                                let location =
+                                    format!("Location: /v2/{repo}/blobs/uploads/{id}\r\n");
+                                reply(&mut stream, "202 Accepted", &location, b"", 0).await;
+                            }
+                            _ => {
+                                let mut data = uploads.remove(id).unwrap();
+                                data.extend_from_slice(&body);
+                                let digest = param("digest").unwrap();
+                                if blob(&data).digest == digest {
+                                    stored.lock().insert(format!("{repo}@{digest}"), data);
+                                    reply(&mut stream, "201 Created", "", b"", 0).await;
+                                } else {
+                                    reply(&mut stream, "400 Bad Request", "", b"", 0).await;
+                                }
+                            }
+                        }
+                    } else if let Some((repo, reference)) = path.split_once("/manifests/") {
+                        let digest = blob(&body).digest;
+                        pushed.lock().insert(format!("{repo}:{reference}"), body);
+                        let headers = format!("Docker-Content-Digest: {digest}\r\n");
+                        reply(&mut stream, "201 Created", &headers, b"", 0).await;
+                    } else {
+                        reply(&mut stream, "200 OK", "", b"", 0).await;
+                    }
+                }
+            });
+
+            Self {
+                url,
+                blobs,
+                manifests,
+                requests,
+            }
+        }
+
+        fn has_blob(&self, repo: &str, data: &[u8]) -> bool {
+            let key = format!("{repo}@{}", blob(data).digest);
+            self.blobs
+                .lock()
+                .get(&key)
+                .is_some_and(|stored| stored == data)
+        }
+
+        fn count(&self, request: &str) -> usize {
+            self.requests
+                .lock()
+                .iter()
+                .filter(|r| r.starts_with(request))
+                .count()
+        }
+    }
+
+    async fn read_request_with_body(stream: &mut TcpStream) -> (String, Vec<u8>) {
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+        let end = loop {
+            if let Some(at) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                break at + 4;
+            }
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break request.len();
+            }
+            request.extend_from_slice(&buf[..n]);
+        };
+        let head = String::from_utf8_lossy(&request[..end]).to_string();
+        let length: usize = head
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length: "))
+            .map_or(0, |length| length.trim().parse().unwrap());
+        while request.len() < end + length {
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+        (head, request[end..].to_vec())
+    }
+
+    /// A local image with the given layers, written to `dir`.
+    fn local_image(dir: &TempDir, layers: &[&[u8]], mount_from: &[&str]) -> LocalImage {
+        let mut layer_paths = Vec::new();
+        for data in layers {
+            let descriptor = blob(data);
+            let path = dir.path().join(descriptor.digest.replace(':', "-"));
+            std::fs::write(&path, data).unwrap();
+            layer_paths.push(path);
+        }
+        LocalImage {
+            manifest: ImageManifest {
+                schema_version: 2,
+                media_type: String::new(),
+                config: Descriptor {
+                    media_type: CONFIG_MEDIA_TYPE.to_string(),
+                    digest: String::new(),
+                    size: 0,
+                    annotations: HashMap::new(),
+                    platform: None,
+                },
+                layers: layers.iter().map(|data| blob(data)).collect(),
+                annotations: HashMap::new(),
+            },
+            config: serde_json::from_str(
+                r#"{"architecture": "amd64", "os": "linux", "rootfs": {"type": "layers", "diff_ids": []}}"#,
+            )
+            .unwrap(),
+            layer_paths,
+            mount_from: mount_from.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn push_options() -> (PushOptions, mpsc::UnboundedReceiver<PushProgress>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (
+            PushOptions {
+                progress: Some(sender),
+            },
+            receiver,
+        )
+    }
+
+    fn pull_options() -> (PullOptions, mpsc::UnboundedReceiver<PullProgress>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let options = PullOptions {
+            platform: None,
+            progress: Some(sender),
+        };
+        (options, receiver)
+    }
+
+    fn registry(dir: &TempDir) -> ImageRegistry {
+        ImageRegistry::new(dir.path().join("cache"))
+            .unwrap()
+            .with_credentials(CredentialStore::new(dir.path().join("config.json")))
+    }
+
+    fn alice(password: &str) -> Credentials {
+        Credentials::Basic {
+            username: "alice".to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_challenge() {
+        assert_eq!(
+            Challenge::parse(
+                r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/alpine:pull""#
+            ),
+            Some(Challenge::Bearer {
+                realm: "https://auth.docker.io/token".to_string(),
+                service: Some("registry.docker.io".to_string()),
+            })
+        );
+        assert_eq!(Challenge::parse(r#"Basic realm="Registry""#), Some(Challenge::Basic));
+        assert_eq!(Challenge::parse("Negotiate"), None);
+    }
+
+    #[test]
+    fn test_registry_url() {
+        assert_eq!(ImageRegistry::registry_url(""), DOCKER_HUB_REGISTRY);
+        assert_eq!(ImageRegistry::registry_url("docker.io"), DOCKER_HUB_REGISTRY);
+        assert_eq!(
+            ImageRegistry::registry_url("registry.example.com:5000"),
+            "https://registry.example.com:5000"
+        );
+        assert_eq!(ImageRegistry::registry_url("http://localhost:5000/"), "http://localhost:5000");
+    }
+
+    #[tokio::test]
+    async fn test_bearer_tokens_are_cached_until_they_expire() {
+        let dir = TempDir::new().unwrap();
+        let stand_in = StandIn::start("alice:secret", Some(3600)).await;
+        let registry = registry(&dir);
+
+        let err = registry
+            .get_manifest(&stand_in.url, "team/app", "1.0", &Platform::host())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CoreError::PermissionDenied { .. }));
+
+        registry
+            .login(&stand_in.url, &alice("secret"))
+            .await
+            .unwrap();
+        registry
+            .get_manifest(&stand_in.url, "team/app", "1.0", &Platform::host())
+            .await
+            .unwrap();
+        registry
+            .get_manifest(&stand_in.url, "team/app", "latest", &Platform::host())
+            .await
+            .unwrap();
+        // One token for the login check, one for the repository scope.
+        assert_eq!(stand_in.tokens_issued(), 2);
+
+        let stand_in = StandIn::start("alice:secret", Some(0)).await;
+        registry
+            .login(&stand_in.url, &alice("secret"))
+            .await
+            .unwrap();
+        registry
+            .get_manifest(&stand_in.url, "team/app", "1.0", &Platform::host())
+            .await
+            .unwrap();
+        registry
+            .get_manifest(&stand_in.url, "team/app", "1.0", &Platform::host())
+            .await
+            .unwrap();
+        assert_eq!(stand_in.tokens_issued(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_basic_auth_login_and_logout() {
+        let dir = TempDir::new().unwrap();
+        let stand_in = StandIn::start("bob:hunter2\nalice:secret", None).await;
+        let registry = registry(&dir);
+
+        let err = registry
+            .login(&stand_in.url, &alice("wrong"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CoreError::PermissionDenied { .. }));
+        assert!(!dir.path().join("config.json").exists());
+
+        registry
+            .login(&stand_in.url, &alice("secret"))
+            .await
+            .unwrap();
+        let manifest = registry
+            .get_manifest(&stand_in.url, "team/app", "1.0", &Platform::host())
+            .await
+            .unwrap();
+        assert_eq!(manifest.config.digest, "sha256:cfg");
+
+        assert!(registry.logout(&stand_in.url).await.unwrap());
+        let err = registry
+            .get_manifest(&stand_in.url, "team/app", "1.0", &Platform::host())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CoreError::PermissionDenied { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_get_manifest_selects_platform_from_index() {
+        let dir = TempDir::new().unwrap();
+        let stand_in = StandIn::start("alice:secret", None).await;
+        let registry = registry(&dir);
+        registry
+            .login(&stand_in.url, &alice("secret"))
+            .await
+            .unwrap();
+
+        for (platform, digest) in [
+            ("linux/amd64", "sha256:amd64"),
+            ("linux/arm64", "sha256:arm64"),
+            ("linux/aarch64/v8", "sha256:arm64"),
+        ] {
+            let manifest = registry
+                .get_manifest(&stand_in.url, "team/app", "multi", &platform.parse().unwrap())
+                .await
+                .unwrap();
+            assert_eq!(manifest.config.digest, digest, "{platform}");
+        }
+
+        // Single-platform images are returned as they are.
+        let manifest = registry
+            .get_manifest(&stand_in.url, "team/app", "1.0", &"linux/s390x".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(manifest.config.digest, "sha256:cfg");
+
+        let err = registry
+            .get_manifest(&stand_in.url, "team/app", "multi", &"linux/s390x".parse().unwrap())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("linux/s390x"), "{err}");
+        assert!(err.contains("linux/amd64, linux/arm64/v8"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_pull_downloads_layers_concurrently_with_progress() {
+        let dir = TempDir::new().unwrap();
+        let layers: [&[u8]; 3] = [b"base layer", b"middle layer", b"top layer"];
+        let server = BlobServer::start(&layers, false).await;
+        let registry = registry(&dir).with_max_concurrent_downloads(2);
+
+        let (options, mut progress) = pull_options();
+        let pulled = registry
+            .pull_from(&server.url, "team/app", "latest", &options)
+            .await
+            .unwrap();
+        drop(options);
+
+        assert_eq!(pulled.layer_paths.len(), 3);
+        for (path, data) in pulled.layer_paths.iter().zip(layers) {
+            assert_eq!(std::fs::read(path).unwrap(), data);
+        }
+        let mut updates = Vec::new();
+        while let Some(update) = progress.recv().await {
+            updates.push(update);
+        }
+        for layer in &server.layers {
+            let statuses: Vec<PullStatus> = updates
+                .iter()
+                .filter(|u| u.layer == layer.digest)
+                .map(|u| u.status)
+                .collect();
+            assert_eq!(statuses.first(), Some(&PullStatus::Waiting));
+            assert_eq!(statuses.last(), Some(&PullStatus::Complete));
+        }
+
+        // A second pull finds every layer in the cache.
+        let (options, mut progress) = pull_options();
+        registry
+            .pull_from(&server.url, "team/app", "latest", &options)
+            .await
+            .unwrap();
+        drop(options);
+        let mut cached = 0;
+        while let Some(update) = progress.recv().await {
+            cached += usize::from(update.status == PullStatus::Cached);
+        }
+        assert_eq!(cached, 3);
+    }
+
+    #[tokio::test]
+    async fn test_download_resumes_after_interruption() {
+        let dir = TempDir::new().unwrap();
+        let data = vec![7u8; 64 * 1024];
+        let server = BlobServer::start(&[&data], true).await;
+        let registry = registry(&dir);
+
+        let pulled = registry
+            .pull_from(&server.url, "team/app", "latest", &PullOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&pulled.layer_paths[0]).unwrap(), data);
+        assert!(!server.ranges.lock().is_empty());
+        assert!(server.ranges.lock().iter().all(|r| r != "bytes=0-"));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_downloads_of_the_same_blob() {
+        let dir = TempDir::new().unwrap();
+        let data: Vec<u8> = (0..=250).cycle().take(64 * 1024).collect();
+        let server = BlobServer::start(&[&data], true).await;
+        let other = registry(&dir);
+        let registry = registry(&dir);
+
+        // A partial file left by someone else is never resumed.
+        let output = dir.path().join("blobs").join("layer");
+        std::fs::create_dir_all(output.parent().unwrap()).unwrap();
+        std::fs::write(output.with_extension("partial"), b"garbage").unwrap();
+
+        let layer = &server.layers[0];
+        let options = PullOptions::default();
+        let downloads = [&registry, &registry, &other].map(|registry| {
+            registry.download_blob(&server.url, "team/app", layer, &output, &options)
+        });
+        for size in futures::future::join_all(downloads).await {
+            assert_eq!(size.unwrap(), data.len() as u64);
+        }
+        assert_eq!(std::fs::read(&output).unwrap(), data);
+        assert!(server.ranges.lock().iter().all(|r| r != "bytes=7-"));
+
+        // Only the leftover remains next to the blob.
+        let mut files: Vec<_> = std::fs::read_dir(output.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["layer", "layer.partial"]);
+        assert!(registry.downloads.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_download_rejects_blob_not_matching_its_digest() {
+        let dir = TempDir::new().unwrap();
+        let server = BlobServer::start(&[], false).await;
+        let registry = registry(&dir);
+
+        // The registry serves different content under the digest.
+        let layer = blob(b"original content");
+        server
+            .blobs
+            .lock()
+            .insert(layer.digest.clone(), b"tampered content".to_vec());
+
+        let output = dir.path().join("blob");
+        let err = registry
+            .download_blob(&server.url, "team/app", &layer, &output, &PullOptions::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("failed verification"), "{err}");
+        assert!(!output.exists());
+        let partials = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("partial".as_ref()))
+            .count();
+        assert_eq!(partials, 0);
+    }
+
+    #[tokio::test]
+    async fn test_push_uploads_blobs_and_manifest() {
+        let dir = TempDir::new().unwrap();
+        let server = PushServer::start(b"base").await;
+        let registry = registry(&dir);
+        let layers: [&[u8]; 2] = [b"first layer", b"second layer"];
+        let local = local_image(&dir, &layers, &[]);
+
+        let (options, mut progress) = push_options();
+        let digest = registry
+            .push_to(&server.url, "team/app", "v1", &local, &options)
+            .await
+            .unwrap();
+        drop(options);
+
+        for data in layers {
+            assert!(server.has_blob("team/app", data));
+        }
+        let manifest = server.manifests.lock().get("team/app:v1").cloned().unwrap();
+        assert_eq!(blob(&manifest).digest, digest);
+        let manifest: ImageManifest = serde_json::from_slice(&manifest).unwrap();
+        assert_eq!(manifest.media_type, MANIFEST_MEDIA_TYPE);
+        let config = canonical_json(&local.config).unwrap();
+        assert_eq!(manifest.config.digest, blob(&config).digest);
+        assert!(server.has_blob("team/app", &config));
+        assert_eq!(server.count("PATCH"), 0);
+
+        let mut complete = 0;
+        while let Some(update) = progress.recv().await {
+            complete += usize::from(update.status == PushStatus::Complete);
+        }
+        assert_eq!(complete, 3);
+
+        // A second push finds every blob in the repository.
+        let (options, mut progress) = push_options();
+        registry
+            .push_to(&server.url, "team/app", "v2", &local, &options)
+            .await
+            .unwrap();
+        drop(options);
+        let mut exists = 0;
+        while let Some(update) = progress.recv().await {
+            exists += usize::from(update.status == PushStatus::Exists);
+        }
+        assert_eq!(exists, 3);
+        assert_eq!(server.count("PUT /v2/team/app/blobs/"), 3);
+    }
+
+    #[tokio::test]
+    async fn test_push_uploads_large_blobs_in_chunks() {
+        let dir = TempDir::new().unwrap();
+        let server = PushServer::start(b"base").await;
+        let registry = registry(&dir).with_upload_chunk_size(4);
+        let layer = b"a layer of 26 bytes, or so";
+        let local = local_image(&dir, &[layer], &[]);
+
+        let (options, _progress) = push_options();
+        registry
+            .push_to(&server.url, "team/app", "latest", &local, &options)
+            .await
+            .unwrap();
+
+        assert!(server.has_blob("team/app", layer));
+        assert!(server.count("PATCH") >= layer.len() / 4);
+    }
+
+    #[tokio::test]
+    async fn test_push_mounts_blobs_from_other_repositories() {
+        let dir = TempDir::new().unwrap();
+        let server = PushServer::start(b"base").await;
+        let registry = registry(&dir);
+        let layers: [&[u8]; 2] = [b"base", b"app layer"];
+        let local = local_image(&dir, &layers, &["team/base"]);
+
+        let (options, mut progress) = push_options();
+        registry
+            .push_to(&server.url, "team/app", "latest", &local, &options)
+            .await
+            .unwrap();
+        drop(options);
+
+        assert!(server.has_blob("team/app", b"base"));
+        assert!(server.has_blob("team/app", b"app layer"));
+        let mut mounted = Vec::new();
+        while let Some(update) = progress.recv().await {
+            if update.status == PushStatus::Mounted {
+                mounted.push(update.layer);
+            }
+        }
+        assert_eq!(mounted, [blob(b"base").digest]);
+        // Only the base layer was mounted rather than uploaded.
+        assert_eq!(server.count("PUT /v2/team/app/blobs/"), 2);
+    }
+
+    #[test]
+    fn test_parse_ref_canonical_docker_hub() {
+        let (registry, name, tag) = ImageRegistry::parse_ref("docker.io/library/alpine:3.19");
+        assert_eq!(registry, DOCKER_HUB_REGISTRY);
+        assert_eq!(name, "library/alpine");
+        assert_eq!(tag, "3.19");
+
+        let (registry, name, _) = ImageRegistry::parse_ref("docker.io/nginx");
+        assert_eq!(registry, DOCKER_HUB_REGISTRY);
+        assert_eq!(name, "library/nginx");
+    }
+
+    #[test]
+    fn test_parse_ref_round_trips_image_ref() {
+        let image = crate::types::ImageRef::parse("redis:7");
+        let (registry, name, tag) = ImageRegistry::parse_ref(&image.full_name());
+        assert_eq!(registry, DOCKER_HUB_REGISTRY);
+        assert_eq!(name, "library/redis");
+        assert_eq!(tag, "7");
+    }
+
+    #[test]
+    fn test_manifest_deserializes_registry_json() {
+        let json = r#"{
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+            "config": {
+                "mediaType": "application/vnd.docker.container.image.v1+json",
+                "size": 1472,
+                "digest": "sha256:cfg"
+            },
+            "layers": [{
+                "mediaType": "application/vnd.docker.image.rootfs.diff.tar.gzip",
+                "size": 3408729,
+                "digest": "sha256:layer"
+            }]
+        }"#;
+        let manifest: ImageManifest = serde_json::from_str(json).unwrap();
+        assert_eq!(manifest.schema_version, 2);
+        assert_eq!(manifest.config.digest, "sha256:cfg");
+        assert_eq!(manifest.layers[0].size, 3_408_729);
+    }
+
+    #[test]
+    fn test_config_deserializes_without_history() {
+        let json = r#"{
+            "architecture": "amd64",
+            "os": "linux",
+            "config": {"Entrypoint": ["/entry"], "Cmd": ["serve"], "WorkingDir": "/app"},
+            "rootfs": {"type": "layers", "diff_ids": ["sha256:diff"]}
+        }"#;
+        let config: ImageConfig = serde_json::from_str(json).unwrap();
+        let container = config.config.unwrap();
+        assert_eq!(container.entrypoint.unwrap(), vec!["/entry"]);
+        assert_eq!(container.working_dir.as_deref(), Some("/app"));
+        assert!(config.history.is_empty());
+    }
+}
@@ -0,0 +1,783 @@
+//! OCI layer application.
+//!
+//! A layer is a tarball, optionally gzip or zstd compressed, of the paths it
+//! adds or changes. Deletions are recorded as whiteouts: an empty
+//! `.wh.<name>` file removes `<name>` from the layers below, and a
+//! `.wh..wh..opq` file in a directory hides everything the layers below put
+//! there.
+//!
+//! [`apply`] unpacks a layer either into its own directory, to be stacked
+//! with overlayfs ([`Whiteouts::Overlay`]), or on top of a flat root
+//! filesystem ([`Whiteouts::Apply`]). Either way no entry may write outside
+//! the target directory, and the digest of the uncompressed tarball is
+//! returned as the layer's `diff_id`.
+
+use crate::error::{CoreError, Result};
+use crate::storage::changes::{OPAQUE_WHITEOUT, WHITEOUT_PREFIX};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashSet, VecDeque};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use tar::{Archive, Entry, EntryType};
+use tracing::debug;
+
+/// Magic bytes at the start of a gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Magic bytes at the start of a zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Prefix of extended attributes in PAX headers.
+const PAX_XATTR_PREFIX: &str = "SCHILY.xattr.";
+
+/// Symbolic links followed while resolving one path, as in Linux.
+const MAX_SYMLINKS: usize = 40;
+
+/// Compression of a layer blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain tarball
+    None,
+    /// gzip (`+gzip`, Docker `.tar.gzip`)
+    Gzip,
+    /// zstd (`+zstd`)
+    Zstd,
+}
+
+impl Compression {
+    /// Compression of a layer from its media type.
+    ///
+    /// Layers stored without a media type are recognized by the first bytes
+    /// of the blob.
+    ///
+    /// # Errors
+    ///
+    /// Returns error for compressed media types other than gzip and zstd.
+    pub fn detect(media_type: &str, head: &[u8]) -> Result<Self> {
+        // `...layer.v1.tar+gzip`, `...rootfs.diff.tar.gzip`, `...layer.v1.tar`
+        match media_type.rsplit(['+', '.']).next().unwrap_or_default() {
+            "gzip" => Ok(Self::Gzip),
+            "zstd" => Ok(Self::Zstd),
+            "tar" => Ok(Self::None),
+            _ if media_type.contains('+') => Err(CoreError::StorageOperation(format!(
+                "unsupported layer media type {media_type}"
+            ))),
+            _ if head.starts_with(&GZIP_MAGIC) => Ok(Self::Gzip),
+            _ if head.starts_with(&ZSTD_MAGIC) => Ok(Self::Zstd),
+            _ => Ok(Self::None),
+        }
+    }
+
+    /// Wrap a blob reader so it yields the uncompressed tarball.
+    fn decoder<'a>(self, reader: impl Read + 'a) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Self::None => Box::new(reader),
+            Self::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Self::Zstd => Box::new(
+                zstd::stream::read::Decoder::new(reader)
+                    .map_err(|e| CoreError::StorageOperation(format!("decompress layer: {e}")))?,
+            ),
+        })
+    }
+}
+
+/// How whiteouts of a layer are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Whiteouts {
+    /// Convert to overlayfs whiteouts, for a layer unpacked into its own
+    /// directory. Where creating those is not permitted the OCI whiteout
+    /// files are kept, which [`changes`](super::changes) understands.
+    Overlay,
+    /// Delete the whited-out paths, for layers unpacked on top of each other.
+    Apply,
+}
+
+/// Result of applying a layer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Unpacked {
+    /// Digest of the uncompressed tarball (sha256:...)
+    pub diff_id: String,
+    /// Size of the uncompressed tarball in bytes
+    pub size: u64,
+}
+
+/// Apply a layer blob stored at `blob` onto `target`.
+///
+/// # Errors
+///
+/// Returns error if the blob cannot be read or decompressed, or an entry
+/// cannot be unpacked or points outside `target`.
+pub fn apply_blob(
+    blob: &Path,
+    media_type: &str,
+    target: &Path,
+    whiteouts: Whiteouts,
+) -> Result<Unpacked> {
+    let mut file = io::BufReader::new(fs::File::open(blob)?);
+    let head = io::BufRead::fill_buf(&mut file)?;
+    let compression = Compression::detect(media_type, head)?;
+    apply(file, compression, target, whiteouts)
+}
+
+/// Apply a layer read from `reader` onto `target`, which is created if
+/// missing.
+///
+/// # Errors
+///
+/// Returns error if the layer cannot be decompressed, or an entry cannot be
+/// unpacked or points outside `target`.
+pub fn apply(
+    reader: impl Read,
+    compression: Compression,
+    target: &Path,
+    whiteouts: Whiteouts,
+) -> Result<Unpacked> {
+    let extract_err = |e: io::Error| CoreError::StorageOperation(format!("extract layer: {e}"));
+
+    fs::create_dir_all(target)?;
+    let root = target.canonicalize()?;
+
+    let mut archive = Archive::new(DigestReader::new(compression.decoder(reader)?));
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+    archive.set_unpack_xattrs(false);
+    archive.set_overwrite(true);
+
+    // Paths of this layer, which its own opaque whiteouts leave alone.
+    let mut unpacked = HashSet::new();
+    for entry in archive.entries().map_err(extract_err)? {
+        let mut entry = entry.map_err(extract_err)?;
+        let path = entry_path(&entry.path().map_err(extract_err)?)?;
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+            // The root directory itself.
+            continue;
+        };
+        let parent = path.parent().unwrap_or_else(|| Path::new(""));
+
+        if name == OPAQUE_WHITEOUT {
+            opaque(&root, parent, &unpacked, whiteouts)?;
+        } else if let Some(hidden) = name.strip_prefix(WHITEOUT_PREFIX) {
+            whiteout(&root, parent, hidden, whiteouts)?;
+        } else {
+            unpack_entry(&mut entry, &root, &path)?;
+            unpacked.insert(path);
+        }
+    }
+
+    // Read the end-of-archive padding, so the digest covers the whole
+    // tarball.
+    let mut reader = archive.into_inner();
+    io::copy(&mut reader, &mut io::sink()).map_err(extract_err)?;
+
+    Ok(reader.finish())
+}
+
+/// Normalize the path of an entry to a path relative to the target.
+fn entry_path(path: &Path) -> Result<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir | Component::Prefix(_) => return Err(outside(path)),
+        }
+    }
+    Ok(relative)
+}
+
+/// Resolve the directory `relative` below `root`, creating it if `create`
+/// is set.
+///
+/// Returns `None` if the directory does not exist and is not created.
+fn resolve_dir(root: &Path, relative: &Path, create: bool) -> Result<Option<PathBuf>> {
+    let host_path = |path: &Path| root.join(path.strip_prefix("/").unwrap_or(path));
+    let dir =
+        resolve_in_root(&Path::new("/").join(relative), true, create, |path| Ok(host_path(path)))?;
+    let dir = host_path(&dir);
+    Ok(dir.is_dir().then_some(dir))
+}
+
+/// Resolve an absolute path below a root directory the way the kernel would
+/// with that directory as `/`, returning the resolved path.
+///
+/// `host_path` maps a path below the root to the host, where symbolic links
+/// are read one component at a time: absolute targets start again at the
+/// root and `..` stops there, so a link such as `/var/run -> /run` resolves
+/// to the root's `/run` and never to the host's. A link in the last component
+/// is only followed if `follow_final` is set. With `create_dirs`, every
+/// component must be a directory and missing ones are created.
+///
+/// # Errors
+///
+/// Returns [`CoreError::InvalidSpec`] for symlink loops and, with
+/// `create_dirs`, components that are not directories. Errors of `host_path`
+/// and of creating directories are passed through.
+pub(crate) fn resolve_in_root(
+    path: &Path,
+    follow_final: bool,
+    create_dirs: bool,
+    host_path: impl Fn(&Path) -> Result<PathBuf>,
+) -> Result<PathBuf> {
+    let mut resolved = PathBuf::from("/");
+    let mut pending = components(path);
+    let mut links = 0;
+    while let Some(part) = pending.pop_front() {
+        if part == ".." {
+            resolved.pop();
+            continue;
+        }
+        let candidate = resolved.join(&part);
+        if !pending.is_empty() || follow_final {
+            let host = host_path(&candidate)?;
+            match fs::symlink_metadata(&host) {
+                Ok(meta) if meta.file_type().is_symlink() => {
+                    links += 1;
+                    if links > MAX_SYMLINKS {
+                        return Err(CoreError::InvalidSpec {
+                            field: "path".to_string(),
+                            reason: format!(
+                                "too many levels of symbolic links in {}",
+                                path.display()
+                            ),
+                        });
+                    }
+                    let target = fs::read_link(&host)?;
+                    if target.is_absolute() {
+                        resolved = PathBuf::from("/");
+                    }
+                    for part in components(&target).into_iter().rev() {
+                        pending.push_front(part);
+                    }
+                    continue;
+                }
+                Ok(meta) if !create_dirs || meta.is_dir() => {}
+                Ok(_) => {
+                    return Err(CoreError::InvalidSpec {
+                        field: "path".to_string(),
+                        reason: format!("{} is not a directory", candidate.display()),
+                    })
+                }
+                Err(_) if create_dirs => fs::create_dir(&host)?,
+                Err(_) => {}
+            }
+        }
+        resolved = candidate;
+    }
+    Ok(resolved)
+}
+
+/// The normal and `..` components of a path.
+fn components(path: &Path) -> VecDeque<OsString> {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_owned()),
+            Component::ParentDir => Some(OsString::from("..")),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Unpack a file, directory, link or device node.
+fn unpack_entry<R: Read>(entry: &mut Entry<'_, R>, root: &Path, path: &Path) -> Result<()> {
+    let unpack_err = |e: io::Error| {
+        CoreError::StorageOperation(format!("extract layer: {}: {e}", path.display()))
+    };
+
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    let dir = resolve_dir(root, parent, true)?.unwrap_or_else(|| root.to_path_buf());
+    let dst = dir.join(path.file_name().unwrap_or_default());
+    let kind = entry.header().entry_type();
+
+    // A later layer may replace a directory with a file or the other way
+    // round. Directories are merged.
+    if let Ok(existing) = fs::symlink_metadata(&dst) {
+        if existing.is_dir() && !kind.is_dir() {
+            fs::remove_dir_all(&dst)?;
+        } else if !existing.is_dir() {
+            fs::remove_file(&dst)?;
+        }
+    }
+
+    let xattrs: Vec<(String, Vec<u8>)> = entry
+        .pax_extensions()
+        .map_err(unpack_err)?
+        .into_iter()
+        .flatten()
+        .filter_map(|ext| {
+            let ext = ext.ok()?;
+            let name = ext.key().ok()?.strip_prefix(PAX_XATTR_PREFIX)?;
+            Some((name.to_string(), ext.value_bytes().to_vec()))
+        })
+        .collect();
+
+    match kind {
+        EntryType::Char | EntryType::Block | EntryType::Fifo => {
+            if !special::mknod(entry.header(), &dst).map_err(unpack_err)? {
+                return Ok(());
+            }
+        }
+        EntryType::Link => {
+            let source = entry
+                .link_name()
+                .map_err(unpack_err)?
+                .ok_or_else(|| unpack_err(io::Error::other("hard link without a target")))?;
+            let source = entry_path(&source)?;
+            let source_dir =
+                resolve_dir(root, source.parent().unwrap_or_else(|| Path::new("")), false)?
+                    .ok_or_else(|| {
+                        unpack_err(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("hard link target {} not found", source.display()),
+                        ))
+                    })?;
+            fs::hard_link(source_dir.join(source.file_name().unwrap_or_default()), &dst)
+                .map_err(unpack_err)?;
+        }
+        _ => {
+            entry.unpack(&dst).map_err(unpack_err)?;
+        }
+    }
+    if kind != EntryType::Link {
+        special::set_owner(entry.header(), &dst).map_err(unpack_err)?;
+    }
+
+    for (name, value) in xattrs {
+        if let Err(e) = special::set_xattr(&dst, &name, &value) {
+            debug!("Skipping xattr {} on {}: {}", name, path.display(), e);
+        }
+    }
+    Ok(())
+}
+
+/// Apply a `.wh.<hidden>` whiteout in `parent`.
+fn whiteout(root: &Path, parent: &Path, hidden: &str, whiteouts: Whiteouts) -> Result<()> {
+    match whiteouts {
+        Whiteouts::Apply => {
+            let Some(dir) = resolve_dir(root, parent, false)? else {
+                return Ok(());
+            };
+            remove(&dir.join(hidden))
+        }
+        Whiteouts::Overlay => {
+            let dir = resolve_dir(root, parent, true)?.unwrap_or_else(|| root.to_path_buf());
+            let dst = dir.join(hidden);
+            remove(&dst)?;
+            if let Err(e) = special::overlay_whiteout(&dst) {
+                debug!("Keeping OCI whiteout for {}: {}", parent.join(hidden).display(), e);
+                fs::write(dir.join(format!("{WHITEOUT_PREFIX}{hidden}")), "")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Apply a `.wh..wh..opq` whiteout in `parent`.
+fn opaque(
+    root: &Path,
+    parent: &Path,
+    unpacked: &HashSet<PathBuf>,
+    whiteouts: Whiteouts,
+) -> Result<()> {
+    match whiteouts {
+        Whiteouts::Apply => {
+            let Some(dir) = resolve_dir(root, parent, false)? else {
+                return Ok(());
+            };
+            for child in fs::read_dir(&dir)? {
+                let name = child?.file_name();
+                if !unpacked.contains(&parent.join(&name)) {
+                    remove(&dir.join(name))?;
+                }
+            }
+            Ok(())
+        }
+        Whiteouts::Overlay => {
+            let dir = resolve_dir(root, parent, true)?.unwrap_or_else(|| root.to_path_buf());
+            if let Err(e) = special::overlay_opaque(&dir) {
+                debug!("Keeping OCI opaque whiteout for {}: {}", parent.display(), e);
+                fs::write(dir.join(OPAQUE_WHITEOUT), "")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Remove a path of any type, if it exists.
+fn remove(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path)?,
+        Ok(_) => fs::remove_file(path)?,
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+fn outside(path: &Path) -> CoreError {
+    CoreError::StorageOperation(format!(
+        "extract layer: {} points outside the root filesystem",
+        path.display()
+    ))
+}
+
+/// Hashes and counts what is read through it.
+struct DigestReader<R> {
+    inner: R,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<R> DigestReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    fn finish(self) -> Unpacked {
+        Unpacked {
+            diff_id: format!("sha256:{:x}", self.hasher.finalize()),
+            size: self.size,
+        }
+    }
+}
+
+impl<R: Read> Read for DigestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+}
+
+/// Ownership, device nodes, extended attributes and overlayfs whiteouts,
+/// which need privileges the daemon may not have.
+#[cfg(unix)]
+mod special {
+    use nix::sys::stat::{makedev, mknod as make_node, Mode, SFlag};
+    use std::fs::Permissions;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use tar::{EntryType, Header};
+
+    /// Extended attribute marking an overlayfs directory opaque.
+    const OPAQUE_XATTR: &str = "trusted.overlay.opaque";
+
+    /// Create the device node or FIFO of `header` at `dst`.
+    ///
+    /// Returns `false` if creating device nodes is not permitted.
+    pub fn mknod(header: &Header, dst: &Path) -> io::Result<bool> {
+        let kind = match header.entry_type() {
+            EntryType::Char => SFlag::S_IFCHR,
+            EntryType::Block => SFlag::S_IFBLK,
+            _ => SFlag::S_IFIFO,
+        };
+        let device = makedev(
+            u64::from(header.device_major()?.unwrap_or(0)),
+            u64::from(header.device_minor()?.unwrap_or(0)),
+        );
+        let mode = Mode::from_bits_truncate(header.mode()?);
+
+        match make_node(dst, kind, mode, device) {
+            Ok(()) => {}
+            Err(nix::errno::Errno::EPERM) if kind != SFlag::S_IFIFO => {
+                tracing::debug!("Not permitted to create device node {}", dst.display());
+                return Ok(false);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        std::fs::set_permissions(dst, Permissions::from_mode(header.mode()?))?;
+        Ok(true)
+    }
+
+    /// Give `dst` the owner of `header`, when running as root.
+    ///
+    /// Changing the owner clears setuid and setgid bits, so the mode is
+    /// applied again afterwards.
+    pub fn set_owner(header: &Header, dst: &Path) -> io::Result<()> {
+        if !nix::unistd::geteuid().is_root() {
+            return Ok(());
+        }
+        let (Ok(uid), Ok(gid)) = (header.uid(), header.gid()) else {
+            return Ok(());
+        };
+        let (Ok(uid), Ok(gid)) = (u32::try_from(uid), u32::try_from(gid)) else {
+            return Ok(());
+        };
+        std::os::unix::fs::lchown(dst, Some(uid), Some(gid))?;
+        if header.entry_type() != EntryType::Symlink {
+            if let Ok(mode) = header.mode() {
+                std::fs::set_permissions(dst, Permissions::from_mode(mode))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace `dst` with an overlayfs whiteout, a `0:0` character device.
+    pub fn overlay_whiteout(dst: &Path) -> io::Result<()> {
+        make_node(dst, SFlag::S_IFCHR, Mode::empty(), makedev(0, 0))?;
+        Ok(())
+    }
+
+    /// Mark `dir` as an opaque overlayfs directory.
+    pub fn overlay_opaque(dir: &Path) -> io::Result<()> {
+        set_xattr(dir, OPAQUE_XATTR, b"y")
+    }
+
+    /// Set an extended attribute without following symbolic links.
+    pub fn set_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+        let path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+        let name = std::ffi::CString::new(name)?;
+        // SAFETY: both strings are NUL-terminated and the length matches
+        // the value buffer.
+        #[allow(unsafe_code)]
+        let result = unsafe {
+            libc::lsetxattr(path.as_ptr(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0)
+        };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod special {
+    use std::io;
+    use std::path::Path;
+    use tar::Header;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(io::ErrorKind::Unsupported, "not supported on this platform")
+    }
+
+    pub fn mknod(_header: &Header, _dst: &Path) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    pub fn set_owner(_header: &Header, _dst: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn overlay_whiteout(_dst: &Path) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn overlay_opaque(_dir: &Path) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn set_xattr(_path: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    use tempfile::TempDir;
+
+    /// Build a tarball from `(path, contents)` pairs; a trailing `/` makes a
+    /// directory.
+    fn tarball(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, contents) in entries {
+            let mut header = tar::Header::new_gnu();
+            if path.ends_with('/') {
+                header.set_entry_type(EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+            } else {
+                header.set_mode(0o644);
+                header.set_size(contents.len() as u64);
+            }
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    /// Append an entry whose name is written verbatim, bypassing the path
+    /// checks of [`tar::Builder`].
+    fn raw_entry(tar: &mut Vec<u8>, name: &str, kind: EntryType, link: &str) {
+        let mut header = tar::Header::new_old();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+        header.set_entry_type(kind);
+        header.set_mode(0o644);
+        header.set_size(0);
+        header.set_cksum();
+        // Drop the end-of-archive blocks, add the entry and end it again.
+        tar.truncate(tar.len() - 1024);
+        tar.extend_from_slice(header.as_bytes());
+        tar.extend_from_slice(&[0; 1024]);
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        io::Write::write_all(&mut encoder, data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_detect_compression() {
+        let detect = |media_type| Compression::detect(media_type, b"").unwrap();
+        assert_eq!(detect("application/vnd.oci.image.layer.v1.tar"), Compression::None);
+        assert_eq!(detect("application/vnd.oci.image.layer.v1.tar+gzip"), Compression::Gzip);
+        assert_eq!(detect("application/vnd.oci.image.layer.v1.tar+zstd"), Compression::Zstd);
+        assert_eq!(detect("application/vnd.docker.image.rootfs.diff.tar.gzip"), Compression::Gzip);
+        assert!(Compression::detect("application/vnd.oci.image.layer.v1.tar+bzip2", b"").is_err());
+
+        assert_eq!(Compression::detect("", &[0x1f, 0x8b, 8]).unwrap(), Compression::Gzip);
+        assert_eq!(Compression::detect("", &ZSTD_MAGIC).unwrap(), Compression::Zstd);
+        assert_eq!(Compression::detect("", b"etc/").unwrap(), Compression::None);
+    }
+
+    #[test]
+    fn test_diff_id_is_digest_of_uncompressed_tarball() {
+        let dir = TempDir::new().unwrap();
+        let tar = tarball(&[("etc/", ""), ("etc/hostname", "box\n")]);
+        let expected = format!("sha256:{:x}", Sha256::digest(&tar));
+
+        let gzipped =
+            apply(gzip(&tar).as_slice(), Compression::Gzip, dir.path(), Whiteouts::Apply).unwrap();
+        assert_eq!(gzipped.diff_id, expected);
+        assert_eq!(gzipped.size, tar.len() as u64);
+
+        let compressed = zstd::encode_all(tar.as_slice(), 0).unwrap();
+        let zstd =
+            apply(compressed.as_slice(), Compression::Zstd, dir.path(), Whiteouts::Apply).unwrap();
+        assert_eq!(zstd.diff_id, expected);
+        assert_eq!(fs::read_to_string(dir.path().join("etc/hostname")).unwrap(), "box\n");
+    }
+
+    #[test]
+    fn test_apply_whiteouts() {
+        let dir = TempDir::new().unwrap();
+        let base = tarball(&[
+            ("etc/", ""),
+            ("etc/motd", "hello"),
+            ("etc/hosts", "127.0.0.1"),
+            ("opt/", ""),
+            ("opt/tool/", ""),
+            ("opt/tool/old", "v1"),
+            ("var/cache", "file"),
+        ]);
+        let top = tarball(&[
+            ("etc/.wh.motd", ""),
+            ("opt/tool/new", "v2"),
+            ("opt/tool/.wh..wh..opq", ""),
+            ("var/cache/", ""),
+            ("var/cache/entry", "dir now"),
+        ]);
+
+        apply(base.as_slice(), Compression::None, dir.path(), Whiteouts::Apply).unwrap();
+        apply(top.as_slice(), Compression::None, dir.path(), Whiteouts::Apply).unwrap();
+
+        let root = dir.path();
+        assert!(!root.join("etc/motd").exists());
+        assert!(!root.join("etc/.wh.motd").exists());
+        assert!(root.join("etc/hosts").exists());
+        assert!(!root.join("opt/tool/old").exists());
+        assert_eq!(fs::read_to_string(root.join("opt/tool/new")).unwrap(), "v2");
+        assert!(!root.join("opt/tool/.wh..wh..opq").exists());
+        assert_eq!(fs::read_to_string(root.join("var/cache/entry")).unwrap(), "dir now");
+    }
+
+    #[test]
+    fn test_overlay_whiteouts() {
+        let dir = TempDir::new().unwrap();
+        let layer = tarball(&[
+            ("etc/", ""),
+            ("etc/.wh.motd", ""),
+            ("opt/", ""),
+            ("opt/.wh..wh..opq", ""),
+        ]);
+
+        apply(layer.as_slice(), Compression::None, dir.path(), Whiteouts::Overlay).unwrap();
+
+        // Either an overlay whiteout or, without the privileges for one, the
+        // OCI whiteout file is left for the layer stack.
+        let motd = dir.path().join("etc/motd");
+        match fs::symlink_metadata(&motd) {
+            Ok(meta) => assert!(meta.file_type().is_char_device() && meta.rdev() == 0),
+            Err(_) => assert!(dir.path().join("etc/.wh.motd").exists()),
+        }
+        assert!(dir.path().join("opt").is_dir());
+    }
+
+    #[test]
+    fn test_hard_links_and_fifos() {
+        let dir = TempDir::new().unwrap();
+        let mut tar = tarball(&[("bin/", ""), ("bin/busybox", "binary")]);
+        raw_entry(&mut tar, "bin/sh", EntryType::Link, "bin/busybox");
+        raw_entry(&mut tar, "run/pipe", EntryType::Fifo, "");
+
+        apply(tar.as_slice(), Compression::None, dir.path(), Whiteouts::Apply).unwrap();
+
+        let busybox = fs::metadata(dir.path().join("bin/busybox")).unwrap();
+        let sh = fs::metadata(dir.path().join("bin/sh")).unwrap();
+        assert_eq!(busybox.ino(), sh.ino());
+        let pipe = fs::symlink_metadata(dir.path().join("run/pipe")).unwrap();
+        assert!(pipe.file_type().is_fifo());
+    }
+
+    #[test]
+    fn test_absolute_symlinks_resolve_inside_target() {
+        let dir = TempDir::new().unwrap();
+        let host = dir.path().join("run");
+        fs::create_dir(&host).unwrap();
+        let target = dir.path().join("rootfs");
+
+        let mut base = tarball(&[("var/", ""), ("run/", "")]);
+        raw_entry(&mut base, "var/run", EntryType::Symlink, "/run");
+        raw_entry(&mut base, "var/lock", EntryType::Symlink, host.to_str().unwrap());
+        apply(base.as_slice(), Compression::None, &target, Whiteouts::Apply).unwrap();
+
+        let upper = tarball(&[("var/run/foo.pid", "42\n"), ("var/lock/held", "")]);
+        apply(upper.as_slice(), Compression::None, &target, Whiteouts::Apply).unwrap();
+
+        assert_eq!(fs::read_to_string(target.join("run/foo.pid")).unwrap(), "42\n");
+        // A link naming a host directory still lands inside the image.
+        assert!(target
+            .join(host.strip_prefix("/").unwrap())
+            .join("held")
+            .is_file());
+        assert!(!host.join("held").exists());
+    }
+
+    #[test]
+    fn test_rejects_paths_outside_target() {
+        let dir = TempDir::new().unwrap();
+        let target = dir.path().join("rootfs");
+
+        let mut tar = tarball(&[]);
+        raw_entry(&mut tar, "../escaped", EntryType::Regular, "");
+        assert!(apply(tar.as_slice(), Compression::None, &target, Whiteouts::Apply).is_err());
+
+        // `..` stops at the root of the image, as it does inside it.
+        let mut tar = tarball(&[]);
+        raw_entry(&mut tar, "up", EntryType::Symlink, "..");
+        raw_entry(&mut tar, "up/escaped", EntryType::Regular, "");
+        apply(tar.as_slice(), Compression::None, &target, Whiteouts::Apply).unwrap();
+        assert!(target.join("escaped").is_file());
+
+        let mut tar = tarball(&[]);
+        raw_entry(&mut tar, "shadow", EntryType::Link, "../secret");
+        assert!(apply(tar.as_slice(), Compression::None, &target, Whiteouts::Apply).is_err());
+
+        assert!(!dir.path().join("escaped").exists());
+    }
+}
@@ -1,5 +1,8 @@
 //! Core type definitions for HyperBox.
 
+use crate::error::{CoreError, Result};
+use crate::runtime::RuntimeType;
+use crate::storage::ContainerConfig;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -97,6 +100,27 @@ impl std::fmt::Display for ContainerState {
     }
 }
 
+impl std::str::FromStr for ContainerState {
+    type Err = CoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "creating" => Ok(Self::Creating),
+            "created" => Ok(Self::Created),
+            "running" => Ok(Self::Running),
+            "paused" => Ok(Self::Paused),
+            "stopped" => Ok(Self::Stopped),
+            "removing" => Ok(Self::Removing),
+            "exited" => Ok(Self::Exited),
+            "unknown" => Ok(Self::Unknown),
+            _ => Err(CoreError::InvalidSpec {
+                field: "status".to_string(),
+                reason: format!("unknown container state '{s}'"),
+            }),
+        }
+    }
+}
+
 /// Image reference (e.g., "docker.io/library/alpine:3.18").
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ImageRef {
@@ -155,6 +179,114 @@ impl From<&str> for ImageRef {
     }
 }
 
+/// Platform an image is built for (e.g. `linux/arm64/v8`).
+///
+/// Serialized like the `platform` object of OCI image index entries.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Platform {
+    /// Operating system (e.g. "linux")
+    pub os: String,
+    /// CPU architecture, named as in Go (e.g. "amd64", "arm64")
+    pub architecture: String,
+    /// CPU variant (e.g. "v7" for 32-bit ARM)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+}
+
+impl Platform {
+    /// The platform of this machine.
+    ///
+    /// Containers on macOS run in a Linux VM, so any host other than
+    /// Windows runs Linux images.
+    #[must_use]
+    pub fn host() -> Self {
+        let os = if cfg!(windows) { "windows" } else { "linux" };
+        Self::new(os, std::env::consts::ARCH, None).normalized()
+    }
+
+    /// Create a platform from its parts.
+    #[must_use]
+    pub fn new(
+        os: impl Into<String>,
+        architecture: impl Into<String>,
+        variant: Option<String>,
+    ) -> Self {
+        Self {
+            os: os.into(),
+            architecture: architecture.into(),
+            variant,
+        }
+    }
+
+    /// Canonical form, with architecture aliases resolved and the default
+    /// variant filled in, so that `linux/aarch64` equals `linux/arm64/v8`.
+    #[must_use]
+    pub fn normalized(&self) -> Self {
+        let os = self.os.to_ascii_lowercase();
+        let arch = self.architecture.to_ascii_lowercase();
+        let variant = self.variant.as_deref().map(str::to_ascii_lowercase);
+
+        let (architecture, variant) = match (arch.as_str(), variant.as_deref()) {
+            ("x86_64" | "x86-64" | "amd64", variant) => ("amd64", variant.map(str::to_string)),
+            ("i386" | "i686" | "x86" | "386", _) => ("386", None),
+            ("aarch64" | "arm64", None | Some("8" | "v8")) => ("arm64", Some("v8".to_string())),
+            ("aarch64" | "arm64", Some(v)) => ("arm64", Some(v.to_string())),
+            ("armhf" | "arm", None | Some("7" | "v7")) => ("arm", Some("v7".to_string())),
+            ("armel", _) => ("arm", Some("v6".to_string())),
+            ("arm", Some(v)) if !v.starts_with('v') => ("arm", Some(format!("v{v}"))),
+            ("powerpc64", variant) => ("ppc64le", variant.map(str::to_string)),
+            (_, variant) => (arch.as_str(), variant.map(str::to_string)),
+        };
+
+        Self {
+            os,
+            architecture: architecture.to_string(),
+            variant,
+        }
+    }
+
+    /// Whether an image for `other` runs on this platform.
+    #[must_use]
+    pub fn matches(&self, other: &Self) -> bool {
+        self.normalized() == other.normalized()
+    }
+}
+
+impl Default for Platform {
+    fn default() -> Self {
+        Self::host()
+    }
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.os, self.architecture)?;
+        if let Some(variant) = &self.variant {
+            write!(f, "/{variant}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Platform {
+    type Err = CoreError;
+
+    /// Parse `os/arch[/variant]`, as in `docker pull --platform`.
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.trim().split('/').collect();
+        match parts.as_slice() {
+            [os, arch] if !os.is_empty() && !arch.is_empty() => Ok(Self::new(*os, *arch, None)),
+            [os, arch, variant] if !os.is_empty() && !arch.is_empty() && !variant.is_empty() => {
+                Ok(Self::new(*os, *arch, Some((*variant).to_string())))
+            }
+            _ => Err(CoreError::InvalidSpec {
+                field: "platform".to_string(),
+                reason: format!("{s} is not os/arch[/variant]"),
+            }),
+        }
+    }
+}
+
 /// Container specification for creation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContainerSpec {
@@ -192,6 +324,43 @@ pub struct ContainerSpec {
     pub tty: bool,
     /// Stdin open
     pub stdin_open: bool,
+    /// Runtime to run the container with, overriding the `hyperbox.runtime`
+    /// label and image-based detection
+    #[serde(default)]
+    pub runtime: Option<RuntimeType>,
+    /// Capabilities added to the default set (`NET_ADMIN` or `CAP_NET_ADMIN`,
+    /// `ALL` for every capability)
+    #[serde(default)]
+    pub cap_add: Vec<String>,
+    /// Capabilities removed from the default set (`ALL` drops every
+    /// capability not explicitly added)
+    #[serde(default)]
+    pub cap_drop: Vec<String>,
+    /// Host devices exposed to the container
+    #[serde(default)]
+    pub devices: Vec<DeviceMapping>,
+    /// Resource limits (rlimits) of the container process
+    #[serde(default)]
+    pub ulimits: Vec<Ulimit>,
+    /// Namespaced kernel parameters
+    #[serde(default)]
+    pub sysctls: HashMap<String, String>,
+    /// Size of `/dev/shm` in bytes
+    #[serde(default)]
+    pub shm_size: Option<u64>,
+    /// Additional groups (names or gids) of the container process
+    #[serde(default)]
+    pub group_add: Vec<String>,
+    /// Signal sent to stop the container (e.g. `SIGQUIT`), overriding the
+    /// image's `StopSignal`
+    #[serde(default)]
+    pub stop_signal: Option<String>,
+    /// Run an init process as PID 1 that forwards signals and reaps zombies
+    #[serde(default)]
+    pub init: bool,
+    /// Platform of the image to run, defaulting to the host's
+    #[serde(default)]
+    pub platform: Option<Platform>,
 }
 
 impl ContainerSpec {
@@ -238,6 +407,17 @@ impl Default for ContainerSpec {
             read_only_rootfs: false,
             tty: false,
             stdin_open: false,
+            runtime: None,
+            cap_add: Vec::new(),
+            cap_drop: Vec::new(),
+            devices: Vec::new(),
+            ulimits: Vec::new(),
+            sysctls: HashMap::new(),
+            shm_size: None,
+            group_add: Vec::new(),
+            stop_signal: None,
+            init: false,
+            platform: None,
         }
     }
 }
@@ -304,6 +484,97 @@ impl ContainerSpecBuilder {
         self
     }
 
+    /// Add a label.
+    #[must_use]
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.spec.labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the restart policy.
+    #[must_use]
+    pub fn restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.spec.restart_policy = policy;
+        self
+    }
+
+    /// Set the runtime to run the container with.
+    #[must_use]
+    pub fn runtime(mut self, runtime: RuntimeType) -> Self {
+        self.spec.runtime = Some(runtime);
+        self
+    }
+
+    /// Add a capability.
+    #[must_use]
+    pub fn cap_add(mut self, cap: impl Into<String>) -> Self {
+        self.spec.cap_add.push(cap.into());
+        self
+    }
+
+    /// Drop a capability.
+    #[must_use]
+    pub fn cap_drop(mut self, cap: impl Into<String>) -> Self {
+        self.spec.cap_drop.push(cap.into());
+        self
+    }
+
+    /// Expose a host device.
+    #[must_use]
+    pub fn device(mut self, device: DeviceMapping) -> Self {
+        self.spec.devices.push(device);
+        self
+    }
+
+    /// Set a resource limit.
+    #[must_use]
+    pub fn ulimit(mut self, ulimit: Ulimit) -> Self {
+        self.spec.ulimits.push(ulimit);
+        self
+    }
+
+    /// Set a kernel parameter.
+    #[must_use]
+    pub fn sysctl(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.spec.sysctls.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the size of `/dev/shm` in bytes.
+    #[must_use]
+    pub fn shm_size(mut self, bytes: u64) -> Self {
+        self.spec.shm_size = Some(bytes);
+        self
+    }
+
+    /// Add a supplementary group.
+    #[must_use]
+    pub fn group_add(mut self, group: impl Into<String>) -> Self {
+        self.spec.group_add.push(group.into());
+        self
+    }
+
+    /// Set the stop signal.
+    #[must_use]
+    pub fn stop_signal(mut self, signal: impl Into<String>) -> Self {
+        self.spec.stop_signal = Some(signal.into());
+        self
+    }
+
+    /// Run an init process as PID 1.
+    #[must_use]
+    pub fn init(mut self, init: bool) -> Self {
+        self.spec.init = init;
+        self
+    }
+
+    /// Set the image platform.
+    #[must_use]
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.spec.platform = Some(platform);
+        self
+    }
+
     /// Build the spec.
     #[must_use]
     pub fn build(self) -> ContainerSpec {
@@ -336,6 +607,105 @@ pub enum MountType {
     Tmpfs,
 }
 
+/// Host device exposed to a container.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceMapping {
+    /// Device path on the host
+    pub host_path: PathBuf,
+    /// Device path in the container
+    pub container_path: PathBuf,
+    /// Cgroup permissions, a combination of `r`, `w` and `m`
+    pub permissions: String,
+}
+
+impl std::str::FromStr for DeviceMapping {
+    type Err = CoreError;
+
+    /// Parse `host[:container][:permissions]`, as in `docker run --device`.
+    fn from_str(s: &str) -> Result<Self> {
+        let is_permissions =
+            |p: &str| !p.is_empty() && p.chars().all(|c| matches!(c, 'r' | 'w' | 'm'));
+        let invalid = || CoreError::InvalidSpec {
+            field: "devices".to_string(),
+            reason: format!("{s} is not host[:container][:permissions]"),
+        };
+
+        let parts: Vec<&str> = s.split(':').collect();
+        let (host, container, permissions) = match parts.as_slice() {
+            [host] => (*host, *host, "rwm"),
+            [host, perms] if is_permissions(perms) => (*host, *host, *perms),
+            [host, container] => (*host, *container, "rwm"),
+            [host, container, perms] if is_permissions(perms) => (*host, *container, *perms),
+            _ => return Err(invalid()),
+        };
+        if !host.starts_with('/') || !container.starts_with('/') {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            host_path: PathBuf::from(host),
+            container_path: PathBuf::from(container),
+            permissions: permissions.to_string(),
+        })
+    }
+}
+
+/// Resource limit of the container process.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ulimit {
+    /// Resource name without the `RLIMIT_` prefix (e.g. `nofile`)
+    pub name: String,
+    /// Soft limit, [`Ulimit::UNLIMITED`] for no limit
+    pub soft: u64,
+    /// Hard limit, [`Ulimit::UNLIMITED`] for no limit
+    pub hard: u64,
+}
+
+impl Ulimit {
+    /// Value of an unlimited soft or hard limit (`RLIM_INFINITY`).
+    pub const UNLIMITED: u64 = u64::MAX;
+}
+
+impl std::str::FromStr for Ulimit {
+    type Err = CoreError;
+
+    /// Parse `name=soft[:hard]`, as in `docker run --ulimit`.
+    ///
+    /// `-1` and `unlimited` mean no limit; without a hard limit the soft
+    /// limit is used for both.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || CoreError::InvalidSpec {
+            field: "ulimits".to_string(),
+            reason: format!("{s} is not name=soft[:hard]"),
+        };
+        let limit = |value: &str| match value {
+            "-1" | "unlimited" => Ok(Self::UNLIMITED),
+            _ => value.parse().map_err(|_| invalid()),
+        };
+
+        let (name, limits) = s.split_once('=').ok_or_else(invalid)?;
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        let (soft, hard) = match limits.split_once(':') {
+            Some((soft, hard)) => (limit(soft)?, limit(hard)?),
+            None => (limit(limits)?, limit(limits)?),
+        };
+        if soft > hard {
+            return Err(CoreError::InvalidSpec {
+                field: "ulimits".to_string(),
+                reason: format!("{name} soft limit exceeds hard limit"),
+            });
+        }
+
+        Ok(Self {
+            name: name.to_lowercase(),
+            soft,
+            hard,
+        })
+    }
+}
+
 /// Port mapping configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortMapping {
@@ -362,8 +732,12 @@ pub enum Protocol {
 /// Resource limits for containers.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResourceLimits {
-    /// CPU limit in millicores (1000 = 1 CPU)
+    /// Hard CPU limit in millicores (1000 = 1 CPU), enforced as a `cpu.max` quota
     pub cpu_millicores: Option<u64>,
+    /// Relative CPU weight under contention (cgroup v2 `cpu.weight`, 1-10000, default 100)
+    pub cpu_weight: Option<u16>,
+    /// CPUs the container may run on, in cpuset list format (e.g. "0-3,6")
+    pub cpuset_cpus: Option<String>,
     /// Memory limit in bytes
     pub memory_bytes: Option<u64>,
     /// Memory swap limit in bytes
@@ -374,26 +748,149 @@ pub struct ResourceLimits {
     pub io_read_bps: Option<u64>,
     /// IO write bytes per second
     pub io_write_bps: Option<u64>,
+    /// IO read operations per second
+    pub io_read_iops: Option<u64>,
+    /// IO write operations per second
+    pub io_write_iops: Option<u64>,
+    /// Proportional IO weight (cgroup v2 `io.weight`, 1-10000, default 100)
+    pub io_weight: Option<u16>,
 }
 
 impl Default for ResourceLimits {
     fn default() -> Self {
         Self {
             cpu_millicores: None,
+            cpu_weight: None,
+            cpuset_cpus: None,
             memory_bytes: None,
             memory_swap_bytes: None,
             pids_limit: Some(4096),
             io_read_bps: None,
             io_write_bps: None,
+            io_read_iops: None,
+            io_write_iops: None,
+            io_weight: None,
+        }
+    }
+}
+
+impl ResourceLimits {
+    /// Names of every limit, as used in [`ResourceUpdateReport`].
+    pub const FIELDS: &'static [&'static str] = &[
+        "cpu_millicores",
+        "cpu_weight",
+        "cpuset_cpus",
+        "memory_bytes",
+        "memory_swap_bytes",
+        "pids_limit",
+        "io_read_bps",
+        "io_write_bps",
+        "io_read_iops",
+        "io_write_iops",
+        "io_weight",
+    ];
+
+    /// Names of the per-device IO throttles.
+    pub const IO_THROTTLE_FIELDS: &'static [&'static str] = &[
+        "io_read_bps",
+        "io_write_bps",
+        "io_read_iops",
+        "io_write_iops",
+    ];
+
+    /// Names of the limits that are set.
+    #[must_use]
+    pub fn set_fields(&self) -> Vec<&'static str> {
+        let set = [
+            self.cpu_millicores.is_some(),
+            self.cpu_weight.is_some(),
+            self.cpuset_cpus.is_some(),
+            self.memory_bytes.is_some(),
+            self.memory_swap_bytes.is_some(),
+            self.pids_limit.is_some(),
+            self.io_read_bps.is_some(),
+            self.io_write_bps.is_some(),
+            self.io_read_iops.is_some(),
+            self.io_write_iops.is_some(),
+            self.io_weight.is_some(),
+        ];
+        Self::FIELDS
+            .iter()
+            .zip(set)
+            .filter_map(|(name, set)| set.then_some(*name))
+            .collect()
+    }
+
+    /// `cpu_weight` for a CPU reservation in millicores.
+    ///
+    /// Reserving one CPU maps to the default weight of 100, so a container
+    /// that reserves more CPU gets proportionally more time under contention.
+    #[must_use]
+    pub fn cpu_weight_for_reservation(millicores: u64) -> u16 {
+        u16::try_from((millicores / 10).clamp(1, 10_000)).unwrap_or(10_000)
+    }
+
+    /// Whether any per-device IO throttle is set.
+    #[must_use]
+    pub const fn has_io_throttles(&self) -> bool {
+        self.io_read_bps.is_some()
+            || self.io_write_bps.is_some()
+            || self.io_read_iops.is_some()
+            || self.io_write_iops.is_some()
+    }
+
+    /// Overlay the limits set in `update`, keeping the others.
+    pub fn merge(&mut self, update: &Self) {
+        self.cpu_millicores = update.cpu_millicores.or(self.cpu_millicores);
+        self.cpu_weight = update.cpu_weight.or(self.cpu_weight);
+        if let Some(cpus) = &update.cpuset_cpus {
+            self.cpuset_cpus = Some(cpus.clone());
+        }
+        self.memory_bytes = update.memory_bytes.or(self.memory_bytes);
+        self.memory_swap_bytes = update.memory_swap_bytes.or(self.memory_swap_bytes);
+        self.pids_limit = update.pids_limit.or(self.pids_limit);
+        self.io_read_bps = update.io_read_bps.or(self.io_read_bps);
+        self.io_write_bps = update.io_write_bps.or(self.io_write_bps);
+        self.io_read_iops = update.io_read_iops.or(self.io_read_iops);
+        self.io_write_iops = update.io_write_iops.or(self.io_write_iops);
+        self.io_weight = update.io_weight.or(self.io_weight);
+    }
+}
+
+/// Outcome of a live resource update.
+///
+/// Entries are [`ResourceLimits`] field names. Only limits set in the update
+/// are listed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUpdateReport {
+    /// Limits now in effect
+    pub applied: Vec<String>,
+    /// Limits the runtime could not change
+    pub unsupported: Vec<String>,
+}
+
+impl ResourceUpdateReport {
+    /// Report the limits set in `limits` as applied, except those named in
+    /// `unsupported`.
+    #[must_use]
+    pub fn new(limits: &ResourceLimits, unsupported: &[&str]) -> Self {
+        let (unsupported, applied): (Vec<_>, Vec<_>) = limits
+            .set_fields()
+            .into_iter()
+            .partition(|field| unsupported.contains(field));
+        Self {
+            applied: applied.into_iter().map(String::from).collect(),
+            unsupported: unsupported.into_iter().map(String::from).collect(),
         }
     }
 }
 
 /// Restart policy.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum RestartPolicy {
     /// Never restart
+    #[default]
     No,
     /// Restart on failure
     OnFailure,
@@ -420,6 +917,9 @@ pub struct ContainerStats {
     pub block_io: BlockIoStats,
     /// PIDs count
     pub pids: u64,
+    /// Resource limit events reported by the kernel
+    #[serde(default)]
+    pub events: ResourceEvents,
 }
 
 /// CPU statistics.
@@ -480,6 +980,22 @@ pub struct BlockIoStats {
     pub write_ops: u64,
 }
 
+/// Counters of resource limit events, from the cgroup's `memory.events`
+/// and `pids.events`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceEvents {
+    /// Times memory usage went over the high boundary and was throttled
+    pub memory_high: u64,
+    /// Times memory usage hit the memory limit
+    pub memory_max: u64,
+    /// Times the cgroup ran out of memory
+    pub oom: u64,
+    /// Processes killed by the OOM killer
+    pub oom_kill: u64,
+    /// Forks that failed because of the PIDs limit
+    pub pids_max: u64,
+}
+
 /// Exec specification.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecSpec {
@@ -503,6 +1019,15 @@ pub struct ExecSpec {
     pub privileged: bool,
 }
 
+/// Terminal dimensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TtySize {
+    /// Number of rows
+    pub rows: u16,
+    /// Number of columns
+    pub cols: u16,
+}
+
 /// Exec result.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecResult {
@@ -514,6 +1039,73 @@ pub struct ExecResult {
     pub stderr: String,
 }
 
+/// How a container's init process exited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContainerExit {
+    /// Exit code (128 + signal number if killed by a signal)
+    pub exit_code: i32,
+    /// Signal that killed the process, if any
+    pub signal: Option<i32>,
+    /// Whether the kernel OOM killer fired in the container's cgroup
+    pub oom_killed: bool,
+    /// When the process exited
+    pub finished_at: DateTime<Utc>,
+}
+
+/// What HyperBox recorded about a container, beyond the runtime's own state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerMetadata {
+    /// Container ID
+    pub id: ContainerId,
+    /// Specification the container was created from
+    pub spec: ContainerSpec,
+    /// OCI bundle directory, for runtimes that use one
+    pub bundle: Option<PathBuf>,
+    /// When the container was created
+    pub created_at: DateTime<Utc>,
+    /// When the container last started
+    pub started_at: Option<DateTime<Utc>>,
+    /// When the container last exited
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Exit code of the last run
+    pub exit_code: Option<i32>,
+}
+
+impl ContainerMetadata {
+    /// Metadata of a container created now from `spec`.
+    #[must_use]
+    pub fn new(id: ContainerId, spec: ContainerSpec) -> Self {
+        Self {
+            id,
+            spec,
+            bundle: None,
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            exit_code: None,
+        }
+    }
+
+    /// Set the bundle directory.
+    #[must_use]
+    pub fn with_bundle(mut self, bundle: impl Into<PathBuf>) -> Self {
+        self.bundle = Some(bundle.into());
+        self
+    }
+
+    /// Container name, if one was given.
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        self.spec.name.as_deref()
+    }
+
+    /// Container labels.
+    #[must_use]
+    pub const fn labels(&self) -> &HashMap<String, String> {
+        &self.spec.labels
+    }
+}
+
 /// Log options.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogOptions {
@@ -547,6 +1139,159 @@ impl Default for LogOptions {
     }
 }
 
+/// Kind of change to a path in a container's filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    /// Path was created
+    Added,
+    /// Path existed in the image and was changed
+    Modified,
+    /// Path existed in the image and was removed
+    Deleted,
+}
+
+impl std::fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The letters `docker diff` prints.
+        let letter = match self {
+            Self::Added => "A",
+            Self::Modified => "C",
+            Self::Deleted => "D",
+        };
+        write!(f, "{letter}")
+    }
+}
+
+/// A path a container changed relative to its image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilesystemChange {
+    /// Absolute path inside the container
+    pub path: String,
+    /// What happened to the path
+    pub kind: ChangeKind,
+}
+
+/// Options for committing a container to an image.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommitOptions {
+    /// Reference to tag the new image with; untagged if `None`
+    pub reference: Option<ImageRef>,
+    /// Image author
+    pub author: Option<String>,
+    /// Commit message, recorded in the image history
+    pub comment: Option<String>,
+    /// Pause the container while its filesystem is captured
+    pub pause: bool,
+    /// Image configuration to apply on top of the base image's
+    #[serde(default)]
+    pub config: ContainerConfig,
+}
+
+/// Options for importing a root filesystem tarball as an image.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportOptions {
+    /// Reference to tag the new image with; untagged if `None`
+    pub reference: Option<ImageRef>,
+    /// Import message, recorded in the image history
+    pub comment: Option<String>,
+    /// Image configuration, such as `Cmd` and `Env`
+    #[serde(default)]
+    pub config: ContainerConfig,
+}
+
+/// Options for pulling an image.
+#[derive(Debug, Clone, Default)]
+pub struct PullOptions {
+    /// Platform to pull a multi-platform image for; the host's if `None`
+    pub platform: Option<Platform>,
+    /// Receives per-layer progress while the image downloads
+    pub progress: Option<tokio::sync::mpsc::UnboundedSender<PullProgress>>,
+}
+
+impl PullOptions {
+    /// Report progress of a layer, if anyone is listening.
+    pub fn report(&self, progress: PullProgress) {
+        if let Some(sender) = &self.progress {
+            let _ = sender.send(progress);
+        }
+    }
+}
+
+/// Progress of one layer of an image pull.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PullProgress {
+    /// Layer digest (or, for Docker, its short layer ID)
+    pub layer: String,
+    /// What is happening to the layer
+    pub status: PullStatus,
+    /// Bytes downloaded so far
+    pub current: u64,
+    /// Size of the layer in bytes, if known
+    pub total: u64,
+}
+
+/// Stage a layer is in while an image is pulled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PullStatus {
+    /// Queued behind other downloads
+    Waiting,
+    /// Already in the local cache
+    Cached,
+    /// Being downloaded
+    Downloading,
+    /// Being checked against its digest
+    Verifying,
+    /// Downloaded and verified
+    Complete,
+}
+
+/// Options for pushing an image.
+#[derive(Debug, Clone, Default)]
+pub struct PushOptions {
+    /// Receives per-blob progress while the image uploads
+    pub progress: Option<tokio::sync::mpsc::UnboundedSender<PushProgress>>,
+}
+
+impl PushOptions {
+    /// Report progress of a blob, if anyone is listening.
+    pub fn report(&self, progress: PushProgress) {
+        if let Some(sender) = &self.progress {
+            let _ = sender.send(progress);
+        }
+    }
+}
+
+/// Progress of one blob (a layer or the configuration) of an image push.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PushProgress {
+    /// Blob digest
+    pub layer: String,
+    /// What is happening to the blob
+    pub status: PushStatus,
+    /// Bytes uploaded so far
+    pub current: u64,
+    /// Size of the blob in bytes, if known
+    pub total: u64,
+}
+
+/// Stage a blob is in while an image is pushed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PushStatus {
+    /// Queued behind other uploads
+    Waiting,
+    /// Already in the repository
+    Exists,
+    /// Mounted from another repository of the registry
+    Mounted,
+    /// Being uploaded
+    Uploading,
+    /// Uploaded
+    Complete,
+}
+
 /// Checkpoint identifier.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct CheckpointId(String);
@@ -570,3 +1315,101 @@ impl std::fmt::Display for CheckpointId {
         write!(f, "{}", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_device_mapping() {
+        let device: DeviceMapping = "/dev/fuse".parse().unwrap();
+        assert_eq!(device.host_path, PathBuf::from("/dev/fuse"));
+        assert_eq!(device.container_path, PathBuf::from("/dev/fuse"));
+        assert_eq!(device.permissions, "rwm");
+
+        let device: DeviceMapping = "/dev/sda:/dev/xvda:r".parse().unwrap();
+        assert_eq!(device.container_path, PathBuf::from("/dev/xvda"));
+        assert_eq!(device.permissions, "r");
+
+        let device: DeviceMapping = "/dev/dri:rw".parse().unwrap();
+        assert_eq!(device.container_path, PathBuf::from("/dev/dri"));
+        assert_eq!(device.permissions, "rw");
+
+        assert!("dev/fuse".parse::<DeviceMapping>().is_err());
+        assert!("/dev/sda:/dev/xvda:rx".parse::<DeviceMapping>().is_err());
+    }
+
+    #[test]
+    fn test_parse_platform() {
+        let platform: Platform = "linux/arm64".parse().unwrap();
+        assert_eq!(platform, Platform::new("linux", "arm64", None));
+        assert_eq!(platform.to_string(), "linux/arm64");
+        let arm: Platform = "linux/arm/v7".parse().unwrap();
+        assert_eq!(arm.variant.as_deref(), Some("v7"));
+        assert!("linux".parse::<Platform>().is_err());
+        assert!("linux/".parse::<Platform>().is_err());
+        assert!("linux/arm/v7/x".parse::<Platform>().is_err());
+    }
+
+    #[test]
+    fn test_platform_matches_aliases_and_default_variants() {
+        let arm64: Platform = "linux/arm64".parse().unwrap();
+        assert!(arm64.matches(&"linux/aarch64".parse().unwrap()));
+        assert!(arm64.matches(&"linux/arm64/v8".parse().unwrap()));
+        assert!(!arm64.matches(&"linux/amd64".parse().unwrap()));
+
+        let arm: Platform = "linux/arm".parse().unwrap();
+        assert!(arm.matches(&"linux/arm/v7".parse().unwrap()));
+        assert!(!arm.matches(&"linux/arm/v6".parse().unwrap()));
+
+        assert!(Platform::new("linux", "x86_64", None).matches(&"linux/amd64".parse().unwrap()));
+        assert!(Platform::host().matches(&Platform::host()));
+    }
+
+    #[test]
+    fn test_parse_ulimit() {
+        let ulimit: Ulimit = "nofile=1024:65536".parse().unwrap();
+        assert_eq!((ulimit.name.as_str(), ulimit.soft, ulimit.hard), ("nofile", 1024, 65536));
+
+        let ulimit: Ulimit = "memlock=-1".parse().unwrap();
+        assert_eq!((ulimit.soft, ulimit.hard), (Ulimit::UNLIMITED, Ulimit::UNLIMITED));
+
+        assert!("nofile".parse::<Ulimit>().is_err());
+        assert!("nofile=abc".parse::<Ulimit>().is_err());
+        assert!("nofile=2048:1024".parse::<Ulimit>().is_err());
+    }
+
+    #[test]
+    fn test_resource_limits_merge_and_report() {
+        let mut limits = ResourceLimits {
+            memory_bytes: Some(512),
+            ..ResourceLimits::default()
+        };
+        let update = ResourceLimits {
+            cpu_millicores: Some(500),
+            cpuset_cpus: Some("0-1".to_string()),
+            memory_bytes: None,
+            pids_limit: None,
+            io_read_bps: Some(1024),
+            ..ResourceLimits::default()
+        };
+        limits.merge(&update);
+        assert_eq!(limits.memory_bytes, Some(512));
+        assert_eq!(limits.cpu_millicores, Some(500));
+        assert_eq!(limits.cpuset_cpus.as_deref(), Some("0-1"));
+        assert_eq!(limits.pids_limit, Some(4096));
+        assert!(limits.has_io_throttles());
+
+        let report = ResourceUpdateReport::new(&update, ResourceLimits::IO_THROTTLE_FIELDS);
+        assert_eq!(report.applied, vec!["cpu_millicores", "cpuset_cpus"]);
+        assert_eq!(report.unsupported, vec!["io_read_bps"]);
+    }
+
+    #[test]
+    fn test_cpu_weight_for_reservation() {
+        assert_eq!(ResourceLimits::cpu_weight_for_reservation(1000), 100);
+        assert_eq!(ResourceLimits::cpu_weight_for_reservation(250), 25);
+        assert_eq!(ResourceLimits::cpu_weight_for_reservation(0), 1);
+        assert_eq!(ResourceLimits::cpu_weight_for_reservation(u64::MAX), 10_000);
+    }
+}
@@ -0,0 +1,234 @@
+//! Runtime conformance tests.
+//!
+//! Runs the same lifecycle scenarios against every runtime registered by
+//! [`RuntimeRegistry::initialize`], so that all backends behave alike.
+//!
+//! Running containers needs root, network access for the image pull and at
+//! least one runtime binary, so these tests only run when
+//! `HYPERBOX_CONFORMANCE=1` is set. `HYPERBOX_CONFORMANCE_IMAGE` overrides
+//! the image (default `alpine:latest`), which must provide `/bin/sh`.
+
+use hyperbox_core::runtime::RuntimeRegistry;
+use hyperbox_core::{
+    ContainerId, ContainerRuntime, ContainerSpec, ContainerState, ExecSpec, ImageRef, LogOptions,
+    RuntimeConfig, RuntimeType,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::AsyncReadExt;
+
+const DEFAULT_IMAGE: &str = "alpine:latest";
+
+/// Timeout for any single scenario step.
+const STEP_TIMEOUT: Duration = Duration::from_secs(60);
+
+struct Backends {
+    // Keeps the runtime state directory alive for the duration of a test.
+    _root: TempDir,
+    image: ImageRef,
+    runtimes: Vec<(RuntimeType, Arc<dyn ContainerRuntime>)>,
+}
+
+/// Initialize a registry in a scratch directory and collect its runtimes.
+///
+/// Returns `None` when conformance tests are not enabled.
+async fn backends() -> Option<Backends> {
+    if std::env::var("HYPERBOX_CONFORMANCE").map_or(true, |v| v != "1") {
+        eprintln!("skipping: set HYPERBOX_CONFORMANCE=1 to run runtime conformance tests");
+        return None;
+    }
+
+    let root = TempDir::new().unwrap();
+    let config = RuntimeConfig {
+        root_dir: root.path().to_path_buf(),
+        ..RuntimeConfig::default()
+    };
+    let registry = RuntimeRegistry::new(config.runtime_type);
+    registry
+        .initialize(&config)
+        .await
+        .expect("no container runtime available");
+
+    let image = ImageRef::parse(
+        &std::env::var("HYPERBOX_CONFORMANCE_IMAGE").unwrap_or_else(|_| DEFAULT_IMAGE.to_string()),
+    );
+    let runtimes = registry
+        .available()
+        .into_iter()
+        // The scenarios need a shell, which WebAssembly images do not have.
+        .filter(|runtime_type| *runtime_type != RuntimeType::Wasm)
+        .filter_map(|runtime_type| registry.get(runtime_type).map(|rt| (runtime_type, rt)))
+        .collect();
+
+    Some(Backends {
+        _root: root,
+        image,
+        runtimes,
+    })
+}
+
+fn shell(image: &ImageRef, script: &str) -> ContainerSpec {
+    ContainerSpec {
+        image: image.clone(),
+        command: vec!["/bin/sh".to_string(), "-c".to_string(), script.to_string()],
+        ..ContainerSpec::default()
+    }
+}
+
+async fn step<T>(
+    runtime_type: RuntimeType,
+    what: &str,
+    fut: impl std::future::Future<Output = hyperbox_core::Result<T>>,
+) -> T {
+    tokio::time::timeout(STEP_TIMEOUT, fut)
+        .await
+        .unwrap_or_else(|_| panic!("{runtime_type}: {what} timed out"))
+        .unwrap_or_else(|e| panic!("{runtime_type}: {what} failed: {e}"))
+}
+
+async fn run(
+    runtime_type: RuntimeType,
+    runtime: &dyn ContainerRuntime,
+    spec: ContainerSpec,
+) -> ContainerId {
+    step(runtime_type, "pull", runtime.pull_image(&spec.image)).await;
+    let id = step(runtime_type, "create", runtime.create(spec)).await;
+    assert_eq!(
+        step(runtime_type, "state", runtime.state(&id)).await,
+        ContainerState::Created,
+        "{runtime_type}: state after create"
+    );
+    step(runtime_type, "start", runtime.start(&id)).await;
+    id
+}
+
+async fn read_logs(
+    runtime_type: RuntimeType,
+    runtime: &dyn ContainerRuntime,
+    id: &ContainerId,
+) -> String {
+    let opts = LogOptions {
+        stdout: true,
+        stderr: true,
+        follow: false,
+        tail: None,
+        since: None,
+        until: None,
+        timestamps: false,
+    };
+    let mut reader = step(runtime_type, "logs", runtime.logs(id, opts)).await;
+    let mut logs = String::new();
+    reader.read_to_string(&mut logs).await.unwrap();
+    logs
+}
+
+#[tokio::test]
+async fn conformance_exit_code_and_logs() {
+    let Some(backends) = backends().await else {
+        return;
+    };
+
+    for (runtime_type, runtime) in &backends.runtimes {
+        let runtime_type = *runtime_type;
+        let spec = shell(&backends.image, "echo to-stdout; echo to-stderr >&2; exit 3");
+        let id = run(runtime_type, runtime.as_ref(), spec).await;
+
+        let code = step(runtime_type, "wait", runtime.wait(&id)).await;
+        assert_eq!(code, 3, "{runtime_type}: exit code");
+
+        let exit = step(runtime_type, "exit_status", runtime.exit_status(&id))
+            .await
+            .unwrap_or_else(|| panic!("{runtime_type}: exit status not recorded"));
+        assert_eq!(exit.exit_code, 3, "{runtime_type}: recorded exit code");
+        assert!(!exit.oom_killed, "{runtime_type}: unexpected OOM kill");
+
+        let logs = read_logs(runtime_type, runtime.as_ref(), &id).await;
+        assert!(logs.contains("to-stdout"), "{runtime_type}: stdout missing from logs: {logs:?}");
+        assert!(logs.contains("to-stderr"), "{runtime_type}: stderr missing from logs: {logs:?}");
+
+        step(runtime_type, "remove", runtime.remove(&id)).await;
+    }
+}
+
+#[tokio::test]
+async fn conformance_exec_top_and_kill() {
+    let Some(backends) = backends().await else {
+        return;
+    };
+
+    for (runtime_type, runtime) in &backends.runtimes {
+        let runtime_type = *runtime_type;
+        let id = run(runtime_type, runtime.as_ref(), shell(&backends.image, "sleep 300")).await;
+        assert_eq!(
+            step(runtime_type, "state", runtime.state(&id)).await,
+            ContainerState::Running,
+            "{runtime_type}: state after start"
+        );
+
+        let exec = ExecSpec {
+            command: vec![
+                "/bin/sh".to_string(),
+                "-c".to_string(),
+                "echo exec-ok; exit 5".to_string(),
+            ],
+            env: HashMap::new(),
+            working_dir: None,
+            user: None,
+            tty: false,
+            attach_stdin: false,
+            attach_stdout: true,
+            attach_stderr: true,
+            privileged: false,
+        };
+        let result = step(runtime_type, "exec", runtime.exec(&id, exec)).await;
+        assert_eq!(result.exit_code, 5, "{runtime_type}: exec exit code");
+        assert!(result.stdout.contains("exec-ok"), "{runtime_type}: exec output");
+
+        let processes = step(runtime_type, "top", runtime.top(&id)).await;
+        assert!(
+            processes.iter().any(|p| p.command.contains("sleep")),
+            "{runtime_type}: init process missing from top: {processes:?}"
+        );
+
+        step(runtime_type, "kill", runtime.kill(&id, "SIGKILL")).await;
+        let code = step(runtime_type, "wait", runtime.wait(&id)).await;
+        assert_eq!(code, 137, "{runtime_type}: exit code after SIGKILL");
+
+        step(runtime_type, "remove", runtime.remove(&id)).await;
+        assert!(
+            runtime.state(&id).await.is_err(),
+            "{runtime_type}: container still exists after remove"
+        );
+    }
+}
+
+#[tokio::test]
+async fn conformance_stop() {
+    let Some(backends) = backends().await else {
+        return;
+    };
+
+    for (runtime_type, runtime) in &backends.runtimes {
+        let runtime_type = *runtime_type;
+        // `exec` makes sleep PID 1, which ignores SIGTERM, so stop must escalate.
+        let id =
+            run(runtime_type, runtime.as_ref(), shell(&backends.image, "exec sleep 300")).await;
+
+        step(runtime_type, "stop", runtime.stop(&id, Duration::from_secs(2))).await;
+        let state = step(runtime_type, "state", runtime.state(&id)).await;
+        assert!(
+            matches!(state, ContainerState::Stopped | ContainerState::Exited),
+            "{runtime_type}: state after stop is {state}"
+        );
+
+        let listed = step(runtime_type, "list", runtime.list()).await;
+        assert!(
+            listed.iter().any(|(listed_id, _)| *listed_id == id),
+            "{runtime_type}: stopped container missing from list"
+        );
+
+        step(runtime_type, "remove", runtime.remove(&id)).await;
+    }
+}
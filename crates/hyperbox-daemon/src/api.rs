@@ -1,5 +1,6 @@
 //! HTTP/REST API server.
 
+use crate::lifecycle;
 use crate::state::{ContainerState, DaemonState, EventType, ImageState};
 use axum::{
     extract::{Path, Query, State},
@@ -8,12 +9,15 @@ use axum::{
         sse::{Event, KeepAlive, Sse},
         IntoResponse,
     },
-    routing::{delete, get, post},
+    routing::{delete, get, patch, post, put},
     Json, Router,
 };
 use futures::stream::StreamExt;
+use hyperbox_core::runtime::{BundleManager, ContainerFilter};
+use hyperbox_core::storage::{Credentials, ImageRegistry};
+use hyperbox_core::types::{Platform, PullOptions, PushOptions, ResourceLimits, RestartPolicy};
 use serde::{Deserialize, Serialize};
-use std::{convert::Infallible, path::PathBuf, time::Duration};
+use std::{collections::HashMap, convert::Infallible, path::PathBuf, time::Duration};
 use tokio::io::AsyncBufReadExt;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
@@ -54,16 +58,30 @@ pub fn create_router(state: DaemonState) -> Router {
         .route("/api/v1/containers/:id/start", post(start_container))
         .route("/api/v1/containers/:id/stop", post(stop_container))
         .route("/api/v1/containers/:id/restart", post(restart_container))
+        .route("/api/v1/containers/:id/pause", post(pause_container))
+        .route("/api/v1/containers/:id/unpause", post(unpause_container))
+        .route("/api/v1/containers/:id/resources", patch(update_container_resources))
         .route("/api/v1/containers/:id/checkpoint", post(checkpoint_container))
         .route("/api/v1/containers/:id/restore", post(restore_container))
         .route("/api/v1/containers/:id/logs", get(container_logs))
         .route("/api/v1/containers/:id/logs/stream", get(container_logs_stream))
         .route("/api/v1/containers/:id/stats", get(container_stats))
+        .route("/api/v1/containers/:id/top", get(container_top))
+        .route("/api/v1/containers/:id/archive", get(container_archive))
+        .route("/api/v1/containers/:id/archive", put(container_extract))
+        .route("/api/v1/containers/:id/changes", get(container_changes))
+        .route("/api/v1/containers/:id/commit", post(commit_container))
+        .route("/api/v1/containers/:id/export", get(export_container))
         // Images
         .route("/api/v1/images", get(list_images))
         .route("/api/v1/images/pull", post(pull_image))
+        .route("/api/v1/images/push", post(push_image))
+        .route("/api/v1/images/import", post(import_image))
         .route("/api/v1/images/:id", get(get_image))
         .route("/api/v1/images/:id", delete(remove_image))
+        // Registries
+        .route("/api/v1/registries/login", post(registry_login))
+        .route("/api/v1/registries/logout", post(registry_logout))
         // Projects
         .route("/api/v1/projects", get(list_projects))
         .route("/api/v1/projects", post(open_project))
@@ -113,6 +131,22 @@ struct CreateContainerRequest {
     env: Option<Vec<String>>,
     ports: Option<Vec<PortMappingRequest>>,
     volumes: Option<Vec<String>>,
+    labels: Option<HashMap<String, String>>,
+    /// Runtime to create the container with (crun, youki, runc, wasm, docker)
+    runtime: Option<String>,
+    restart_policy: Option<RestartPolicy>,
+    cap_add: Option<Vec<String>>,
+    cap_drop: Option<Vec<String>>,
+    /// Host devices as `host[:container][:permissions]`
+    devices: Option<Vec<String>>,
+    /// Resource limits as `name=soft[:hard]`
+    ulimits: Option<Vec<String>>,
+    sysctls: Option<HashMap<String, String>>,
+    /// Size of `/dev/shm` in bytes
+    shm_size: Option<u64>,
+    group_add: Option<Vec<String>>,
+    stop_signal: Option<String>,
+    init: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -128,6 +162,26 @@ struct PullImageRequest {
     platform: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct PushImageRequest {
+    image: String,
+}
+
+#[derive(Deserialize)]
+struct LoginRequest {
+    /// Registry server, Docker Hub when empty
+    #[serde(default)]
+    server: String,
+    username: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct LogoutRequest {
+    #[serde(default)]
+    server: String,
+}
+
 #[derive(Deserialize)]
 struct OpenProjectRequest {
     path: String,
@@ -138,6 +192,8 @@ struct OpenProjectRequest {
 struct ListQuery {
     all: Option<bool>,
     project: Option<String>,
+    /// Docker-style filters as JSON, e.g. `{"label":["env=prod"]}`
+    filters: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -220,7 +276,23 @@ async fn events(State(state): State<DaemonState>) -> impl IntoResponse {
 async fn list_containers(
     State(state): State<DaemonState>,
     Query(query): Query<ListQuery>,
-) -> Json<ApiResponse<Vec<ContainerState>>> {
+) -> impl IntoResponse {
+    let filter = match parse_filters(query.filters.as_deref()) {
+        Ok(filter) => filter,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Invalid filters: {}", e)),
+                }),
+            );
+        }
+    };
+    // Like `docker ps`, a status filter also shows stopped containers.
+    let all = query.all.unwrap_or(false) || !filter.statuses.is_empty();
+
     let containers: Vec<_> = state
         .containers
         .iter()
@@ -232,12 +304,26 @@ async fn list_containers(
             }
         })
         .filter(|c| {
-            query.all.unwrap_or(false) || c.status == crate::state::ContainerStatus::Running
+            all || matches!(
+                c.status,
+                crate::state::ContainerStatus::Running | crate::state::ContainerStatus::Paused
+            )
         })
+        .filter(|c| filter.matches(&c.filter_target()))
         .map(|c| c.clone())
         .collect();
 
-    Json(ApiResponse::success(containers))
+    (StatusCode::OK, Json(ApiResponse::success(containers)))
+}
+
+/// Parse the JSON `filters` query parameter of a list request.
+fn parse_filters(filters: Option<&str>) -> Result<ContainerFilter, String> {
+    let Some(filters) = filters else {
+        return Ok(ContainerFilter::default());
+    };
+    let filters: HashMap<String, Vec<String>> =
+        serde_json::from_str(filters).map_err(|e| e.to_string())?;
+    ContainerFilter::from_map(&filters).map_err(|e| e.to_string())
 }
 
 async fn create_container(
@@ -271,16 +357,71 @@ async fn create_container(
         }
     }
 
+    if let Some(ref labels) = req.labels {
+        for (key, value) in labels {
+            spec = spec.label(key, value);
+        }
+    }
+
+    if let Some(policy) = req.restart_policy {
+        spec = spec.restart_policy(policy);
+    }
+
+    for cap in req.cap_add.iter().flatten() {
+        spec = spec.cap_add(cap);
+    }
+    for cap in req.cap_drop.iter().flatten() {
+        spec = spec.cap_drop(cap);
+    }
+    for device in req.devices.iter().flatten() {
+        match device.parse() {
+            Ok(device) => spec = spec.device(device),
+            Err(e) => return create_error(StatusCode::BAD_REQUEST, &e),
+        }
+    }
+    for ulimit in req.ulimits.iter().flatten() {
+        match ulimit.parse() {
+            Ok(ulimit) => spec = spec.ulimit(ulimit),
+            Err(e) => return create_error(StatusCode::BAD_REQUEST, &e),
+        }
+    }
+    for (key, value) in req.sysctls.iter().flatten() {
+        spec = spec.sysctl(key, value);
+    }
+    if let Some(bytes) = req.shm_size {
+        spec = spec.shm_size(bytes);
+    }
+    for group in req.group_add.iter().flatten() {
+        spec = spec.group_add(group);
+    }
+    if let Some(ref signal) = req.stop_signal {
+        spec = spec.stop_signal(signal);
+    }
+    if let Some(init) = req.init {
+        spec = spec.init(init);
+    }
+
+    if let Some(ref runtime) = req.runtime {
+        match runtime.parse() {
+            Ok(runtime) => spec = spec.runtime(runtime),
+            Err(e) => return create_error(StatusCode::BAD_REQUEST, &e),
+        }
+    }
+
     let container_spec = spec.build();
+    let (runtime_type, runtime) = match state.runtimes.select(&container_spec) {
+        Ok(selected) => selected,
+        Err(e) => return create_error(StatusCode::BAD_REQUEST, &e),
+    };
 
     state.emit(
         EventType::ContainerCreate,
         "",
-        serde_json::json!({"image": req.image, "status": "creating"}),
+        serde_json::json!({"image": req.image, "runtime": runtime_type, "status": "creating"}),
     );
 
-    // Create container via runtime
-    match state.runtime.create(container_spec).await {
+    // Create container via the selected runtime
+    match runtime.create(container_spec).await {
         Ok(container_id) => {
             let id_str = container_id.to_string();
 
@@ -291,19 +432,28 @@ async fn create_container(
                 image: req.image.clone(),
                 status: crate::state::ContainerStatus::Created,
                 project_id: None,
+                labels: req.labels.clone().unwrap_or_default(),
+                runtime: Some(runtime_type),
                 ports: vec![],
                 created_at: chrono::Utc::now(),
                 started_at: None,
                 pid: None,
                 has_checkpoint: false,
                 is_prewarmed: false,
+                restart_policy: req.restart_policy.unwrap_or_default(),
+                restart_count: 0,
+                exit_code: None,
+                manually_stopped: false,
+                oom_killed: false,
             };
             state.containers.insert(id_str.clone(), container_state);
+            lifecycle::watch_events(&state, &id_str);
+            lifecycle::persist(&state);
 
             state.emit(
                 EventType::ContainerCreate,
                 &id_str,
-                serde_json::json!({"image": req.image, "status": "created"}),
+                serde_json::json!({"image": req.image, "runtime": runtime_type, "status": "created"}),
             );
 
             (
@@ -320,20 +470,38 @@ async fn create_container(
                 "",
                 serde_json::json!({"status": "error", "error": e.to_string()}),
             );
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiResponse {
-                    success: false,
-                    data: Some(
-                        serde_json::json!({"error": format!("Failed to create container: {}", e)}),
-                    ),
-                    error: Some(format!("Failed to create container: {}", e)),
-                }),
-            )
+            create_error(StatusCode::INTERNAL_SERVER_ERROR, &e)
         }
     }
 }
 
+fn create_error(
+    status: StatusCode,
+    e: &hyperbox_core::CoreError,
+) -> (StatusCode, Json<ApiResponse<serde_json::Value>>) {
+    (
+        status,
+        Json(ApiResponse {
+            success: false,
+            data: Some(serde_json::json!({"error": format!("Failed to create container: {}", e)})),
+            error: Some(format!("Failed to create container: {}", e)),
+        }),
+    )
+}
+
+/// Status code for a runtime error on a container or image.
+fn error_status(e: &hyperbox_core::CoreError) -> StatusCode {
+    use hyperbox_core::CoreError;
+
+    match e {
+        CoreError::ContainerNotFound(_) | CoreError::ImageNotFound(_) => StatusCode::NOT_FOUND,
+        CoreError::Io(io) if io.kind() == std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        CoreError::InvalidSpec { .. } => StatusCode::BAD_REQUEST,
+        CoreError::ContainerNotRunning(_) => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
 async fn get_container(
     State(state): State<DaemonState>,
     Path(id): Path<String>,
@@ -348,12 +516,19 @@ async fn get_container(
                 image: String::new(),
                 status: crate::state::ContainerStatus::Dead,
                 project_id: None,
+                labels: HashMap::new(),
+                runtime: None,
                 ports: vec![],
                 created_at: chrono::Utc::now(),
                 started_at: None,
                 pid: None,
                 has_checkpoint: false,
                 is_prewarmed: false,
+                restart_policy: RestartPolicy::No,
+                restart_count: 0,
+                exit_code: None,
+                manually_stopped: false,
+                oom_killed: false,
             })),
         ),
     }
@@ -367,13 +542,18 @@ async fn start_container(
 
     state.emit(EventType::ContainerStart, &id, serde_json::json!({"status": "starting"}));
 
-    match state.runtime.start(&container_id).await {
+    match state.runtime_for(&id).start(&container_id).await {
         Ok(()) => {
             // Update container status in daemon state
             if let Some(mut container) = state.containers.get_mut(&id) {
                 container.status = crate::state::ContainerStatus::Running;
                 container.started_at = Some(chrono::Utc::now());
+                container.manually_stopped = false;
+                container.restart_count = 0;
             }
+            lifecycle::watch_events(&state, &id);
+            lifecycle::supervise(&state, &id);
+            lifecycle::persist(&state);
 
             state.emit(EventType::ContainerStart, &id, serde_json::json!({"status": "running"}));
             Json(ApiResponse::success(serde_json::json!({
@@ -407,12 +587,16 @@ async fn stop_container(
 
     state.emit(EventType::ContainerStop, &id, serde_json::json!({"status": "stopping"}));
 
-    match state.runtime.stop(&container_id, timeout).await {
+    // Tell the restart supervisor this exit is not a crash
+    let was_stopped = lifecycle::set_manually_stopped(&state, &id, true);
+
+    match state.runtime_for(&id).stop(&container_id, timeout).await {
         Ok(()) => {
             // Update container status in daemon state
             if let Some(mut container) = state.containers.get_mut(&id) {
                 container.status = crate::state::ContainerStatus::Stopped;
             }
+            lifecycle::persist(&state);
 
             state.emit(EventType::ContainerStop, &id, serde_json::json!({"status": "stopped"}));
             Json(ApiResponse::success(serde_json::json!({
@@ -421,6 +605,8 @@ async fn stop_container(
             })))
         }
         Err(e) => {
+            // Still running, so its restart policy applies again
+            lifecycle::set_manually_stopped(&state, &id, was_stopped);
             state.emit(
                 EventType::ContainerStop,
                 &id,
@@ -444,8 +630,10 @@ async fn restart_container(
     let container_id = hyperbox_core::types::ContainerId::from(id.clone());
     let timeout = std::time::Duration::from_secs(10);
 
-    // Stop container
-    if let Err(e) = state.runtime.stop(&container_id, timeout).await {
+    // Stop container, without the supervisor restarting it meanwhile
+    let was_stopped = lifecycle::set_manually_stopped(&state, &id, true);
+    if let Err(e) = state.runtime_for(&id).stop(&container_id, timeout).await {
+        lifecycle::set_manually_stopped(&state, &id, was_stopped);
         return Json(ApiResponse {
             success: false,
             data: Some(
@@ -456,13 +644,18 @@ async fn restart_container(
     }
 
     // Start container
-    match state.runtime.start(&container_id).await {
+    match state.runtime_for(&id).start(&container_id).await {
         Ok(()) => {
             // Update container status in daemon state
             if let Some(mut container) = state.containers.get_mut(&id) {
                 container.status = crate::state::ContainerStatus::Running;
                 container.started_at = Some(chrono::Utc::now());
+                container.manually_stopped = false;
+                container.restart_count = 0;
             }
+            lifecycle::watch_events(&state, &id);
+            lifecycle::supervise(&state, &id);
+            lifecycle::persist(&state);
 
             Json(ApiResponse::success(serde_json::json!({
                 "id": id,
@@ -479,6 +672,99 @@ async fn restart_container(
     }
 }
 
+/// Freeze every process in a container.
+async fn pause_container(
+    State(state): State<DaemonState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let container_id = hyperbox_core::types::ContainerId::from_string(&id);
+
+    match state.runtime_for(&id).pause(&container_id).await {
+        Ok(()) => {
+            if let Some(mut container) = state.containers.get_mut(&id) {
+                container.status = crate::state::ContainerStatus::Paused;
+            }
+            lifecycle::persist(&state);
+
+            state.emit(EventType::ContainerPause, &id, serde_json::json!({"status": "paused"}));
+            (
+                StatusCode::OK,
+                Json(ApiResponse::success(serde_json::json!({"id": id, "status": "paused"}))),
+            )
+        }
+        Err(e) => (
+            error_status(&e),
+            Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("Failed to pause container: {}", e)),
+            }),
+        ),
+    }
+}
+
+/// Thaw a paused container.
+async fn unpause_container(
+    State(state): State<DaemonState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let container_id = hyperbox_core::types::ContainerId::from_string(&id);
+
+    match state.runtime_for(&id).resume(&container_id).await {
+        Ok(()) => {
+            if let Some(mut container) = state.containers.get_mut(&id) {
+                container.status = crate::state::ContainerStatus::Running;
+            }
+            lifecycle::persist(&state);
+
+            state.emit(EventType::ContainerUnpause, &id, serde_json::json!({"status": "running"}));
+            (
+                StatusCode::OK,
+                Json(ApiResponse::success(serde_json::json!({"id": id, "status": "running"}))),
+            )
+        }
+        Err(e) => (
+            error_status(&e),
+            Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("Failed to unpause container: {}", e)),
+            }),
+        ),
+    }
+}
+
+/// Change the resource limits of a container.
+///
+/// Limits missing from the body keep their current value. The response
+/// lists which limits the runtime applied and which it cannot change.
+async fn update_container_resources(
+    State(state): State<DaemonState>,
+    Path(id): Path<String>,
+    Json(limits): Json<ResourceLimits>,
+) -> impl IntoResponse {
+    let container_id = hyperbox_core::types::ContainerId::from_string(&id);
+
+    match state.runtime_for(&id).update(&container_id, limits).await {
+        Ok(report) => {
+            state.emit(
+                EventType::ContainerUpdate,
+                &id,
+                serde_json::json!({"applied": report.applied, "unsupported": report.unsupported}),
+            );
+            (StatusCode::OK, Json(ApiResponse::success(report)))
+        }
+        Err(e) => (
+            error_status(&e),
+            Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("Failed to update container: {}", e)),
+            }),
+        ),
+    }
+}
+
 async fn remove_container(
     State(state): State<DaemonState>,
     Path(id): Path<String>,
@@ -487,10 +773,14 @@ async fn remove_container(
 
     state.emit(EventType::ContainerRemove, &id, serde_json::json!({"status": "removing"}));
 
-    match state.runtime.remove(&container_id).await {
+    lifecycle::unsupervise(&state, &id);
+    lifecycle::unwatch_events(&state, &id);
+
+    match state.runtime_for(&id).remove(&container_id).await {
         Ok(()) => {
             // Remove from daemon state
             state.containers.remove(&id);
+            lifecycle::persist(&state);
 
             state.emit(EventType::ContainerRemove, &id, serde_json::json!({"status": "removed"}));
             Json(ApiResponse::success(serde_json::json!({
@@ -544,6 +834,12 @@ struct LogsQuery {
     stdout: Option<bool>,
     #[serde(default)]
     stderr: Option<bool>,
+    /// Only show logs since this RFC 3339 timestamp
+    #[serde(default)]
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only show logs until this RFC 3339 timestamp
+    #[serde(default)]
+    until: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 async fn container_logs(
@@ -562,11 +858,11 @@ async fn container_logs(
         timestamps: query.timestamps,
         stdout: query.stdout.unwrap_or(true),
         stderr: query.stderr.unwrap_or(true),
-        since: None,
-        until: None,
+        since: query.since,
+        until: query.until,
     };
 
-    match state.runtime.logs(&container_id, log_opts).await {
+    match state.runtime_for(&id).logs(&container_id, log_opts).await {
         Ok(reader) => {
             let mut buffer = Vec::new();
             // Read up to 1MB of logs
@@ -623,13 +919,13 @@ async fn container_logs_stream(
         timestamps: query.timestamps,
         stdout: query.stdout.unwrap_or(true),
         stderr: query.stderr.unwrap_or(true),
-        since: None,
-        until: None,
+        since: query.since,
+        until: query.until,
     };
 
     // Create the SSE stream
     let stream = async_stream::stream! {
-        match state.runtime.logs(&container_id, log_opts).await {
+        match state.runtime_for(&id).logs(&container_id, log_opts).await {
             Ok(reader) => {
                 let buf_reader = tokio::io::BufReader::new(reader);
                 let mut lines = buf_reader.lines();
@@ -665,6 +961,201 @@ async fn container_stats(
     })))
 }
 
+/// Query parameters for container processes
+#[derive(Debug, Deserialize)]
+struct TopQuery {
+    /// `ps`-style column selection, e.g. `pid,comm` or `-o pid,comm`
+    #[serde(default)]
+    ps_args: Option<String>,
+}
+
+/// Container processes, as returned by `docker top`
+#[derive(Serialize)]
+struct TopResponse {
+    titles: Vec<String>,
+    processes: Vec<Vec<String>>,
+}
+
+async fn container_top(
+    State(state): State<DaemonState>,
+    Path(id): Path<String>,
+    Query(query): Query<TopQuery>,
+) -> impl IntoResponse {
+    let container_id = hyperbox_core::types::ContainerId::from_string(&id);
+
+    let table = state.runtime_for(&id).top(&container_id).await.and_then(|processes| {
+        hyperbox_core::runtime::ps_table(&processes, query.ps_args.as_deref())
+    });
+
+    match table {
+        Ok((titles, processes)) => Json(ApiResponse::success(TopResponse { titles, processes })),
+        Err(e) => Json(ApiResponse::<TopResponse> {
+            success: false,
+            data: None,
+            error: Some(format!("Failed to list processes: {}", e)),
+        }),
+    }
+}
+
+/// Query parameters for copying files in and out of a container
+#[derive(Debug, Deserialize)]
+struct ArchiveQuery {
+    /// Absolute path inside the container
+    path: String,
+}
+
+/// Download a tar archive of a path in a container.
+async fn container_archive(
+    State(state): State<DaemonState>,
+    Path(id): Path<String>,
+    Query(query): Query<ArchiveQuery>,
+) -> axum::response::Response {
+    let container_id = hyperbox_core::types::ContainerId::from_string(&id);
+
+    match state
+        .runtime_for(&id)
+        .archive_path(&container_id, std::path::Path::new(&query.path))
+        .await
+    {
+        Ok(reader) => (
+            [(axum::http::header::CONTENT_TYPE, "application/x-tar")],
+            axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(reader)),
+        )
+            .into_response(),
+        Err(e) => (
+            error_status(&e),
+            Json(ApiResponse::<()>::error(&format!("Failed to copy from container: {}", e))),
+        )
+            .into_response(),
+    }
+}
+
+/// Extract a tar archive into a directory in a container.
+async fn container_extract(
+    State(state): State<DaemonState>,
+    Path(id): Path<String>,
+    Query(query): Query<ArchiveQuery>,
+    body: axum::body::Body,
+) -> impl IntoResponse {
+    let container_id = hyperbox_core::types::ContainerId::from_string(&id);
+    let stream = body
+        .into_data_stream()
+        .map(|chunk| chunk.map_err(std::io::Error::other));
+    let reader = Box::new(tokio_util::io::StreamReader::new(stream));
+
+    match state
+        .runtime_for(&id)
+        .extract_archive(&container_id, std::path::Path::new(&query.path), reader)
+        .await
+    {
+        Ok(()) => (StatusCode::OK, Json(ApiResponse::success(()))),
+        Err(e) => (
+            error_status(&e),
+            Json(ApiResponse::<()>::error(&format!("Failed to copy into container: {}", e))),
+        ),
+    }
+}
+
+/// List the paths a container changed relative to its image.
+async fn container_changes(
+    State(state): State<DaemonState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let container_id = hyperbox_core::types::ContainerId::from_string(&id);
+
+    match state.runtime_for(&id).diff(&container_id).await {
+        Ok(changes) => (StatusCode::OK, Json(ApiResponse::success(changes))),
+        Err(e) => (
+            error_status(&e),
+            Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("Failed to get changes: {}", e)),
+            }),
+        ),
+    }
+}
+
+/// Request body for committing a container
+#[derive(Debug, Default, Deserialize)]
+struct CommitRequest {
+    /// Reference to tag the new image with
+    #[serde(default)]
+    reference: Option<String>,
+    #[serde(default)]
+    author: Option<String>,
+    #[serde(default)]
+    comment: Option<String>,
+    /// Pause the container while committing (default true)
+    #[serde(default)]
+    pause: Option<bool>,
+    /// Image configuration changes, with Docker `Config` field names
+    #[serde(default)]
+    config: hyperbox_core::storage::ContainerConfig,
+}
+
+/// Snapshot a container's filesystem into a new image.
+async fn commit_container(
+    State(state): State<DaemonState>,
+    Path(id): Path<String>,
+    req: Option<Json<CommitRequest>>,
+) -> impl IntoResponse {
+    use hyperbox_core::types::{CommitOptions, ImageRef};
+
+    let Json(req) = req.unwrap_or_default();
+    let container_id = hyperbox_core::types::ContainerId::from_string(&id);
+    let options = CommitOptions {
+        reference: req.reference.as_deref().map(ImageRef::parse),
+        author: req.author,
+        comment: req.comment,
+        pause: req.pause.unwrap_or(true),
+        config: req.config,
+    };
+
+    match state.runtime_for(&id).commit(&container_id, options).await {
+        Ok(image_id) => {
+            state.emit(
+                EventType::ContainerCommit,
+                &id,
+                serde_json::json!({"image": image_id, "reference": req.reference}),
+            );
+            (StatusCode::OK, Json(ApiResponse::success(serde_json::json!({"id": image_id}))))
+        }
+        Err(e) => (
+            error_status(&e),
+            Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("Failed to commit container: {}", e)),
+            }),
+        ),
+    }
+}
+
+/// Stream a container's flattened root filesystem as a tar archive.
+async fn export_container(
+    State(state): State<DaemonState>,
+    Path(id): Path<String>,
+) -> axum::response::Response {
+    let container_id = hyperbox_core::types::ContainerId::from_string(&id);
+
+    match state.runtime_for(&id).export(&container_id).await {
+        Ok(reader) => {
+            state.emit(EventType::ContainerExport, &id, serde_json::json!({}));
+            (
+                [(axum::http::header::CONTENT_TYPE, "application/x-tar")],
+                axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(reader)),
+            )
+                .into_response()
+        }
+        Err(e) => (
+            error_status(&e),
+            Json(ApiResponse::<()>::error(&format!("Failed to export container: {}", e))),
+        )
+            .into_response(),
+    }
+}
+
 // === Image Handlers ===
 
 async fn list_images(State(state): State<DaemonState>) -> impl IntoResponse {
@@ -699,6 +1190,24 @@ async fn pull_image(
 ) -> impl IntoResponse {
     // Parse image reference
     let image_ref = hyperbox_core::types::ImageRef::parse(&req.image);
+    let platform = match req
+        .platform
+        .as_deref()
+        .map(str::parse::<Platform>)
+        .transpose()
+    {
+        Ok(platform) => platform,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(e.to_string()),
+                }),
+            );
+        }
+    };
 
     // Emit start event
     state.emit(
@@ -707,8 +1216,34 @@ async fn pull_image(
         serde_json::json!({"platform": req.platform, "status": "pulling"}),
     );
 
-    // Actually pull the image via the runtime
-    match state.runtime.pull_image(&image_ref).await {
+    // Pull via the runtime that will run the image, e.g. wasm for modules
+    let spec = hyperbox_core::types::ContainerSpec::builder().image(&req.image).build();
+    let runtime = state
+        .runtimes
+        .select(&spec)
+        .map_or_else(|_| state.runtime.clone(), |(_, runtime)| runtime);
+
+    // Surface layer progress as pull events while the image downloads
+    let (progress, mut updates) = tokio::sync::mpsc::unbounded_channel();
+    let forward = {
+        let state = state.clone();
+        let image = req.image.clone();
+        tokio::spawn(async move {
+            while let Some(update) = updates.recv().await {
+                let data = serde_json::to_value(&update).unwrap_or_default();
+                state.emit(EventType::ImagePull, &image, data);
+            }
+        })
+    };
+
+    let options = PullOptions {
+        platform,
+        progress: Some(progress),
+    };
+    let pulled = runtime.pull_image_with(&image_ref, options).await;
+    let _ = forward.await;
+
+    match pulled {
         Ok(()) => {
             // Update metrics
             {
@@ -719,10 +1254,13 @@ async fn pull_image(
             // Emit success event
             state.emit(EventType::ImagePull, &req.image, serde_json::json!({"status": "complete"}));
 
-            Json(ApiResponse::success(serde_json::json!({
-                "image": req.image,
-                "status": "pulled"
-            })))
+            (
+                StatusCode::OK,
+                Json(ApiResponse::success(serde_json::json!({
+                    "image": req.image,
+                    "status": "pulled"
+                }))),
+            )
         }
         Err(e) => {
             // Emit error event
@@ -732,12 +1270,162 @@ async fn pull_image(
                 serde_json::json!({"status": "error", "error": e.to_string()}),
             );
 
+            (
+                StatusCode::OK,
+                Json(ApiResponse {
+                    success: false,
+                    data: Some(
+                        serde_json::json!({"error": format!("Failed to pull image: {}", e)}),
+                    ),
+                    error: Some(format!("Failed to pull image: {}", e)),
+                }),
+            )
+        }
+    }
+}
+
+async fn push_image(
+    State(state): State<DaemonState>,
+    Json(req): Json<PushImageRequest>,
+) -> impl IntoResponse {
+    let image_ref = hyperbox_core::types::ImageRef::parse(&req.image);
+
+    state.emit(EventType::ImagePush, &req.image, serde_json::json!({"status": "pushing"}));
+
+    // Push from the runtime that stores the image
+    let spec = hyperbox_core::types::ContainerSpec::builder()
+        .image(&req.image)
+        .build();
+    let runtime = state
+        .runtimes
+        .select(&spec)
+        .map_or_else(|_| state.runtime.clone(), |(_, runtime)| runtime);
+
+    // Surface blob progress as push events while the image uploads
+    let (progress, mut updates) = tokio::sync::mpsc::unbounded_channel();
+    let forward = {
+        let state = state.clone();
+        let image = req.image.clone();
+        tokio::spawn(async move {
+            while let Some(update) = updates.recv().await {
+                let data = serde_json::to_value(&update).unwrap_or_default();
+                state.emit(EventType::ImagePush, &image, data);
+            }
+        })
+    };
+
+    let options = PushOptions {
+        progress: Some(progress),
+    };
+    let pushed = runtime.push_image(&image_ref, options).await;
+    let _ = forward.await;
+
+    match pushed {
+        Ok(digest) => {
+            state.emit(
+                EventType::ImagePush,
+                &req.image,
+                serde_json::json!({"status": "complete", "digest": digest}),
+            );
+
+            (
+                StatusCode::OK,
+                Json(ApiResponse::success(serde_json::json!({
+                    "image": req.image,
+                    "digest": digest,
+                    "status": "pushed"
+                }))),
+            )
+        }
+        Err(e) => {
+            state.emit(
+                EventType::ImagePush,
+                &req.image,
+                serde_json::json!({"status": "error", "error": e.to_string()}),
+            );
+
+            (
+                StatusCode::OK,
+                Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to push image: {}", e)),
+                }),
+            )
+        }
+    }
+}
+
+/// Query parameters for importing a root filesystem tarball
+#[derive(Debug, Deserialize)]
+struct ImportQuery {
+    /// Reference to tag the new image with
+    #[serde(default)]
+    reference: Option<String>,
+    #[serde(default)]
+    comment: Option<String>,
+    /// Dockerfile instructions to apply, one per line (e.g. `CMD ["sh"]`)
+    #[serde(default)]
+    changes: Option<String>,
+}
+
+/// Create a single-layer image from a root filesystem tarball.
+async fn import_image(
+    State(state): State<DaemonState>,
+    Query(query): Query<ImportQuery>,
+    body: axum::body::Body,
+) -> impl IntoResponse {
+    use hyperbox_core::storage::ContainerConfig;
+    use hyperbox_core::types::{ImageRef, ImportOptions};
+
+    let changes: Vec<&str> = query
+        .changes
+        .as_deref()
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let config = match ContainerConfig::from_changes(&changes) {
+        Ok(config) => config,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to import image: {}", e)),
+                }),
+            );
+        }
+    };
+    let options = ImportOptions {
+        reference: query.reference.as_deref().map(ImageRef::parse),
+        comment: query.comment,
+        config,
+    };
+
+    let stream = body
+        .into_data_stream()
+        .map(|chunk| chunk.map_err(std::io::Error::other));
+    let reader = Box::new(tokio_util::io::StreamReader::new(stream));
+
+    match state.runtime.import_image(reader, options).await {
+        Ok(image_id) => {
+            state.emit(
+                EventType::ImageImport,
+                &image_id,
+                serde_json::json!({"reference": query.reference}),
+            );
+            (StatusCode::OK, Json(ApiResponse::success(serde_json::json!({"id": image_id}))))
+        }
+        Err(e) => (
+            error_status(&e),
             Json(ApiResponse {
                 success: false,
-                data: Some(serde_json::json!({"error": format!("Failed to pull image: {}", e)})),
-                error: Some(format!("Failed to pull image: {}", e)),
-            })
-        }
+                data: None,
+                error: Some(format!("Failed to import image: {}", e)),
+            }),
+        ),
     }
 }
 
@@ -763,6 +1451,96 @@ async fn remove_image(
     Json(ApiResponse::success(serde_json::json!({"removed": true})))
 }
 
+// === Registry Handlers ===
+
+/// Image store of the native runtimes, whose registry client pulls and
+/// pushes with the credentials saved here.
+fn registry_bundles(
+    state: &DaemonState,
+) -> Result<std::sync::Arc<BundleManager>, (StatusCode, Json<ApiResponse<serde_json::Value>>)> {
+    state.runtimes.bundles().ok_or_else(|| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some("No native runtime to store registry credentials".to_string()),
+            }),
+        )
+    })
+}
+
+/// Check credentials against a registry and save them for the daemon.
+async fn registry_login(
+    State(state): State<DaemonState>,
+    Json(req): Json<LoginRequest>,
+) -> impl IntoResponse {
+    let registry_url = ImageRegistry::registry_url(&req.server);
+    let bundles = match registry_bundles(&state) {
+        Ok(bundles) => bundles,
+        Err(response) => return response,
+    };
+
+    let credentials = Credentials::Basic {
+        username: req.username,
+        password: req.password,
+    };
+    match bundles.registry().login(&registry_url, &credentials).await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(ApiResponse::success(serde_json::json!({
+                "registry": registry_url,
+                "config": bundles.registry().credentials().path(),
+            }))),
+        ),
+        Err(e) => {
+            let status = if matches!(e, hyperbox_core::CoreError::PermissionDenied { .. }) {
+                StatusCode::UNAUTHORIZED
+            } else {
+                StatusCode::BAD_GATEWAY
+            };
+            (
+                status,
+                Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Login to {registry_url} failed: {e}")),
+                }),
+            )
+        }
+    }
+}
+
+/// Remove the daemon's saved credentials for a registry.
+async fn registry_logout(
+    State(state): State<DaemonState>,
+    Json(req): Json<LogoutRequest>,
+) -> impl IntoResponse {
+    let registry_url = ImageRegistry::registry_url(&req.server);
+    let bundles = match registry_bundles(&state) {
+        Ok(bundles) => bundles,
+        Err(response) => return response,
+    };
+
+    match bundles.registry().logout(&registry_url).await {
+        Ok(removed) => (
+            StatusCode::OK,
+            Json(ApiResponse::success(serde_json::json!({
+                "registry": registry_url,
+                "removed": removed,
+            }))),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                success: false,
+                data: None,
+                error: Some(format!("Logout from {registry_url} failed: {e}")),
+            }),
+        ),
+    }
+}
+
 // === Project Handlers ===
 
 async fn list_projects(State(state): State<DaemonState>) -> impl IntoResponse {
@@ -50,6 +50,37 @@ pub struct RuntimeConfig {
 
     /// Enable rootless mode
     pub rootless: bool,
+
+    /// Restart policy enforcement
+    #[serde(default)]
+    pub restart: RestartConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartConfig {
+    /// Consecutive restarts before giving up
+    pub max_retries: u32,
+
+    /// Delay before the first restart in milliseconds, doubled on every retry
+    pub initial_backoff_ms: u64,
+
+    /// Upper bound for the restart delay in milliseconds
+    pub max_backoff_ms: u64,
+
+    /// Seconds a container must run before its retry count is reset
+    pub reset_after_seconds: u64,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 10,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 60_000,
+            reset_after_seconds: 10,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -136,6 +167,7 @@ impl Default for DaemonConfig {
                 crun_path: PathBuf::from("/usr/bin/crun"),
                 runc_path: PathBuf::from("/usr/bin/runc"),
                 rootless: false,
+                restart: RestartConfig::default(),
             },
             storage: StorageConfig {
                 driver: "composefs".to_string(),
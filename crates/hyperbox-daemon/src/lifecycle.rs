@@ -1,13 +1,25 @@
 //! Container lifecycle management.
 
+use crate::config::RestartConfig;
 use crate::state::{ContainerState, ContainerStatus, DaemonState, EventType};
 use chrono::Utc;
+use futures::StreamExt;
+use hyperbox_core::types::{ContainerId, ResourceEvents, RestartPolicy};
+use hyperbox_core::{ContainerRuntime, CoreError};
 use hyperbox_optimize::predict::UsageEvent;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+/// How long to let the event watcher of a container that exited catch up,
+/// so an OOM kill that ended it is still reported.
+const EVENT_DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
 
 /// Manage container lifecycles.
 pub async fn manager(state: DaemonState) -> anyhow::Result<()> {
+    restore_restart_policies(&state).await;
+
     let mut interval = tokio::time::interval(Duration::from_secs(5));
 
     loop {
@@ -57,6 +69,7 @@ async fn cleanup_dead_containers(state: &DaemonState) {
 
     for id in dead_containers {
         debug!("Cleaning up dead container: {}", id);
+        unwatch_events(state, &id);
         state.containers.remove(&id);
     }
 }
@@ -87,6 +100,400 @@ async fn update_predictor(state: &DaemonState) {
     }
 }
 
+// === Restart policy supervisor ===
+
+/// Supervise a started container: record how it exits and restart it as its
+/// restart policy asks.
+///
+/// Replaces any supervisor already watching the container.
+pub fn supervise(state: &DaemonState, id: &str) {
+    let handle = tokio::spawn(run_supervisor(state.clone(), id.to_string()));
+    if let Some(previous) = state.supervisors.insert(id.to_string(), handle) {
+        previous.abort();
+    }
+}
+
+/// Stop supervising a container that is being removed.
+pub fn unsupervise(state: &DaemonState, id: &str) {
+    if let Some((_, handle)) = state.supervisors.remove(id) {
+        handle.abort();
+    }
+}
+
+/// Mark whether a container was stopped through the API, which keeps its
+/// restart policy from bringing it back, and return the previous mark.
+pub fn set_manually_stopped(state: &DaemonState, id: &str, stopped: bool) -> bool {
+    state
+        .containers
+        .get_mut(id)
+        .is_some_and(|mut c| std::mem::replace(&mut c.manually_stopped, stopped))
+}
+
+/// Report OOM kills and PIDs limit hits of a created or started container
+/// until its cgroup goes away.
+///
+/// Replaces any watcher already running for the container, as starting a
+/// stopped container gives it a new cgroup.
+pub fn watch_events(state: &DaemonState, id: &str) {
+    if let Some(mut container) = state.containers.get_mut(id) {
+        container.oom_killed = false;
+    }
+    let handle = tokio::spawn(run_event_watcher(state.clone(), id.to_string()));
+    if let Some(previous) = state.event_watchers.insert(id.to_string(), handle) {
+        previous.abort();
+    }
+}
+
+/// Stop watching the resource events of a container that is being removed.
+pub fn unwatch_events(state: &DaemonState, id: &str) {
+    if let Some((_, handle)) = state.event_watchers.remove(id) {
+        handle.abort();
+    }
+}
+
+/// Save container records, logging failures.
+pub fn persist(state: &DaemonState) {
+    if let Err(e) = state.save_containers() {
+        warn!("Failed to save container records: {}", e);
+    }
+}
+
+async fn run_supervisor(state: DaemonState, id: String) {
+    let container_id = ContainerId::from_string(&id);
+    let runtime = state.runtime_for(&id);
+    let config = &state.config.runtime.restart;
+
+    loop {
+        let watcher = state.event_watchers.get(&id).map(|handle| handle.id());
+        let exited = runtime.wait(&container_id).await;
+        drain_events(&state, &id, watcher).await;
+
+        let exit_code = match exited {
+            Ok(code) => Some(code),
+            // Containers that exited while no process of the runtime was
+            // watching them, e.g. across a daemon restart, have no exit code.
+            Err(e) if has_exited(&runtime, &container_id).await => {
+                debug!("Exit code of container {} is unknown: {}", id, e);
+                None
+            }
+            Err(e) => {
+                warn!("Lost track of container {}: {}", id, e);
+                return;
+            }
+        };
+
+        let Some((policy, mut restart_count)) = record_exit(&state, &id, exit_code, config) else {
+            // Removed while running
+            return;
+        };
+        let oom_killed = state.containers.get(&id).is_some_and(|c| c.oom_killed);
+        state.emit(
+            EventType::ContainerDie,
+            &id,
+            serde_json::json!({"exit_code": exit_code, "oom_killed": oom_killed}),
+        );
+        persist(&state);
+
+        loop {
+            let manually_stopped = match state.containers.get(&id) {
+                Some(c) => c.manually_stopped,
+                None => return,
+            };
+            let Some(delay) =
+                restart_delay(policy, exit_code, manually_stopped, restart_count, config)
+            else {
+                if restart_count >= config.max_retries && !manually_stopped {
+                    warn!("Container {} restarted {} times, giving up", id, restart_count);
+                }
+                return;
+            };
+
+            debug!("Restarting container {} in {:?}", id, delay);
+            tokio::time::sleep(delay).await;
+
+            // The container may have been stopped or removed meanwhile.
+            match state.containers.get(&id) {
+                Some(c) if !c.manually_stopped => {}
+                _ => return,
+            }
+
+            restart_count += 1;
+            let result = runtime.start(&container_id).await;
+            if let Some(mut container) = state.containers.get_mut(&id) {
+                container.restart_count = restart_count;
+                if result.is_ok() {
+                    container.status = ContainerStatus::Running;
+                    container.started_at = Some(Utc::now());
+                }
+            }
+
+            match result {
+                Ok(()) => {
+                    info!("Restarted container {} (attempt {})", id, restart_count);
+                    state.emit(
+                        EventType::ContainerRestart,
+                        &id,
+                        serde_json::json!({
+                            "exit_code": exit_code,
+                            "restart_count": restart_count,
+                            "delay_ms": delay.as_millis(),
+                        }),
+                    );
+                    watch_events(&state, &id);
+                    persist(&state);
+                    break;
+                }
+                Err(e) => {
+                    warn!("Failed to restart container {}: {}", id, e);
+                    state.emit(
+                        EventType::ContainerRestart,
+                        &id,
+                        serde_json::json!({
+                            "status": "error",
+                            "error": e.to_string(),
+                            "restart_count": restart_count,
+                        }),
+                    );
+                }
+            }
+        }
+    }
+}
+
+async fn run_event_watcher(state: DaemonState, id: String) {
+    let container_id = ContainerId::from_string(&id);
+    let runtime = state.runtime_for(&id);
+    let mut events = match runtime.resource_events(&container_id).await {
+        Ok(events) => events,
+        Err(e) => {
+            debug!("Not watching resource events of container {}: {}", id, e);
+            return;
+        }
+    };
+
+    // The first counters are the baseline; only later increases are events.
+    let mut last: Option<ResourceEvents> = None;
+    while let Some(current) = events.next().await {
+        report_resource_events(&state, &id, last, current);
+        last = Some(current);
+    }
+}
+
+/// Let the event watcher that was running while a container ran, if it is
+/// still the current one, report what is queued and stop it.
+async fn drain_events(state: &DaemonState, id: &str, watcher: Option<tokio::task::Id>) {
+    let Some(watcher) = watcher else {
+        return;
+    };
+    let Some((_, mut handle)) = state.event_watchers.remove_if(id, |_, h| h.id() == watcher) else {
+        return;
+    };
+    let drained = tokio::time::timeout(EVENT_DRAIN_TIMEOUT, &mut handle).await;
+    if drained.is_err() {
+        handle.abort();
+    }
+}
+
+/// Whether the runtime reports a container as no longer running.
+async fn has_exited(runtime: &Arc<dyn ContainerRuntime>, id: &ContainerId) -> bool {
+    matches!(
+        runtime.state(id).await,
+        Ok(hyperbox_core::types::ContainerState::Exited
+            | hyperbox_core::types::ContainerState::Stopped)
+    )
+}
+
+/// Emit events for resource limits a container hit since `last`.
+fn report_resource_events(
+    state: &DaemonState,
+    id: &str,
+    last: Option<ResourceEvents>,
+    current: ResourceEvents,
+) {
+    let Some(last) = last else {
+        return;
+    };
+
+    if current.oom_kill > last.oom_kill {
+        warn!("Container {} ran out of memory and the kernel killed a process", id);
+        if let Some(mut container) = state.containers.get_mut(id) {
+            container.oom_killed = true;
+        }
+        state.emit(
+            EventType::ContainerOom,
+            id,
+            serde_json::json!({
+                "oom_kill": current.oom_kill,
+                "memory_max": current.memory_max,
+                "memory_high": current.memory_high,
+            }),
+        );
+        persist(state);
+    }
+
+    if current.pids_max > last.pids_max {
+        warn!("Container {} hit its PIDs limit", id);
+        state.emit(
+            EventType::PidsLimitReached,
+            id,
+            serde_json::json!({"pids_max": current.pids_max}),
+        );
+    }
+}
+
+/// Record that a container exited, with `None` for an unknown exit code,
+/// and return its restart policy and consecutive restart count.
+fn record_exit(
+    state: &DaemonState,
+    id: &str,
+    exit_code: Option<i32>,
+    config: &RestartConfig,
+) -> Option<(RestartPolicy, u32)> {
+    let mut container = state.containers.get_mut(id)?;
+    container.status = ContainerStatus::Stopped;
+    container.exit_code = exit_code;
+    container.pid = None;
+
+    // A container that stayed up for a while is not crash-looping.
+    let reset_after = chrono::Duration::seconds(config.reset_after_seconds as i64);
+    if container.started_at.is_some_and(|started| Utc::now() - started >= reset_after) {
+        container.restart_count = 0;
+    }
+
+    Some((container.restart_policy, container.restart_count))
+}
+
+/// Delay before restarting a container that exited, or `None` if it stays
+/// down.
+///
+/// Containers stopped through the API are never restarted; otherwise the
+/// delay doubles with every consecutive restart. An unknown exit code counts
+/// as a failure.
+fn restart_delay(
+    policy: RestartPolicy,
+    exit_code: Option<i32>,
+    manually_stopped: bool,
+    restart_count: u32,
+    config: &RestartConfig,
+) -> Option<Duration> {
+    let restart = match policy {
+        RestartPolicy::No => false,
+        RestartPolicy::OnFailure => exit_code != Some(0),
+        RestartPolicy::Always | RestartPolicy::UnlessStopped => true,
+    };
+    if !restart || manually_stopped || restart_count >= config.max_retries {
+        return None;
+    }
+
+    let backoff = config
+        .initial_backoff_ms
+        .saturating_mul(2_u64.saturating_pow(restart_count))
+        .min(config.max_backoff_ms);
+    Some(Duration::from_millis(backoff))
+}
+
+/// Re-apply restart policies to the containers of a previous daemon run.
+///
+/// Running and paused containers are supervised again, with their status
+/// taken from the runtime. Containers that exited while the daemon was down
+/// are restarted like Docker does: `always` even if they were stopped
+/// through the API, `unless-stopped` and `on-failure` only if they were not.
+async fn restore_restart_policies(state: &DaemonState) {
+    for container in state.get_containers() {
+        if !matches!(container.status, ContainerStatus::Running | ContainerStatus::Paused)
+            && container.restart_policy == RestartPolicy::No
+        {
+            continue;
+        }
+
+        let id = container.id.clone();
+        let container_id = ContainerId::from_string(&id);
+        let runtime = state.runtime_for(&id);
+
+        match runtime.state(&container_id).await {
+            Ok(
+                runtime_state @ (hyperbox_core::types::ContainerState::Running
+                | hyperbox_core::types::ContainerState::Paused),
+            ) => {
+                debug!("Resuming supervision of container {}", id);
+                if let Some(mut c) = state.containers.get_mut(&id) {
+                    c.status = if runtime_state == hyperbox_core::types::ContainerState::Paused {
+                        ContainerStatus::Paused
+                    } else {
+                        ContainerStatus::Running
+                    };
+                }
+                watch_events(state, &id);
+                supervise(state, &id);
+                continue;
+            }
+            Ok(_) => {}
+            Err(CoreError::ContainerNotFound(_)) => {
+                warn!("Container {} no longer exists", id);
+                if let Some(mut c) = state.containers.get_mut(&id) {
+                    c.status = ContainerStatus::Dead;
+                }
+                continue;
+            }
+            Err(e) => {
+                warn!("Cannot restore container {}: {}", id, e);
+                continue;
+            }
+        }
+
+        // A container recorded as running exited while nobody watched it;
+        // unless the runtime recorded its exit, the exit code is unknown.
+        let exit_code = match runtime.exit_status(&container_id).await {
+            Ok(Some(exit)) => Some(exit.exit_code),
+            _ if matches!(container.status, ContainerStatus::Running | ContainerStatus::Paused) => {
+                None
+            }
+            _ => container.exit_code,
+        };
+        // `always` brings back even containers stopped through the API.
+        let manually_stopped =
+            container.manually_stopped && container.restart_policy != RestartPolicy::Always;
+        let restart = restart_delay(
+            container.restart_policy,
+            exit_code,
+            manually_stopped,
+            0,
+            &state.config.runtime.restart,
+        )
+        .is_some();
+
+        if let Some(mut c) = state.containers.get_mut(&id) {
+            c.status = ContainerStatus::Stopped;
+            c.exit_code = exit_code;
+            c.restart_count = 0;
+        }
+        if !restart {
+            continue;
+        }
+
+        match runtime.start(&container_id).await {
+            Ok(()) => {
+                info!("Restarted container {} after daemon restart", id);
+                if let Some(mut c) = state.containers.get_mut(&id) {
+                    c.status = ContainerStatus::Running;
+                    c.started_at = Some(Utc::now());
+                    c.manually_stopped = false;
+                }
+                state.emit(
+                    EventType::ContainerRestart,
+                    &id,
+                    serde_json::json!({"exit_code": exit_code, "daemon_restart": true}),
+                );
+                watch_events(state, &id);
+                supervise(state, &id);
+            }
+            Err(e) => warn!("Failed to restart container {}: {}", id, e),
+        }
+    }
+
+    persist(state);
+}
+
 /// Container lifecycle operations.
 pub struct ContainerLifecycle {
     state: DaemonState,
@@ -115,12 +522,19 @@ impl ContainerLifecycle {
             image: image.to_string(),
             status: ContainerStatus::Created,
             project_id: project_id.map(String::from),
+            labels: HashMap::new(),
+            runtime: None,
             ports: vec![],
             created_at: Utc::now(),
             started_at: None,
             pid: None,
             has_checkpoint: false,
             is_prewarmed: false,
+            restart_policy: RestartPolicy::No,
+            restart_count: 0,
+            exit_code: None,
+            manually_stopped: false,
+            oom_killed: false,
         };
 
         self.state.containers.insert(id.clone(), container.clone());
@@ -244,6 +658,7 @@ impl ContainerLifecycle {
 
         // Would stop container here
         container.status = ContainerStatus::Stopped;
+        container.manually_stopped = true;
 
         self.state.emit(
             EventType::ContainerStop,
@@ -262,7 +677,7 @@ impl ContainerLifecycle {
             .get(id)
             .ok_or_else(|| anyhow::anyhow!("Container not found"))?;
 
-        if container.status == ContainerStatus::Running && !force {
+        if matches!(container.status, ContainerStatus::Running | ContainerStatus::Paused) && !force {
             return Err(anyhow::anyhow!(
                 "Container is running. Stop it first or use force"
             ));
@@ -282,3 +697,539 @@ impl ContainerLifecycle {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DaemonConfig, OptimizationConfig, StorageConfig};
+    use async_trait::async_trait;
+    use hyperbox_core::runtime::{ImageInfo, ProcessInfo, RuntimeRegistry};
+    use hyperbox_core::types::{
+        CheckpointId, ContainerExit, ContainerSpec, ContainerState as RuntimeState, ContainerStats,
+        ExecResult, ExecSpec, ImageRef, LogOptions, ResourceLimits, ResourceUpdateReport,
+    };
+    use hyperbox_core::RuntimeType;
+    use parking_lot::Mutex;
+    use std::collections::VecDeque;
+    use std::path::Path;
+    use tempfile::TempDir;
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    type Stdio = (
+        Box<dyn AsyncWrite + Send + Unpin>,
+        Box<dyn AsyncRead + Send + Unpin>,
+        Box<dyn AsyncRead + Send + Unpin>,
+    );
+
+    /// A runtime that reports the states and exit codes it is given and
+    /// records which containers were started.
+    #[derive(Default)]
+    struct FakeRuntime {
+        states: Mutex<HashMap<String, RuntimeState>>,
+        exit_codes: HashMap<String, i32>,
+        /// Results of `wait`, in order; afterwards `wait` never returns.
+        waits: Mutex<VecDeque<hyperbox_core::Result<i32>>>,
+        started: Mutex<Vec<String>>,
+        /// Counters `resource_events` yields before the cgroup goes away.
+        events: Vec<ResourceEvents>,
+    }
+
+    impl FakeRuntime {
+        fn with_state(self, id: &str, state: RuntimeState) -> Self {
+            self.states.lock().insert(id.to_string(), state);
+            self
+        }
+
+        fn with_exit_code(mut self, id: &str, code: i32) -> Self {
+            self.exit_codes.insert(id.to_string(), code);
+            self
+        }
+
+        fn started(&self) -> Vec<String> {
+            self.started.lock().clone()
+        }
+    }
+
+    fn unsupported<T>() -> hyperbox_core::Result<T> {
+        Err(CoreError::Internal("not supported by the fake runtime".to_string()))
+    }
+
+    #[async_trait]
+    impl ContainerRuntime for FakeRuntime {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        async fn version(&self) -> hyperbox_core::Result<String> {
+            Ok("1.0".to_string())
+        }
+
+        async fn is_available(&self) -> bool {
+            true
+        }
+
+        async fn create(&self, _spec: ContainerSpec) -> hyperbox_core::Result<ContainerId> {
+            unsupported()
+        }
+
+        async fn start(&self, id: &ContainerId) -> hyperbox_core::Result<()> {
+            self.started.lock().push(id.to_string());
+            self.states
+                .lock()
+                .insert(id.to_string(), RuntimeState::Running);
+            Ok(())
+        }
+
+        async fn stop(&self, _id: &ContainerId, _timeout: Duration) -> hyperbox_core::Result<()> {
+            unsupported()
+        }
+
+        async fn kill(&self, _id: &ContainerId, _signal: &str) -> hyperbox_core::Result<()> {
+            unsupported()
+        }
+
+        async fn remove(&self, _id: &ContainerId) -> hyperbox_core::Result<()> {
+            unsupported()
+        }
+
+        async fn pause(&self, _id: &ContainerId) -> hyperbox_core::Result<()> {
+            unsupported()
+        }
+
+        async fn resume(&self, _id: &ContainerId) -> hyperbox_core::Result<()> {
+            unsupported()
+        }
+
+        async fn exec(
+            &self,
+            _id: &ContainerId,
+            _spec: ExecSpec,
+        ) -> hyperbox_core::Result<ExecResult> {
+            unsupported()
+        }
+
+        async fn state(&self, id: &ContainerId) -> hyperbox_core::Result<RuntimeState> {
+            self.states
+                .lock()
+                .get(&id.to_string())
+                .copied()
+                .ok_or_else(|| CoreError::ContainerNotFound(id.to_string()))
+        }
+
+        async fn stats(&self, _id: &ContainerId) -> hyperbox_core::Result<ContainerStats> {
+            unsupported()
+        }
+
+        async fn logs(
+            &self,
+            _id: &ContainerId,
+            _opts: LogOptions,
+        ) -> hyperbox_core::Result<Box<dyn AsyncRead + Send + Unpin>> {
+            unsupported()
+        }
+
+        async fn attach(&self, _id: &ContainerId) -> hyperbox_core::Result<Stdio> {
+            unsupported()
+        }
+
+        async fn list(&self) -> hyperbox_core::Result<Vec<(ContainerId, RuntimeState)>> {
+            unsupported()
+        }
+
+        async fn wait(&self, _id: &ContainerId) -> hyperbox_core::Result<i32> {
+            let next = self.waits.lock().pop_front();
+            match next {
+                Some(result) => result,
+                None => futures::future::pending().await,
+            }
+        }
+
+        async fn resource_events(
+            &self,
+            _id: &ContainerId,
+        ) -> hyperbox_core::Result<futures::stream::BoxStream<'static, ResourceEvents>> {
+            Ok(futures::stream::iter(self.events.clone()).boxed())
+        }
+
+        async fn exit_status(
+            &self,
+            id: &ContainerId,
+        ) -> hyperbox_core::Result<Option<ContainerExit>> {
+            Ok(self
+                .exit_codes
+                .get(&id.to_string())
+                .map(|&exit_code| ContainerExit {
+                    exit_code,
+                    signal: None,
+                    oom_killed: false,
+                    finished_at: Utc::now(),
+                }))
+        }
+
+        async fn checkpoint(
+            &self,
+            _id: &ContainerId,
+            _path: &Path,
+        ) -> hyperbox_core::Result<CheckpointId> {
+            unsupported()
+        }
+
+        async fn restore(
+            &self,
+            _path: &Path,
+            _spec: ContainerSpec,
+        ) -> hyperbox_core::Result<ContainerId> {
+            unsupported()
+        }
+
+        async fn update(
+            &self,
+            _id: &ContainerId,
+            _resources: ResourceLimits,
+        ) -> hyperbox_core::Result<ResourceUpdateReport> {
+            unsupported()
+        }
+
+        async fn top(&self, _id: &ContainerId) -> hyperbox_core::Result<Vec<ProcessInfo>> {
+            unsupported()
+        }
+
+        async fn pull_image(&self, _image: &ImageRef) -> hyperbox_core::Result<()> {
+            unsupported()
+        }
+
+        async fn image_exists(&self, _image: &str) -> hyperbox_core::Result<bool> {
+            unsupported()
+        }
+
+        async fn list_images(&self) -> hyperbox_core::Result<Vec<ImageInfo>> {
+            unsupported()
+        }
+    }
+
+    fn restart_config() -> RestartConfig {
+        RestartConfig {
+            max_retries: 3,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+            reset_after_seconds: 10,
+        }
+    }
+
+    fn test_state(dir: &TempDir, runtime: Arc<FakeRuntime>) -> DaemonState {
+        let defaults = DaemonConfig::default();
+        let root = dir.path();
+        let config = DaemonConfig {
+            data_dir: root.to_path_buf(),
+            runtime: crate::config::RuntimeConfig {
+                restart: RestartConfig {
+                    initial_backoff_ms: 1,
+                    ..restart_config()
+                },
+                ..defaults.runtime.clone()
+            },
+            storage: StorageConfig {
+                images_dir: root.join("images"),
+                containers_dir: root.join("containers"),
+                volumes_dir: root.join("volumes"),
+                ..defaults.storage.clone()
+            },
+            optimization: OptimizationConfig {
+                checkpoints_dir: root.join("checkpoints"),
+                ..defaults.optimization.clone()
+            },
+            ..defaults
+        };
+        config.ensure_directories().unwrap();
+
+        let runtimes = RuntimeRegistry::new(RuntimeType::Crun);
+        runtimes.register(RuntimeType::Crun, runtime);
+        DaemonState::with_runtimes(config, runtimes).unwrap()
+    }
+
+    fn container(id: &str, policy: RestartPolicy, status: ContainerStatus) -> ContainerState {
+        ContainerState {
+            id: id.to_string(),
+            name: id.to_string(),
+            image: "alpine".to_string(),
+            status,
+            project_id: None,
+            labels: HashMap::new(),
+            runtime: None,
+            ports: vec![],
+            created_at: Utc::now(),
+            started_at: None,
+            pid: None,
+            has_checkpoint: false,
+            is_prewarmed: false,
+            restart_policy: policy,
+            restart_count: 0,
+            exit_code: None,
+            manually_stopped: false,
+            oom_killed: false,
+        }
+    }
+
+    fn add(state: &DaemonState, container: ContainerState) {
+        state.containers.insert(container.id.clone(), container);
+    }
+
+    #[test]
+    fn test_restart_delay_per_policy() {
+        let config = restart_config();
+        let delay = |policy, exit_code| restart_delay(policy, exit_code, false, 0, &config);
+        let first = Some(Duration::from_millis(100));
+
+        for exit_code in [Some(0), Some(1), None] {
+            assert_eq!(delay(RestartPolicy::No, exit_code), None);
+            assert_eq!(delay(RestartPolicy::Always, exit_code), first);
+            assert_eq!(delay(RestartPolicy::UnlessStopped, exit_code), first);
+        }
+        assert_eq!(delay(RestartPolicy::OnFailure, Some(0)), None);
+        assert_eq!(delay(RestartPolicy::OnFailure, Some(137)), first);
+        // An exit nobody recorded may have been a crash.
+        assert_eq!(delay(RestartPolicy::OnFailure, None), first);
+    }
+
+    #[test]
+    fn test_restart_delay_after_manual_stop() {
+        let config = restart_config();
+        for policy in [
+            RestartPolicy::Always,
+            RestartPolicy::UnlessStopped,
+            RestartPolicy::OnFailure,
+        ] {
+            assert_eq!(restart_delay(policy, Some(1), true, 0, &config), None);
+        }
+    }
+
+    #[test]
+    fn test_restart_delay_backoff() {
+        let config = restart_config();
+        let delay = |count| restart_delay(RestartPolicy::Always, Some(1), false, count, &config);
+
+        assert_eq!(delay(1), Some(Duration::from_millis(200)));
+        assert_eq!(delay(2), Some(Duration::from_millis(400)));
+        // Gives up after `max_retries` consecutive restarts.
+        assert_eq!(delay(3), None);
+
+        let config = RestartConfig {
+            max_retries: 100,
+            ..restart_config()
+        };
+        assert_eq!(
+            restart_delay(RestartPolicy::Always, Some(1), false, 5, &config),
+            Some(Duration::from_millis(1000))
+        );
+        assert_eq!(
+            restart_delay(RestartPolicy::Always, Some(1), false, 90, &config),
+            Some(Duration::from_millis(1000))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_exit_resets_restart_count_after_a_long_run() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir, Arc::new(FakeRuntime::default()));
+        let config = restart_config();
+
+        let mut flapping = container("flapping", RestartPolicy::Always, ContainerStatus::Running);
+        flapping.started_at = Some(Utc::now());
+        flapping.restart_count = 2;
+        flapping.pid = Some(42);
+        add(&state, flapping);
+        let mut stable = container("stable", RestartPolicy::OnFailure, ContainerStatus::Running);
+        stable.started_at = Some(Utc::now() - chrono::Duration::minutes(5));
+        stable.restart_count = 2;
+        add(&state, stable);
+
+        assert_eq!(
+            record_exit(&state, "flapping", Some(1), &config),
+            Some((RestartPolicy::Always, 2))
+        );
+        let flapping = state.get_container("flapping").unwrap();
+        assert_eq!(flapping.status, ContainerStatus::Stopped);
+        assert_eq!(flapping.exit_code, Some(1));
+        assert_eq!(flapping.pid, None);
+
+        assert_eq!(
+            record_exit(&state, "stable", None, &config),
+            Some((RestartPolicy::OnFailure, 0))
+        );
+        assert_eq!(state.get_container("stable").unwrap().exit_code, None);
+
+        assert_eq!(record_exit(&state, "removed", Some(0), &config), None);
+    }
+
+    #[tokio::test]
+    async fn test_restore_restart_policies() {
+        let dir = TempDir::new().unwrap();
+        let runtime = Arc::new(
+            FakeRuntime::default()
+                .with_state("running", RuntimeState::Running)
+                .with_state("always", RuntimeState::Stopped)
+                .with_exit_code("always", 0)
+                .with_state("unless-stopped", RuntimeState::Stopped)
+                .with_state("clean-exit", RuntimeState::Stopped)
+                .with_exit_code("clean-exit", 0)
+                .with_state("crashed", RuntimeState::Stopped)
+                .with_exit_code("crashed", 1),
+        );
+        let state = test_state(&dir, runtime.clone());
+
+        add(&state, container("running", RestartPolicy::No, ContainerStatus::Running));
+        let mut always = container("always", RestartPolicy::Always, ContainerStatus::Running);
+        always.manually_stopped = true;
+        add(&state, always);
+        let mut unless_stopped =
+            container("unless-stopped", RestartPolicy::UnlessStopped, ContainerStatus::Stopped);
+        unless_stopped.manually_stopped = true;
+        add(&state, unless_stopped);
+        add(
+            &state,
+            container("clean-exit", RestartPolicy::OnFailure, ContainerStatus::Running),
+        );
+        add(&state, container("crashed", RestartPolicy::OnFailure, ContainerStatus::Running));
+        add(&state, container("gone", RestartPolicy::Always, ContainerStatus::Running));
+
+        restore_restart_policies(&state).await;
+
+        let mut started = runtime.started();
+        started.sort();
+        assert_eq!(started, ["always", "crashed"]);
+
+        let status = |id| state.get_container(id).unwrap().status;
+        assert_eq!(status("running"), ContainerStatus::Running);
+        assert_eq!(status("always"), ContainerStatus::Running);
+        assert!(!state.get_container("always").unwrap().manually_stopped);
+        assert_eq!(status("unless-stopped"), ContainerStatus::Stopped);
+        assert_eq!(status("clean-exit"), ContainerStatus::Stopped);
+        assert_eq!(state.get_container("crashed").unwrap().exit_code, Some(1));
+        assert_eq!(status("crashed"), ContainerStatus::Running);
+        assert_eq!(status("gone"), ContainerStatus::Dead);
+
+        for id in ["running", "always", "crashed"] {
+            assert!(state.supervisors.contains_key(id), "{id} is not supervised");
+        }
+        assert!(!state.supervisors.contains_key("clean-exit"));
+    }
+
+    #[tokio::test]
+    async fn test_restore_restarts_on_failure_container_with_unknown_exit() {
+        let dir = TempDir::new().unwrap();
+        let runtime = Arc::new(
+            FakeRuntime::default()
+                .with_state("web", RuntimeState::Stopped)
+                .with_state("stopped", RuntimeState::Stopped),
+        );
+        let state = test_state(&dir, runtime.clone());
+        add(&state, container("web", RestartPolicy::OnFailure, ContainerStatus::Running));
+        let mut stopped = container("stopped", RestartPolicy::OnFailure, ContainerStatus::Stopped);
+        stopped.exit_code = Some(0);
+        add(&state, stopped);
+
+        restore_restart_policies(&state).await;
+
+        assert_eq!(runtime.started(), ["web"]);
+        let web = state.get_container("web").unwrap();
+        assert_eq!(web.status, ContainerStatus::Running);
+        assert_eq!(web.exit_code, None);
+        let stopped = state.get_container("stopped").unwrap();
+        assert_eq!(stopped.status, ContainerStatus::Stopped);
+        assert_eq!(stopped.exit_code, Some(0));
+        unsupervise(&state, "web");
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_restarts_container_whose_exit_was_not_recorded() {
+        let dir = TempDir::new().unwrap();
+        let runtime = Arc::new(FakeRuntime::default().with_state("web", RuntimeState::Stopped));
+        runtime.waits.lock().push_back(Err(CoreError::Internal(
+            "exit status of container web was not recorded".to_string(),
+        )));
+        let state = test_state(&dir, runtime.clone());
+        add(&state, container("web", RestartPolicy::OnFailure, ContainerStatus::Running));
+
+        supervise(&state, "web");
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while runtime.started().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let web = state.get_container("web").unwrap();
+        assert_eq!(web.status, ContainerStatus::Running);
+        assert_eq!(web.restart_count, 1);
+        assert_eq!(web.exit_code, None);
+        unsupervise(&state, "web");
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_gives_up_on_containers_it_cannot_find() {
+        let dir = TempDir::new().unwrap();
+        let runtime = Arc::new(FakeRuntime::default());
+        runtime
+            .waits
+            .lock()
+            .push_back(Err(CoreError::ContainerNotFound("web".to_string())));
+        let state = test_state(&dir, runtime.clone());
+        add(&state, container("web", RestartPolicy::Always, ContainerStatus::Running));
+
+        supervise(&state, "web");
+        let (_, supervisor) = state.supervisors.remove("web").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), supervisor)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(runtime.started().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_events_are_watched_without_a_supervisor() {
+        let dir = TempDir::new().unwrap();
+        let baseline = ResourceEvents::default();
+        let runtime = Arc::new(FakeRuntime {
+            events: vec![
+                baseline,
+                ResourceEvents {
+                    oom_kill: 1,
+                    ..baseline
+                },
+            ],
+            ..FakeRuntime::default()
+        });
+        let state = test_state(&dir, runtime);
+        let mut events = state.events.subscribe();
+        add(&state, container("web", RestartPolicy::No, ContainerStatus::Created));
+
+        watch_events(&state, "web");
+        let (_, watcher) = state.event_watchers.remove("web").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), watcher)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(state.get_container("web").unwrap().oom_killed);
+        assert!(!state.supervisors.contains_key("web"));
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.event_type, EventType::ContainerOom);
+        assert_eq!(event.target, "web");
+    }
+
+    #[tokio::test]
+    async fn test_failed_stop_keeps_restart_policy() {
+        let dir = TempDir::new().unwrap();
+        let state = test_state(&dir, Arc::new(FakeRuntime::default()));
+        add(&state, container("web", RestartPolicy::Always, ContainerStatus::Running));
+
+        let was_stopped = set_manually_stopped(&state, "web", true);
+        assert!(!was_stopped);
+        assert!(state.get_container("web").unwrap().manually_stopped);
+        set_manually_stopped(&state, "web", was_stopped);
+        assert!(!state.get_container("web").unwrap().manually_stopped);
+        assert!(!set_manually_stopped(&state, "removed", true));
+    }
+}
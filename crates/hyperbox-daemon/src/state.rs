@@ -4,7 +4,11 @@ use crate::config::DaemonConfig;
 use crate::error::{DaemonError, Result};
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
-use hyperbox_core::runtime::{ContainerRuntime, DockerRuntime};
+use hyperbox_core::runtime::{
+    ContainerRuntime, DockerRuntime, FilterTarget, RuntimeConfig, RuntimeRegistry, RuntimeType,
+    PROJECT_LABEL,
+};
+use hyperbox_core::types::RestartPolicy;
 use hyperbox_optimize::criu::CriuManager;
 use hyperbox_optimize::lazy_load::LazyLayerLoader;
 use hyperbox_optimize::predict::UsagePredictor;
@@ -12,8 +16,12 @@ use hyperbox_optimize::prewarm::PrewarmManager;
 use hyperbox_project::manager::ProjectManager;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
 use uuid::Uuid;
 
 /// Shared daemon state.
@@ -22,12 +30,21 @@ pub struct DaemonState {
     /// Configuration
     pub config: DaemonConfig,
 
-    /// Container runtime (Docker, crun, etc.)
+    /// Default container runtime
     pub runtime: Arc<dyn ContainerRuntime>,
 
+    /// All available container runtimes (Docker, crun, etc.)
+    pub runtimes: Arc<RuntimeRegistry>,
+
     /// Active containers
     pub containers: Arc<DashMap<String, ContainerState>>,
 
+    /// Restart supervisors of started containers
+    pub supervisors: Arc<DashMap<String, JoinHandle<()>>>,
+
+    /// Resource event watchers of created containers
+    pub event_watchers: Arc<DashMap<String, JoinHandle<()>>>,
+
     /// Active projects
     pub projects: Arc<ProjectManager>,
 
@@ -74,6 +91,14 @@ pub struct ContainerState {
     /// Associated project ID
     pub project_id: Option<String>,
 
+    /// Labels
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+
+    /// Runtime that created the container (the default runtime if unset)
+    #[serde(default)]
+    pub runtime: Option<RuntimeType>,
+
     /// Port mappings
     pub ports: Vec<PortMapping>,
 
@@ -91,6 +116,26 @@ pub struct ContainerState {
 
     /// Is pre-warmed
     pub is_prewarmed: bool,
+
+    /// Restart policy
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
+
+    /// Consecutive restarts by the supervisor
+    #[serde(default)]
+    pub restart_count: u32,
+
+    /// Exit code of the last run
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+
+    /// Stopped through the API rather than by exiting
+    #[serde(default)]
+    pub manually_stopped: bool,
+
+    /// A process was killed for running out of memory since the last start
+    #[serde(default)]
+    pub oom_killed: bool,
 }
 
 /// Container status.
@@ -165,9 +210,20 @@ pub enum EventType {
     ContainerStart,
     ContainerStop,
     ContainerRemove,
+    ContainerDie,
+    ContainerRestart,
+    ContainerPause,
+    ContainerUnpause,
+    ContainerUpdate,
+    ContainerOom,
+    PidsLimitReached,
     ContainerCheckpoint,
     ContainerRestore,
+    ContainerCommit,
+    ContainerExport,
     ImagePull,
+    ImagePush,
+    ImageImport,
     ImageRemove,
     ProjectOpen,
     ProjectStart,
@@ -229,14 +285,19 @@ impl DaemonState {
             .ensure_directories()
             .map_err(|e| DaemonError::Internal(e.to_string()))?;
 
+        let runtimes = Self::init_runtimes(&config).await?;
+        Self::with_runtimes(config, runtimes)
+    }
+
+    /// Create the daemon state around runtimes that are already registered.
+    pub fn with_runtimes(config: DaemonConfig, runtimes: RuntimeRegistry) -> Result<Self> {
         // Create event channel
         let (events, _) = broadcast::channel(1024);
 
-        // Initialize container runtime (Docker by default)
-        let runtime: Arc<dyn ContainerRuntime> = Arc::new(
-            DockerRuntime::new()
-                .map_err(|e| DaemonError::Internal(format!("Failed to initialize Docker runtime: {}", e)))?
-        );
+        let runtimes = Arc::new(runtimes);
+        let runtime = runtimes
+            .default()
+            .ok_or_else(|| DaemonError::Runtime("No container runtime available".to_string()))?;
 
         // Initialize CRIU manager (not async)
         let criu = CriuManager::new(config.optimization.checkpoints_dir.clone());
@@ -267,7 +328,10 @@ impl DaemonState {
         Ok(Self {
             config: config.clone(),
             runtime,
-            containers: Arc::new(DashMap::new()),
+            runtimes,
+            containers: Arc::new(Self::load_containers(&config)),
+            supervisors: Arc::new(DashMap::new()),
+            event_watchers: Arc::new(DashMap::new()),
             projects: Arc::new(ProjectManager::new(config.data_dir.join("projects"))),
             images: Arc::new(DashMap::new()),
             criu: Arc::new(criu),
@@ -280,6 +344,51 @@ impl DaemonState {
         })
     }
 
+    /// Register every available runtime.
+    ///
+    /// Docker is registered alongside the native runtimes; the configured
+    /// default is used for containers that do not ask for a runtime.
+    async fn init_runtimes(config: &DaemonConfig) -> Result<RuntimeRegistry> {
+        let default_runtime: RuntimeType = config
+            .runtime
+            .default_runtime
+            .parse()
+            .map_err(|e| DaemonError::Config(format!("Invalid default runtime: {}", e)))?;
+        let registry = RuntimeRegistry::new(default_runtime);
+
+        match DockerRuntime::new() {
+            Ok(docker) => registry.register(RuntimeType::Docker, Arc::new(docker)),
+            Err(e) => warn!("Docker runtime not available: {}", e),
+        }
+
+        registry.set_binary_path(RuntimeType::Crun, &config.runtime.crun_path);
+        registry.set_binary_path(RuntimeType::Runc, &config.runtime.runc_path);
+        let runtime_config = RuntimeConfig {
+            runtime_type: default_runtime,
+            root_dir: config.data_dir.join("runtime"),
+            ..RuntimeConfig::default()
+        };
+        // Fails only when no native runtime is installed; Docker may still be.
+        if let Err(e) = registry.initialize(&runtime_config).await {
+            warn!("No native container runtime available: {}", e);
+        }
+
+        info!("Default runtime: {:?}", registry.default_type());
+        Ok(registry)
+    }
+
+    /// Runtime that manages a container.
+    ///
+    /// Containers the daemon did not create are assumed to belong to the
+    /// default runtime.
+    pub fn runtime_for(&self, id: &str) -> Arc<dyn ContainerRuntime> {
+        self.containers
+            .get(id)
+            .and_then(|c| c.runtime)
+            .and_then(|runtime_type| self.runtimes.get(runtime_type))
+            .unwrap_or_else(|| Arc::clone(&self.runtime))
+    }
+
     /// Emit an event.
     pub fn emit(&self, event_type: EventType, target: &str, data: serde_json::Value) {
         let event = DaemonEvent {
@@ -302,8 +411,36 @@ impl DaemonState {
             .await
             .map_err(|e| DaemonError::State(e.to_string()))?;
 
-        // Would save other state here
+        self.save_containers()?;
+
+        Ok(())
+    }
+
+    /// Path of the container records.
+    fn containers_path(config: &DaemonConfig) -> PathBuf {
+        config.data_dir.join("containers.json")
+    }
+
+    /// Load the container records saved by a previous daemon run.
+    fn load_containers(config: &DaemonConfig) -> DashMap<String, ContainerState> {
+        let path = Self::containers_path(config);
+        let containers: Vec<ContainerState> = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable container records {:?}: {}", path, e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+
+        containers.into_iter().map(|c| (c.id.clone(), c)).collect()
+    }
 
+    /// Save container records so restart policies survive a daemon restart.
+    pub fn save_containers(&self) -> Result<()> {
+        let path = Self::containers_path(&self.config);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&self.get_containers())?)?;
+        std::fs::rename(&tmp, &path)?;
         Ok(())
     }
 
@@ -332,6 +469,35 @@ impl DaemonState {
     }
 }
 
+impl ContainerState {
+    /// The fields container list filters look at.
+    pub fn filter_target(&self) -> FilterTarget<'_> {
+        FilterTarget {
+            name: Some(&self.name),
+            image: hyperbox_core::types::ImageRef::parse(&self.image),
+            labels: &self.labels,
+            project: self
+                .project_id
+                .as_deref()
+                .or_else(|| self.labels.get(PROJECT_LABEL).map(String::as_str)),
+            state: self.status.into(),
+            created_at: self.created_at,
+        }
+    }
+}
+
+impl From<ContainerStatus> for hyperbox_core::types::ContainerState {
+    fn from(status: ContainerStatus) -> Self {
+        match status {
+            ContainerStatus::Created => Self::Created,
+            ContainerStatus::Running => Self::Running,
+            ContainerStatus::Paused => Self::Paused,
+            ContainerStatus::Stopped | ContainerStatus::Dead => Self::Stopped,
+            ContainerStatus::Removing => Self::Removing,
+        }
+    }
+}
+
 impl std::fmt::Display for ContainerStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
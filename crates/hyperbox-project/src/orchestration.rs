@@ -8,7 +8,8 @@ use crate::error::{ProjectError, Result};
 use crate::Project;
 use hyperbox_core::runtime::ContainerRuntime;
 use hyperbox_core::types::{
-    ContainerId, ContainerSpec, ImageRef, Mount, MountType, PortMapping, Protocol, ResourceLimits,
+    ContainerId, ContainerSpec, ImageRef, Mount, MountType, Platform, PortMapping, Protocol,
+    ResourceLimits,
 };
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -171,6 +172,16 @@ impl ProjectOrchestrator {
         labels.insert("hyperbox.project.id".to_string(), project.id.to_string());
         labels.insert("hyperbox.service".to_string(), def.name.clone());
 
+        // Images are pulled for the project's build platform, if it sets one
+        let platform = project
+            .config
+            .build
+            .platform
+            .as_deref()
+            .map(str::parse::<Platform>)
+            .transpose()
+            .map_err(|e| ProjectError::InvalidConfig(e.to_string()))?;
+
         Ok(ContainerSpec {
             name: Some(name),
             image,
@@ -189,6 +200,9 @@ impl ProjectOrchestrator {
             read_only_rootfs: false,
             tty: false,
             stdin_open: false,
+            runtime: None,
+            platform,
+            ..ContainerSpec::default()
         })
     }
 
@@ -260,10 +274,14 @@ impl ProjectOrchestrator {
         match res {
             Some(r) => {
                 // Parse cpu_limit string (e.g., "0.5") to millicores
-                let cpu_millicores = r
-                    .cpu_limit
-                    .as_ref()
-                    .and_then(|cpu| cpu.parse::<f64>().ok().map(|v| (v * 1000.0) as u64));
+                let cpu_millicores = r.cpu_limit.as_deref().and_then(Self::parse_cpu_string);
+
+                // A CPU reservation becomes a proportional weight
+                let cpu_weight = r
+                    .cpu_reservation
+                    .as_deref()
+                    .and_then(Self::parse_cpu_string)
+                    .map(ResourceLimits::cpu_weight_for_reservation);
 
                 // Parse memory_limit string (e.g., "512m") to bytes
                 let memory_bytes = r
@@ -273,17 +291,27 @@ impl ProjectOrchestrator {
 
                 ResourceLimits {
                     cpu_millicores,
+                    cpu_weight,
+                    cpuset_cpus: None,
                     memory_bytes,
                     memory_swap_bytes: None,
                     pids_limit: Some(4096),
                     io_read_bps: None,
                     io_write_bps: None,
+                    io_read_iops: None,
+                    io_write_iops: None,
+                    io_weight: None,
                 }
             }
             None => ResourceLimits::default(),
         }
     }
 
+    /// Parse a CPU count like "0.5" or "2" to millicores.
+    fn parse_cpu_string(cpu: &str) -> Option<u64> {
+        cpu.trim().parse::<f64>().ok().map(|v| (v * 1000.0) as u64)
+    }
+
     /// Parse a memory string like "512m", "1g", "256M" to bytes.
     fn parse_memory_string(&self, mem: &str) -> u64 {
         let mem = mem.trim().to_lowercase();
@@ -416,6 +444,43 @@ mod tests {
         assert!(order[3] == "d");
     }
 
+    #[test]
+    fn test_resource_def_to_limits() {
+        let orchestrator = ProjectOrchestrator {
+            runtime: Arc::new(DummyRuntime),
+        };
+        let def = ResourceDef {
+            cpu_limit: Some("0.5".to_string()),
+            memory_limit: Some("512m".to_string()),
+            cpu_reservation: Some("0.25".to_string()),
+            memory_reservation: None,
+        };
+
+        let limits = orchestrator.resource_def_to_limits(Some(&def));
+        assert_eq!(limits.cpu_millicores, Some(500));
+        assert_eq!(limits.cpu_weight, Some(25));
+        assert_eq!(limits.memory_bytes, Some(512 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_container_def_to_spec_uses_build_platform() {
+        let orchestrator = ProjectOrchestrator {
+            runtime: Arc::new(DummyRuntime),
+        };
+        let mut project = Project::new("app", PathBuf::from("/tmp/app"));
+        let def = make_container("web", vec![]);
+
+        let spec = orchestrator.container_def_to_spec(&def, &project).unwrap();
+        assert_eq!(spec.platform, None);
+
+        project.config.build.platform = Some("linux/arm64".to_string());
+        let spec = orchestrator.container_def_to_spec(&def, &project).unwrap();
+        assert_eq!(spec.platform, Some(Platform::new("linux", "arm64", None)));
+
+        project.config.build.platform = Some("arm64".to_string());
+        assert!(orchestrator.container_def_to_spec(&def, &project).is_err());
+    }
+
     // Dummy runtime for testing
     struct DummyRuntime;
 
@@ -514,8 +579,8 @@ mod tests {
             &self,
             _: &ContainerId,
             _: hyperbox_core::types::ResourceLimits,
-        ) -> hyperbox_core::error::Result<()> {
-            Ok(())
+        ) -> hyperbox_core::error::Result<hyperbox_core::types::ResourceUpdateReport> {
+            Ok(hyperbox_core::types::ResourceUpdateReport::default())
         }
         async fn top(
             &self,
@@ -1,6 +1,7 @@
 //! Tests for image pulling functionality.
 
 use hyperbox_core::storage::registry::ImageRegistry;
+use hyperbox_core::types::{Platform, PullOptions};
 use tempfile::TempDir;
 
 #[tokio::test]
@@ -11,7 +12,7 @@ async fn test_pull_alpine_image() {
     let cache_dir = temp_dir.path().join("cache");
 
     // Create registry client
-    let mut registry = ImageRegistry::new(&cache_dir).unwrap();
+    let registry = ImageRegistry::new(&cache_dir).unwrap();
 
     // Pull alpine:latest
     let pulled = registry.pull("alpine:latest").await;
@@ -40,7 +41,7 @@ async fn test_pull_and_extract_alpine() {
     let rootfs_dir = temp_dir.path().join("rootfs");
 
     // Create registry client
-    let mut registry = ImageRegistry::new(&cache_dir).unwrap();
+    let registry = ImageRegistry::new(&cache_dir).unwrap();
 
     // Pull alpine:latest
     let pulled = registry.pull("alpine:latest").await.unwrap();
@@ -64,6 +65,30 @@ async fn test_pull_and_extract_alpine() {
     println!("Successfully extracted alpine:latest to {:?}", rootfs_dir);
 }
 
+#[tokio::test]
+#[ignore = "requires network access and docker registry"]
+async fn test_pull_alpine_for_other_platform() {
+    let temp_dir = TempDir::new().unwrap();
+    let registry = ImageRegistry::new(temp_dir.path().join("cache")).unwrap();
+
+    // alpine:latest is a multi-platform image; pick the one we're not on
+    let platform: Platform = if Platform::host().architecture == "arm64" {
+        "linux/amd64"
+    } else {
+        "linux/arm64"
+    }
+    .parse()
+    .unwrap();
+
+    let options = PullOptions {
+        platform: Some(platform.clone()),
+        ..Default::default()
+    };
+    let pulled = registry.pull_with("alpine:latest", &options).await.unwrap();
+    assert!(pulled.config.platform().matches(&platform));
+    assert!(!pulled.layer_paths.is_empty(), "No layers downloaded");
+}
+
 #[tokio::test]
 async fn test_parse_image_reference() {
     // Test Docker Hub official image